@@ -1,34 +1,300 @@
 //! Armistice client
 
-use crate::error::Error;
-use armistice_schema::{veriform::Decoder, Message, Request, Response};
+use crate::{
+    error::{Error, Kind},
+    known_devices::KnownDevices,
+    transport::Transport,
+};
+use armistice_schema::{
+    error::Code,
+    info,
+    session::{self, Initiator, PublicKey, Session},
+    veriform::{Decoder, Sha256Digest},
+    Message, Request, Response, Signature,
+};
+use rand_core::OsRng;
 
-#[cfg(feature = "usbarmory")]
-use crate::usbarmory;
-
-/// Armistice client
-// TODO(tarcieri): support for other connection methods besides USB
+/// Armistice client.
+///
+/// Requests are sent within an encrypted session established with the
+/// device's session key, which should be pinned using
+/// [`Armistice::with_device_key`], or checked against those of
+/// [`KnownDevices`] using [`Armistice::with_known_devices`]. Otherwise the
+/// key is obtained from the device the first time a session is established
+/// and trusted for the lifetime of the client only.
+///
+/// Device IDs and session keys obtained from the device are unauthenticated:
+/// a host or proxy relaying messages can substitute its own. Keys should
+/// only be trusted when obtained over a connection known not to be
+/// intercepted.
 pub struct Armistice {
-    /// USB armory connection
-    usb: usbarmory::BulkPair,
+    /// Transport used to communicate with Armistice
+    transport: Box<dyn Transport>,
+
+    /// Device's session public key (if known)
+    device_key: Option<PublicKey>,
+
+    /// Session keys of devices trusted previously (if any)
+    known_devices: Option<KnownDevices>,
+
+    /// Should devices missing from the known devices be trusted (and
+    /// recorded) rather than refused?
+    trust_new_devices: bool,
+
+    /// Currently established session (if any)
+    session: Option<Session>,
+
+    /// Has a caller identity been authenticated within a session? If so, a
+    /// session which is lost can't be replaced without authenticating again.
+    authenticated: bool,
 }
 
 impl Armistice {
-    /// Open a connection to Armistice
-    // TODO(tarcieri): credentials, non-USB connection support
-    pub fn new() -> Result<Self, Error> {
-        let usb = usbarmory::BulkPair::open(consts::VID, consts::PID)?;
-        Ok(Armistice { usb })
+    /// Create a client which communicates with Armistice using the given
+    /// [`Transport`]
+    // TODO(tarcieri): credentials
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        Armistice {
+            transport: Box::new(transport),
+            device_key: None,
+            known_devices: None,
+            trust_new_devices: false,
+            session: None,
+            authenticated: false,
+        }
     }
 
-    /// Send a request to Armistice, parsing the response
+    /// Create a client which communicates with the Armistice device with the
+    /// given session public key (e.g. one obtained when it was provisioned)
+    pub fn with_device_key(transport: impl Transport + 'static, device_key: PublicKey) -> Self {
+        Armistice {
+            device_key: Some(device_key),
+            ..Self::new(transport)
+        }
+    }
+
+    /// Create a client which obtains the device's session key from the
+    /// device the first time a session is established, refusing to connect
+    /// unless it matches the key recorded for the same device in the given
+    /// [`KnownDevices`].
+    ///
+    /// Devices which aren't known are refused, unless `trust_new_devices` is
+    /// set, in which case their keys are recorded. It should only be set
+    /// once the user has confirmed the device's key, as anyone relaying
+    /// messages to the device can pose as a new device.
+    pub fn with_known_devices(
+        transport: impl Transport + 'static,
+        known_devices: KnownDevices,
+        trust_new_devices: bool,
+    ) -> Self {
+        Armistice {
+            known_devices: Some(known_devices),
+            trust_new_devices,
+            ..Self::new(transport)
+        }
+    }
+
+    /// Get the device's session public key, if it's known (i.e. it was
+    /// pinned or a session has been established)
+    pub fn device_key(&self) -> Option<&PublicKey> {
+        self.device_key.as_ref()
+    }
+
+    /// Send a request to Armistice within an encrypted session (establishing
+    /// one if need be), parsing the response.
+    ///
+    /// Error responses from the device are returned as [`Error`]s with a
+    /// [`Kind::Device`] error kind.
+    ///
+    /// If the session is lost (e.g. the device restarted) the request fails
+    /// with a [`Kind::Device`] error with a [`Code::Session`] code. It isn't
+    /// retried automatically: the error arrives outside of the session, so
+    /// it may have been injected by a host after the device performed the
+    /// request. A new session is established for the next request.
+    ///
+    /// [`Kind::Device`]: crate::error::Kind::Device
     pub fn send_request(&mut self, request: impl Into<Request>) -> Result<Response, Error> {
-        self.usb.write(&request.into().encode_vec()?)?;
+        let request = request.into().encode_vec()?;
+        self.send_encrypted(&request)
+    }
+
+    /// Get information about the device.
+    ///
+    /// Fails with [`Kind::Version`] if the device firmware was built with an
+    /// incompatible message schema.
+    pub fn info(&mut self) -> Result<info::Response, Error> {
+        let info = self
+            .send_request(info::Request {})?
+            .get_info()
+            .cloned()
+            .ok_or_else(|| Kind::Encoding.context("unexpected response to info request"))?;
+
+        check_info(&info)?;
+        Ok(info)
+    }
+
+    /// Identify ourselves within the current session (establishing one if
+    /// need be) as the holder of the given key, for key policies which only
+    /// allow certain callers. The given function signs the session's
+    /// authentication digest with that key.
+    ///
+    /// Authentication lasts for the lifetime of the session: if a new one
+    /// has to be established (e.g. because the device restarted), requests
+    /// fail with [`Kind::Session`] until it's repeated.
+    pub fn authenticate(
+        &mut self,
+        public_key: armistice_schema::PublicKey,
+        sign: impl FnOnce(&Sha256Digest) -> Signature,
+    ) -> Result<(), Error> {
+        if self.session.is_none() {
+            self.session = Some(self.establish_session()?);
+        }
+
+        let digest = self.session.as_ref().unwrap().authentication_digest();
+        let request = Request::from(session::AuthenticateRequest {
+            public_key,
+            signature: sign(&digest),
+        });
+
+        self.send_encrypted(&request.encode_vec()?)?
+            .session_authenticate()
+            .ok_or_else(|| {
+                Kind::Session.context("unexpected response to authentication request")
+            })?;
+
+        self.authenticated = true;
+        Ok(())
+    }
 
-        let mut buf = vec![0; self.usb.in_max_packet_size().into()];
-        let response = self.usb.read(&mut buf)?;
+    /// Encrypt an encoded request within the current session (establishing
+    /// one if need be), send it, and decrypt the response
+    fn send_encrypted(&mut self, request: &[u8]) -> Result<Response, Error> {
+        if self.session.is_none() {
+            // Don't silently continue without the caller's identity
+            if self.authenticated {
+                return Err(Kind::Session
+                    .context("session lost: authenticate again to continue")
+                    .into());
+            }
 
-        let mut decoder = Decoder::new();
-        Ok(Response::decode(&mut decoder, response)?)
+            self.session = Some(self.establish_session()?);
+        }
+
+        // Messages in a session are sequenced, so a session in which a
+        // message failed to be sent or received can't be used any further
+        let mut session = self.session.take().unwrap();
+        let encrypted = session.encrypt(request)?;
+
+        let response = match self.send_cleartext(encrypted.into()) {
+            Err(e) if e.kind() == &Kind::Device(Code::Session) => {
+                return Err(Kind::Device(Code::Session)
+                    .context("session lost: the device may have performed the request")
+                    .into())
+            }
+            result => result?,
+        };
+
+        let response = response
+            .session()
+            .cloned()
+            .ok_or_else(|| Kind::Session.context("unexpected response to session request"))?;
+
+        let response = Response::decode(&mut Decoder::new(), &session.decrypt(&response)?)?;
+        self.session = Some(session);
+
+        match response {
+            Response::Error(err) => Err(err.into()),
+            response => Ok(response),
+        }
     }
+
+    /// Establish a session with the device, first obtaining its session key
+    /// (if it isn't already known) and checking it against known devices
+    fn establish_session(&mut self) -> Result<Session, Error> {
+        let device_key = match self.device_key {
+            Some(device_key) => device_key,
+            None => {
+                let info = self
+                    .send_cleartext(info::Request {}.into())?
+                    .get_info()
+                    .cloned()
+                    .ok_or_else(|| Kind::Encoding.context("unexpected response to info request"))?;
+
+                check_info(&info)?;
+                let session_key = PublicKey::from(info.session_key);
+
+                if let Some(known_devices) = &mut self.known_devices {
+                    match known_devices.get(&info.device_id) {
+                        Some(known_key) if known_key != &session_key => {
+                            return Err(Kind::Session
+                                .context(format!(
+                                    "session key of device {} differs from the known one: \
+                                     refusing to connect",
+                                    hex::encode(info.device_id)
+                                ))
+                                .into())
+                        }
+                        Some(_) => (),
+                        None if self.trust_new_devices => {
+                            known_devices.insert(info.device_id, session_key)?
+                        }
+                        None => {
+                            return Err(Kind::Session
+                                .context(format!(
+                                    "unknown device {} presenting session key {}: \
+                                     refusing to connect until its key is confirmed",
+                                    hex::encode(info.device_id),
+                                    hex::encode(session_key.as_bytes())
+                                ))
+                                .into())
+                        }
+                    }
+                }
+
+                *self.device_key.get_or_insert(session_key)
+            }
+        };
+
+        let (initiator, request) = Initiator::new(&device_key, OsRng)?;
+
+        let response = match self.send_cleartext(request.into()) {
+            Err(e) if e.kind() == &Kind::Device(Code::Session) => {
+                return Err(Kind::Session
+                    .context("handshake failed (does the device key match?)")
+                    .into())
+            }
+            result => result?,
+        };
+
+        let response = response
+            .session_init()
+            .ok_or_else(|| Kind::Session.context("unexpected response to session request"))?;
+
+        Ok(initiator.finish(response)?)
+    }
+
+    /// Send a request outside of any session
+    fn send_cleartext(&mut self, request: Request) -> Result<Response, Error> {
+        let response = self.transport.send_request(&request.encode_vec()?)?;
+
+        match Response::decode(&mut Decoder::new(), &response)? {
+            Response::Error(err) => Err(err.into()),
+            response => Ok(response),
+        }
+    }
+}
+
+/// Ensure the device firmware was built with a compatible message schema
+fn check_info(info: &info::Response) -> Result<(), Error> {
+    if !info.is_compatible() {
+        return Err(Kind::Version
+            .context(format!(
+                "device schema version {} (expected {})",
+                info.schema_version,
+                info::SCHEMA_VERSION
+            ))
+            .into());
+    }
+
+    Ok(())
 }
@@ -0,0 +1,85 @@
+//! Armistice proxy daemon: exposes a USB armory attached to this host over
+//! TCP or a Unix domain socket
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    transport::{TcpTransport, UsbTransport},
+    Proxy,
+};
+use gumdrop::Options;
+use std::{
+    fmt::Display,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    process,
+};
+
+/// Command-line options
+#[derive(Debug, Options)]
+struct Opts {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// TCP address to listen on
+    #[options(no_short, help = "TCP address to listen on (e.g. 127.0.0.1:6660)")]
+    tcp: Option<SocketAddr>,
+
+    /// Unix domain socket to listen on
+    #[options(no_short, help = "path of a Unix domain socket to listen on")]
+    unix: Option<PathBuf>,
+
+    /// Forward to Armistice at this TCP address instead of a USB device
+    #[options(
+        no_short,
+        help = "forward to Armistice at this TCP address (e.g. a simulator) instead of USB"
+    )]
+    upstream: Option<SocketAddr>,
+}
+
+fn main() {
+    let opts = Opts::parse_args_default_or_exit();
+
+    let proxy = match opts.upstream {
+        Some(addr) => TcpTransport::connect(addr).map(Proxy::new),
+        None => UsbTransport::open().map(Proxy::new),
+    }
+    .unwrap_or_else(|e| exit(e));
+
+    let result = match (opts.tcp, opts.unix) {
+        (Some(addr), None) => {
+            let listener = TcpListener::bind(addr).unwrap_or_else(|e| exit(e));
+            eprintln!("armistice-proxy: listening on tcp://{}", addr);
+            proxy.listen_tcp(listener)
+        }
+        #[cfg(unix)]
+        (None, Some(path)) => {
+            // Remove the socket left behind by a previous run, if any
+            if path.exists() {
+                std::fs::remove_file(&path).unwrap_or_else(|e| exit(e));
+            }
+
+            let listener =
+                std::os::unix::net::UnixListener::bind(&path).unwrap_or_else(|e| exit(e));
+            eprintln!("armistice-proxy: listening on unix://{}", path.display());
+            proxy.listen_unix(listener)
+        }
+        _ => {
+            eprintln!("error: specify exactly one of --tcp or --unix\n");
+            eprintln!("{}", Opts::usage());
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        exit(e);
+    }
+}
+
+/// Print an error and exit
+fn exit(err: impl Display) -> ! {
+    eprintln!("error: {}", err);
+    process::exit(1);
+}
@@ -0,0 +1,956 @@
+//! Subcommands of the command-line tool
+
+use crate::{
+    keys,
+    output::{KeyOutput, Output},
+};
+use armistice::{
+    ceremony::{Ceremony, Status},
+    error::{Error, Kind},
+    schema::{
+        authorization::{Binding, DeviceId},
+        challenge, domain, error, key, policy,
+        signature::Signatures,
+        veriform::{Decoder, Sha256Digest},
+        Message, PublicKey, Request, Response,
+    },
+    Armistice,
+};
+use gumdrop::Options;
+use serde_json::json;
+use std::{
+    fs,
+    io::{self, Read},
+    time::SystemTime,
+};
+
+/// Subcommands
+#[derive(Debug, Options)]
+pub enum Command {
+    /// Show information about the device
+    #[options(help = "show information about the device")]
+    Info(InfoCommand),
+
+    /// Provision the device from a ceremony file
+    #[options(help = "provision the device from a ceremony file")]
+    Provision(ProvisionCommand),
+
+    /// Generate a key within a domain
+    #[options(help = "generate a key within a domain")]
+    Keygen(KeygenCommand),
+
+    /// Sign a message
+    #[options(help = "sign a message (read from a file or stdin)")]
+    Sign(SignCommand),
+
+    /// Show the public key of a key
+    #[options(help = "show the public key of a key")]
+    Pubkey(PubkeyCommand),
+
+    /// Export a key wrapped under the device's root key
+    #[options(help = "export a key wrapped under the device's root key")]
+    Export(ExportCommand),
+
+    /// Import a previously exported key
+    #[options(help = "import a previously exported key")]
+    Import(ImportCommand),
+
+    /// Rotate the root keys from a ceremony file
+    #[options(help = "rotate the root keys from a ceremony file")]
+    RotateRoot(RotateRootCommand),
+
+    /// Offline root key ceremonies
+    #[options(help = "build, sign, verify, and assemble ceremony files (offline)")]
+    Ceremony(CeremonyCommand),
+
+    /// Submit an assembled ceremony request
+    #[options(help = "submit an assembled ceremony request to the device")]
+    Submit(SubmitCommand),
+}
+
+impl Command {
+    /// Run this command, connecting to the device only if it needs one
+    pub fn run(&self, connect: impl FnOnce() -> Result<Armistice, Error>) -> Result<Output, Error> {
+        match self {
+            Command::Info(cmd) => cmd.run(&mut connect()?),
+            Command::Provision(cmd) => cmd.run(&mut connect()?),
+            Command::Keygen(cmd) => cmd.run(&mut connect()?),
+            Command::Sign(cmd) => cmd.run(&mut connect()?),
+            Command::Pubkey(cmd) => cmd.run(&mut connect()?),
+            Command::Export(cmd) => cmd.run(&mut connect()?),
+            Command::Import(cmd) => cmd.run(&mut connect()?),
+            Command::RotateRoot(cmd) => cmd.run(&mut connect()?),
+            Command::Ceremony(cmd) => cmd.run(),
+            Command::Submit(cmd) => cmd.run(&mut connect()?),
+        }
+    }
+}
+
+/// `info` subcommand
+#[derive(Debug, Options)]
+pub struct InfoCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+}
+
+impl InfoCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let info = armistice.info()?;
+
+        let algorithms = keys::ALGORITHMS
+            .iter()
+            .filter(|(algorithm, _)| info.supports(*algorithm))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+
+        let slots = info
+            .slots
+            .iter()
+            .map(|slot| json!({ "domain": slot.domain, "used": slot.used, "max": slot.max }))
+            .collect::<Vec<_>>();
+
+        let domain_counters = info
+            .domain_counters
+            .iter()
+            .map(|counter| json!({ "domain": counter.domain, "counter": counter.counter }))
+            .collect::<Vec<_>>();
+
+        Ok(Output::new()
+            .field(
+                "firmware_version",
+                info.firmware_version().unwrap_or("unknown"),
+            )
+            .field("schema_version", info.schema_version)
+            .field("algorithms", algorithms)
+            .field("provisioned", info.is_provisioned())
+            .field("uuid", info.uuid.to_string())
+            .field("root_version", info.root_version)
+            .field("max_message_size", info.max_message_size)
+            .field("session_key", hex::encode(info.session_key))
+            .field("device_id", hex::encode(info.device_id))
+            .field("authorization_counter", info.authorization_counter)
+            .field("domain_counters", domain_counters)
+            .field("slots", slots))
+    }
+}
+
+/// `provision` subcommand
+#[derive(Debug, Options)]
+pub struct ProvisionCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Root key files to sign with (in addition to the ceremony's signatures)
+    #[options(help = "root key file to sign with (may be repeated)")]
+    key: Vec<String>,
+
+    /// Ceremony file
+    #[options(free, required, help = "ceremony file (TOML or JSON)")]
+    ceremony: String,
+}
+
+impl ProvisionCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let ceremony = Ceremony::load(&self.ceremony)?;
+
+        if ceremony.is_rotation() {
+            return Err(Kind::Ceremony
+                .context("root rotation ceremonies must be run with `rotate-root`")
+                .into());
+        }
+
+        let request = sign_ceremony(ceremony, &self.key)?;
+        submit(armistice, request)
+    }
+}
+
+/// `keygen` subcommand
+#[derive(Debug, Options)]
+pub struct KeygenCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Domain to generate the key in
+    #[options(required, help = "domain to generate the key in")]
+    domain: domain::Id,
+
+    /// Key algorithm
+    #[options(
+        default = "ed25519",
+        help = "key algorithm (ed25519, ecdsa-p256, ecdsa-secp256k1, bls12-381)"
+    )]
+    algorithm: String,
+
+    /// Domain administrator key files to sign the request with
+    #[options(help = "domain administrator key file (may be repeated)")]
+    key: Vec<String>,
+
+    /// Message prefixes the key may sign
+    #[options(
+        no_short,
+        help = "only sign messages beginning with this prefix (may be repeated)"
+    )]
+    prefix: Vec<String>,
+
+    /// Minimum length of messages
+    #[options(no_short, help = "minimum length of messages the key signs")]
+    min_length: u64,
+
+    /// Maximum length of messages
+    #[options(no_short, help = "maximum length of messages the key signs")]
+    max_length: u64,
+
+    /// Allowed hash algorithms
+    #[options(
+        no_short,
+        help = "allowed hash algorithm (intrinsic, sha256; may be repeated)"
+    )]
+    hash: Vec<String>,
+
+    /// Maximum number of signatures per window
+    #[options(no_short, help = "maximum number of signatures per window")]
+    max_signatures: u64,
+
+    /// Length of the rate limit window
+    #[options(
+        no_short,
+        help = "length of the window (in operations authorized in the domain)"
+    )]
+    window: u64,
+
+    /// Number of administrator approvals required to sign
+    #[options(no_short, help = "administrator approvals required to sign")]
+    approvals: u64,
+
+    /// Session identities allowed to use the key (without any, it may only
+    /// be used with the approval of the domain's administrators)
+    #[options(
+        no_short,
+        help = "hex-encoded Ed25519 session identity allowed to sign (may be repeated; \
+                without any, the key only signs with administrator approval)"
+    )]
+    caller: Vec<String>,
+}
+
+impl KeygenCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let request = round_trip(&key::GenerateRequest {
+            domain: self.domain,
+            algorithm: keys::parse_algorithm(&self.algorithm)?.into(),
+            timestamp: keys::now(),
+            policy: key_policy(
+                policy::Policy {
+                    min_length: self.min_length,
+                    max_length: self.max_length,
+                    max_signatures: self.max_signatures,
+                    window: self.window,
+                    required_approvals: self.approvals,
+                    ..policy::Policy::default()
+                },
+                &self.prefix,
+                &self.hash,
+                &self.caller,
+            )?,
+            digest: None,
+        })?;
+
+        let (binding, signatures) = authorize(armistice, self.domain, request.digest, &self.key)?;
+
+        let response = armistice.send_request(key::SignedGenerateRequest {
+            request,
+            signatures,
+            binding,
+        })?;
+
+        let generated = response
+            .generate_key()
+            .ok_or_else(|| unexpected_response("key generation"))?;
+
+        Ok(KeyOutput::new(self.domain, generated.slot, &generated.public_key).into())
+    }
+}
+
+/// `sign` subcommand
+#[derive(Debug, Options)]
+pub struct SignCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Domain containing the signing key
+    #[options(required, help = "domain containing the signing key")]
+    domain: domain::Id,
+
+    /// Slot of the signing key
+    #[options(required, help = "slot of the signing key")]
+    slot: key::Slot,
+
+    /// Sign a hex-encoded SHA-256 digest rather than a message
+    #[options(no_short, help = "sign a hex-encoded SHA-256 digest (ECDSA only)")]
+    sha256: Option<String>,
+
+    /// Key file of a caller the key's policy allows
+    #[options(
+        no_short,
+        help = "key file to authenticate as a caller the key's policy allows"
+    )]
+    caller_key: Option<String>,
+
+    /// File containing the message to sign
+    #[options(free, help = "file containing the message to sign (default: stdin)")]
+    file: Option<String>,
+}
+
+impl SignCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let payload = match &self.sha256 {
+            Some(encoded) => {
+                let mut digest = [0u8; 32];
+                hex::decode_to_slice(encoded.trim(), &mut digest)
+                    .map_err(|_| Kind::Encoding.context("malformed SHA-256 digest"))?;
+                key::Payload::Sha256(digest)
+            }
+            None => {
+                let bytes = match &self.file {
+                    Some(path) => fs::read(path)?,
+                    None => {
+                        let mut bytes = Vec::new();
+                        io::stdin().read_to_end(&mut bytes)?;
+                        bytes
+                    }
+                };
+
+                let mut message = key::MessageBytes::new();
+                message
+                    .extend_from_slice(&bytes)
+                    .map_err(|_| Kind::Encoding.context("message too long"))?;
+
+                key::Payload::Message(message)
+            }
+        };
+
+        if let Some(path) = &self.caller_key {
+            keys::authenticate(armistice, path)?;
+        }
+
+        let response = armistice.send_request(key::SignRequest {
+            domain: self.domain,
+            slot: self.slot,
+            payload,
+        })?;
+
+        let signature = &response
+            .sign()
+            .ok_or_else(|| unexpected_response("sign"))?
+            .signature;
+
+        Ok(Output::new()
+            .field("domain", self.domain)
+            .field("slot", self.slot)
+            .field("signature", hex::encode(keys::signature_bytes(signature))))
+    }
+}
+
+/// `pubkey` subcommand
+#[derive(Debug, Options)]
+pub struct PubkeyCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Domain containing the key
+    #[options(required, help = "domain containing the key")]
+    domain: domain::Id,
+
+    /// Slot of the key
+    #[options(required, help = "slot of the key")]
+    slot: key::Slot,
+}
+
+impl PubkeyCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let response = armistice.send_request(key::PublicKeyRequest {
+            domain: self.domain,
+            slot: self.slot,
+        })?;
+
+        let public_key = &response
+            .get_public_key()
+            .ok_or_else(|| unexpected_response("public key"))?
+            .public_key;
+
+        Ok(KeyOutput::new(self.domain, self.slot, public_key).into())
+    }
+}
+
+/// `export` subcommand
+#[derive(Debug, Options)]
+pub struct ExportCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Domain containing the key
+    #[options(required, help = "domain containing the key")]
+    domain: domain::Id,
+
+    /// Slot of the key
+    #[options(required, help = "slot of the key")]
+    slot: key::Slot,
+
+    /// Domain administrator key files to sign the request with
+    #[options(help = "domain administrator key file (may be repeated)")]
+    key: Vec<String>,
+
+    /// File to write the hex-encoded exported key (its wrapping and policy)
+    /// to
+    #[options(help = "file to write the exported key to")]
+    output: Option<String>,
+}
+
+impl ExportCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let request = round_trip(&key::ExportRequest {
+            domain: self.domain,
+            slot: self.slot,
+            timestamp: keys::now(),
+            digest: None,
+        })?;
+
+        let (binding, signatures) = authorize(armistice, self.domain, request.digest, &self.key)?;
+
+        let response = armistice.send_request(key::SignedExportRequest {
+            request,
+            signatures,
+            binding,
+        })?;
+
+        let exported = hex::encode(
+            response
+                .export_key()
+                .ok_or_else(|| unexpected_response("export"))?
+                .encode_vec()?,
+        );
+
+        if let Some(path) = &self.output {
+            fs::write(path, format!("{}\n", exported))?;
+        }
+
+        Ok(Output::new()
+            .field("domain", self.domain)
+            .field("slot", self.slot)
+            .field("exported_key", exported))
+    }
+}
+
+/// `import` subcommand
+#[derive(Debug, Options)]
+pub struct ImportCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Domain to import the key into
+    #[options(required, help = "domain the key was exported from")]
+    domain: domain::Id,
+
+    /// Domain administrator key files to sign the request with
+    #[options(help = "domain administrator key file (may be repeated)")]
+    key: Vec<String>,
+
+    /// File containing the hex-encoded exported key
+    #[options(
+        free,
+        required,
+        help = "file containing the exported key (restored with its policy)"
+    )]
+    file: String,
+}
+
+impl ImportCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let encoded = fs::read_to_string(&self.file)?;
+        let bytes = hex::decode(encoded.trim())
+            .map_err(|_| Kind::Encoding.context("malformed exported key"))?;
+        let exported = key::ExportResponse::decode(&mut Decoder::new(), &bytes)
+            .map_err(|_| Kind::Encoding.context("malformed exported key"))?;
+
+        let request = round_trip(&key::ImportRequest {
+            domain: self.domain,
+            wrapped_key: exported.wrapped_key,
+            timestamp: keys::now(),
+            policy: exported.policy,
+            digest: None,
+        })?;
+
+        let (binding, signatures) = authorize(armistice, self.domain, request.digest, &self.key)?;
+
+        let response = armistice.send_request(key::SignedImportRequest {
+            request,
+            signatures,
+            binding,
+        })?;
+
+        let imported = response
+            .import_key()
+            .ok_or_else(|| unexpected_response("import"))?;
+
+        Ok(KeyOutput::new(self.domain, imported.slot, &imported.public_key).into())
+    }
+}
+
+/// `rotate-root` subcommand
+#[derive(Debug, Options)]
+pub struct RotateRootCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Root key files to sign with (in addition to the ceremony's signatures)
+    #[options(help = "current or new root key file to sign with (may be repeated)")]
+    key: Vec<String>,
+
+    /// Ceremony file
+    #[options(free, required, help = "ceremony file (TOML or JSON)")]
+    ceremony: String,
+}
+
+impl RotateRootCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let ceremony = Ceremony::load(&self.ceremony)?;
+
+        if !ceremony.is_rotation() {
+            return Err(Kind::Ceremony
+                .context("provisioning ceremonies must be run with `provision`")
+                .into());
+        }
+
+        let request = sign_ceremony(ceremony, &self.key)?;
+        submit(armistice, request)
+    }
+}
+
+/// `ceremony` subcommand
+#[derive(Debug, Options)]
+pub struct CeremonyCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Ceremony subcommand to run
+    #[options(command)]
+    command: Option<CeremonySubcommand>,
+}
+
+/// Subcommands of `ceremony`
+#[derive(Debug, Options)]
+pub enum CeremonySubcommand {
+    /// Create an unsigned ceremony file
+    #[options(help = "create an unsigned provisioning or root rotation ceremony file")]
+    Init(CeremonyInitCommand),
+
+    /// Sign a ceremony file
+    #[options(help = "sign a ceremony file with root key files")]
+    Sign(CeremonySignCommand),
+
+    /// Verify the signatures in a ceremony file
+    #[options(help = "verify the signatures in a ceremony file")]
+    Verify(CeremonyVerifyCommand),
+
+    /// Assemble the final signed request
+    #[options(help = "assemble the signed request once the threshold is met")]
+    Assemble(CeremonyAssembleCommand),
+}
+
+impl CeremonyCommand {
+    fn run(&self) -> Result<Output, Error> {
+        match &self.command {
+            Some(CeremonySubcommand::Init(cmd)) => cmd.run(),
+            Some(CeremonySubcommand::Sign(cmd)) => cmd.run(),
+            Some(CeremonySubcommand::Verify(cmd)) => cmd.run(),
+            Some(CeremonySubcommand::Assemble(cmd)) => cmd.run(),
+            None => Err(Kind::Ceremony
+                .context(format!(
+                    "missing ceremony subcommand\n\n{}",
+                    CeremonySubcommand::usage()
+                ))
+                .into()),
+        }
+    }
+}
+
+/// `ceremony init` subcommand
+#[derive(Debug, Options)]
+pub struct CeremonyInitCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// ID of the device the ceremony's request is intended for
+    #[options(
+        no_short,
+        required,
+        meta = "HEX",
+        help = "hex-encoded ID of the target device (see `armistice info`)"
+    )]
+    device_id: String,
+
+    /// Authorization counter to bind the request to
+    #[options(
+        no_short,
+        required,
+        help = "one greater than the device's authorization counter (see `armistice info`)"
+    )]
+    counter: u64,
+
+    /// Number of root key signatures required
+    #[options(required, help = "number of root key signatures required")]
+    threshold: u64,
+
+    /// Hex-encoded Ed25519 root public keys
+    #[options(meta = "HEX", help = "hex-encoded root public key (may be repeated)")]
+    root_key: Vec<String>,
+
+    /// Date/time of the ceremony
+    #[options(
+        no_short,
+        meta = "RFC3339",
+        help = "date/time of the ceremony (default: now)"
+    )]
+    timestamp: Option<String>,
+
+    /// New root version (makes this a root rotation)
+    #[options(no_short, help = "new root version (root rotation only)")]
+    version: Option<u64>,
+
+    /// Threshold of the current root keys
+    #[options(
+        no_short,
+        help = "threshold of the current root keys (root rotation only)"
+    )]
+    previous_threshold: Option<u64>,
+
+    /// Hex-encoded Ed25519 current root public keys
+    #[options(
+        no_short,
+        meta = "HEX",
+        help = "hex-encoded current root public key (root rotation only, may be repeated)"
+    )]
+    previous_root_key: Vec<String>,
+
+    /// Ceremony file to create
+    #[options(free, required, help = "ceremony file to create (TOML or JSON)")]
+    file: String,
+}
+
+impl CeremonyInitCommand {
+    fn run(&self) -> Result<Output, Error> {
+        let timestamp = match &self.timestamp {
+            Some(timestamp) => keys::parse_time(timestamp)?,
+            None => SystemTime::now(),
+        };
+
+        let root_keys = parse_public_keys(&self.root_key)?;
+        let binding = Binding {
+            device_id: parse_device_id(&self.device_id)?,
+            counter: self.counter,
+        };
+
+        let ceremony = match self.version {
+            Some(version) => {
+                let previous_threshold = self.previous_threshold.ok_or_else(|| {
+                    Kind::Ceremony.context("root rotation requires --previous-threshold")
+                })?;
+
+                Ceremony::rotate_root(
+                    binding,
+                    version,
+                    self.threshold,
+                    &root_keys,
+                    previous_threshold,
+                    &parse_public_keys(&self.previous_root_key)?,
+                    timestamp,
+                )
+            }
+            None => Ceremony::provision(binding, self.threshold, &root_keys, timestamp),
+        };
+
+        let digest = ceremony.digest()?;
+        ceremony.save(&self.file)?;
+
+        Ok(Output::new()
+            .field("file", self.file.as_str())
+            .field("digest", hex::encode(digest)))
+    }
+}
+
+/// `ceremony sign` subcommand
+#[derive(Debug, Options)]
+pub struct CeremonySignCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Root key files to sign with
+    #[options(help = "root key file to sign with (may be repeated)")]
+    key: Vec<String>,
+
+    /// Ceremony file
+    #[options(free, required, help = "ceremony file (TOML or JSON)")]
+    ceremony: String,
+}
+
+impl CeremonySignCommand {
+    fn run(&self) -> Result<Output, Error> {
+        if self.key.is_empty() {
+            return Err(Kind::Ceremony
+                .context("no key files given (use --key)")
+                .into());
+        }
+
+        let mut ceremony = Ceremony::load(&self.ceremony)?;
+
+        for keypair in keys::load_keypairs(&self.key)? {
+            ceremony.sign(&keypair)?;
+        }
+
+        let status = ceremony.verify()?;
+        ceremony.save(&self.ceremony)?;
+
+        Ok(status_output(&status))
+    }
+}
+
+/// `ceremony verify` subcommand
+#[derive(Debug, Options)]
+pub struct CeremonyVerifyCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Ceremony file
+    #[options(free, required, help = "ceremony file (TOML or JSON)")]
+    ceremony: String,
+}
+
+impl CeremonyVerifyCommand {
+    fn run(&self) -> Result<Output, Error> {
+        let status = Ceremony::load(&self.ceremony)?.verify()?;
+        Ok(status_output(&status))
+    }
+}
+
+/// `ceremony assemble` subcommand
+#[derive(Debug, Options)]
+pub struct CeremonyAssembleCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// File to write the signed request to
+    #[options(required, help = "file to write the signed request to")]
+    output: String,
+
+    /// Ceremony file
+    #[options(free, required, help = "ceremony file (TOML or JSON)")]
+    ceremony: String,
+}
+
+impl CeremonyAssembleCommand {
+    fn run(&self) -> Result<Output, Error> {
+        let ceremony = Ceremony::load(&self.ceremony)?;
+        let request = ceremony.assemble()?;
+        fs::write(&self.output, request.encode_vec()?)?;
+
+        Ok(Output::new()
+            .field("file", self.output.as_str())
+            .field("digest", hex::encode(ceremony.digest()?)))
+    }
+}
+
+/// `submit` subcommand
+#[derive(Debug, Options)]
+pub struct SubmitCommand {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// File containing the signed request
+    #[options(free, required, help = "file produced by `ceremony assemble`")]
+    file: String,
+}
+
+impl SubmitCommand {
+    fn run(&self, armistice: &mut Armistice) -> Result<Output, Error> {
+        let request = Request::decode(&mut Decoder::new(), &fs::read(&self.file)?)?;
+
+        match request {
+            Request::Provision(_) | Request::RootRotate(_) => submit(armistice, request),
+            _ => Err(Kind::Ceremony
+                .context(format!("{} does not contain a ceremony request", self.file))
+                .into()),
+        }
+    }
+}
+
+/// Sign a ceremony with each of the given key files and assemble its request
+fn sign_ceremony(mut ceremony: Ceremony, key_files: &[String]) -> Result<Request, Error> {
+    for keypair in keys::load_keypairs(key_files)? {
+        ceremony.sign(&keypair)?;
+    }
+
+    ceremony.assemble()
+}
+
+/// Send an assembled provisioning or root rotation request to the device
+fn submit(armistice: &mut Armistice, request: Request) -> Result<Output, Error> {
+    match armistice.send_request(request)? {
+        Response::Provision(response) => Ok(Output::new().field("uuid", response.uuid.to_string())),
+        Response::RootRotate(response) => Ok(Output::new().field("root_version", response.version)),
+        _ => Err(unexpected_response("ceremony")),
+    }
+}
+
+/// Output the progress of a ceremony
+fn status_output(status: &Status) -> Output {
+    let mut output = Output::new()
+        .field("digest", hex::encode(status.digest))
+        .field("signers", status.signers)
+        .field("threshold", status.threshold);
+
+    if let (Some(signers), Some(threshold)) = (status.previous_signers, status.previous_threshold) {
+        output = output
+            .field("previous_signers", signers)
+            .field("previous_threshold", threshold);
+    }
+
+    output.field("complete", status.is_complete())
+}
+
+/// Decode a list of hex-encoded Ed25519 public keys
+fn parse_public_keys(encoded: &[String]) -> Result<Vec<ed25519_dalek::PublicKey>, Error> {
+    encoded
+        .iter()
+        .map(|key| keys::parse_ed25519_public_key(key))
+        .collect()
+}
+
+/// Complete a key policy with the given message prefixes, hash algorithm
+/// names, and hex-encoded caller identities
+fn key_policy(
+    mut policy: policy::Policy,
+    prefixes: &[String],
+    hash_algorithms: &[String],
+    callers: &[String],
+) -> Result<policy::Policy, Error> {
+    for prefix in prefixes {
+        let mut allowed = policy::Prefix::default();
+        allowed
+            .bytes
+            .extend_from_slice(prefix.as_bytes())
+            .map_err(|_| Kind::Encoding.context(format!("prefix too long: {}", prefix)))?;
+        policy
+            .prefixes
+            .push(allowed)
+            .map_err(|_| Kind::Encoding.context("too many prefixes"))?;
+    }
+
+    let hash_algorithms = hash_algorithms
+        .iter()
+        .map(|name| keys::parse_hash_algorithm(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    policy.hash_algorithms = policy::hash_algorithm_mask(&hash_algorithms);
+
+    for caller in parse_public_keys(callers)? {
+        policy
+            .callers
+            .push(PublicKey::Ed25519(caller.to_bytes()))
+            .map_err(|_| Kind::Encoding.context("too many callers"))?;
+    }
+
+    Ok(policy)
+}
+
+/// Decode a hex-encoded device ID
+fn parse_device_id(encoded: &str) -> Result<DeviceId, Error> {
+    let mut device_id = DeviceId::default();
+
+    hex::decode_to_slice(encoded.trim(), &mut device_id)
+        .map_err(|_| Kind::Encoding.context(format!("malformed device ID: {}", encoded)))?;
+
+    Ok(device_id)
+}
+
+/// Sign the digest of a request in the given domain with each of the given
+/// key files, binding it to the domain's next authorization counter and (if
+/// the domain's policy requires it) to a challenge issued by the device
+fn authorize(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    digest: Option<Sha256Digest>,
+    key_files: &[String],
+) -> Result<(Binding, Signatures), Error> {
+    // Digests are computed by `veriform` when the request is round tripped
+    let digest = digest.expect("digest not computed");
+    let keypairs = keys::load_keypairs(key_files)?;
+
+    let binding = armistice
+        .info()?
+        .next_domain_binding(domain)
+        .ok_or_else(|| {
+            Kind::Device(error::Code::NotFound).context(format!(
+                "no such domain, or its authorization counter is exhausted: {}",
+                domain
+            ))
+        })?;
+
+    let mut authorization_digest = binding.digest(&digest);
+
+    if requires_challenge(armistice, domain)? {
+        let response = armistice.send_request(challenge::Request {
+            domain,
+            operation: digest,
+        })?;
+
+        let challenge = response
+            .get_challenge()
+            .ok_or_else(|| unexpected_response("challenge"))?;
+
+        authorization_digest = challenge.digest(&authorization_digest);
+    }
+
+    let signatures = keys::sign_digest(&authorization_digest, &keypairs)?;
+    Ok((binding, signatures))
+}
+
+/// Does the given domain's policy require operations to sign a challenge?
+fn requires_challenge(armistice: &mut Armistice, domain: domain::Id) -> Result<bool, Error> {
+    let response = armistice.send_request(domain::ListRequest::default())?;
+
+    let domains = &response
+        .domain_list()
+        .ok_or_else(|| unexpected_response("domain list"))?
+        .domains;
+
+    Ok(domains
+        .iter()
+        .find(|config| config.id == domain)
+        .map(|config| config.policy.challenge_lifetime != 0)
+        .unwrap_or(false))
+}
+
+/// Round trip a request through the encoder, which causes `veriform` to
+/// compute its digest
+fn round_trip<M: Message>(message: &M) -> Result<M, Error> {
+    Ok(M::decode(&mut Decoder::new(), &message.encode_vec()?)?)
+}
+
+/// Error for a response which doesn't match the request
+fn unexpected_response(request: &str) -> Error {
+    Kind::Encoding
+        .context(format!("unexpected response to {} request", request))
+        .into()
+}
@@ -0,0 +1,147 @@
+//! Key files, encodings, and timestamps used by the command-line tool
+
+use armistice::{
+    ceremony,
+    error::{Error, Kind},
+    schema::{
+        key::Algorithm, policy::HashAlgorithm, session, signature::Signatures, PublicKey,
+        Signature, Timestamp,
+    },
+    Armistice,
+};
+use ed25519_dalek::{Keypair, Signer};
+use std::time::SystemTime;
+
+/// Names of key algorithms, as used on the command line and in output
+pub const ALGORITHMS: &[(Algorithm, &str)] = &[
+    (Algorithm::Ed25519, "ed25519"),
+    (Algorithm::EcdsaP256, "ecdsa-p256"),
+    (Algorithm::EcdsaSecp256k1, "ecdsa-secp256k1"),
+    (Algorithm::Bls12381, "bls12-381"),
+];
+
+/// Parse the name of a key algorithm
+pub fn parse_algorithm(name: &str) -> Result<Algorithm, Error> {
+    ALGORITHMS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(algorithm, _)| *algorithm)
+        .ok_or_else(|| {
+            Kind::Encoding
+                .context(format!("unknown algorithm: {}", name))
+                .into()
+        })
+}
+
+/// Get the name of a key algorithm
+pub fn algorithm_name(algorithm: Algorithm) -> &'static str {
+    ALGORITHMS
+        .iter()
+        .find(|(a, _)| *a == algorithm)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+/// Names of hash algorithms, as used on the command line
+pub const HASH_ALGORITHMS: &[(HashAlgorithm, &str)] = &[
+    (HashAlgorithm::Intrinsic, "intrinsic"),
+    (HashAlgorithm::Sha256, "sha256"),
+];
+
+/// Parse the name of a hash algorithm
+pub fn parse_hash_algorithm(name: &str) -> Result<HashAlgorithm, Error> {
+    HASH_ALGORITHMS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(algorithm, _)| *algorithm)
+        .ok_or_else(|| {
+            Kind::Encoding
+                .context(format!("unknown hash algorithm: {}", name))
+                .into()
+        })
+}
+
+/// Load each of the given key files
+pub fn load_keypairs(paths: &[String]) -> Result<Vec<Keypair>, Error> {
+    paths.iter().map(ceremony::load_signing_key).collect()
+}
+
+/// Sign a request digest with each of the given keypairs
+pub fn sign_digest(digest: &[u8], keypairs: &[Keypair]) -> Result<Signatures, Error> {
+    let mut signatures = Signatures::new();
+
+    for keypair in keypairs {
+        signatures
+            .push(Signature::Ed25519(keypair.sign(digest).to_bytes()))
+            .map_err(|_| Kind::Encoding.context("too many signatures"))?;
+    }
+
+    Ok(signatures)
+}
+
+/// Identify ourselves to the device within the current session as the
+/// holder of the key in the given key file
+pub fn authenticate(armistice: &mut Armistice, path: &str) -> Result<(), Error> {
+    let keypair = ceremony::load_signing_key(path)?;
+
+    armistice.authenticate(PublicKey::Ed25519(keypair.public.to_bytes()), |digest| {
+        Signature::Ed25519(keypair.sign(digest).to_bytes())
+    })
+}
+
+/// Get the algorithm name and raw bytes of a public key
+pub fn public_key_parts(public_key: &PublicKey) -> (&'static str, &[u8]) {
+    match public_key {
+        PublicKey::Ed25519(bytes) => (algorithm_name(Algorithm::Ed25519), bytes),
+        PublicKey::EcdsaP256(bytes) => (algorithm_name(Algorithm::EcdsaP256), bytes),
+        PublicKey::EcdsaSecp256k1(bytes) => (algorithm_name(Algorithm::EcdsaSecp256k1), bytes),
+        PublicKey::Bls12381(bytes) => (algorithm_name(Algorithm::Bls12381), bytes),
+    }
+}
+
+/// Get the raw bytes of a signature
+pub fn signature_bytes(signature: &Signature) -> &[u8] {
+    match signature {
+        Signature::Ed25519(bytes)
+        | Signature::EcdsaP256(bytes)
+        | Signature::EcdsaSecp256k1(bytes) => bytes,
+        Signature::Bls12381(bytes) => bytes,
+    }
+}
+
+/// Get the current time as a TAI64N timestamp
+pub fn now() -> Timestamp {
+    ceremony::timestamp(SystemTime::now())
+}
+
+/// Decode a hex-encoded Ed25519 public key
+pub fn parse_ed25519_public_key(encoded: &str) -> Result<ed25519_dalek::PublicKey, Error> {
+    hex::decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| ed25519_dalek::PublicKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| {
+            Kind::Encoding
+                .context(format!("malformed Ed25519 public key: {}", encoded))
+                .into()
+        })
+}
+
+/// Decode a hex-encoded device session public key
+pub fn parse_device_key(encoded: &str) -> Result<session::PublicKey, Error> {
+    let mut bytes = [0u8; session::PUBLIC_KEY_SIZE];
+
+    hex::decode_to_slice(encoded.trim(), &mut bytes).map_err(|_| {
+        Kind::Encoding.context(format!("malformed device session key: {}", encoded))
+    })?;
+
+    Ok(bytes.into())
+}
+
+/// Parse an RFC 3339 date/time (e.g. `2020-05-21T00:00:00Z`)
+pub fn parse_time(rfc3339: &str) -> Result<SystemTime, Error> {
+    humantime::parse_rfc3339(rfc3339).map_err(|e| {
+        Kind::Encoding
+            .context(format!("invalid timestamp: {}", e))
+            .into()
+    })
+}
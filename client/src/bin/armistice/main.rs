@@ -0,0 +1,161 @@
+//! Armistice command-line tool: provision devices, manage keys, and sign
+//! messages using any of the client transports
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+mod commands;
+mod keys;
+mod output;
+
+use self::commands::Command;
+use armistice::{
+    error::{Error, Kind},
+    transport::{TcpTransport, Transport},
+    Armistice, KnownDevices,
+};
+use gumdrop::Options;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Command-line options
+#[derive(Debug, Options)]
+struct Opts {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// Output JSON rather than human-readable text
+    #[options(no_short, help = "output JSON")]
+    json: bool,
+
+    /// Connect over TCP (e.g. to a proxy or simulator)
+    #[options(
+        no_short,
+        meta = "ADDR",
+        help = "connect to Armistice at this TCP address"
+    )]
+    tcp: Option<String>,
+
+    /// Connect over a Unix domain socket (e.g. to a proxy or simulator)
+    #[options(
+        no_short,
+        meta = "PATH",
+        help = "connect to Armistice at this Unix domain socket"
+    )]
+    unix: Option<String>,
+
+    /// Pin the device's session public key
+    #[options(
+        no_short,
+        meta = "HEX",
+        help = "expected session public key of the device (hex)"
+    )]
+    device_key: Option<String>,
+
+    /// File of trusted device session keys
+    #[options(
+        no_short,
+        meta = "PATH",
+        help = "file of trusted device session keys (default: ~/.armistice/known_devices)"
+    )]
+    known_devices: Option<String>,
+
+    /// Trust the session key a device which isn't known yet presents
+    #[options(
+        no_short,
+        help = "trust and record the session key of a device which isn't known yet"
+    )]
+    trust_new_device: bool,
+
+    /// Subcommand to run
+    #[options(command)]
+    command: Option<Command>,
+}
+
+impl Opts {
+    /// Open a connection to Armistice using the selected transport (a USB
+    /// armory if none is given)
+    fn transport(&self) -> Result<Box<dyn Transport>, Error> {
+        match (&self.tcp, &self.unix) {
+            (Some(addr), None) => Ok(Box::new(TcpTransport::connect(addr.as_str())?)),
+            #[cfg(unix)]
+            (None, Some(path)) => Ok(Box::new(armistice::transport::UnixTransport::connect(
+                path,
+            )?)),
+            #[cfg(feature = "usbarmory")]
+            (None, None) => Ok(Box::new(armistice::transport::UsbTransport::open()?)),
+            _ => Err(Kind::Io
+                .context("specify at most one of --tcp or --unix")
+                .into()),
+        }
+    }
+
+    /// Connect to Armistice, pinning its session key if one was given (and
+    /// recording it among the known devices), or otherwise checking it
+    /// against the known devices
+    fn connect(&self) -> Result<Armistice, Error> {
+        let transport = self.transport()?;
+
+        if let Some(device_key) = &self.device_key {
+            let device_key = keys::parse_device_key(device_key)?;
+            let mut armistice = Armistice::with_device_key(transport, device_key);
+
+            // The device's ID is obtained within a session established with
+            // the pinned key, so it can be trusted
+            if let Ok(path) = self.known_devices_path() {
+                let device_id = armistice.info()?.device_id;
+                let mut known_devices = KnownDevices::load(path)?;
+
+                if known_devices.get(&device_id) != Some(&device_key) {
+                    known_devices.insert(device_id, device_key)?;
+                }
+            }
+
+            return Ok(armistice);
+        }
+
+        Ok(Armistice::with_known_devices(
+            transport,
+            KnownDevices::load(self.known_devices_path()?)?,
+            self.trust_new_device,
+        ))
+    }
+
+    /// Get the path of the known devices file
+    fn known_devices_path(&self) -> Result<PathBuf, Error> {
+        match &self.known_devices {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => env::var_os("HOME")
+                .map(|home| Path::new(&home).join(".armistice").join("known_devices"))
+                .ok_or_else(|| {
+                    Kind::Session
+                        .context("HOME is unset: pass --device-key or --known-devices")
+                        .into()
+                }),
+        }
+    }
+}
+
+fn main() {
+    let opts = Opts::parse_args_default_or_exit();
+
+    let command = opts.command.as_ref().unwrap_or_else(|| {
+        eprintln!("{}\n\nCommands:\n{}", Opts::usage(), Command::usage());
+        process::exit(2);
+    });
+
+    let result = command.run(|| opts.connect());
+
+    match result {
+        Ok(output) if opts.json => println!("{}", output.to_json()),
+        Ok(output) => print!("{}", output),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Command output, printed either for humans or as JSON
+
+use crate::keys;
+use armistice::schema::{domain, key, PublicKey};
+use serde_json::{Map, Value};
+use std::fmt::{self, Display};
+
+/// Output of a command: an ordered list of named fields
+#[derive(Debug, Default)]
+pub struct Output {
+    /// Output fields
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Output {
+    /// Create empty output
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a field to the output
+    pub fn field(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.fields.push((name, value.into()));
+        self
+    }
+
+    /// Serialize the output as JSON
+    pub fn to_json(&self) -> String {
+        let map = self
+            .fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<Map<_, _>>();
+
+        Value::Object(map).to_string()
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.fields {
+            match value {
+                Value::Array(items) if items.iter().all(Value::is_object) => {
+                    writeln!(f, "{}:", name)?;
+
+                    for item in items {
+                        let pairs = item
+                            .as_object()
+                            .unwrap()
+                            .iter()
+                            .map(|(k, v)| format!("{}={}", k, human(v)))
+                            .collect::<Vec<_>>();
+
+                        writeln!(f, "  {}", pairs.join(" "))?;
+                    }
+                }
+                _ => writeln!(f, "{}: {}", name, human(value))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Output describing a key within a domain
+pub struct KeyOutput<'a> {
+    /// Domain containing the key
+    domain: domain::Id,
+
+    /// Slot of the key within the domain
+    slot: key::Slot,
+
+    /// Public key
+    public_key: &'a PublicKey,
+}
+
+impl<'a> KeyOutput<'a> {
+    /// Describe the key in the given domain and slot
+    pub fn new(domain: domain::Id, slot: key::Slot, public_key: &'a PublicKey) -> Self {
+        KeyOutput {
+            domain,
+            slot,
+            public_key,
+        }
+    }
+}
+
+impl From<KeyOutput<'_>> for Output {
+    fn from(key: KeyOutput<'_>) -> Output {
+        let (algorithm, bytes) = keys::public_key_parts(key.public_key);
+
+        Output::new()
+            .field("domain", key.domain)
+            .field("slot", key.slot)
+            .field("algorithm", algorithm)
+            .field("public_key", hex::encode(bytes))
+    }
+}
+
+/// Format a value for humans
+fn human(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(human).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
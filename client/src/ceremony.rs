@@ -0,0 +1,468 @@
+//! Offline root key ceremonies: build a provisioning or root rotation
+//! request, collect signatures from the root key holders, and assemble the
+//! final signed request once a threshold of them have signed.
+//!
+//! Ceremonies are stored in files which are passed from holder to holder
+//! (TOML, or JSON if the filename ends in `.json`):
+//!
+//! ```toml
+//! device_id = "<hex device ID>"
+//! counter = 1
+//! threshold = 2
+//! root_keys = ["<hex Ed25519 public key>", "<hex Ed25519 public key>"]
+//! timestamp = "2020-05-21T00:00:00Z"
+//!
+//! # Root rotation only: the new root version and the current root keys
+//! version = 2
+//! previous_threshold = 1
+//! previous_root_keys = ["<hex Ed25519 public key>"]
+//!
+//! [[signatures]]
+//! public_key = "<hex Ed25519 public key>"
+//! signature = "<hex Ed25519 signature over the authorization digest>"
+//! ```
+//!
+//! Each holder signs the request's authorization digest: its `veriform`
+//! digest (computed from everything except the signatures) bound to the
+//! target device's ID and the authorization counter following the device's
+//! current one (see `armistice info`). Signing keys are stored in key files
+//! containing a hex-encoded 32-byte Ed25519 seed.
+
+use crate::error::{Error, Kind};
+use armistice_schema::{
+    authorization::{Binding, DeviceId},
+    provision, root,
+    signature::Signatures,
+    threshold,
+    veriform::Decoder,
+    veriform::Sha256Digest,
+    Message, PublicKey, Request, Signature, ThresholdKeySet, Timestamp,
+};
+use ed25519_dalek::{Keypair, SecretKey, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// TAI64 label of the Unix epoch (1970-01-01 00:00:00 TAI, which is 10
+/// seconds ahead of UTC)
+const TAI64_EPOCH: u64 = (1 << 62) + 10;
+
+/// Root key ceremony
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Ceremony {
+    /// Hex-encoded ID of the device the request is intended for
+    pub device_id: String,
+
+    /// Authorization counter the request is bound to
+    pub counter: u64,
+
+    /// Number of signatures required to perform root key operations
+    pub threshold: u64,
+
+    /// Hex-encoded Ed25519 root public keys
+    pub root_keys: Vec<String>,
+
+    /// Date/time of the ceremony (RFC 3339)
+    pub timestamp: String,
+
+    /// New root version number (root rotation only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+
+    /// Threshold of the current root keys (root rotation only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_threshold: Option<u64>,
+
+    /// Hex-encoded Ed25519 current root public keys (root rotation only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_root_keys: Vec<String>,
+
+    /// Signatures collected from root key holders
+    #[serde(default)]
+    pub signatures: Vec<PartialSignature>,
+}
+
+/// Signature over a ceremony's authorization digest from one root key holder
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PartialSignature {
+    /// Hex-encoded Ed25519 public key of the signer
+    pub public_key: String,
+
+    /// Hex-encoded Ed25519 signature
+    pub signature: String,
+}
+
+/// Progress of a ceremony towards its signature thresholds
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Status {
+    /// Authorization digest being signed
+    pub digest: Sha256Digest,
+
+    /// Number of (new) root keys which have signed
+    pub signers: u64,
+
+    /// Number of signatures required from the (new) root keys
+    pub threshold: u64,
+
+    /// Number of current root keys which have signed (root rotation only)
+    pub previous_signers: Option<u64>,
+
+    /// Number of signatures required from the current root keys (root
+    /// rotation only)
+    pub previous_threshold: Option<u64>,
+}
+
+impl Status {
+    /// Have enough root key holders signed to assemble the request?
+    pub fn is_complete(&self) -> bool {
+        let previous_complete = match (self.previous_signers, self.previous_threshold) {
+            (Some(signers), Some(threshold)) => signers >= threshold,
+            (_, None) => true,
+            (None, Some(_)) => false,
+        };
+
+        self.signers >= self.threshold && previous_complete
+    }
+}
+
+impl Ceremony {
+    /// Begin a ceremony to provision a device with the given root keys
+    pub fn provision(
+        binding: Binding,
+        threshold: u64,
+        root_keys: &[ed25519_dalek::PublicKey],
+        timestamp: SystemTime,
+    ) -> Self {
+        Ceremony {
+            device_id: hex::encode(binding.device_id),
+            counter: binding.counter,
+            threshold,
+            root_keys: root_keys.iter().map(hex::encode).collect(),
+            timestamp: humantime::format_rfc3339(timestamp).to_string(),
+            version: None,
+            previous_threshold: None,
+            previous_root_keys: vec![],
+            signatures: vec![],
+        }
+    }
+
+    /// Begin a ceremony to rotate a device's root keys: the rotation must
+    /// be signed by a threshold of both the current and the new root keys
+    pub fn rotate_root(
+        binding: Binding,
+        version: u64,
+        threshold: u64,
+        root_keys: &[ed25519_dalek::PublicKey],
+        previous_threshold: u64,
+        previous_root_keys: &[ed25519_dalek::PublicKey],
+        timestamp: SystemTime,
+    ) -> Self {
+        Ceremony {
+            version: Some(version),
+            previous_threshold: Some(previous_threshold),
+            previous_root_keys: previous_root_keys.iter().map(hex::encode).collect(),
+            ..Self::provision(binding, threshold, root_keys, timestamp)
+        }
+    }
+
+    /// Load a ceremony file (JSON if its extension is `.json`, otherwise TOML)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let result = if is_json(path) {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        };
+
+        result.map_err(|e| {
+            Kind::Encoding
+                .context(format!("malformed ceremony file {}: {}", path.display(), e))
+                .into()
+        })
+    }
+
+    /// Save this ceremony to a file (JSON if its extension is `.json`,
+    /// otherwise TOML)
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        let contents = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+        } else {
+            toml::to_string(self).map_err(|e| e.to_string())
+        }
+        .map_err(|e| Kind::Encoding.context(e))?;
+
+        Ok(fs::write(path, contents)?)
+    }
+
+    /// Is this a root rotation ceremony (as opposed to provisioning)?
+    pub fn is_rotation(&self) -> bool {
+        self.version.is_some()
+    }
+
+    /// Get the binding of this ceremony's request to the target device
+    pub fn binding(&self) -> Result<Binding, Error> {
+        let mut device_id = DeviceId::default();
+
+        hex::decode_to_slice(self.device_id.trim(), &mut device_id).map_err(|_| {
+            Kind::Encoding.context(format!("malformed device ID: {}", self.device_id))
+        })?;
+
+        Ok(Binding {
+            device_id,
+            counter: self.counter,
+        })
+    }
+
+    /// Get the unsigned request this ceremony is signing
+    pub fn request(&self) -> Result<Request, Error> {
+        let timestamp = parse_timestamp(&self.timestamp)?;
+        let binding = self.binding()?;
+
+        let request = match self.version {
+            Some(version) => Request::RootRotate(root::SignedRotateRequest {
+                request: round_trip(&root::RotateRequest {
+                    version,
+                    key_set: ThresholdKeySet {
+                        threshold: self.threshold,
+                        public_keys: public_keys(&self.root_keys)?,
+                    },
+                    timestamp,
+                    digest: None,
+                })?,
+                signatures: root::RotationSignatures::new(),
+                binding,
+            }),
+            None => Request::Provision(provision::SignedRequest {
+                request: round_trip(&provision::Request {
+                    root_key_threshold: self.threshold,
+                    root_keys: public_keys(&self.root_keys)?,
+                    timestamp,
+                    digest: None,
+                })?,
+                signatures: Signatures::new(),
+                binding,
+            }),
+        };
+
+        Ok(request)
+    }
+
+    /// Compute the authorization digest which root key holders sign
+    pub fn digest(&self) -> Result<Sha256Digest, Error> {
+        let digest = match self.request()? {
+            Request::RootRotate(rotate) => rotate.request.digest,
+            Request::Provision(provision) => provision.request.digest,
+            _ => unreachable!(),
+        };
+
+        // Digests are computed by `veriform` when the request is decoded
+        Ok(self
+            .binding()?
+            .digest(&digest.expect("digest not computed")))
+    }
+
+    /// Sign the request with the given root key, replacing any signature
+    /// previously made by the same key
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<(), Error> {
+        let public_key = hex::encode(keypair.public);
+
+        if !self.is_eligible(&public_key) {
+            return Err(Kind::Ceremony
+                .context(format!("{} is not a root key of this ceremony", public_key))
+                .into());
+        }
+
+        let signature = hex::encode(keypair.sign(&self.digest()?).to_bytes());
+        self.signatures.retain(|s| s.public_key != public_key);
+        self.signatures.push(PartialSignature {
+            public_key,
+            signature,
+        });
+
+        Ok(())
+    }
+
+    /// Verify the signatures collected so far, returning the ceremony's
+    /// progress towards its thresholds
+    pub fn verify(&self) -> Result<Status, Error> {
+        let digest = self.digest()?;
+
+        for partial in &self.signatures {
+            let public_key = parse_public_key(&partial.public_key)?;
+            let signature = parse_signature(&partial.signature)?;
+
+            if !self.is_eligible(&hex::encode(public_key)) {
+                return Err(Kind::Ceremony
+                    .context(format!("{} is not a root key", partial.public_key))
+                    .into());
+            }
+
+            if public_key.verify(&digest, &signature).is_err() {
+                return Err(Kind::Ceremony
+                    .context(format!("invalid signature from {}", partial.public_key))
+                    .into());
+            }
+        }
+
+        if self.is_rotation() && self.previous_threshold.is_none() {
+            return Err(Kind::Ceremony
+                .context("root rotation ceremony has no previous threshold")
+                .into());
+        }
+
+        Ok(Status {
+            digest,
+            signers: self.count_signers(&self.root_keys),
+            threshold: self.threshold,
+            previous_signers: self
+                .previous_threshold
+                .map(|_| self.count_signers(&self.previous_root_keys)),
+            previous_threshold: self.previous_threshold,
+        })
+    }
+
+    /// Assemble the final signed request, ensuring all signatures are valid
+    /// and a threshold of root key holders have signed
+    pub fn assemble(&self) -> Result<Request, Error> {
+        let status = self.verify()?;
+
+        if !status.is_complete() {
+            return Err(Kind::Ceremony
+                .context("insufficient signatures to meet threshold")
+                .into());
+        }
+
+        let mut signatures = vec![];
+
+        for partial in &self.signatures {
+            let signature = parse_signature(&partial.signature)?;
+            signatures.push(Signature::Ed25519(signature.to_bytes()));
+        }
+
+        let mut request = self.request()?;
+
+        let result = match &mut request {
+            Request::RootRotate(rotate) => rotate.signatures.extend_from_slice(&signatures),
+            Request::Provision(provision) => provision.signatures.extend_from_slice(&signatures),
+            _ => unreachable!(),
+        };
+
+        result.map_err(|_| Kind::Ceremony.context("too many signatures"))?;
+        Ok(request)
+    }
+
+    /// Is the given hex-encoded public key allowed to sign this ceremony?
+    fn is_eligible(&self, public_key: &str) -> bool {
+        self.root_keys
+            .iter()
+            .chain(self.previous_root_keys.iter())
+            .any(|key| key.eq_ignore_ascii_case(public_key))
+    }
+
+    /// Count how many of the given keys have signed
+    fn count_signers(&self, keys: &[String]) -> u64 {
+        keys.iter()
+            .filter(|key| {
+                self.signatures
+                    .iter()
+                    .any(|s| s.public_key.eq_ignore_ascii_case(key))
+            })
+            .count() as u64
+    }
+}
+
+/// Load an Ed25519 signing key from a key file containing its hex-encoded
+/// 32-byte seed
+pub fn load_signing_key(path: impl AsRef<Path>) -> Result<Keypair, Error> {
+    let path = path.as_ref();
+    let encoded = fs::read_to_string(path)?;
+
+    let secret = hex::decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| SecretKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| {
+            Kind::Encoding.context(format!("malformed Ed25519 key file: {}", path.display()))
+        })?;
+
+    let public = (&secret).into();
+    Ok(Keypair { secret, public })
+}
+
+/// Convert a system time into a TAI64N timestamp
+pub fn timestamp(time: SystemTime) -> Timestamp {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before Unix epoch");
+
+    let mut bytes = [0u8; 12];
+    bytes[..8].copy_from_slice(&(TAI64_EPOCH + since_epoch.as_secs()).to_be_bytes());
+    bytes[8..].copy_from_slice(&since_epoch.subsec_nanos().to_be_bytes());
+    Timestamp::from_slice(&bytes).expect("invalid TAI64N timestamp")
+}
+
+/// Parse an RFC 3339 date/time (e.g. `2020-05-21T00:00:00Z`) into a TAI64N
+/// timestamp
+fn parse_timestamp(rfc3339: &str) -> Result<Timestamp, Error> {
+    humantime::parse_rfc3339(rfc3339)
+        .map(timestamp)
+        .map_err(|e| {
+            Kind::Encoding
+                .context(format!("invalid timestamp: {}", e))
+                .into()
+        })
+}
+
+/// Decode a hex-encoded Ed25519 public key
+fn parse_public_key(encoded: &str) -> Result<ed25519_dalek::PublicKey, Error> {
+    hex::decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| ed25519_dalek::PublicKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| {
+            Kind::Encoding
+                .context(format!("malformed Ed25519 public key: {}", encoded))
+                .into()
+        })
+}
+
+/// Decode a hex-encoded Ed25519 signature
+fn parse_signature(encoded: &str) -> Result<ed25519_dalek::Signature, Error> {
+    let mut bytes = [0u8; 64];
+
+    hex::decode_to_slice(encoded.trim(), &mut bytes)
+        .map_err(|_| Kind::Encoding.context(format!("malformed Ed25519 signature: {}", encoded)))?;
+
+    Ok(ed25519_dalek::Signature::from(bytes))
+}
+
+/// Decode a list of hex-encoded Ed25519 public keys
+fn public_keys(encoded: &[String]) -> Result<threshold::PublicKeys, Error> {
+    let mut public_keys = threshold::PublicKeys::new();
+
+    for key in encoded {
+        public_keys
+            .push(PublicKey::Ed25519(parse_public_key(key)?.to_bytes()))
+            .map_err(|_| Kind::Encoding.context("too many root keys"))?;
+    }
+
+    Ok(public_keys)
+}
+
+/// Round trip a request through the encoder, which causes `veriform` to
+/// compute its digest
+fn round_trip<M: Message>(message: &M) -> Result<M, Error> {
+    Ok(M::decode(&mut Decoder::new(), &message.encode_vec()?)?)
+}
+
+/// Is the given path a JSON file?
+fn is_json(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "json").unwrap_or(false)
+}
@@ -1,6 +1,7 @@
 //! Error types
 
 use anomaly::BoxError;
+use armistice_schema::error::Code;
 use displaydoc::Display;
 use std::{
     fmt::{self, Display},
@@ -10,11 +11,26 @@ use std::{
 /// Kinds of errors
 #[derive(Clone, Debug, Display, Eq, PartialEq)]
 pub enum Kind {
+    /// Ceremony error
+    Ceremony,
+
+    /// Device error: {0}
+    Device(Code),
+
     /// Encoding error
     Encoding,
 
+    /// I/O error
+    Io,
+
+    /// Session error
+    Session,
+
     /// USB error
     Usb,
+
+    /// Incompatible version
+    Version,
 }
 
 impl Kind {
@@ -33,6 +49,20 @@ pub type Context = anomaly::Context<Kind>;
 #[derive(Debug)]
 pub struct Error(Box<Context>);
 
+impl Error {
+    /// Did this error occur because the other end closed the connection?
+    pub fn is_disconnect(&self) -> bool {
+        use std::error::Error as _;
+
+        self.kind() == &Kind::Io
+            && self
+                .source()
+                .and_then(|source| source.downcast_ref::<std::io::Error>())
+                .map(|e| e.kind() == std::io::ErrorKind::UnexpectedEof)
+                .unwrap_or(false)
+    }
+}
+
 impl Deref for Error {
     type Target = Context;
 
@@ -65,6 +95,43 @@ impl From<Context> for Error {
     }
 }
 
+#[cfg(feature = "in-process")]
+impl From<armistice_core::Error> for Error {
+    fn from(err: armistice_core::Error) -> Error {
+        Kind::Device(err.into()).context(err.to_string()).into()
+    }
+}
+
+impl From<armistice_schema::error::Response> for Error {
+    fn from(response: armistice_schema::error::Response) -> Error {
+        let kind = Kind::Device(response.code());
+
+        match response.detail() {
+            Some(detail) => kind.context(detail.to_owned()).into(),
+            None => kind.into(),
+        }
+    }
+}
+
+impl From<armistice_schema::framing::Error> for Error {
+    fn from(err: armistice_schema::framing::Error) -> Error {
+        Kind::Encoding.context(err.as_str()).into()
+    }
+}
+
+impl From<armistice_schema::session::Error> for Error {
+    fn from(err: armistice_schema::session::Error) -> Error {
+        Kind::Session.context(err.as_str()).into()
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Kind::Io.context(err).into()
+    }
+}
+
+#[cfg(feature = "usbarmory")]
 impl From<rusb::Error> for Error {
     fn from(err: rusb::Error) -> Error {
         Kind::Usb.context(err).into()
@@ -0,0 +1,88 @@
+//! Known devices: session keys of devices trusted the first time a session
+//! was established with them, persisted so later connections to the same
+//! device refuse any other key (much like SSH's `known_hosts`).
+//!
+//! Known devices files contain a line for each device with its hex-encoded
+//! ID and session public key, separated by a space.
+
+use crate::error::{Error, Kind};
+use armistice_schema::{
+    authorization::DeviceId,
+    session::{PublicKey, PUBLIC_KEY_SIZE},
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Session keys of known devices, persisted to a file
+#[derive(Clone, Debug)]
+pub struct KnownDevices {
+    /// File the known devices are persisted to
+    path: PathBuf,
+
+    /// Session public keys of known devices, by device ID
+    keys: BTreeMap<DeviceId, PublicKey>,
+}
+
+impl KnownDevices {
+    /// Load the known devices persisted to the given file (there are none
+    /// if it doesn't exist yet)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_owned();
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut keys = BTreeMap::new();
+
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let malformed = || {
+                Kind::Encoding.context(format!("malformed entry in {}: {}", path.display(), line))
+            };
+
+            let mut device_id = DeviceId::default();
+            let mut session_key = [0u8; PUBLIC_KEY_SIZE];
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+
+            match fields.as_slice() {
+                [id, key] => {
+                    hex::decode_to_slice(id, &mut device_id).map_err(|_| malformed())?;
+                    hex::decode_to_slice(key, &mut session_key).map_err(|_| malformed())?;
+                }
+                _ => return Err(malformed().into()),
+            }
+
+            keys.insert(device_id, PublicKey::from(session_key));
+        }
+
+        Ok(KnownDevices { path, keys })
+    }
+
+    /// Get the session key of the device with the given ID, if it's known
+    pub fn get(&self, device_id: &DeviceId) -> Option<&PublicKey> {
+        self.keys.get(device_id)
+    }
+
+    /// Record the session key of a device, persisting it to the file
+    pub fn insert(&mut self, device_id: DeviceId, session_key: PublicKey) -> Result<(), Error> {
+        self.keys.insert(device_id, session_key);
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = self
+            .keys
+            .iter()
+            .map(|(id, key)| format!("{} {}\n", hex::encode(id), hex::encode(key.as_bytes())))
+            .collect::<String>();
+
+        fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
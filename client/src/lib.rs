@@ -5,10 +5,18 @@
 #![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
 
 pub mod armistice;
+#[cfg(feature = "ceremony")]
+pub mod ceremony;
 pub mod error;
+pub mod known_devices;
+pub mod proxy;
+pub mod transport;
 
 #[cfg(feature = "usbarmory")]
 pub mod usbarmory;
 
-pub use crate::{armistice::Armistice, error::Error};
+pub use crate::{
+    armistice::Armistice, error::Error, known_devices::KnownDevices, proxy::Proxy,
+    transport::Transport,
+};
 pub use armistice_schema as schema;
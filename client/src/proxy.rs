@@ -0,0 +1,112 @@
+//! Network proxy: expose a device (e.g. a USB armory attached to this host)
+//! to clients connecting over TCP or a Unix domain socket.
+//!
+//! Clients speak the same framed wire protocol as [`StreamTransport`]. Each
+//! connection is served by its own thread, with requests serialized onto the
+//! single upstream [`Transport`] one message at a time. Request and response
+//! messages are forwarded verbatim: the proxy never decodes them.
+//!
+//! [`StreamTransport`]: crate::transport::StreamTransport
+
+use crate::{
+    error::Error,
+    transport::{
+        stream::{read_message, write_message},
+        Transport,
+    },
+};
+use armistice_schema::{error, framing::Reassembler, Message, Response};
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+/// Network proxy which forwards requests to an upstream [`Transport`]
+#[derive(Clone)]
+pub struct Proxy {
+    /// Upstream transport shared by all connections
+    upstream: Arc<Mutex<Box<dyn Transport + Send>>>,
+}
+
+impl Proxy {
+    /// Create a proxy which forwards requests to the given transport
+    pub fn new(upstream: impl Transport + Send + 'static) -> Self {
+        Proxy {
+            upstream: Arc::new(Mutex::new(Box::new(upstream))),
+        }
+    }
+
+    /// Accept connections on the given TCP listener, serving each in its
+    /// own thread
+    pub fn listen_tcp(&self, listener: TcpListener) -> Result<(), Error> {
+        for stream in listener.incoming() {
+            self.spawn(stream?);
+        }
+
+        Ok(())
+    }
+
+    /// Accept connections on the given Unix domain socket listener, serving
+    /// each in its own thread
+    #[cfg(unix)]
+    pub fn listen_unix(&self, listener: UnixListener) -> Result<(), Error> {
+        for stream in listener.incoming() {
+            self.spawn(stream?);
+        }
+
+        Ok(())
+    }
+
+    /// Forward requests received over the given stream (i.e. a client
+    /// connection) until it's closed
+    pub fn serve(&self, mut stream: impl Read + Write) -> Result<(), Error> {
+        let mut reassembler = Reassembler::new();
+
+        loop {
+            let request = match read_message(&mut stream, &mut reassembler) {
+                Ok(request) => request,
+                Err(e) if e.is_disconnect() => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            let response = self.forward(&request);
+            write_message(&mut stream, &response)?;
+        }
+    }
+
+    /// Forward a request upstream, returning the response (or an error
+    /// response if the upstream transport failed)
+    fn forward(&self, request: &[u8]) -> Vec<u8> {
+        // A panic while holding the lock can't leave the transport in a worse
+        // state than an I/O error would, so keep going if it's poisoned
+        let mut upstream = self
+            .upstream
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        upstream.send_request(request).unwrap_or_else(|e| {
+            Response::from(error::Response::new(
+                error::Code::Internal,
+                &format!("proxy: {}", e),
+            ))
+            .encode_vec()
+            .expect("couldn't encode error response")
+        })
+    }
+
+    /// Serve a client connection in a new thread
+    fn spawn(&self, stream: impl Read + Write + Send + 'static) {
+        let proxy = self.clone();
+
+        thread::spawn(move || {
+            if let Err(e) = proxy.serve(stream) {
+                eprintln!("armistice-proxy: connection error: {}", e);
+            }
+        });
+    }
+}
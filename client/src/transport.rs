@@ -0,0 +1,32 @@
+//! Transports: connection methods for communicating with Armistice
+
+#[cfg(feature = "in-process")]
+mod in_process;
+pub mod stream;
+#[cfg(feature = "usbarmory")]
+mod usb;
+
+#[cfg(feature = "embedded")]
+pub use self::in_process::EmbeddedTransport;
+#[cfg(feature = "in-process")]
+pub use self::in_process::InProcessTransport;
+#[cfg(unix)]
+pub use self::stream::UnixTransport;
+pub use self::stream::{StreamTransport, TcpTransport, PACKET_SIZE};
+#[cfg(feature = "usbarmory")]
+pub use self::usb::UsbTransport;
+
+use crate::error::Error;
+
+/// Transports carry encoded requests to Armistice and return the encoded
+/// responses
+pub trait Transport {
+    /// Send an encoded request to Armistice, returning the encoded response
+    fn send_request(&mut self, request: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send_request(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        (**self).send_request(request)
+    }
+}
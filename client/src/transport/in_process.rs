@@ -0,0 +1,102 @@
+//! In-process transport: embed Armistice Core directly into a host-side
+//! application (useful for prototyping and CI)
+
+use super::Transport;
+use crate::error::Error;
+use armistice_core::{
+    block_cipher::{
+        generic_array::{typenum::U16, ArrayLength, GenericArray},
+        BlockCipher,
+    },
+    rand_core::{CryptoRng, RngCore},
+    Armistice, MonotonicCounter, Storage,
+};
+use armistice_schema::{error, veriform::Decoder, Message, Request, Response};
+
+#[cfg(feature = "embedded")]
+use {
+    aes::{block_cipher::NewBlockCipher, Aes128},
+    armistice_core::{counter::MemoryCounter, storage::MemoryStorage},
+    rand_core::OsRng,
+};
+
+/// In-process transport which embeds Armistice Core with a software root key
+/// and in-memory storage (state is lost when it's dropped)
+#[cfg(feature = "embedded")]
+pub type EmbeddedTransport = InProcessTransport<Aes128, OsRng, MemoryStorage, MemoryCounter>;
+
+/// In-process transport: requests are decoded and handled by an embedded
+/// instance of Armistice Core, exactly as they would be on a device
+pub struct InProcessTransport<B, R, S, C>
+where
+    B: BlockCipher<BlockSize = U16>,
+    B::ParBlocks: ArrayLength<GenericArray<u8, B::BlockSize>>,
+    R: CryptoRng + RngCore,
+    S: Storage,
+    C: MonotonicCounter,
+{
+    /// Embedded Armistice Core
+    armistice: Armistice<B, R, S, C>,
+}
+
+impl<B, R, S, C> InProcessTransport<B, R, S, C>
+where
+    B: BlockCipher<BlockSize = U16>,
+    B::ParBlocks: ArrayLength<GenericArray<u8, B::BlockSize>>,
+    R: CryptoRng + RngCore,
+    S: Storage,
+    C: MonotonicCounter,
+{
+    /// Create a new in-process transport which wraps the given Armistice Core
+    pub fn new(armistice: Armistice<B, R, S, C>) -> Self {
+        InProcessTransport { armistice }
+    }
+
+    /// Get the embedded Armistice Core
+    pub fn armistice(&self) -> &Armistice<B, R, S, C> {
+        &self.armistice
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl EmbeddedTransport {
+    /// Embed Armistice Core with a randomly generated software root key
+    pub fn generate() -> Result<Self, Error> {
+        let mut root_key = [0u8; 16];
+        OsRng.fill_bytes(&mut root_key);
+        Self::from_root_key(&root_key)
+    }
+
+    /// Embed Armistice Core with the given software root key
+    pub fn from_root_key(root_key: &[u8; 16]) -> Result<Self, Error> {
+        let armistice = Armistice::new(
+            Aes128::new(root_key.into()),
+            OsRng,
+            MemoryStorage::new(),
+            MemoryCounter::new(),
+        )?;
+
+        Ok(Self::new(armistice))
+    }
+}
+
+impl<B, R, S, C> Transport for InProcessTransport<B, R, S, C>
+where
+    B: BlockCipher<BlockSize = U16>,
+    B::ParBlocks: ArrayLength<GenericArray<u8, B::BlockSize>>,
+    R: CryptoRng + RngCore,
+    S: Storage,
+    C: MonotonicCounter,
+{
+    fn send_request(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let response = match Request::decode(&mut Decoder::new(), request) {
+            Ok(request) => self
+                .armistice
+                .handle_request(request)
+                .unwrap_or_else(Response::from),
+            Err(_) => Response::from(error::Response::from(error::Code::Decode)),
+        };
+
+        Ok(response.encode_vec()?)
+    }
+}
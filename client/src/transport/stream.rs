@@ -0,0 +1,110 @@
+//! Stream transports: communicate with Armistice over a byte stream (e.g. TCP)
+//!
+//! Messages are split into the same fragments used over USB, with packets of
+//! [`PACKET_SIZE`] bytes written back-to-back. Since streams don't preserve
+//! packet boundaries, the size of each fragment is determined from its header
+//! (see [`framing::payload_len`]).
+
+use super::Transport;
+use crate::error::Error;
+use armistice_schema::framing::{self, Fragment, MaxMessageSize, Reassembler, HEADER_SIZE};
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+
+/// Size of the packets messages are split into (the same as the USB
+/// armory's bulk endpoints)
+pub const PACKET_SIZE: usize = 512;
+
+/// Transport over a byte stream
+#[derive(Debug)]
+pub struct StreamTransport<S: Read + Write> {
+    /// Underlying stream
+    stream: S,
+}
+
+/// Transport over TCP (e.g. to a proxy or simulator)
+pub type TcpTransport = StreamTransport<TcpStream>;
+
+/// Transport over a Unix domain socket (e.g. to a proxy or simulator)
+#[cfg(unix)]
+pub type UnixTransport = StreamTransport<UnixStream>;
+
+impl<S: Read + Write> StreamTransport<S> {
+    /// Create a new transport which communicates over the given stream
+    pub fn new(stream: S) -> Self {
+        StreamTransport { stream }
+    }
+
+    /// Get the underlying stream
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl TcpTransport {
+    /// Connect to Armistice at the given address
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, Error> {
+        Ok(Self::new(TcpStream::connect(addr)?))
+    }
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    /// Connect to Armistice listening on the Unix domain socket at the given
+    /// path
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::new(UnixStream::connect(path)?))
+    }
+}
+
+impl<S: Read + Write> Transport for StreamTransport<S> {
+    fn send_request(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        write_message(&mut self.stream, request)?;
+        read_message(&mut self.stream, &mut Reassembler::new())
+    }
+}
+
+/// Split a message into fragments and write them to the given stream
+pub fn write_message(stream: &mut impl Write, message: &[u8]) -> Result<(), Error> {
+    let mut packet = [0u8; PACKET_SIZE];
+
+    for fragment in framing::fragments(message, PACKET_SIZE)? {
+        stream.write_all(fragment.encode(&mut packet)?)?;
+    }
+
+    Ok(stream.flush()?)
+}
+
+/// Read fragments from the given stream until a message has been
+/// reassembled
+pub fn read_message(
+    stream: &mut impl Read,
+    reassembler: &mut Reassembler<MaxMessageSize>,
+) -> Result<Vec<u8>, Error> {
+    let mut packet = [0u8; PACKET_SIZE];
+
+    loop {
+        let len = read_packet(stream, &mut packet)?;
+
+        if let Some(message) = reassembler.push(&packet[..len])? {
+            return Ok(message.to_vec());
+        }
+    }
+}
+
+/// Read the packet containing the next fragment from the given stream,
+/// returning its length
+pub fn read_packet(stream: &mut impl Read, packet: &mut [u8; PACKET_SIZE]) -> Result<usize, Error> {
+    stream.read_exact(&mut packet[..HEADER_SIZE])?;
+
+    let header = Fragment::parse(&packet[..HEADER_SIZE])?;
+    let len = HEADER_SIZE + framing::payload_len(header.sequence, header.length, PACKET_SIZE)?;
+
+    stream.read_exact(&mut packet[HEADER_SIZE..len])?;
+    Ok(len)
+}
@@ -0,0 +1,42 @@
+//! USB transport: communicate with Armistice running on a USB armory MkII
+
+use super::Transport;
+use crate::{error::Error, usbarmory::BulkPair};
+use armistice_schema::framing::{self, MaxMessageSize, Reassembler};
+
+/// USB transport: requests and responses are split into fragments which
+/// fit within the packet size of the device's bulk endpoints
+pub struct UsbTransport {
+    /// USB armory connection
+    usb: BulkPair,
+}
+
+impl UsbTransport {
+    /// Open a connection to a USB armory running Armistice
+    pub fn open() -> Result<Self, Error> {
+        let usb = BulkPair::open(consts::VID, consts::PID)?;
+        Ok(UsbTransport { usb })
+    }
+}
+
+impl Transport for UsbTransport {
+    fn send_request(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let packet_size = self.usb.out_max_packet_size().into();
+        let mut packet = vec![0; packet_size];
+
+        for fragment in framing::fragments(request, packet_size)? {
+            self.usb.write(fragment.encode(&mut packet)?)?;
+        }
+
+        let mut reassembler = Reassembler::<MaxMessageSize>::new();
+        let mut buf = vec![0; self.usb.in_max_packet_size().into()];
+
+        loop {
+            let packet = self.usb.read(&mut buf)?;
+
+            if let Some(response) = reassembler.push(packet)? {
+                return Ok(response.to_vec());
+            }
+        }
+    }
+}
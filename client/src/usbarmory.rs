@@ -122,20 +122,23 @@ impl BulkPair {
     }
 
     /// Returns the max packet size of the IN endpoint
-    #[allow(dead_code)]
     pub fn in_max_packet_size(&self) -> u16 {
         self.in_max_packet_size
     }
 
+    /// Returns the max packet size of the OUT endpoint
+    pub fn out_max_packet_size(&self) -> u16 {
+        self.out_max_packet_size
+    }
+
     /// Writes data into the OUT endpoint
     ///
     /// *NOTE* The length of the `bytes` argument cannot exceed the endpoint
-    /// maximum packet size
+    /// maximum packet size: larger messages must be split into fragments
+    /// (see `armistice_schema::framing`)
     pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
-        // XXX alternatively we could split `bytes` in `out_max_packet_size`
-        // chunks
         assert!(
-            bytes.len() < self.out_max_packet_size.into(),
+            bytes.len() <= self.out_max_packet_size.into(),
             "the length of `write` argument cannot exceed the max_packet_size ({} bytes) ",
             self.out_max_packet_size
         );
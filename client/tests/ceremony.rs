@@ -0,0 +1,174 @@
+//! Offline root key ceremony tests: build, sign, and assemble requests and
+//! submit them to Armistice Core embedded in-process
+
+#![cfg(all(feature = "ceremony", feature = "embedded"))]
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    ceremony::Ceremony,
+    error::Kind,
+    schema::{authorization::Binding, error::Code},
+    transport::EmbeddedTransport,
+    Armistice,
+};
+use ed25519_dalek::{Keypair, SecretKey};
+use std::{
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Create an Ed25519 keypair from the given seed byte
+fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = (&secret).into();
+    Keypair { secret, public }
+}
+
+/// 2020-05-21T00:00:00Z
+fn timestamp() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_590_019_200)
+}
+
+/// Binding for a device which hasn't authorized any requests
+fn example_binding() -> Binding {
+    Binding {
+        device_id: [7; 16],
+        counter: 1,
+    }
+}
+
+/// Create a 2-of-3 provisioning ceremony for the keypairs with seeds 1-3
+fn provisioning_ceremony(binding: Binding) -> Ceremony {
+    let root_keys = [keypair(1).public, keypair(2).public, keypair(3).public];
+    Ceremony::provision(binding, 2, &root_keys, timestamp())
+}
+
+#[test]
+fn threshold_provisioning() {
+    let mut armistice = Armistice::new(EmbeddedTransport::generate().unwrap());
+    let mut ceremony = provisioning_ceremony(armistice.info().unwrap().next_binding().unwrap());
+
+    ceremony.sign(&keypair(1)).unwrap();
+    let status = ceremony.verify().unwrap();
+    assert_eq!(status.signers, 1);
+    assert!(!status.is_complete());
+
+    let err = ceremony.assemble().unwrap_err();
+    assert_eq!(err.kind(), &Kind::Ceremony);
+
+    // Signing again with the same key doesn't count twice
+    ceremony.sign(&keypair(1)).unwrap();
+    assert_eq!(ceremony.verify().unwrap().signers, 1);
+
+    ceremony.sign(&keypair(3)).unwrap();
+    let status = ceremony.verify().unwrap();
+    assert_eq!(status.signers, 2);
+    assert!(status.is_complete());
+
+    let response = armistice
+        .send_request(ceremony.assemble().unwrap())
+        .unwrap();
+    let uuid = response.provision().unwrap().uuid;
+    assert_eq!(armistice.info().unwrap().uuid, uuid);
+}
+
+#[test]
+fn root_rotation() {
+    let mut armistice = Armistice::new(EmbeddedTransport::generate().unwrap());
+
+    let binding = armistice.info().unwrap().next_binding().unwrap();
+    let mut provisioning = Ceremony::provision(binding, 1, &[keypair(1).public], timestamp());
+    provisioning.sign(&keypair(1)).unwrap();
+    armistice
+        .send_request(provisioning.assemble().unwrap())
+        .unwrap();
+
+    let mut rotation = Ceremony::rotate_root(
+        armistice.info().unwrap().next_binding().unwrap(),
+        2,
+        2,
+        &[keypair(2).public, keypair(3).public],
+        1,
+        &[keypair(1).public],
+        timestamp() + Duration::from_secs(86400),
+    );
+
+    // The current root keys alone can't rotate to the new ones
+    rotation.sign(&keypair(1)).unwrap();
+    let status = rotation.verify().unwrap();
+    assert_eq!(status.previous_signers, Some(1));
+    assert_eq!(status.signers, 0);
+    assert!(!status.is_complete());
+
+    rotation.sign(&keypair(2)).unwrap();
+    rotation.sign(&keypair(3)).unwrap();
+    assert!(rotation.verify().unwrap().is_complete());
+
+    let response = armistice
+        .send_request(rotation.assemble().unwrap())
+        .unwrap();
+    assert_eq!(response.root_rotate().unwrap().version, 2);
+}
+
+#[test]
+fn ineligible_signer_rejected() {
+    let mut ceremony = provisioning_ceremony(example_binding());
+    let err = ceremony.sign(&keypair(4)).unwrap_err();
+    assert_eq!(err.kind(), &Kind::Ceremony);
+    assert!(ceremony.signatures.is_empty());
+}
+
+#[test]
+fn invalid_signature_rejected() {
+    let mut ceremony = provisioning_ceremony(example_binding());
+    ceremony.sign(&keypair(1)).unwrap();
+    ceremony.sign(&keypair(2)).unwrap();
+
+    // Signatures are over the request digest, so changing the request after
+    // signing invalidates them
+    ceremony.threshold = 1;
+
+    let err = ceremony.verify().unwrap_err();
+    assert_eq!(err.kind(), &Kind::Ceremony);
+    assert!(ceremony.assemble().is_err());
+}
+
+#[test]
+fn save_and_load() {
+    let dir = std::env::temp_dir().join(format!("armistice-ceremony-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut ceremony = provisioning_ceremony(example_binding());
+    ceremony.sign(&keypair(2)).unwrap();
+
+    for filename in &["ceremony.toml", "ceremony.json"] {
+        let path = dir.join(filename);
+        ceremony.save(&path).unwrap();
+
+        let loaded = Ceremony::load(&path).unwrap();
+        assert_eq!(loaded, ceremony);
+        assert_eq!(loaded.digest().unwrap(), ceremony.digest().unwrap());
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ceremony_bound_to_device() {
+    let mut armistice = Armistice::new(EmbeddedTransport::generate().unwrap());
+    let mut ceremony = provisioning_ceremony(example_binding());
+    ceremony.sign(&keypair(1)).unwrap();
+    ceremony.sign(&keypair(2)).unwrap();
+
+    // Requests signed for one device can't be submitted to another
+    let err = armistice
+        .send_request(ceremony.assemble().unwrap())
+        .unwrap_err();
+    assert_eq!(err.kind(), &Kind::Device(Code::Replay));
+    assert!(!armistice.info().unwrap().is_provisioned());
+
+    // Rebinding the request to the device invalidates the signatures
+    ceremony.device_id = hex::encode(armistice.info().unwrap().device_id);
+    assert_eq!(ceremony.verify().unwrap_err().kind(), &Kind::Ceremony);
+}
@@ -0,0 +1,654 @@
+//! Command-line tool tests: run the `armistice` binary against Armistice Core
+//! embedded in a proxy listening on a local TCP port
+
+#![cfg(all(feature = "cli", feature = "embedded"))]
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    schema::{
+        challenge, domain, signature::Signatures, threshold, veriform::Decoder, Message, PublicKey,
+        Signature, ThresholdKeySet, Timestamp,
+    },
+    transport::{EmbeddedTransport, TcpTransport},
+    Armistice, Proxy,
+};
+use ed25519_dalek::{Keypair, SecretKey, Signer, Verifier};
+use serde_json::Value;
+use std::{
+    fs,
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Start a proxy to an embedded Armistice Core on an ephemeral local port
+fn start_device() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let proxy = Proxy::new(EmbeddedTransport::generate().unwrap());
+
+    thread::spawn(move || proxy.listen_tcp(listener).unwrap());
+    addr
+}
+
+/// Create a scratch directory unique to the given test
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("armistice-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Create an Ed25519 keypair from the given seed byte, writing it to a key
+/// file in the given directory
+fn keypair(dir: &Path, seed: u8) -> (Keypair, String) {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = (&secret).into();
+    let path = dir.join(format!("{}.key", seed));
+    fs::write(&path, format!("{}\n", hex::encode([seed; 32]))).unwrap();
+    (
+        Keypair { secret, public },
+        path.to_str().unwrap().to_owned(),
+    )
+}
+
+/// Run the `armistice` CLI against the device at the given address (trusting
+/// it if it's new), returning its JSON output
+fn armistice_json(addr: SocketAddr, args: &[&str]) -> Value {
+    let known_devices = known_devices(addr);
+    let addr = addr.to_string();
+    let mut command_line = vec![
+        "--tcp",
+        &addr,
+        "--known-devices",
+        &known_devices,
+        "--trust-new-device",
+    ];
+    command_line.extend_from_slice(args);
+    armistice_offline_json(&command_line)
+}
+
+/// Run the `armistice` CLI against the device at the given address,
+/// expecting it to fail and returning its error output
+fn armistice_failure(addr: SocketAddr, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("--tcp")
+        .arg(addr.to_string())
+        .arg("--known-devices")
+        .arg(known_devices(addr))
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success(), "armistice {:?} succeeded", args);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+/// Known devices file used when connecting to the device at the given
+/// address
+fn known_devices(addr: SocketAddr) -> String {
+    let file = format!(
+        "armistice-cli-{}-{}.known_devices",
+        addr.port(),
+        std::process::id()
+    );
+
+    std::env::temp_dir().join(file).to_str().unwrap().to_owned()
+}
+
+/// Run the `armistice` CLI without a device, returning its JSON output
+fn armistice_offline_json(args: &[&str]) -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("--json")
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "armistice {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// Write a 1-of-1 provisioning ceremony file for the device with the given
+/// info (as output by `armistice info`)
+fn write_provisioning_ceremony(path: &Path, info: &Value, root_keypair: &Keypair) {
+    fs::write(
+        path,
+        format!(
+            "device_id = \"{}\"\ncounter = {}\nthreshold = 1\nroot_keys = [\"{}\"]\ntimestamp = \"2020-05-21T00:00:00Z\"\n",
+            info["device_id"].as_str().unwrap(),
+            info["authorization_counter"].as_u64().unwrap() + 1,
+            hex::encode(root_keypair.public.as_bytes())
+        ),
+    )
+    .unwrap();
+}
+
+/// Create a domain administered by the given key whose operations require
+/// challenges using the client library
+fn create_domain(addr: SocketAddr, root_keypair: &Keypair, admin_keypair: &Keypair) {
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys
+        .push(PublicKey::Ed25519(admin_keypair.public.to_bytes()))
+        .unwrap();
+
+    let request = domain::CreateRequest {
+        config: domain::Config {
+            id: DOMAIN_ID,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            // Operations must sign a challenge, which the CLI obtains itself
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime: 16,
+            },
+        },
+        timestamp: Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208])
+            .unwrap(),
+        digest: None,
+    };
+
+    let request =
+        domain::CreateRequest::decode(&mut Decoder::new(), &request.encode_vec().unwrap()).unwrap();
+
+    let mut armistice = Armistice::new(TcpTransport::connect(addr).unwrap());
+    let binding = armistice.info().unwrap().next_binding().unwrap();
+
+    let mut signatures = Signatures::new();
+    signatures
+        .push(Signature::Ed25519(
+            root_keypair
+                .sign(&binding.digest(&request.digest.unwrap()))
+                .to_bytes(),
+        ))
+        .unwrap();
+
+    armistice
+        .send_request(domain::SignedCreateRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+}
+
+/// Delete the test domain using the library directly, signing the challenge
+/// it requires with the given root key
+fn delete_domain(addr: SocketAddr, root_keypair: &Keypair) {
+    let request = domain::DeleteRequest {
+        id: DOMAIN_ID,
+        timestamp: Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208])
+            .unwrap(),
+        digest: None,
+    };
+
+    let request =
+        domain::DeleteRequest::decode(&mut Decoder::new(), &request.encode_vec().unwrap()).unwrap();
+
+    let mut armistice = Armistice::new(TcpTransport::connect(addr).unwrap());
+    let binding = armistice.info().unwrap().next_binding().unwrap();
+    let challenge = armistice
+        .send_request(challenge::Request {
+            domain: DOMAIN_ID,
+            operation: request.digest.unwrap(),
+        })
+        .unwrap()
+        .get_challenge()
+        .cloned()
+        .unwrap();
+
+    let digest = challenge.digest(&binding.digest(&request.digest.unwrap()));
+    let mut signatures = Signatures::new();
+    signatures
+        .push(Signature::Ed25519(root_keypair.sign(&digest).to_bytes()))
+        .unwrap();
+
+    armistice
+        .send_request(domain::SignedDeleteRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+}
+
+#[test]
+fn provision_and_rotate_root() {
+    let dir = scratch_dir("root");
+    let addr = start_device();
+    let (root_keypair, root_key_file) = keypair(&dir, 1);
+    let (new_root_keypair, new_root_key_file) = keypair(&dir, 2);
+
+    let info = armistice_json(addr, &["info"]);
+    assert_eq!(info["provisioned"], false);
+    assert!(info["algorithms"]
+        .as_array()
+        .unwrap()
+        .contains(&"ed25519".into()));
+
+    let ceremony = dir.join("provision.toml");
+    write_provisioning_ceremony(&ceremony, &info, &root_keypair);
+
+    let provisioned = armistice_json(
+        addr,
+        &[
+            "provision",
+            "--key",
+            &root_key_file,
+            ceremony.to_str().unwrap(),
+        ],
+    );
+
+    let info = armistice_json(addr, &["info"]);
+    assert_eq!(info["provisioned"], true);
+    assert_eq!(info["uuid"], provisioned["uuid"]);
+    assert_eq!(info["root_version"], 1);
+
+    // The device's session key can be pinned
+    let session_key = info["session_key"].as_str().unwrap();
+    let pinned = armistice_json(addr, &["--device-key", session_key, "info"]);
+    assert_eq!(pinned["session_key"], session_key);
+
+    // Rotation ceremonies may be JSON too
+    let ceremony = dir.join("rotate.json");
+    fs::write(
+        &ceremony,
+        serde_json::json!({
+            "device_id": info["device_id"],
+            "counter": info["authorization_counter"].as_u64().unwrap() + 1,
+            "threshold": 1,
+            "root_keys": [hex::encode(new_root_keypair.public.as_bytes())],
+            "timestamp": "2020-05-22T00:00:00Z",
+            "version": 2,
+            "previous_threshold": 1,
+            "previous_root_keys": [hex::encode(root_keypair.public.as_bytes())],
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let rotated = armistice_json(
+        addr,
+        &[
+            "rotate-root",
+            "--key",
+            &root_key_file,
+            "--key",
+            &new_root_key_file,
+            ceremony.to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(rotated["root_version"], 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn offline_ceremony() {
+    let dir = scratch_dir("ceremony");
+    let addr = start_device();
+    let (root_keypair_1, root_key_file_1) = keypair(&dir, 1);
+    let (root_keypair_2, root_key_file_2) = keypair(&dir, 2);
+    let (_, other_key_file) = keypair(&dir, 3);
+
+    let ceremony = dir.join("ceremony.toml");
+    let ceremony = ceremony.to_str().unwrap();
+    let root_key_1 = hex::encode(root_keypair_1.public.as_bytes());
+    let root_key_2 = hex::encode(root_keypair_2.public.as_bytes());
+
+    // The ceremony is bound to the device it's intended for
+    let info = armistice_json(addr, &["info"]);
+    let device_id = info["device_id"].as_str().unwrap();
+    let counter = (info["authorization_counter"].as_u64().unwrap() + 1).to_string();
+
+    let init = armistice_offline_json(&[
+        "ceremony",
+        "init",
+        "--device-id",
+        device_id,
+        "--counter",
+        &counter,
+        "--threshold",
+        "2",
+        "--root-key",
+        &root_key_1,
+        "--root-key",
+        &root_key_2,
+        "--timestamp",
+        "2020-05-21T00:00:00Z",
+        ceremony,
+    ]);
+
+    // Each root key holder signs the ceremony file in turn
+    let status = armistice_offline_json(&["ceremony", "sign", "--key", &root_key_file_1, ceremony]);
+    assert_eq!(status["digest"], init["digest"]);
+    assert_eq!(status["signers"], 1);
+    assert_eq!(status["complete"], false);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("ceremony")
+        .arg("sign")
+        .arg("--key")
+        .arg(&other_key_file)
+        .arg(ceremony)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+
+    armistice_offline_json(&["ceremony", "sign", "--key", &root_key_file_2, ceremony]);
+
+    let status = armistice_offline_json(&["ceremony", "verify", ceremony]);
+    assert_eq!(status["signers"], 2);
+    assert_eq!(status["complete"], true);
+
+    let request = dir.join("provision.req");
+    let request = request.to_str().unwrap();
+    armistice_offline_json(&["ceremony", "assemble", "--output", request, ceremony]);
+
+    let provisioned = armistice_json(addr, &["submit", request]);
+    let info = armistice_json(addr, &["info"]);
+    assert_eq!(info["provisioned"], true);
+    assert_eq!(info["uuid"], provisioned["uuid"]);
+    assert_eq!(info["authorization_counter"], 1);
+
+    // Assembled requests can't be submitted again
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("--tcp")
+        .arg(addr.to_string())
+        .arg("--known-devices")
+        .arg(known_devices(addr))
+        .arg("submit")
+        .arg(request)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keygen_sign_export_import() {
+    let dir = scratch_dir("keys");
+    let addr = start_device();
+    let (root_keypair, root_key_file) = keypair(&dir, 1);
+    let (admin_keypair, admin_key_file) = keypair(&dir, 2);
+    let (caller_keypair, caller_key_file) = keypair(&dir, 3);
+    let caller = hex::encode(caller_keypair.public.as_bytes());
+
+    let ceremony = dir.join("provision.toml");
+    write_provisioning_ceremony(&ceremony, &armistice_json(addr, &["info"]), &root_keypair);
+
+    armistice_json(
+        addr,
+        &[
+            "provision",
+            "--key",
+            &root_key_file,
+            ceremony.to_str().unwrap(),
+        ],
+    );
+
+    create_domain(addr, &root_keypair, &admin_keypair);
+
+    let generated = armistice_json(
+        addr,
+        &[
+            "keygen",
+            "--domain",
+            "1",
+            "--key",
+            &admin_key_file,
+            "--caller",
+            &caller,
+        ],
+    );
+    assert_eq!(generated["slot"], 0);
+    assert_eq!(generated["algorithm"], "ed25519");
+
+    let pubkey = armistice_json(addr, &["pubkey", "--domain", "1", "--slot", "0"]);
+    assert_eq!(pubkey["public_key"], generated["public_key"]);
+
+    let message = dir.join("message.txt");
+    fs::write(&message, b"example message").unwrap();
+
+    // Only callers named by the key's policy may use it
+    let sign = ["sign", "--domain", "1", "--slot", "0"];
+    let message = message.to_str().unwrap();
+    assert!(
+        armistice_failure(addr, &[&sign[..], &[message]].concat()).contains("caller not allowed")
+    );
+
+    let signed = armistice_json(
+        addr,
+        &[&sign[..], &["--caller-key", &caller_key_file, message]].concat(),
+    );
+
+    let public_key = ed25519_dalek::PublicKey::from_bytes(
+        &hex::decode(generated["public_key"].as_str().unwrap()).unwrap(),
+    )
+    .unwrap();
+
+    let mut signature = [0u8; 64];
+    hex::decode_to_slice(signed["signature"].as_str().unwrap(), &mut signature).unwrap();
+    let signature = ed25519_dalek::Signature::from(signature);
+
+    public_key.verify(b"example message", &signature).unwrap();
+
+    let exported_key = dir.join("exported.key");
+    armistice_json(
+        addr,
+        &[
+            "export",
+            "--domain",
+            "1",
+            "--slot",
+            "0",
+            "--key",
+            &admin_key_file,
+            "--output",
+            exported_key.to_str().unwrap(),
+        ],
+    );
+
+    let import = [
+        "import",
+        "--domain",
+        "1",
+        "--key",
+        &admin_key_file,
+        exported_key.to_str().unwrap(),
+    ];
+
+    // Keys can't be imported into a domain which already contains them...
+    assert!(armistice_failure(addr, &import).contains("duplicate"));
+
+    // ...but can be restored once it has been recreated
+    delete_domain(addr, &root_keypair);
+    create_domain(addr, &root_keypair, &admin_keypair);
+
+    let imported = armistice_json(addr, &import);
+    assert_eq!(imported["slot"], 0);
+    assert_eq!(imported["public_key"], generated["public_key"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keygen_with_policy() {
+    let dir = scratch_dir("policy");
+    let addr = start_device();
+    let (root_keypair, root_key_file) = keypair(&dir, 1);
+    let (admin_keypair, admin_key_file) = keypair(&dir, 2);
+    let (caller_keypair, caller_key_file) = keypair(&dir, 3);
+
+    let ceremony = dir.join("provision.toml");
+    write_provisioning_ceremony(&ceremony, &armistice_json(addr, &["info"]), &root_keypair);
+
+    armistice_json(
+        addr,
+        &[
+            "provision",
+            "--key",
+            &root_key_file,
+            ceremony.to_str().unwrap(),
+        ],
+    );
+
+    create_domain(addr, &root_keypair, &admin_keypair);
+
+    armistice_json(
+        addr,
+        &[
+            "keygen",
+            "--domain",
+            "1",
+            "--key",
+            &admin_key_file,
+            "--caller",
+            &hex::encode(caller_keypair.public.as_bytes()),
+            "--prefix",
+            "hello",
+            "--max-signatures",
+            "1",
+            "--window",
+            "10",
+        ],
+    );
+
+    let message = dir.join("message.txt");
+    let sign = [
+        "sign",
+        "--domain",
+        "1",
+        "--slot",
+        "0",
+        "--caller-key",
+        &caller_key_file,
+        message.to_str().unwrap(),
+    ];
+
+    fs::write(&message, b"goodbye").unwrap();
+    assert!(armistice_failure(addr, &sign).contains("message prefix not allowed"));
+
+    fs::write(&message, b"hello, world").unwrap();
+    armistice_json(addr, &sign);
+    assert!(armistice_failure(addr, &sign).contains("signature rate limit exceeded"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn human_output() {
+    let addr = start_device();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("--tcp")
+        .arg(addr.to_string())
+        .arg("--known-devices")
+        .arg(known_devices(addr))
+        .arg("--trust-new-device")
+        .arg("info")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("provisioned: false\n"));
+    assert!(stdout.contains("algorithms: ed25519"));
+}
+
+#[test]
+fn unknown_device_refused() {
+    let addr = start_device();
+
+    // Devices which aren't known yet are refused unless their key is
+    // confirmed, as a host could pose as any new device...
+    let error = armistice_failure(addr, &["info"]);
+    assert!(error.contains("unknown device"));
+    assert!(!Path::new(&known_devices(addr)).exists());
+
+    // ...e.g. by pinning it, which records the device
+    let session_key = error
+        .split("session key ")
+        .nth(1)
+        .unwrap()
+        .split(':')
+        .next()
+        .unwrap();
+
+    let info = armistice_offline_json(&[
+        "--tcp",
+        &addr.to_string(),
+        "--known-devices",
+        &known_devices(addr),
+        "--device-key",
+        session_key,
+        "info",
+    ]);
+    assert_eq!(info["session_key"], session_key);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("--tcp")
+        .arg(addr.to_string())
+        .arg("--known-devices")
+        .arg(known_devices(addr))
+        .arg("info")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    fs::remove_file(known_devices(addr)).unwrap();
+}
+
+#[test]
+fn known_device_key_mismatch_refused() {
+    let addr = start_device();
+
+    // The device's session key is recorded the first time it's used...
+    let info = armistice_json(addr, &["info"]);
+    let known_devices = known_devices(addr);
+    let entry = format!(
+        "{} {}\n",
+        info["device_id"].as_str().unwrap(),
+        info["session_key"].as_str().unwrap()
+    );
+    assert_eq!(fs::read_to_string(&known_devices).unwrap(), entry);
+    armistice_json(addr, &["info"]);
+
+    // ...and a device presenting a different one is refused
+    fs::write(
+        &known_devices,
+        format!(
+            "{} {}\n",
+            info["device_id"].as_str().unwrap(),
+            "11".repeat(32)
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_armistice"))
+        .arg("--tcp")
+        .arg(addr.to_string())
+        .arg("--known-devices")
+        .arg(&known_devices)
+        .arg("info")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("refusing to connect"));
+    fs::remove_file(&known_devices).unwrap();
+}
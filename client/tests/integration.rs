@@ -1,39 +1,224 @@
 //! Armistice client integration tests
 //!
-//! Tests which require a USB armory MkII device running Armistice Core in
-//! order to pass are tagged with `#[ignore]` and must be run with:
+//! When the `embedded` feature is enabled, these tests run against Armistice
+//! Core embedded in-process (e.g. in CI).
+//!
+//! Otherwise they require a USB armory MkII device running Armistice Core
+//! in order to pass, are tagged with `#[ignore]`, and must be run with:
 //!
 //! ```text
 //! $ cargo test -- --ignored
 //! ```
 
+#![cfg(any(feature = "embedded", feature = "usbarmory"))]
 #![forbid(unsafe_code)]
 #![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
 
 use armistice::{
-    schema::{provision, PublicKey, Timestamp},
+    error::Kind,
+    schema::{
+        authorization::Binding, domain, error, key, policy, provision, signature::Signatures,
+        threshold, veriform::Decoder, Message, PublicKey, Signature, ThresholdKeySet, Timestamp,
+    },
     Armistice,
 };
+use ed25519_dalek::{Keypair, SecretKey, Signer, Verifier};
 
-#[test]
-#[ignore]
-fn perform_provisioning() {
-    let mut armistice = Armistice::new().unwrap();
+/// Open a connection to Armistice Core embedded in-process
+#[cfg(feature = "embedded")]
+fn armistice() -> Armistice {
+    Armistice::new(armistice::transport::EmbeddedTransport::generate().unwrap())
+}
 
-    let mut root_keys = provision::RootKeys::new();
-    root_keys.push(PublicKey::Ed25519([0u8; 32])).unwrap();
+/// Open a connection to a USB armory running Armistice Core
+#[cfg(not(feature = "embedded"))]
+fn armistice() -> Armistice {
+    Armistice::new(armistice::transport::UsbTransport::open().unwrap())
+}
+
+/// Create an Ed25519 keypair from the given seed byte
+fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = (&secret).into();
+    Keypair { secret, public }
+}
+
+/// TAI64N timestamp for 2020-05-21
+fn timestamp() -> Timestamp {
+    Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap()
+}
+
+/// Round trip a message through the encoder to compute its digest
+fn round_trip<M: Message>(message: &M) -> M {
+    M::decode(&mut Decoder::new(), &message.encode_vec().unwrap()).unwrap()
+}
+
+/// Sign a request digest under the given binding with the given keypair
+fn sign(binding: &Binding, digest: &[u8; 32], keypair: &Keypair) -> Signatures {
+    let mut signatures = Signatures::new();
+    signatures
+        .push(Signature::Ed25519(
+            keypair.sign(&binding.digest(digest)).to_bytes(),
+        ))
+        .unwrap();
+    signatures
+}
 
-    // TAI64N for 2020-05-21
-    let timestamp =
-        Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap();
+/// Create a provisioning request for the given root keypair, bound to the
+/// device's next authorization counter
+fn provision_request(
+    armistice: &mut Armistice,
+    root_keypair: &Keypair,
+) -> provision::SignedRequest {
+    let mut root_keys = provision::RootKeys::new();
+    root_keys
+        .push(PublicKey::Ed25519(root_keypair.public.to_bytes()))
+        .unwrap();
 
-    let request = provision::Request {
+    let request = round_trip(&provision::Request {
         root_key_threshold: 1,
         root_keys,
-        timestamp,
+        timestamp: timestamp(),
         digest: None,
-    };
+    });
+
+    let binding = armistice.info().unwrap().next_binding().unwrap();
+    let signatures = sign(&binding, &request.digest.unwrap(), root_keypair);
 
+    provision::SignedRequest {
+        request,
+        signatures,
+        binding,
+    }
+}
+
+#[test]
+#[cfg_attr(not(feature = "embedded"), ignore)]
+fn perform_provisioning() {
+    let mut armistice = armistice();
+    let root_keypair = keypair(1);
+
+    let request = provision_request(&mut armistice, &root_keypair);
     let response = armistice.send_request(request).unwrap();
-    dbg!(&response);
+
+    let uuid = response.provision().unwrap().uuid;
+    let info = armistice.info().unwrap();
+    assert!(info.is_provisioned());
+    assert_eq!(info.uuid, uuid);
+}
+
+#[test]
+#[cfg_attr(not(feature = "embedded"), ignore)]
+fn provisioning_twice_fails() {
+    let mut armistice = armistice();
+    let root_keypair = keypair(1);
+
+    let request = provision_request(&mut armistice, &root_keypair);
+    armistice.send_request(request).unwrap();
+
+    let request = provision_request(&mut armistice, &root_keypair);
+    let err = armistice.send_request(request).unwrap_err();
+
+    assert_eq!(err.kind(), &Kind::Device(error::Code::Provisioned));
+}
+
+#[test]
+#[cfg_attr(not(feature = "embedded"), ignore)]
+fn generate_key_and_sign() {
+    let mut armistice = armistice();
+    let root_keypair = keypair(1);
+    let admin_keypair = keypair(2);
+    let caller_keypair = keypair(3);
+
+    let request = provision_request(&mut armistice, &root_keypair);
+    armistice.send_request(request).unwrap();
+
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys
+        .push(PublicKey::Ed25519(admin_keypair.public.to_bytes()))
+        .unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id: 1,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 1,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = armistice.info().unwrap().next_binding().unwrap();
+    let signatures = sign(&binding, &request.digest.unwrap(), &root_keypair);
+    let signed_request = domain::SignedCreateRequest {
+        request,
+        signatures,
+        binding,
+    };
+    armistice.send_request(signed_request.clone()).unwrap();
+
+    // The signed request can't be replayed
+    let err = armistice.send_request(signed_request).unwrap_err();
+    assert_eq!(err.kind(), &Kind::Device(error::Code::Replay));
+
+    // Keys may only be used by the callers their policy names
+    let mut key_policy = policy::Policy::default();
+    key_policy
+        .callers
+        .push(PublicKey::Ed25519(caller_keypair.public.to_bytes()))
+        .unwrap();
+
+    let request = round_trip(&key::GenerateRequest {
+        domain: 1,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: key_policy,
+        digest: None,
+    });
+
+    let binding = armistice.info().unwrap().next_domain_binding(1).unwrap();
+    let signatures = sign(&binding, &request.digest.unwrap(), &admin_keypair);
+    let response = armistice
+        .send_request(key::SignedGenerateRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+
+    let public_key = match &response.generate_key().unwrap().public_key {
+        PublicKey::Ed25519(bytes) => ed25519_dalek::PublicKey::from_bytes(bytes).unwrap(),
+        other => panic!("unexpected public key: {:?}", other),
+    };
+
+    let mut message = key::MessageBytes::new();
+    message.extend_from_slice(b"example message").unwrap();
+
+    armistice
+        .authenticate(
+            PublicKey::Ed25519(caller_keypair.public.to_bytes()),
+            |digest| Signature::Ed25519(caller_keypair.sign(digest).to_bytes()),
+        )
+        .unwrap();
+
+    let response = armistice
+        .send_request(key::SignRequest {
+            domain: 1,
+            slot: 0,
+            payload: key::Payload::Message(message),
+        })
+        .unwrap();
+
+    let signature = match &response.sign().unwrap().signature {
+        Signature::Ed25519(bytes) => ed25519_dalek::Signature::from(*bytes),
+        other => panic!("unexpected signature: {:?}", other),
+    };
+
+    public_key.verify(b"example message", &signature).unwrap();
 }
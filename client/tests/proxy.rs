@@ -0,0 +1,77 @@
+//! Proxy tests (run against Armistice Core embedded in-process)
+
+#![cfg(feature = "embedded")]
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    error::Kind,
+    schema::{error, info},
+    transport::{EmbeddedTransport, TcpTransport, Transport},
+    Armistice, Error, Proxy,
+};
+use std::{
+    net::{SocketAddr, TcpListener},
+    thread,
+};
+
+/// Number of concurrent clients
+const NUM_CLIENTS: usize = 4;
+
+/// Start a proxy listening on an ephemeral local port
+fn start_proxy(upstream: impl Transport + Send + 'static) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let proxy = Proxy::new(upstream);
+
+    thread::spawn(move || proxy.listen_tcp(listener).unwrap());
+    addr
+}
+
+#[test]
+fn concurrent_clients() {
+    let addr = start_proxy(EmbeddedTransport::generate().unwrap());
+
+    let clients = (0..NUM_CLIENTS)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut armistice = Armistice::new(TcpTransport::connect(addr).unwrap());
+
+                (0..8)
+                    .map(|_| armistice.info().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let responses = clients
+        .into_iter()
+        .flat_map(|client| client.join().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(responses.len(), NUM_CLIENTS * 8);
+
+    for response in &responses {
+        assert!(!response.is_provisioned());
+    }
+}
+
+/// Transport whose device has gone away
+struct Unplugged;
+
+impl Transport for Unplugged {
+    fn send_request(&mut self, _request: &[u8]) -> Result<Vec<u8>, Error> {
+        Err(Kind::Usb.context("device unplugged").into())
+    }
+}
+
+#[test]
+fn upstream_errors() {
+    let addr = start_proxy(Unplugged);
+    let mut armistice = Armistice::new(TcpTransport::connect(addr).unwrap());
+
+    for _ in 0..2 {
+        let err = armistice.send_request(info::Request {}).unwrap_err();
+        assert_eq!(err.kind(), &Kind::Device(error::Code::Internal));
+    }
+}
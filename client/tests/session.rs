@@ -0,0 +1,260 @@
+//! Encrypted session tests (run against Armistice Core embedded in-process)
+
+#![cfg(feature = "embedded")]
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    error::Kind,
+    schema::{
+        domain, error, info, provision, session::PublicKey, signature::Signatures,
+        veriform::Decoder, Message, Request, Response, Signature, Timestamp,
+    },
+    transport::{EmbeddedTransport, Transport},
+    Armistice, KnownDevices,
+};
+use ed25519_dalek::{Keypair, SecretKey, Signer};
+use std::{cell::Cell, fs, rc::Rc};
+
+/// Software root key of the embedded device
+const ROOT_KEY: [u8; 16] = [0x42; 16];
+
+/// Embed Armistice Core with the test root key
+fn transport() -> EmbeddedTransport {
+    EmbeddedTransport::from_root_key(&ROOT_KEY).unwrap()
+}
+
+/// Send a request outside of any session, as a host relaying messages could
+fn send_cleartext(transport: &mut impl Transport, request: impl Into<Request>) -> Response {
+    let request = request.into().encode_vec().unwrap();
+    let response = transport.send_request(&request).unwrap();
+    Response::decode(&mut Decoder::new(), &response).unwrap()
+}
+
+/// Transport relaying requests to the device which can replace its responses
+/// to session requests with cleartext session errors, as a malicious host
+/// could
+struct Interceptor {
+    /// Transport to the device
+    transport: EmbeddedTransport,
+
+    /// Number of session requests relayed to the device
+    relayed: Rc<Cell<usize>>,
+
+    /// Should responses to session requests be replaced?
+    inject: Rc<Cell<bool>>,
+}
+
+impl Transport for Interceptor {
+    fn send_request(&mut self, request: &[u8]) -> Result<Vec<u8>, armistice::error::Error> {
+        let response = self.transport.send_request(request)?;
+
+        if let Ok(Request::Session(_)) = Request::decode(&mut Decoder::new(), request) {
+            self.relayed.set(self.relayed.get() + 1);
+
+            if self.inject.get() {
+                let error = error::Response::new(error::Code::Session, "no such session");
+                return Ok(Response::from(error).encode_vec()?);
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+/// Create a signed provisioning request for a 1-of-1 Ed25519 root key,
+/// bound to the device's next authorization counter
+fn provision_request(transport: &mut impl Transport) -> provision::SignedRequest {
+    let secret = SecretKey::from_bytes(&[1; 32]).unwrap();
+    let public = (&secret).into();
+    let keypair = Keypair { secret, public };
+
+    let mut root_keys = provision::RootKeys::new();
+    root_keys
+        .push(armistice::schema::PublicKey::Ed25519(
+            keypair.public.to_bytes(),
+        ))
+        .unwrap();
+
+    let request = provision::Request {
+        root_key_threshold: 1,
+        root_keys,
+        timestamp: Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208])
+            .unwrap(),
+        digest: None,
+    };
+
+    // Round trip the request through the encoder to compute its digest
+    let request =
+        provision::Request::decode(&mut Decoder::new(), &request.encode_vec().unwrap()).unwrap();
+
+    let binding = send_cleartext(transport, info::Request {})
+        .get_info()
+        .unwrap()
+        .next_binding()
+        .unwrap();
+
+    let mut signatures = Signatures::new();
+    signatures
+        .push(Signature::Ed25519(
+            keypair
+                .sign(&binding.digest(&request.digest.unwrap()))
+                .to_bytes(),
+        ))
+        .unwrap();
+
+    provision::SignedRequest {
+        request,
+        signatures,
+        binding,
+    }
+}
+
+#[test]
+fn device_key_pinning() {
+    let mut armistice = Armistice::new(transport());
+    assert!(armistice.device_key().is_none());
+
+    let info = armistice.info().unwrap();
+    let device_key = *armistice.device_key().unwrap();
+    assert_eq!(device_key, PublicKey::from(info.session_key));
+
+    let mut pinned = Armistice::with_device_key(transport(), device_key);
+    assert_eq!(pinned.info().unwrap().session_key, info.session_key);
+
+    // A device with a different root key can't complete the handshake
+    let other = EmbeddedTransport::from_root_key(&[0x43; 16]).unwrap();
+    let mut impostor = Armistice::with_device_key(other, device_key);
+    assert_eq!(impostor.info().unwrap_err().kind(), &Kind::Session);
+}
+
+#[test]
+fn known_devices() {
+    let path = std::env::temp_dir().join(format!("armistice-known-devices-{}", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    // Devices which aren't known are refused...
+    let known_devices = KnownDevices::load(&path).unwrap();
+    let mut unknown = Armistice::with_known_devices(transport(), known_devices, false);
+    assert_eq!(unknown.info().unwrap_err().kind(), &Kind::Session);
+    assert!(!path.exists());
+
+    // ...unless they're explicitly trusted, in which case they're recorded...
+    let known_devices = KnownDevices::load(&path).unwrap();
+    let info = Armistice::with_known_devices(transport(), known_devices, true)
+        .info()
+        .unwrap();
+
+    let known_devices = KnownDevices::load(&path).unwrap();
+    assert_eq!(
+        known_devices.get(&info.device_id),
+        Some(&PublicKey::from(info.session_key))
+    );
+
+    Armistice::with_known_devices(transport(), known_devices, false)
+        .info()
+        .unwrap();
+
+    // ...and a device presenting another key is refused
+    let mut known_devices = KnownDevices::load(&path).unwrap();
+    known_devices
+        .insert(info.device_id, PublicKey::from([0x11; 32]))
+        .unwrap();
+
+    let mut impostor = Armistice::with_known_devices(transport(), known_devices, true);
+    assert_eq!(impostor.info().unwrap_err().kind(), &Kind::Session);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn lost_session_not_retried() {
+    let (relayed, inject) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(false)));
+    let mut armistice = Armistice::new(Interceptor {
+        transport: transport(),
+        relayed: relayed.clone(),
+        inject: inject.clone(),
+    });
+
+    armistice.info().unwrap();
+    assert_eq!(relayed.get(), 1);
+
+    // The device may have performed a request whose response is replaced
+    // with a session error, so it isn't sent again...
+    inject.set(true);
+    let err = armistice.info().unwrap_err();
+    assert_eq!(err.kind(), &Kind::Device(error::Code::Session));
+    assert_eq!(relayed.get(), 2);
+
+    // ...but the next request establishes a new session
+    inject.set(false);
+    armistice.info().unwrap();
+    assert_eq!(relayed.get(), 3);
+}
+
+#[test]
+fn cleartext_rejected_once_provisioned() {
+    let mut transport = transport();
+
+    // Devices can be provisioned in cleartext...
+    let request = provision_request(&mut transport);
+    let response = send_cleartext(&mut transport, request);
+    assert!(response.provision().is_some());
+
+    // ...but afterwards hosts can't send requests outside of a session
+    let response = send_cleartext(&mut transport, domain::ListRequest::default());
+    assert_eq!(response.error().unwrap().code(), error::Code::Session);
+}
+
+#[test]
+fn caller_authentication() {
+    let secret = SecretKey::from_bytes(&[9; 32]).unwrap();
+    let public = (&secret).into();
+    let caller = Keypair { secret, public };
+    let public_key = armistice::schema::PublicKey::Ed25519(caller.public.to_bytes());
+
+    let mut armistice = Armistice::new(transport());
+    armistice
+        .authenticate(public_key.clone(), |digest| {
+            Signature::Ed25519(caller.sign(digest).to_bytes())
+        })
+        .unwrap();
+
+    // Signatures which don't cover the session's authentication digest are
+    // rejected
+    let err = armistice
+        .authenticate(public_key, |_| {
+            Signature::Ed25519(caller.sign(b"hello, world").to_bytes())
+        })
+        .unwrap_err();
+
+    assert_eq!(err.kind(), &Kind::Device(error::Code::Unauthorized));
+}
+
+#[test]
+fn caller_identity_not_dropped_with_session() {
+    let secret = SecretKey::from_bytes(&[9; 32]).unwrap();
+    let public = (&secret).into();
+    let caller = Keypair { secret, public };
+    let public_key = armistice::schema::PublicKey::Ed25519(caller.public.to_bytes());
+    let inject = Rc::new(Cell::new(false));
+
+    let mut armistice = Armistice::new(Interceptor {
+        transport: transport(),
+        relayed: Rc::new(Cell::new(0)),
+        inject: inject.clone(),
+    });
+
+    let sign = |digest: &[u8; 32]| Signature::Ed25519(caller.sign(digest).to_bytes());
+    armistice.authenticate(public_key.clone(), sign).unwrap();
+
+    inject.set(true);
+    armistice.info().unwrap_err();
+    inject.set(false);
+
+    // Requests aren't silently sent without the caller's identity...
+    assert_eq!(armistice.info().unwrap_err().kind(), &Kind::Session);
+
+    // ...until it's authenticated again
+    armistice.authenticate(public_key, sign).unwrap();
+    armistice.info().unwrap();
+}
@@ -0,0 +1,47 @@
+//! Stream transport tests
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    error::Kind,
+    schema::{error, framing::Reassembler, info, veriform::Decoder, Message, Request, Response},
+    transport::{
+        stream::{read_message, write_message},
+        TcpTransport,
+    },
+    Armistice,
+};
+use std::{error::Error, net::TcpListener, thread};
+
+/// Detail message in error responses from the test server
+const DETAIL: &str = "this is a long error message which requires more than one packet";
+
+#[test]
+fn tcp_round_trip() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        for _ in 0..2 {
+            let request = read_message(&mut stream, &mut Reassembler::new()).unwrap();
+            let request = Request::decode(&mut Decoder::new(), &request).unwrap();
+            assert!(request.get_info().is_some());
+
+            let response = Response::from(error::Response::new(error::Code::Internal, DETAIL));
+            write_message(&mut stream, &response.encode_vec().unwrap()).unwrap();
+        }
+    });
+
+    let mut armistice = Armistice::new(TcpTransport::connect(addr).unwrap());
+
+    for _ in 0..2 {
+        let err = armistice.send_request(info::Request {}).unwrap_err();
+        assert_eq!(err.kind(), &Kind::Device(error::Code::Internal));
+        assert_eq!(err.source().unwrap().to_string(), DETAIL);
+    }
+
+    server.join().unwrap();
+}
@@ -0,0 +1,310 @@
+//! Approval queue: pending operations which collect approvals from a
+//! domain's administrators over multiple requests
+//!
+//! See [`schema::approval`] for the protocol. Pending operations are held in
+//! volatile memory: they are discarded if the device restarts, or if the
+//! domain they are performed in is deleted. Approvals are recounted against
+//! the domain's administrators each time one is added, so signatures from
+//! keys which have since been removed from the domain no longer count.
+//!
+//! The nonce assigned to each operation serves as its challenge: pending
+//! operations expire after [`PENDING_LIFETIME`] requests, or after the
+//! domain's challenge lifetime if its operations require challenges.
+//! Operations other than signing are also bound to the authorization counter
+//! following their domain's when they're submitted, and are refused if any
+//! other operation is authorized in the domain before they're performed.
+//!
+//! Operations may only be submitted by callers the domain recognizes: its
+//! administrators, or for signing operations, callers the key's policy
+//! names (identified by the session identity they authenticated as). Each
+//! submitter may only have [`MAX_PENDING_PER_SUBMITTER`] operations pending
+//! at once, so callers can't deny others the queue by authenticating as
+//! identities of their own.
+
+use crate::{
+    crypto::PublicKey,
+    domain,
+    error::Error,
+    schema::{
+        self,
+        approval::{Id, MaxPending, Nonce, Operation, SubmitRequest},
+        veriform::Sha256Digest,
+        Signature,
+    },
+    threshold::ThresholdKeySet,
+};
+use block_cipher::generic_array::typenum::Unsigned;
+use core::slice;
+use heapless::Vec;
+use rand_core::{CryptoRng, RngCore};
+
+/// Maximum number of operations which can be pending at once: submitting
+/// another fails until one has been performed or withdrawn
+pub const MAX_PENDING: usize = <MaxPending as Unsigned>::USIZE;
+
+/// Maximum number of operations each submitter can have pending at once
+pub const MAX_PENDING_PER_SUBMITTER: usize = 1;
+
+/// Number of requests after which pending operations expire, unless their
+/// domain's operations require challenges
+pub const PENDING_LIFETIME: u64 = 256;
+
+/// Operation awaiting approval
+#[derive(Clone, Debug)]
+pub struct PendingOperation {
+    /// Identifier assigned to this operation
+    id: Id,
+
+    /// Request the operation was submitted with
+    request: SubmitRequest,
+
+    /// Digest of the submission request
+    digest: Sha256Digest,
+
+    /// Random nonce which approvals must cover
+    nonce: Nonce,
+
+    /// Approvals collected so far (at most one per administrator)
+    approvals: schema::signature::Signatures,
+
+    /// Session identity of the caller who submitted the operation
+    caller: PublicKey,
+
+    /// Authorization counter the operation is bound to (`None` for signing)
+    counter: Option<u64>,
+
+    /// Request count after which the operation expires
+    expires_at: u64,
+}
+
+impl PendingOperation {
+    /// Get the identifier assigned to this operation
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Get the domain this operation is performed in
+    pub fn domain(&self) -> domain::Id {
+        self.request.operation.domain()
+    }
+
+    /// Get the operation to be performed once approved
+    pub fn operation(&self) -> &Operation {
+        &self.request.operation
+    }
+
+    /// Get the session identity of the caller who submitted this operation.
+    /// Key policies are evaluated against it.
+    pub fn caller(&self) -> &PublicKey {
+        &self.caller
+    }
+
+    /// Get the authorization counter this operation is bound to, which must
+    /// follow its domain's when it's performed (`None` for signing)
+    pub fn counter(&self) -> Option<u64> {
+        self.counter
+    }
+
+    /// Count the approvals made by the given administrators
+    pub fn count_approvals(&self, admins: &ThresholdKeySet) -> usize {
+        admins.count_signers(&self.approval_digest(), &self.approvals)
+    }
+
+    /// Record an approval by one of the given administrators, returning the
+    /// number of approvals collected so far.
+    ///
+    /// Fails with [`Error::Unauthorized`] if the signature isn't a valid
+    /// approval by an administrator, or [`Error::Duplicate`] if that
+    /// administrator has already approved the operation.
+    pub(crate) fn approve(
+        &mut self,
+        admins: &ThresholdKeySet,
+        signature: &Signature,
+    ) -> Result<usize, Error> {
+        let digest = self.approval_digest();
+
+        if admins.count_signers(&digest, slice::from_ref(signature)) == 0 {
+            return Err(Error::Unauthorized);
+        }
+
+        // Discard approvals by keys which are no longer administrators
+        let mut approvals = schema::signature::Signatures::new();
+
+        for approval in self.approvals.iter() {
+            if admins.count_signers(&digest, slice::from_ref(approval)) > 0 {
+                approvals
+                    .push(approval.clone())
+                    .map_err(|_| Error::Capacity)?;
+            }
+        }
+
+        let count = admins.count_signers(&digest, &approvals);
+        approvals
+            .push(signature.clone())
+            .map_err(|_| Error::Capacity)?;
+
+        let new_count = admins.count_signers(&digest, &approvals);
+
+        if new_count == count {
+            return Err(Error::Duplicate);
+        }
+
+        self.approvals = approvals;
+        Ok(new_count)
+    }
+
+    /// Check the given signature is a valid cancellation by one of the given
+    /// administrators
+    pub(crate) fn verify_cancellation(
+        &self,
+        admins: &ThresholdKeySet,
+        signature: &Signature,
+    ) -> Result<(), Error> {
+        let digest = schema::approval::cancellation_digest(&self.nonce, &self.digest);
+
+        if admins.count_signers(&digest, slice::from_ref(signature)) > 0 {
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+
+    /// Summarize this operation, counting approvals made by the given
+    /// administrators towards the given threshold
+    pub fn summary(&self, admins: &ThresholdKeySet, threshold: usize) -> schema::approval::Pending {
+        schema::approval::Pending {
+            id: self.id,
+            domain: self.domain(),
+            digest: self.digest,
+            nonce: self.nonce,
+            approvals: self.count_approvals(admins) as u64,
+            threshold: threshold as u64,
+        }
+    }
+
+    /// Compute the digest administrators sign to approve this operation
+    fn approval_digest(&self) -> Sha256Digest {
+        schema::approval::approval_digest(&self.nonce, &self.digest)
+    }
+}
+
+/// Queue of operations awaiting approval
+#[derive(Debug, Default)]
+pub struct Queue {
+    /// Pending operations (in no particular order)
+    operations: Vec<PendingOperation, MaxPending>,
+
+    /// Identifier to assign the next operation
+    next_id: Id,
+}
+
+impl Queue {
+    /// Add an operation submitted by the given caller to the queue, bound
+    /// to the given authorization counter (if any) and expiring after the
+    /// given request count, returning its identifier and nonce.
+    ///
+    /// Fails with [`Error::Capacity`] if the queue is full or the caller
+    /// already has the maximum number of operations pending, or
+    /// [`Error::Duplicate`] if the same request is already pending.
+    pub(crate) fn submit(
+        &mut self,
+        request: &SubmitRequest,
+        caller: &PublicKey,
+        counter: Option<u64>,
+        expires_at: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<(Id, Nonce), Error> {
+        // Digests are computed by `veriform` when the request is decoded
+        let digest = request.digest.ok_or(Error::Unauthorized)?;
+
+        if self.operations.iter().any(|op| op.digest == digest) {
+            return Err(Error::Duplicate);
+        }
+
+        let submitted = self
+            .operations
+            .iter()
+            .filter(|op| &op.caller == caller)
+            .count();
+
+        if submitted >= MAX_PENDING_PER_SUBMITTER {
+            return Err(Error::Capacity);
+        }
+
+        let mut nonce = Nonce::default();
+        rng.fill_bytes(&mut nonce);
+
+        let id = self.next_id;
+        self.operations
+            .push(PendingOperation {
+                id,
+                request: request.clone(),
+                digest,
+                nonce,
+                approvals: schema::signature::Signatures::new(),
+                caller: *caller,
+                counter,
+                expires_at,
+            })
+            .map_err(|_| Error::Capacity)?;
+
+        self.next_id = id.wrapping_add(1);
+        Ok((id, nonce))
+    }
+
+    /// Get the pending operation with the given identifier
+    pub fn get(&self, id: Id) -> Option<&PendingOperation> {
+        self.operations.iter().find(|op| op.id == id)
+    }
+
+    /// Get a mutable reference to the pending operation with the given
+    /// identifier
+    pub(crate) fn get_mut(&mut self, id: Id) -> Option<&mut PendingOperation> {
+        self.operations.iter_mut().find(|op| op.id == id)
+    }
+
+    /// Remove the pending operation with the given identifier
+    pub(crate) fn remove(&mut self, id: Id) -> Result<PendingOperation, Error> {
+        let index = self
+            .operations
+            .iter()
+            .position(|op| op.id == id)
+            .ok_or(Error::NotFound)?;
+
+        Ok(self.operations.swap_remove(index))
+    }
+
+    /// Remove any pending operations which have expired as of the given
+    /// request count
+    pub(crate) fn expire(&mut self, request_count: u64) {
+        while let Some(index) = self
+            .operations
+            .iter()
+            .position(|op| op.expires_at < request_count)
+        {
+            self.operations.swap_remove(index);
+        }
+    }
+
+    /// Remove all pending operations in the given domain
+    pub(crate) fn remove_domain(&mut self, domain: domain::Id) {
+        while let Some(index) = self.operations.iter().position(|op| op.domain() == domain) {
+            self.operations.swap_remove(index);
+        }
+    }
+
+    /// Iterate over the pending operations (in no particular order)
+    pub fn iter(&self) -> slice::Iter<'_, PendingOperation> {
+        self.operations.iter()
+    }
+
+    /// Get the number of pending operations
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Are there no pending operations?
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
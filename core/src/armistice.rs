@@ -1,40 +1,211 @@
 //! Armistice core state
 
 use crate::{
-    crypto::{PublicKey, RootKey},
+    approval,
+    challenge::Challenges,
+    counter::MonotonicCounter,
+    crypto::{self, signing_key, RootKey},
+    domain::{self, Domain, Domains},
     error::Error,
+    policy,
+    program::Program,
     root,
-    schema::{self, Request, Response},
+    schema::{
+        self,
+        authorization::Binding,
+        session::{self, PublicKey, Session, StaticSecret},
+        veriform::{Decoder, Sha256Digest},
+        Message, Request, Response,
+    },
+    state,
+    storage::Storage,
+    threshold::ThresholdKeySet,
+    wrap,
 };
 use block_cipher::{
-    generic_array::{typenum::U16, ArrayLength, GenericArray},
+    generic_array::{
+        typenum::{Unsigned, U16},
+        ArrayLength, GenericArray,
+    },
     BlockCipher,
 };
+use core::convert::TryFrom;
+use rand_core::{CryptoRng, RngCore};
+
+/// Root version number assigned at provisioning time
+const INITIAL_ROOT_VERSION: u64 = 1;
+
+/// Input block encrypted under the root key to derive the device ID
+const DEVICE_ID_INPUT: &[u8; 16] = b"armistice.dev.id";
+
+/// Input blocks encrypted under the root key to derive the device's static
+/// session key
+const SESSION_KEY_INPUTS: [&[u8; 16]; 2] = [b"armistice.sess.0", b"armistice.sess.1"];
+
+/// Maximum number of concurrently established sessions: establishing
+/// another replaces the least recently used one
+pub const MAX_SESSIONS: usize = 4;
+
+/// Maximum size of an encoded request or response message (which may span
+/// several transport packets; see [`schema::framing`])
+pub const MAX_MESSAGE_SIZE: usize = <schema::framing::MaxMessageSize as Unsigned>::USIZE;
+
+/// Device-unique identifier
+pub type DeviceId = [u8; 16];
 
 /// Armistice Core State
-pub struct Armistice<B>
+pub struct Armistice<B, R, S, C>
 where
     B: BlockCipher<BlockSize = U16>,
     B::ParBlocks: ArrayLength<GenericArray<u8, B::BlockSize>>,
+    R: CryptoRng + RngCore,
+    S: Storage,
+    C: MonotonicCounter,
 {
+    /// Device-unique identifier derived from the root key
+    device_id: DeviceId,
+
+    /// Static key used to establish sessions, derived from the root key
+    session_key: StaticSecret,
+
+    /// Established sessions
+    sessions: [Option<Session>; MAX_SESSIONS],
+
+    /// Session identities callers have authenticated as within each
+    /// established session, indexed like `sessions`
+    callers: [Option<crypto::PublicKey>; MAX_SESSIONS],
+
+    /// Session activity as of each established session's most recent use,
+    /// indexed like `sessions`
+    session_last_used: [u64; MAX_SESSIONS],
+
+    /// Number of requests received within sessions and sessions established
+    /// since the device started, which orders sessions by their use
+    session_activity: u64,
+
+    /// Identifier to assign the next session
+    next_session_id: session::Id,
+
     /// Root configuration
     root_config: root::Config,
 
+    /// Domains
+    domains: Domains,
+
+    /// Challenges issued for pending operations
+    challenges: Challenges,
+
+    /// Operations awaiting approval
+    approvals: approval::Queue,
+
+    /// Number of requests received since the device started, which measures
+    /// the lifetimes of challenges and pending operations
+    request_count: u64,
+
     /// Root symmetric key
     root_key: RootKey<B>,
+
+    /// Cryptographically secure random number generator
+    rng: R,
+
+    /// Persistent storage for state sealed under the root key
+    storage: S,
+
+    /// Monotonic counter which protects sealed state against rollback
+    counter: C,
+
+    /// Version counter of the most recently persisted state
+    state_version: u64,
+
+    /// Authorization counter of the most recent request authorized by the
+    /// root keys
+    authorization_counter: u64,
+
+    /// Greatest authorization counter of any deleted domain, which domains
+    /// created afterwards continue from so requests authorized for a deleted
+    /// domain can't be replayed to a new one with the same ID
+    deleted_domain_counter: u64,
 }
 
-impl<B> Armistice<B>
+impl<B, R, S, C> Armistice<B, R, S, C>
 where
     B: BlockCipher<BlockSize = U16>,
     B::ParBlocks: ArrayLength<GenericArray<u8, B::BlockSize>>,
+    R: CryptoRng + RngCore,
+    S: Storage,
+    C: MonotonicCounter,
 {
-    /// Create new [`Armistice`] core state
-    pub fn new(root_key: B) -> Self {
-        Self {
-            root_config: root::Config::default(),
-            root_key: root_key.into(),
+    /// Create new [`Armistice`] core state, loading any state which was
+    /// previously sealed under the root key and persisted to storage.
+    ///
+    /// Fails with [`Error::Version`] if the persisted state is behind the
+    /// monotonic counter (i.e. it has been rolled back).
+    pub fn new(root_key: B, rng: R, mut storage: S, mut counter: C) -> Result<Self, Error> {
+        let mut block = GenericArray::clone_from_slice(DEVICE_ID_INPUT);
+        root_key.encrypt_block(&mut block);
+
+        let mut device_id = DeviceId::default();
+        device_id.copy_from_slice(&block);
+
+        let mut session_key = [0u8; 32];
+
+        for (input, output) in SESSION_KEY_INPUTS.iter().zip(session_key.chunks_mut(16)) {
+            let mut block = GenericArray::clone_from_slice(*input);
+            root_key.encrypt_block(&mut block);
+            output.copy_from_slice(&block);
+        }
+
+        let root_key = RootKey::from(root_key);
+        let mut root_config = root::Config::default();
+        let mut domains = Domains::default();
+        let mut state_version = 0;
+        let mut authorization_counter = 0;
+        let mut deleted_domain_counter = 0;
+
+        if let Some(state) = state::load(&root_key, &mut storage, &mut counter)? {
+            root_config = root::Config::try_from(&state.root)?;
+
+            for domain in &state.domains {
+                domains.insert(Domain::try_from(domain)?)?;
+            }
+
+            state_version = state.version;
+            authorization_counter = state.authorization_counter;
+            deleted_domain_counter = state.deleted_domain_counter;
         }
+
+        Ok(Self {
+            device_id,
+            session_key: StaticSecret::from(session_key),
+            sessions: Default::default(),
+            callers: Default::default(),
+            session_last_used: Default::default(),
+            session_activity: 0,
+            next_session_id: 0,
+            root_config,
+            domains,
+            challenges: Challenges::default(),
+            approvals: approval::Queue::default(),
+            request_count: 0,
+            root_key,
+            rng,
+            storage,
+            counter,
+            state_version,
+            authorization_counter,
+            deleted_domain_counter,
+        })
+    }
+
+    /// Get the [`DeviceId`]: a device-unique identifier derived from the
+    /// root key which salts the UUIDs of root configurations
+    pub fn device_id(&self) -> &DeviceId {
+        &self.device_id
+    }
+
+    /// Get the public key clients use to establish sessions with this device
+    pub fn session_public_key(&self) -> PublicKey {
+        PublicKey::from(&self.session_key)
     }
 
     /// Get the [`root::Config`]
@@ -42,42 +213,1246 @@ where
         &self.root_config
     }
 
+    /// Get the [`Domains`] on this device
+    pub fn domains(&self) -> &Domains {
+        &self.domains
+    }
+
+    /// Get the [`Challenges`] presently outstanding
+    pub fn challenges(&self) -> &Challenges {
+        &self.challenges
+    }
+
+    /// Get the queue of operations awaiting approval
+    pub fn approvals(&self) -> &approval::Queue {
+        &self.approvals
+    }
+
+    /// Get the number of requests received since the device started
+    pub fn request_count(&self) -> u64 {
+        self.request_count
+    }
+
     /// Get the [`RootKey`]
     pub fn root_key(&self) -> &RootKey<B> {
         &self.root_key
     }
 
-    /// Process the given [`Request`], returning a [`Response`] or an [`Error`]
+    /// Get the [`Storage`] where state is persisted
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Get the [`MonotonicCounter`] which protects state against rollback
+    pub fn counter(&self) -> &C {
+        &self.counter
+    }
+
+    /// Get the version counter of the most recently persisted state
+    /// (zero if state has never been persisted)
+    pub fn state_version(&self) -> u64 {
+        self.state_version
+    }
+
+    /// Get the authorization counter of the most recent request authorized by
+    /// the root keys (zero if none have been). The next must be bound to the
+    /// counter following it.
+    ///
+    /// Each domain has its own counter for requests authorized by its
+    /// administrators (see [`Domain::authorization_counter`]).
+    pub fn authorization_counter(&self) -> u64 {
+        self.authorization_counter
+    }
+
+    /// Process the given [`Request`], returning a [`Response`] or an [`Error`].
+    ///
+    /// Once the device is provisioned, requests other than [`Request::GetInfo`]
+    /// and [`Request::SessionInit`] must be sent within an encrypted session
+    /// (i.e. as [`Request::Session`]), and fail with [`Error::Session`]
+    /// otherwise.
     pub fn handle_request(&mut self, request: Request) -> Result<Response, Error> {
+        self.request_count = self.request_count.saturating_add(1);
+        self.challenges.expire(self.request_count);
+        self.approvals.expire(self.request_count);
+
         match request {
-            Request::Provision(provision) => self
-                .provision(
-                    provision.root_key_threshold as usize,
-                    provision.root_keys.into_iter().map(Into::into),
-                )
-                .map(Into::into),
+            Request::SessionInit(init) => self.init_session(&init).map(Into::into),
+            Request::Session(encrypted) => self.handle_encrypted(&encrypted).map(Into::into),
+            Request::GetInfo(_) => self.info().map(Into::into),
+            _ if self.is_provisioned() => Err(Error::Session),
+            _ => self.dispatch(request, None),
         }
     }
 
-    /// Perform initial device provisioning
+    /// Establish a new session, replacing the least recently used one if
+    /// the maximum number of sessions are already established
+    pub fn init_session(
+        &mut self,
+        request: &session::InitRequest,
+    ) -> Result<session::InitResponse, Error> {
+        let slot = self.free_session_slot();
+        let id = self.next_session_id;
+        let (session, response) = session::respond(&self.session_key, id, &mut self.rng, request)
+            .map_err(|_| Error::Session)?;
+
+        self.sessions[slot] = Some(session);
+        self.callers[slot] = None;
+        self.session_activity = self.session_activity.saturating_add(1);
+        self.session_last_used[slot] = self.session_activity;
+        self.next_session_id = id.wrapping_add(1);
+        Ok(response)
+    }
+
+    /// Find a slot for a new session: a free one, or otherwise that of the
+    /// least recently used session.
+    ///
+    /// Sessions are always replaceable, so hosts establishing sessions
+    /// repeatedly can't prevent others from being established (although
+    /// they can cause sessions to be lost, as they can by not relaying
+    /// messages at all).
+    fn free_session_slot(&self) -> usize {
+        self.sessions
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or_else(|| {
+                (0..MAX_SESSIONS)
+                    .min_by_key(|&slot| self.session_last_used[slot])
+                    .unwrap()
+            })
+    }
+
+    /// Decrypt and process a request sent within an established session,
+    /// returning its encrypted response.
+    ///
+    /// Errors processing the inner request are returned as encrypted error
+    /// responses: only failing to decrypt the request (in which case the
+    /// session is left unchanged) fails with [`Error::Session`].
+    fn handle_encrypted(
+        &mut self,
+        encrypted: &session::Encrypted,
+    ) -> Result<session::Encrypted, Error> {
+        let slot = self
+            .sessions
+            .iter()
+            .position(|session| session.as_ref().map(Session::id) == Some(encrypted.session_id))
+            .ok_or(Error::Session)?;
+
+        let mut session = self.sessions[slot].take().unwrap();
+
+        let plaintext = match session.decrypt(encrypted) {
+            Ok(plaintext) => plaintext,
+            Err(_) => {
+                self.sessions[slot] = Some(session);
+                return Err(Error::Session);
+            }
+        };
+
+        self.session_activity = self.session_activity.saturating_add(1);
+        self.session_last_used[slot] = self.session_activity;
+
+        let caller = self.callers[slot];
+        let response: Response = match Request::decode(&mut Decoder::new(), &plaintext) {
+            Ok(Request::SessionInit(_)) | Ok(Request::Session(_)) => Error::Session.into(),
+            Ok(Request::SessionAuthenticate(authenticate)) => self
+                .authenticate_session(slot, &session, &authenticate)
+                .map(Into::into)
+                .unwrap_or_else(Response::from),
+            Ok(request) => self
+                .dispatch(request, caller.as_ref())
+                .unwrap_or_else(Response::from),
+            Err(_) => schema::error::Response::from(schema::error::Code::Decode).into(),
+        };
+
+        let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+        let encoded = response.encode(&mut buffer).map_err(|_| Error::Capacity)?;
+
+        let result = session.encrypt(encoded).map_err(|_| Error::Session);
+        self.sessions[slot] = Some(session);
+        result
+    }
+
+    /// Authenticate the caller within an established session as the holder
+    /// of the given public key, which key policies may require.
+    ///
+    /// Fails with [`Error::Unauthorized`] if the signature doesn't cover the
+    /// session's authentication digest.
+    fn authenticate_session(
+        &mut self,
+        slot: usize,
+        session: &Session,
+        request: &session::AuthenticateRequest,
+    ) -> Result<session::AuthenticateResponse, Error> {
+        let public_key = crypto::PublicKey::try_from(&request.public_key)?;
+
+        public_key
+            .verify(&session.authentication_digest(), &request.signature)
+            .map_err(|_| Error::Unauthorized)?;
+
+        self.callers[slot] = Some(public_key);
+
+        Ok(session::AuthenticateResponse {
+            public_key: request.public_key.clone(),
+        })
+    }
+
+    /// Dispatch a request (other than a session request) made by the given
+    /// caller to its handler
+    fn dispatch(
+        &mut self,
+        request: Request,
+        caller: Option<&crypto::PublicKey>,
+    ) -> Result<Response, Error> {
+        match request {
+            Request::Provision(provision) => self.provision(&provision).map(Into::into),
+            Request::RootRotate(rotate) => self.rotate_root(&rotate).map(Into::into),
+            Request::DomainCreate(create) => self.create_domain(&create).map(Into::into),
+            Request::DomainUpdate(update) => self.update_domain(&update).map(Into::into),
+            Request::DomainDelete(delete) => self.delete_domain(&delete).map(Into::into),
+            Request::DomainList(_) => self.list_domains().map(Into::into),
+            Request::GenerateKey(generate) => self.generate_key(&generate).map(Into::into),
+            Request::Sign(sign) => self.sign_as(&sign, caller).map(Into::into),
+            Request::ProvePossession(possession) => {
+                self.prove_possession(&possession).map(Into::into)
+            }
+            Request::GetInfo(_) => self.info().map(Into::into),
+            Request::GetPublicKey(public_key) => self.public_key(&public_key).map(Into::into),
+            Request::ExportKey(export) => self.export_key(&export).map(Into::into),
+            Request::ImportKey(import) => self.import_key(&import).map(Into::into),
+            Request::GetChallenge(challenge) => self.get_challenge(&challenge).map(Into::into),
+            Request::SubmitOperation(submit) => {
+                self.submit_operation_as(&submit, caller).map(Into::into)
+            }
+            Request::Approve(approve) => self.approve(&approve),
+            Request::ListPending(_) => self.list_pending().map(Into::into),
+            Request::Cancel(cancel) => self.cancel_operation(&cancel).map(Into::into),
+            Request::ProgramInstall(install) => self.install_program(&install).map(Into::into),
+            Request::SessionInit(_) | Request::Session(_) | Request::SessionAuthenticate(_) => {
+                Err(Error::Session)
+            }
+        }
+    }
+
+    /// Perform initial device provisioning.
+    ///
+    /// The request must be signed by a threshold of the root keys it contains.
     pub fn provision(
         &mut self,
-        threshold: usize,
-        keys: impl IntoIterator<Item = PublicKey>,
+        signed_request: &schema::provision::SignedRequest,
     ) -> Result<schema::provision::Response, Error> {
         if self.is_provisioned() {
-            return Err(Error::Provision);
+            return Err(Error::Provisioned);
         }
 
-        self.root_config = root::Config::new(threshold, keys)?;
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.authorization_counter,
+            request.digest,
+        )?;
+        let uuid = request.uuid(&self.device_id).ok_or(Error::Unauthorized)?;
+
+        let key_set = ThresholdKeySet::from_schema(request.root_key_threshold, &request.root_keys)?;
+
+        key_set.verify(&digest, &signed_request.signatures)?;
+        self.persist(Changes {
+            root_config: Some(root::Config::new(uuid, INITIAL_ROOT_VERSION, key_set)),
+            authorization_counter: Some(signed_request.binding.counter),
+            ..Changes::default()
+        })?;
 
         Ok(schema::provision::Response {
             uuid: self.root_config.uuid(),
         })
     }
 
+    /// Rotate the root key set.
+    ///
+    /// The request must be signed by a threshold of both the current and the
+    /// new root keys, and must increment the root version by exactly one.
+    pub fn rotate_root(
+        &mut self,
+        signed_request: &schema::root::SignedRotateRequest,
+    ) -> Result<schema::root::RotateResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.authorization_counter,
+            request.digest,
+        )?;
+
+        if Some(request.version) != self.root_config.version().checked_add(1) {
+            return Err(Error::Version);
+        }
+
+        let key_set = ThresholdKeySet::try_from(&request.key_set)?;
+
+        // Both the outgoing and incoming root keys must approve the rotation
+        self.root_config
+            .verify(&digest, &signed_request.signatures)?;
+        key_set.verify(&digest, &signed_request.signatures)?;
+
+        let uuid = self.root_config.uuid();
+        self.persist(Changes {
+            root_config: Some(root::Config::new(uuid, request.version, key_set)),
+            authorization_counter: Some(signed_request.binding.counter),
+            ..Changes::default()
+        })?;
+
+        Ok(schema::root::RotateResponse {
+            version: request.version,
+        })
+    }
+
+    /// Create a new domain.
+    ///
+    /// The request must be signed by a threshold of the root keys.
+    pub fn create_domain(
+        &mut self,
+        signed_request: &schema::domain::SignedCreateRequest,
+    ) -> Result<schema::domain::CreateResponse, Error> {
+        let request = &signed_request.request;
+        self.verify_root_signatures(
+            &signed_request.binding,
+            request.digest,
+            &signed_request.signatures,
+        )?;
+
+        let mut domain = Domain::try_from(&request.config)?;
+        let id = domain.id();
+
+        if self.domains.get(id).is_some() {
+            return Err(Error::Duplicate);
+        }
+
+        domain.set_authorization_counter(self.deleted_domain_counter);
+        self.persist(Changes {
+            authorization_counter: Some(signed_request.binding.counter),
+            domain: Some(domain),
+            ..Changes::default()
+        })?;
+
+        Ok(schema::domain::CreateResponse { id })
+    }
+
+    /// Update an existing domain's administrators and policy.
+    ///
+    /// The request must be signed by a threshold of the domain's current
+    /// administrators.
+    pub fn update_domain(
+        &mut self,
+        signed_request: &schema::domain::SignedUpdateRequest,
+    ) -> Result<schema::domain::UpdateResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let updated = Domain::try_from(&request.config)?;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.domain_counter(updated.id())?,
+            request.digest,
+        )?;
+        let digest = self.approval_digest(updated.id(), request.digest, digest)?;
+
+        let id = updated.id();
+        self.verify_admin_signatures(id, &digest, &signed_request.signatures)?;
+
+        let mut domain = self.stage_domain(id)?;
+        domain.update(updated)?;
+        domain.set_authorization_counter(signed_request.binding.counter);
+        self.persist(Changes {
+            domain: Some(domain),
+            ..Changes::default()
+        })?;
+        self.consume_challenge(id, request.digest);
+
+        Ok(schema::domain::UpdateResponse { id })
+    }
+
+    /// Delete a domain along with any keys it contains.
+    ///
+    /// The request must be signed by a threshold of the root keys.
+    pub fn delete_domain(
+        &mut self,
+        signed_request: &schema::domain::SignedDeleteRequest,
+    ) -> Result<schema::domain::DeleteResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.authorization_counter,
+            request.digest,
+        )?;
+        let digest = self.approval_digest(request.id, request.digest, digest)?;
+
+        self.root_config
+            .verify(&digest, &signed_request.signatures)?;
+
+        if self.domains.get(request.id).is_none() {
+            return Err(Error::NotFound);
+        }
+
+        self.persist(Changes {
+            authorization_counter: Some(signed_request.binding.counter),
+            deleted_domain: Some(request.id),
+            ..Changes::default()
+        })?;
+
+        Ok(schema::domain::DeleteResponse { id: request.id })
+    }
+
+    /// List the configurations of all domains
+    pub fn list_domains(&self) -> Result<schema::domain::ListResponse, Error> {
+        let mut domains = schema::domain::Configs::new();
+
+        for domain in self.domains.iter() {
+            domains
+                .push(schema::domain::Config::try_from(domain)?)
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        Ok(schema::domain::ListResponse { domains })
+    }
+
+    /// Generate a new key within a domain.
+    ///
+    /// The request must be signed by a threshold of the domain's
+    /// administrators.
+    pub fn generate_key(
+        &mut self,
+        signed_request: &schema::key::SignedGenerateRequest,
+    ) -> Result<schema::key::GenerateResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.domain_counter(request.domain)?,
+            request.digest,
+        )?;
+        let digest = self.approval_digest(request.domain, request.digest, digest)?;
+        self.verify_admin_signatures(request.domain, &digest, &signed_request.signatures)?;
+
+        let response = self.perform_generate_key(request, Some(&signed_request.binding))?;
+        self.consume_challenge(request.domain, request.digest);
+        Ok(response)
+    }
+
+    /// Sign a message (or a message digest) using the key in the given
+    /// domain and slot on behalf of the given caller (`None` if anonymous).
+    ///
+    /// Fails with [`Error::Policy`] if the key's policy denies it.
+    pub fn sign_as(
+        &mut self,
+        request: &schema::key::SignRequest,
+        caller: Option<&crypto::PublicKey>,
+    ) -> Result<schema::key::SignResponse, Error> {
+        self.sign_approved(request, caller, 0)
+    }
+
+    /// Produce a proof of possession for the key in the given domain and
+    /// slot (BLS keys only)
+    pub fn prove_possession(
+        &self,
+        request: &schema::key::PossessionRequest,
+    ) -> Result<schema::key::PossessionResponse, Error> {
+        let key = self
+            .domains
+            .get(request.domain)
+            .and_then(|domain| domain.key(request.slot))
+            .ok_or(Error::NotFound)?;
+
+        Ok(schema::key::PossessionResponse {
+            proof: key.prove_possession()?,
+        })
+    }
+
+    /// Get the public key of the key in the given domain and slot
+    pub fn public_key(
+        &self,
+        request: &schema::key::PublicKeyRequest,
+    ) -> Result<schema::key::PublicKeyResponse, Error> {
+        let key = self
+            .domains
+            .get(request.domain)
+            .and_then(|domain| domain.key(request.slot))
+            .ok_or(Error::NotFound)?;
+
+        Ok(schema::key::PublicKeyResponse {
+            public_key: schema::PublicKey::try_from(&key.public_key())?,
+        })
+    }
+
+    /// Export the key in the given domain and slot, wrapped under the root
+    /// key and bound to the current root configuration.
+    ///
+    /// The request must be signed by a threshold of the domain's
+    /// administrators.
+    pub fn export_key(
+        &mut self,
+        signed_request: &schema::key::SignedExportRequest,
+    ) -> Result<schema::key::ExportResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.domain_counter(request.domain)?,
+            request.digest,
+        )?;
+        let digest = self.approval_digest(request.domain, request.digest, digest)?;
+        self.verify_admin_signatures(request.domain, &digest, &signed_request.signatures)?;
+
+        let response = self.perform_export_key(request, Some(&signed_request.binding))?;
+        self.consume_challenge(request.domain, request.digest);
+        Ok(response)
+    }
+
+    /// Import a key previously exported by this device into the next free
+    /// slot of the domain it was exported from, restoring the policy it was
+    /// exported with.
+    ///
+    /// The request must be signed by a threshold of the domain's
+    /// administrators. Fails with [`Error::Crypto`] if the key wasn't
+    /// exported from the same domain with the request's policy, or
+    /// [`Error::Duplicate`] if the domain already contains it.
+    pub fn import_key(
+        &mut self,
+        signed_request: &schema::key::SignedImportRequest,
+    ) -> Result<schema::key::ImportResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.domain_counter(request.domain)?,
+            request.digest,
+        )?;
+        let digest = self.approval_digest(request.domain, request.digest, digest)?;
+        self.verify_admin_signatures(request.domain, &digest, &signed_request.signatures)?;
+
+        let response = self.perform_import_key(request, Some(&signed_request.binding))?;
+        self.consume_challenge(request.domain, request.digest);
+        Ok(response)
+    }
+
+    /// Issue a challenge for a pending operation in a domain whose policy
+    /// requires them: its approvers must sign the challenge along with the
+    /// operation before the device will perform it.
+    ///
+    /// Fails with [`Error::Challenge`] if the domain's operations don't
+    /// require challenges, or [`Error::Capacity`] if the maximum number of
+    /// challenges are outstanding for the domain (see
+    /// [`crate::challenge::MAX_CHALLENGES`]).
+    pub fn get_challenge(
+        &mut self,
+        request: &schema::challenge::Request,
+    ) -> Result<schema::challenge::Response, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let lifetime = self
+            .domains
+            .get(request.domain)
+            .ok_or(Error::NotFound)?
+            .policy()
+            .challenge_lifetime()
+            .ok_or(Error::Challenge)?;
+
+        self.challenges.issue(
+            request.domain,
+            request.operation,
+            lifetime,
+            self.request_count,
+            &mut self.rng,
+        )
+    }
+
+    /// Add an operation to the approval queue on behalf of the given caller
+    /// (`None` if anonymous). It will be performed once a threshold of its
+    /// domain's administrators have approved it. The caller's identity is
+    /// checked against the key's policy when a signing operation is
+    /// performed, and other operations are bound to the authorization
+    /// counter following their domain's.
+    ///
+    /// Fails with [`Error::Unauthorized`] unless the caller is one of the
+    /// domain's administrators or (for signing operations) is named by the
+    /// key's policy, or [`Error::Capacity`] if the maximum number of
+    /// operations are already pending, or the caller already has the
+    /// maximum number of operations pending.
+    pub fn submit_operation_as(
+        &mut self,
+        request: &schema::approval::SubmitRequest,
+        caller: Option<&crypto::PublicKey>,
+    ) -> Result<schema::approval::SubmitResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let threshold = self.approval_threshold(&request.operation)?;
+        let domain = request.operation.domain();
+        let caller = caller
+            .filter(|caller| self.is_submitter(&request.operation, caller))
+            .ok_or(Error::Unauthorized)?;
+
+        let counter = match request.operation {
+            schema::approval::Operation::Sign(_) => None,
+            _ => Some(
+                self.domain_counter(domain)?
+                    .checked_add(1)
+                    .ok_or(Error::Replay)?,
+            ),
+        };
+
+        let lifetime = self
+            .domains
+            .get(domain)
+            .ok_or(Error::NotFound)?
+            .policy()
+            .challenge_lifetime()
+            .unwrap_or(approval::PENDING_LIFETIME);
+
+        let (id, nonce) = self.approvals.submit(
+            request,
+            caller,
+            counter,
+            self.request_count.saturating_add(lifetime),
+            &mut self.rng,
+        )?;
+
+        Ok(schema::approval::SubmitResponse {
+            id,
+            nonce,
+            threshold: threshold as u64,
+        })
+    }
+
+    /// Approve a pending operation on behalf of one of its domain's
+    /// administrators.
+    ///
+    /// If this approval meets the threshold (the greater of the domain's
+    /// administrator threshold and the number of approvals the key's policy
+    /// requires), the operation is removed from the queue and performed, and
+    /// its response is returned. Otherwise the operation remains pending and
+    /// a [`schema::Response::Approve`] is returned.
+    pub fn approve(
+        &mut self,
+        request: &schema::approval::ApproveRequest,
+    ) -> Result<Response, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let threshold = self.approval_threshold(
+            self.approvals
+                .get(request.id)
+                .ok_or(Error::NotFound)?
+                .operation(),
+        )?;
+
+        let operation = self.approvals.get_mut(request.id).ok_or(Error::NotFound)?;
+        let admins = self
+            .domains
+            .get(operation.domain())
+            .ok_or(Error::NotFound)?
+            .admins();
+
+        let approvals = operation.approve(admins, &request.signature)?;
+
+        if approvals < threshold {
+            return Ok(schema::approval::ApproveResponse {
+                id: request.id,
+                approvals: approvals as u64,
+                threshold: threshold as u64,
+            }
+            .into());
+        }
+
+        let operation = self.approvals.remove(request.id)?;
+        self.perform(&operation, approvals)
+    }
+
+    /// List summaries of all pending operations (oldest first)
+    pub fn list_pending(&self) -> Result<schema::approval::ListResponse, Error> {
+        let mut pending = schema::approval::PendingOperations::new();
+
+        for operation in self.approvals.iter() {
+            let domain = self
+                .domains
+                .get(operation.domain())
+                .ok_or(Error::NotFound)?;
+
+            let threshold = self.approval_threshold(operation.operation())?;
+
+            pending
+                .push(operation.summary(domain.admins(), threshold))
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        pending.sort_unstable_by_key(|summary| summary.id);
+        Ok(schema::approval::ListResponse { pending })
+    }
+
+    /// Withdraw a pending operation on behalf of any one of its domain's
+    /// administrators
+    pub fn cancel_operation(
+        &mut self,
+        request: &schema::approval::CancelRequest,
+    ) -> Result<schema::approval::CancelResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let operation = self.approvals.get(request.id).ok_or(Error::NotFound)?;
+        let domain = self
+            .domains
+            .get(operation.domain())
+            .ok_or(Error::NotFound)?;
+
+        operation.verify_cancellation(domain.admins(), &request.signature)?;
+        self.approvals.remove(request.id)?;
+
+        Ok(schema::approval::CancelResponse { id: request.id })
+    }
+
+    /// Install an authorization program in a domain, replacing any installed
+    /// previously, or remove it if the request's program is empty.
+    ///
+    /// The request must be signed by a threshold of either the domain's
+    /// administrators or the root keys.
+    pub fn install_program(
+        &mut self,
+        signed_request: &schema::program::SignedInstallRequest,
+    ) -> Result<schema::program::InstallResponse, Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let request = &signed_request.request;
+        let digest = self.authorization_digest(
+            &signed_request.binding,
+            self.domain_counter(request.domain)?,
+            request.digest,
+        )?;
+        let digest = self.approval_digest(request.domain, request.digest, digest)?;
+
+        let program = if request.program.code.is_empty() {
+            None
+        } else {
+            Some(Program::try_from(&request.program)?)
+        };
+
+        if self
+            .verify_admin_signatures(request.domain, &digest, &signed_request.signatures)
+            .is_err()
+        {
+            self.root_config
+                .verify(&digest, &signed_request.signatures)?;
+        }
+
+        let mut domain = self.stage_domain(request.domain)?;
+        domain.set_program(program);
+        domain.set_authorization_counter(signed_request.binding.counter);
+        self.persist(Changes {
+            domain: Some(domain),
+            ..Changes::default()
+        })?;
+        self.consume_challenge(request.domain, request.digest);
+
+        Ok(schema::program::InstallResponse {
+            domain: request.domain,
+        })
+    }
+
+    /// Get information about this device: its firmware, the algorithms it
+    /// supports, its provisioning state, and its key slot usage
+    pub fn info(&self) -> Result<schema::info::Response, Error> {
+        let mut firmware_version = schema::info::FirmwareVersion::new();
+        firmware_version
+            .extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes())
+            .map_err(|_| Error::Capacity)?;
+
+        let mut slots = schema::info::SlotUsages::new();
+        let mut domain_counters = schema::info::DomainCounters::new();
+
+        for domain in self.domains.iter() {
+            slots
+                .push(schema::info::SlotUsage {
+                    domain: domain.id(),
+                    used: domain.keys().len() as u64,
+                    max: domain.policy().max_keys() as u64,
+                })
+                .map_err(|_| Error::Capacity)?;
+
+            domain_counters
+                .push(schema::info::DomainCounter {
+                    domain: domain.id(),
+                    counter: domain.authorization_counter(),
+                })
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        Ok(schema::info::Response {
+            firmware_version,
+            schema_version: schema::info::SCHEMA_VERSION,
+            algorithms: schema::info::algorithm_mask(signing_key::ALGORITHMS),
+            uuid: self.root_config.uuid(),
+            root_version: self.root_config.version(),
+            slots,
+            max_message_size: MAX_MESSAGE_SIZE as u64,
+            session_key: self.session_public_key().to_bytes(),
+            device_id: self.device_id,
+            authorization_counter: self.authorization_counter,
+            domain_counters,
+        })
+    }
+
     /// Are we already provisioned?
     pub fn is_provisioned(&self) -> bool {
         !self.root_config.is_empty()
     }
+
+    /// Seal the current state with the given changes made to it under the
+    /// root key and persist it to storage, incrementing the state version
+    /// counter, then apply the changes.
+    ///
+    /// If the state can't be persisted, the changes are discarded and the
+    /// current state is left as it was.
+    fn persist(&mut self, changes: Changes) -> Result<(), Error> {
+        let version = self.state_version.checked_add(1).ok_or(Error::Version)?;
+        let mut deleted_domain_counter = self.deleted_domain_counter;
+        let mut new_domain = changes.domain.as_ref();
+        let mut domains = schema::state::Domains::new();
+
+        for domain in self.domains.iter() {
+            if changes.deleted_domain == Some(domain.id()) {
+                deleted_domain_counter = deleted_domain_counter.max(domain.authorization_counter());
+                continue;
+            }
+
+            let domain = match &changes.domain {
+                Some(changed) if changed.id() == domain.id() => {
+                    new_domain = None;
+                    changed
+                }
+                _ => domain,
+            };
+
+            domains
+                .push(schema::state::Domain::try_from(domain)?)
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        if let Some(domain) = new_domain {
+            domains
+                .push(schema::state::Domain::try_from(domain)?)
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        let state = schema::state::State {
+            version,
+            root: schema::state::RootConfig::try_from(
+                changes.root_config.as_ref().unwrap_or(&self.root_config),
+            )?,
+            domains,
+            authorization_counter: changes
+                .authorization_counter
+                .unwrap_or(self.authorization_counter),
+            deleted_domain_counter,
+        };
+
+        state::save(
+            &self.root_key,
+            &mut self.rng,
+            &mut self.storage,
+            &mut self.counter,
+            &state,
+        )?;
+        self.state_version = version;
+
+        if let Some(root_config) = changes.root_config {
+            self.root_config = root_config;
+        }
+
+        if let Some(counter) = changes.authorization_counter {
+            self.authorization_counter = counter;
+        }
+
+        if let Some(id) = changes.deleted_domain {
+            self.domains.remove(id)?;
+            self.approvals.remove_domain(id);
+            self.challenges.remove_domain(id);
+        }
+
+        if let Some(domain) = changes.domain {
+            self.domains.replace(domain)?;
+        }
+
+        self.deleted_domain_counter = deleted_domain_counter;
+        Ok(())
+    }
+
+    /// Copy the given domain so changes to it can be persisted before
+    /// they're applied
+    fn stage_domain(&self, domain: domain::Id) -> Result<Domain, Error> {
+        self.domains.get(domain).cloned().ok_or(Error::NotFound)
+    }
+
+    /// Get the authorization counter of the given domain
+    fn domain_counter(&self, domain: domain::Id) -> Result<u64, Error> {
+        self.domains
+            .get(domain)
+            .map(Domain::authorization_counter)
+            .ok_or(Error::NotFound)
+    }
+
+    /// Compute the digest which must be signed to authorize a request with
+    /// the given digest and binding, ensuring the binding is to this device
+    /// and to the counter following the given one: that of the most recent
+    /// request authorized by the same role (the root keys or a domain)
+    fn authorization_digest(
+        &self,
+        binding: &Binding,
+        counter: u64,
+        request_digest: Option<Sha256Digest>,
+    ) -> Result<Sha256Digest, Error> {
+        // Digests are computed by `veriform` when the request is decoded
+        let request_digest = request_digest.ok_or(Error::Unauthorized)?;
+
+        if binding.device_id != self.device_id || Some(binding.counter) != counter.checked_add(1) {
+            return Err(Error::Replay);
+        }
+
+        Ok(binding.digest(&request_digest))
+    }
+
+    /// Compute the digest which approvers of an operation in the given domain
+    /// must sign: if the domain's policy requires challenges, this covers the
+    /// nonce of the challenge issued for the operation as well as the given
+    /// authorization digest. The challenge must be consumed with
+    /// [`Armistice::consume_challenge`] once the operation is performed.
+    fn approval_digest(
+        &self,
+        domain: domain::Id,
+        request_digest: Option<Sha256Digest>,
+        authorization_digest: Sha256Digest,
+    ) -> Result<Sha256Digest, Error> {
+        let domain = self.domains.get(domain).ok_or(Error::NotFound)?;
+
+        if domain.policy().challenge_lifetime().is_none() {
+            return Ok(authorization_digest);
+        }
+
+        // Digests are computed by `veriform` when the request is decoded
+        let request_digest = request_digest.ok_or(Error::Unauthorized)?;
+        self.challenges
+            .digest(domain.id(), &request_digest, &authorization_digest)
+    }
+
+    /// Consume the challenge (if any) covered by the approvals of an
+    /// operation performed in the given domain, so they can't be used again.
+    /// Challenges are only consumed once the operation's changes are
+    /// persisted, so operations which fail can be retried.
+    fn consume_challenge(&mut self, domain: domain::Id, request_digest: Option<Sha256Digest>) {
+        if let Some(request_digest) = request_digest {
+            self.challenges.remove(domain, &request_digest);
+        }
+    }
+
+    /// Get the number of approvals required to perform the given operation
+    /// via the approval queue
+    fn approval_threshold(&self, operation: &schema::approval::Operation) -> Result<usize, Error> {
+        let domain = self
+            .domains
+            .get(operation.domain())
+            .ok_or(Error::NotFound)?;
+
+        let threshold = domain.admins().threshold();
+
+        match operation {
+            schema::approval::Operation::Sign(request) => Ok(domain
+                .key_policy(request.slot)
+                .map(|policy| policy.required_approvals().max(threshold))
+                .unwrap_or(threshold)),
+            _ => Ok(threshold),
+        }
+    }
+
+    /// Is the given caller allowed to submit the given operation: one of its
+    /// domain's administrators, or (for signing) named by the key's policy?
+    fn is_submitter(
+        &self,
+        operation: &schema::approval::Operation,
+        caller: &crypto::PublicKey,
+    ) -> bool {
+        let domain = match self.domains.get(operation.domain()) {
+            Some(domain) => domain,
+            None => return false,
+        };
+
+        if domain.admins().contains(caller) {
+            return true;
+        }
+
+        match operation {
+            schema::approval::Operation::Sign(request) => domain
+                .key_policy(request.slot)
+                .map(|policy| policy.names_caller(caller))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Perform an operation which has been approved by the given number of
+    /// administrators via the approval queue
+    fn perform(
+        &mut self,
+        operation: &approval::PendingOperation,
+        approvals: usize,
+    ) -> Result<Response, Error> {
+        use schema::approval::Operation;
+
+        match operation.operation() {
+            Operation::Sign(request) => self
+                .sign_approved(request, Some(operation.caller()), approvals)
+                .map(Into::into),
+            Operation::GenerateKey(request) => {
+                let binding = self.pending_binding(operation)?;
+                self.perform_generate_key(request, Some(&binding))
+                    .map(Into::into)
+            }
+            Operation::ExportKey(request) => {
+                let binding = self.pending_binding(operation)?;
+                self.perform_export_key(request, Some(&binding))
+                    .map(Into::into)
+            }
+            Operation::ImportKey(request) => {
+                let binding = self.pending_binding(operation)?;
+                self.perform_import_key(request, Some(&binding))
+                    .map(Into::into)
+            }
+        }
+    }
+
+    /// Get the binding of an approved operation to the authorization counter
+    /// it was submitted with, ensuring nothing else has been authorized in
+    /// its domain since
+    fn pending_binding(&self, operation: &approval::PendingOperation) -> Result<Binding, Error> {
+        let counter = operation.counter().ok_or(Error::Replay)?;
+
+        if self.domain_counter(operation.domain())?.checked_add(1) != Some(counter) {
+            return Err(Error::Replay);
+        }
+
+        Ok(Binding {
+            device_id: self.device_id,
+            counter,
+        })
+    }
+
+    /// Sign as described by a request made by the given caller which has
+    /// been approved by the given number of administrators, provided the
+    /// key's policy allows it
+    fn sign_approved(
+        &mut self,
+        request: &schema::key::SignRequest,
+        caller: Option<&crypto::PublicKey>,
+        approvals: usize,
+    ) -> Result<schema::key::SignResponse, Error> {
+        let domain = self.domains.get(request.domain).ok_or(Error::NotFound)?;
+        let context = policy::Context {
+            request_count: self.request_count,
+            authorization_counter: domain.authorization_counter(),
+            approvals,
+            caller,
+        };
+
+        let key = domain.check_key(request.slot, &request.payload, &context)?;
+        let signature = match &request.payload {
+            schema::key::Payload::Message(message) => key.sign(message)?,
+            schema::key::Payload::Sha256(digest) => key.sign_prehashed(digest)?,
+        };
+
+        // Uses of rate limited keys are persisted before their signatures
+        // are returned, so their windows survive restarts
+        if domain
+            .key_policy(request.slot)
+            .map(policy::Policy::is_rate_limited)
+            .unwrap_or(false)
+        {
+            let mut domain = self.stage_domain(request.domain)?;
+            domain.record_use(request.slot)?;
+            self.persist(Changes {
+                domain: Some(domain),
+                ..Changes::default()
+            })?;
+        }
+
+        Ok(schema::key::SignResponse { signature })
+    }
+
+    /// Generate a new key as described by an authorized request, recording
+    /// the authorization counter of its binding (if it has one)
+    fn perform_generate_key(
+        &mut self,
+        request: &schema::key::GenerateRequest,
+        binding: Option<&Binding>,
+    ) -> Result<schema::key::GenerateResponse, Error> {
+        let algorithm = request.algorithm().ok_or(Error::Crypto)?;
+        let policy = policy::Policy::try_from(&request.policy)?;
+
+        let mut domain = self.stage_domain(request.domain)?;
+        let slot = domain.generate_key(algorithm, policy, &mut self.rng)?;
+        let public_key = domain.key(slot).ok_or(Error::NotFound)?.public_key();
+
+        if let Some(binding) = binding {
+            domain.set_authorization_counter(binding.counter);
+        }
+
+        self.persist(Changes {
+            domain: Some(domain),
+            ..Changes::default()
+        })?;
+
+        Ok(schema::key::GenerateResponse {
+            slot,
+            public_key: schema::PublicKey::try_from(&public_key)?,
+        })
+    }
+
+    /// Export a key as described by an authorized request, recording the
+    /// authorization counter of its binding (if it has one)
+    fn perform_export_key(
+        &mut self,
+        request: &schema::key::ExportRequest,
+        binding: Option<&Binding>,
+    ) -> Result<schema::key::ExportResponse, Error> {
+        let domain = self.domains.get(request.domain).ok_or(Error::NotFound)?;
+        let key = domain.key(request.slot).ok_or(Error::NotFound)?;
+        let policy = schema::policy::Policy::try_from(
+            domain.key_policy(request.slot).ok_or(Error::NotFound)?,
+        )?;
+
+        let uuid = self.root_config.uuid();
+        let wrapped_key = wrap::wrap(
+            &self.root_key,
+            &mut self.rng,
+            &uuid,
+            request.domain,
+            &policy,
+            key,
+        )?;
+
+        if let Some(binding) = binding {
+            let mut domain = self.stage_domain(request.domain)?;
+            domain.set_authorization_counter(binding.counter);
+            self.persist(Changes {
+                domain: Some(domain),
+                ..Changes::default()
+            })?;
+        }
+
+        Ok(schema::key::ExportResponse {
+            wrapped_key,
+            policy,
+        })
+    }
+
+    /// Import a key as described by an authorized request, recording the
+    /// authorization counter of its binding (if it has one)
+    fn perform_import_key(
+        &mut self,
+        request: &schema::key::ImportRequest,
+        binding: Option<&Binding>,
+    ) -> Result<schema::key::ImportResponse, Error> {
+        let uuid = self.root_config.uuid();
+        let policy = policy::Policy::try_from(&request.policy)?;
+        let mut domain = self.stage_domain(request.domain)?;
+
+        // Keys are restored with the policy they were exported with, which
+        // is compared in the same form it was exported in
+        let key = wrap::unwrap(
+            &self.root_key,
+            &uuid,
+            request.domain,
+            &schema::policy::Policy::try_from(&policy)?,
+            &request.wrapped_key,
+        )?;
+        let public_key = schema::PublicKey::try_from(&key.public_key())?;
+        let slot = domain.insert_key(key, policy)?;
+
+        if let Some(binding) = binding {
+            domain.set_authorization_counter(binding.counter);
+        }
+
+        self.persist(Changes {
+            domain: Some(domain),
+            ..Changes::default()
+        })?;
+
+        Ok(schema::key::ImportResponse { slot, public_key })
+    }
+
+    /// Verify a threshold of the given domain's administrators have signed
+    /// the given digest
+    fn verify_admin_signatures(
+        &self,
+        domain: domain::Id,
+        digest: &Sha256Digest,
+        signatures: &[schema::Signature],
+    ) -> Result<(), Error> {
+        self.domains
+            .get(domain)
+            .ok_or(Error::NotFound)?
+            .admins()
+            .verify(digest, signatures)
+    }
+
+    /// Verify a threshold of root keys have signed the given request digest
+    /// under the given binding
+    fn verify_root_signatures(
+        &self,
+        binding: &Binding,
+        request_digest: Option<Sha256Digest>,
+        signatures: &[schema::Signature],
+    ) -> Result<(), Error> {
+        if !self.is_provisioned() {
+            return Err(Error::Unprovisioned);
+        }
+
+        let digest =
+            self.authorization_digest(binding, self.authorization_counter, request_digest)?;
+        self.root_config.verify(&digest, signatures)
+    }
+}
+
+/// Changes to the device's state, which are persisted before they're applied
+#[derive(Default)]
+struct Changes {
+    /// New root configuration
+    root_config: Option<root::Config>,
+
+    /// Authorization counter of a request the root keys have authorized
+    authorization_counter: Option<u64>,
+
+    /// Domain to add, or to replace the existing one with the same ID
+    domain: Option<Domain>,
+
+    /// ID of a domain to delete
+    deleted_domain: Option<domain::Id>,
 }
@@ -0,0 +1,192 @@
+//! Challenges: device-issued nonces bound to pending operations
+//!
+//! Operations in domains whose policy requires challenges must be approved
+//! by signatures over a challenge the device issued for that operation (see
+//! [`schema::challenge`]). Challenges are held in volatile memory, are
+//! single use, and expire after the domain's configured number of requests.
+//!
+//! Anyone may request a challenge, so outstanding challenges are never
+//! replaced before they expire or are used: each operation has at most one,
+//! and no more are issued for a domain while it has the maximum number
+//! outstanding. Each domain's challenges are limited separately, so
+//! requesting challenges for one domain can't prevent them being issued
+//! for others.
+
+use crate::{
+    domain::{self, MaxDomains},
+    error::Error,
+    schema::{self, challenge::Nonce, veriform::Sha256Digest},
+};
+use block_cipher::generic_array::typenum::Unsigned;
+use rand_core::{CryptoRng, RngCore};
+
+/// Maximum number of outstanding challenges per domain
+pub const MAX_CHALLENGES: usize = 4;
+
+/// Maximum number of outstanding challenges across all domains
+const MAX_OUTSTANDING: usize = MAX_CHALLENGES * <MaxDomains as Unsigned>::USIZE;
+
+/// Challenge issued for a pending operation
+#[derive(Clone, Debug)]
+struct Challenge {
+    /// Domain the operation is performed in
+    domain: domain::Id,
+
+    /// Digest of the operation's request
+    operation: Sha256Digest,
+
+    /// Random nonce which must be signed along with the operation
+    nonce: Nonce,
+
+    /// Request count after which the challenge has expired
+    expires_at: u64,
+}
+
+/// Outstanding challenges
+#[derive(Debug, Default)]
+pub struct Challenges {
+    /// Issued challenges
+    slots: [Option<Challenge>; MAX_OUTSTANDING],
+}
+
+impl Challenges {
+    /// Expire any challenges which have outlived their lifetime as of the
+    /// given count of requests received by the device
+    pub(crate) fn expire(&mut self, request_count: u64) {
+        for slot in self.slots.iter_mut() {
+            if slot
+                .as_ref()
+                .map(|challenge| challenge.expires_at < request_count)
+                .unwrap_or(false)
+            {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Issue a challenge for the given operation which expires after the
+    /// given number of requests following the given request count, or get
+    /// the challenge already outstanding for the same operation.
+    ///
+    /// Fails with [`Error::Capacity`] if the maximum number of challenges
+    /// are already outstanding for the domain.
+    pub(crate) fn issue(
+        &mut self,
+        domain: domain::Id,
+        operation: Sha256Digest,
+        lifetime: u64,
+        request_count: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<schema::challenge::Response, Error> {
+        if let Some(index) = self.position(domain, &operation) {
+            let challenge = self.slots[index].as_ref().unwrap();
+
+            return Ok(schema::challenge::Response {
+                nonce: challenge.nonce,
+                expires_in: challenge.expires_at.saturating_sub(request_count),
+            });
+        }
+
+        if self.domain_len(domain) >= MAX_CHALLENGES {
+            return Err(Error::Capacity);
+        }
+
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(Error::Capacity)?;
+
+        let mut nonce = Nonce::default();
+        rng.fill_bytes(&mut nonce);
+
+        *slot = Some(Challenge {
+            domain,
+            operation,
+            nonce,
+            expires_at: request_count.saturating_add(lifetime),
+        });
+
+        Ok(schema::challenge::Response {
+            nonce,
+            expires_in: lifetime,
+        })
+    }
+
+    /// Compute the digest approvers of the given operation must sign, which
+    /// covers the nonce of its outstanding challenge. The challenge remains
+    /// outstanding until it's [`Challenges::remove`]d.
+    ///
+    /// Fails with [`Error::Challenge`] if no challenge was issued for the
+    /// operation, or it has expired.
+    pub(crate) fn digest(
+        &self,
+        domain: domain::Id,
+        operation: &Sha256Digest,
+        authorization_digest: &Sha256Digest,
+    ) -> Result<Sha256Digest, Error> {
+        let challenge = self
+            .position(domain, operation)
+            .and_then(|index| self.slots[index].as_ref())
+            .ok_or(Error::Challenge)?;
+
+        Ok(schema::challenge::digest(
+            &challenge.nonce,
+            authorization_digest,
+        ))
+    }
+
+    /// Remove the outstanding challenge for the given operation (if any),
+    /// e.g. once it has been used
+    pub(crate) fn remove(&mut self, domain: domain::Id, operation: &Sha256Digest) {
+        if let Some(index) = self.position(domain, operation) {
+            self.slots[index] = None;
+        }
+    }
+
+    /// Remove all outstanding challenges for the given domain, e.g. once
+    /// it has been deleted
+    pub(crate) fn remove_domain(&mut self, domain: domain::Id) {
+        for slot in self.slots.iter_mut() {
+            if slot
+                .as_ref()
+                .map(|challenge| challenge.domain == domain)
+                .unwrap_or(false)
+            {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Get the number of challenges presently outstanding
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Are there presently no outstanding challenges?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of challenges presently outstanding for the given
+    /// domain
+    fn domain_len(&self, domain: domain::Id) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| {
+                slot.as_ref()
+                    .map(|challenge| challenge.domain == domain)
+                    .unwrap_or(false)
+            })
+            .count()
+    }
+
+    /// Find the slot holding the challenge for the given operation
+    fn position(&self, domain: domain::Id, operation: &Sha256Digest) -> Option<usize> {
+        self.slots.iter().position(|slot| {
+            slot.as_ref()
+                .map(|c| c.domain == domain && &c.operation == operation)
+                .unwrap_or(false)
+        })
+    }
+}
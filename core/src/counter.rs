@@ -0,0 +1,27 @@
+//! Monotonic counters: provide rollback protection for sealed state
+//!
+//! The counter value is bound into the associated data of sealed state, and
+//! state whose counter is behind the current value is refused. Counters
+//! should be backed by storage an attacker can't roll back independently of
+//! the sealed state (e.g. eFuses or a replay-protected memory block).
+
+mod memory;
+
+#[cfg(feature = "std")]
+mod file;
+
+pub use self::memory::MemoryCounter;
+
+#[cfg(feature = "std")]
+pub use self::file::FileCounter;
+
+use crate::error::Error;
+
+/// Monotonic counters
+pub trait MonotonicCounter {
+    /// Get the current value of the counter
+    fn get(&mut self) -> Result<u64, Error>;
+
+    /// Increment the counter, returning its new value
+    fn increment(&mut self) -> Result<u64, Error>;
+}
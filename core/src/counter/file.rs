@@ -0,0 +1,59 @@
+//! File-backed monotonic counter (for host testing and simulation)
+
+use super::MonotonicCounter;
+use crate::error::Error;
+use std::{
+    convert::TryInto,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// File-backed monotonic counter: the value is stored as a big endian
+/// 64-bit integer
+#[derive(Clone, Debug)]
+pub struct FileCounter {
+    /// Path to the file containing the counter value
+    path: PathBuf,
+}
+
+impl FileCounter {
+    /// Open a file-backed counter at the given path.
+    ///
+    /// The counter starts at zero if the file doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        FileCounter {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Get the path to the file containing the counter value
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl MonotonicCounter for FileCounter {
+    fn get(&mut self) -> Result<u64, Error> {
+        match fs::read(&self.path) {
+            Ok(bytes) => bytes
+                .as_slice()
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| Error::Storage),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(_) => Err(Error::Storage),
+        }
+    }
+
+    fn increment(&mut self) -> Result<u64, Error> {
+        let value = self.get()?.checked_add(1).ok_or(Error::Capacity)?;
+
+        // Write to a temporary file and rename it into place so a partially
+        // written value is never observed
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, value.to_be_bytes()).map_err(|_| Error::Storage)?;
+        fs::rename(&tmp_path, &self.path).map_err(|_| Error::Storage)?;
+
+        Ok(value)
+    }
+}
@@ -0,0 +1,29 @@
+//! In-memory monotonic counter (lost on reset; useful for host testing)
+
+use super::MonotonicCounter;
+use crate::error::Error;
+
+/// In-memory monotonic counter
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCounter {
+    /// Current counter value
+    value: u64,
+}
+
+impl MemoryCounter {
+    /// Create a new in-memory counter starting at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MonotonicCounter for MemoryCounter {
+    fn get(&mut self) -> Result<u64, Error> {
+        Ok(self.value)
+    }
+
+    fn increment(&mut self) -> Result<u64, Error> {
+        self.value = self.value.checked_add(1).ok_or(Error::Capacity)?;
+        Ok(self.value)
+    }
+}
@@ -1,7 +1,11 @@
 //! Cryptographic functionality
 
+#[cfg(feature = "bls")]
+pub mod bls;
 pub mod public_key;
 pub mod root_key;
+pub mod signing_key;
 
 pub use public_key::PublicKey;
 pub use root_key::RootKey;
+pub use signing_key::SigningKey;
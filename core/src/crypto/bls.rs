@@ -0,0 +1,175 @@
+//! BLS12-381 signatures using the Ethereum 2.0 ciphersuite.
+//!
+//! Public keys are G1 points and signatures are G2 points, using the
+//! proof-of-possession scheme from the IETF BLS signature draft:
+//!
+//! <https://tools.ietf.org/html/draft-irtf-cfrg-bls-signature-02>
+
+use crate::error::Error;
+use bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve},
+    pairing, G1Affine, G1Projective, G2Affine, G2Projective, Scalar,
+};
+use core::fmt;
+use rand_core::{CryptoRng, RngCore};
+
+/// Domain separation tag for signatures (Ethereum 2.0 ciphersuite)
+pub const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Domain separation tag for proofs of possession
+pub const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Size of a serialized (compressed G1) public key
+pub const PUBLIC_KEY_SIZE: usize = 48;
+
+/// Size of a serialized (compressed G2) signature
+pub const SIGNATURE_SIZE: usize = 96;
+
+/// BLS secret keys
+#[derive(Clone)]
+pub struct SecretKey(Scalar);
+
+impl SecretKey {
+    /// Generate a random [`SecretKey`]
+    pub fn generate(rng: &mut (impl CryptoRng + RngCore)) -> Self {
+        let mut bytes = [0u8; 64];
+
+        loop {
+            rng.fill_bytes(&mut bytes);
+
+            // Reducing 512 bits modulo the group order yields a uniform scalar
+            let scalar = Scalar::from_bytes_wide(&bytes);
+
+            if scalar != Scalar::zero() {
+                return SecretKey(scalar);
+            }
+        }
+    }
+
+    /// Parse a secret scalar (little endian), rejecting zero
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, Error> {
+        Option::<Scalar>::from(Scalar::from_bytes(bytes))
+            .filter(|scalar| *scalar != Scalar::zero())
+            .map(SecretKey)
+            .ok_or(Error::Crypto)
+    }
+
+    /// Serialize the secret scalar (little endian)
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+
+    /// Get the [`PublicKey`] which corresponds to this secret key
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(G1Affine::from(G1Projective::generator() * self.0))
+    }
+
+    /// Sign the given message
+    pub fn sign(&self, msg: &[u8]) -> Signature {
+        self.sign_with_dst(msg, SIGNATURE_DST)
+    }
+
+    /// Produce a proof of possession of this secret key: a signature over the
+    /// serialized public key under a separate domain
+    pub fn prove_possession(&self) -> Signature {
+        self.sign_with_dst(&self.public_key().to_bytes(), POP_DST)
+    }
+
+    /// Sign a message under the given domain separation tag
+    fn sign_with_dst(&self, msg: &[u8], dst: &[u8]) -> Signature {
+        Signature(G2Affine::from(hash_to_g2(msg, dst) * self.0))
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid leaking secret key material via `Debug`
+        f.debug_tuple("SecretKey")
+            .field(&self.public_key())
+            .finish()
+    }
+}
+
+/// BLS public keys (G1 points)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PublicKey(G1Affine);
+
+impl PublicKey {
+    /// Parse a compressed G1 point, rejecting the identity
+    pub fn from_bytes(bytes: &[u8; PUBLIC_KEY_SIZE]) -> Result<Self, Error> {
+        Option::<G1Affine>::from(G1Affine::from_compressed(bytes))
+            .filter(|point| !bool::from(point.is_identity()))
+            .map(PublicKey)
+            .ok_or(Error::Crypto)
+    }
+
+    /// Serialize this public key as a compressed G1 point
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_SIZE] {
+        self.0.to_compressed()
+    }
+
+    /// Aggregate the given public keys into a single public key.
+    ///
+    /// Proofs of possession must be verified for all keys prior to using
+    /// the result to verify an aggregate signature.
+    pub fn aggregate<'a>(keys: impl IntoIterator<Item = &'a PublicKey>) -> Result<Self, Error> {
+        let mut keys = keys.into_iter();
+        let first = keys.next().ok_or(Error::Crypto)?;
+        let sum = keys.fold(G1Projective::from(first.0), |sum, key| sum + key.0);
+        Ok(PublicKey(G1Affine::from(sum)))
+    }
+
+    /// Verify a signature over the given message
+    pub fn verify(&self, msg: &[u8], signature: &Signature) -> Result<(), Error> {
+        self.verify_with_dst(msg, signature, SIGNATURE_DST)
+    }
+
+    /// Verify a proof of possession for this public key
+    pub fn verify_possession(&self, proof: &Signature) -> Result<(), Error> {
+        self.verify_with_dst(&self.to_bytes(), proof, POP_DST)
+    }
+
+    /// Verify a signature under the given domain separation tag
+    fn verify_with_dst(&self, msg: &[u8], signature: &Signature, dst: &[u8]) -> Result<(), Error> {
+        let hash = G2Affine::from(hash_to_g2(msg, dst));
+
+        if pairing(&self.0, &hash) == pairing(&G1Affine::generator(), &signature.0) {
+            Ok(())
+        } else {
+            Err(Error::Crypto)
+        }
+    }
+}
+
+/// BLS signatures (G2 points)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Signature(G2Affine);
+
+impl Signature {
+    /// Parse a compressed G2 point
+    pub fn from_bytes(bytes: &[u8; SIGNATURE_SIZE]) -> Result<Self, Error> {
+        Option::<G2Affine>::from(G2Affine::from_compressed(bytes))
+            .map(Signature)
+            .ok_or(Error::Crypto)
+    }
+
+    /// Serialize this signature as a compressed G2 point
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_SIZE] {
+        self.0.to_compressed()
+    }
+
+    /// Aggregate the given signatures into a single signature
+    pub fn aggregate<'a>(
+        signatures: impl IntoIterator<Item = &'a Signature>,
+    ) -> Result<Self, Error> {
+        let mut signatures = signatures.into_iter();
+        let first = signatures.next().ok_or(Error::Crypto)?;
+        let sum = signatures.fold(G2Projective::from(first.0), |sum, sig| sum + sig.0);
+        Ok(Signature(G2Affine::from(sum)))
+    }
+}
+
+/// Hash a message to a G2 point under the given domain separation tag
+fn hash_to_g2(msg: &[u8], dst: &[u8]) -> G2Projective {
+    <G2Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(msg, dst)
+}
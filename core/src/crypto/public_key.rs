@@ -1,10 +1,19 @@
 //! Public key types
 
-use crate::schema;
+use crate::{error::Error, schema};
+use core::convert::TryFrom;
+use ed25519_dalek::Verifier;
+
+#[cfg(feature = "bls")]
+use super::bls;
 
 /// Public keys
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum PublicKey {
+    /// BLS12-381 public keys
+    #[cfg(feature = "bls")]
+    Bls12381(bls::PublicKey),
+
     /// ECDSA public keys
     #[cfg(feature = "ecdsa")]
     Ecdsa(EcdsaKey),
@@ -14,19 +23,128 @@ pub enum PublicKey {
     Ed25519([u8; 32]),
 }
 
-// TODO(tarcieri): this should eventually be a `TryFrom`
-impl From<schema::public_key::PublicKey> for PublicKey {
-    fn from(key: schema::public_key::PublicKey) -> PublicKey {
+impl PublicKey {
+    /// Verify a signature over the given message using this public key
+    pub fn verify(&self, msg: &[u8], signature: &schema::Signature) -> Result<(), Error> {
+        match (self, signature) {
+            (PublicKey::Ed25519(bytes), schema::Signature::Ed25519(signature)) => {
+                let public_key =
+                    ed25519_dalek::PublicKey::from_bytes(bytes).map_err(|_| Error::Crypto)?;
+
+                public_key
+                    .verify(msg, &ed25519_dalek::Signature::new(*signature))
+                    .map_err(|_| Error::Crypto)
+            }
+            #[cfg(feature = "bls")]
+            (PublicKey::Bls12381(key), schema::Signature::Bls12381(signature)) => {
+                key.verify(msg, &bls::Signature::from_bytes(signature)?)
+            }
+            #[cfg(feature = "ecdsa")]
+            (PublicKey::Ecdsa(key), signature) => key.verify(msg, signature),
+            _ => Err(Error::Crypto),
+        }
+    }
+}
+
+impl TryFrom<&schema::PublicKey> for PublicKey {
+    type Error = Error;
+
+    fn try_from(key: &schema::PublicKey) -> Result<Self, Error> {
+        match key {
+            schema::PublicKey::Ed25519(bytes) => {
+                // Ensure the key is a valid curve point before accepting it
+                ed25519_dalek::PublicKey::from_bytes(bytes).map_err(|_| Error::Crypto)?;
+                Ok(PublicKey::Ed25519(*bytes))
+            }
+            #[cfg(feature = "ecdsa")]
+            schema::PublicKey::EcdsaP256(bytes) => {
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                    .map(|key| PublicKey::Ecdsa(EcdsaKey::P256(key)))
+                    .map_err(|_| Error::Crypto)
+            }
+            #[cfg(feature = "secp256k1")]
+            schema::PublicKey::EcdsaSecp256k1(bytes) => {
+                k256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                    .map(|key| PublicKey::Ecdsa(EcdsaKey::Secp256k1(key)))
+                    .map_err(|_| Error::Crypto)
+            }
+            #[cfg(feature = "bls")]
+            schema::PublicKey::Bls12381(bytes) => {
+                bls::PublicKey::from_bytes(bytes).map(PublicKey::Bls12381)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::Crypto),
+        }
+    }
+}
+
+impl TryFrom<&PublicKey> for schema::PublicKey {
+    type Error = Error;
+
+    fn try_from(key: &PublicKey) -> Result<Self, Error> {
         match key {
-            schema::public_key::PublicKey::Ed25519(bytes) => PublicKey::Ed25519(bytes),
+            PublicKey::Ed25519(bytes) => Ok(schema::PublicKey::Ed25519(*bytes)),
+            #[cfg(feature = "bls")]
+            PublicKey::Bls12381(key) => Ok(schema::PublicKey::Bls12381(key.to_bytes())),
+            #[cfg(feature = "ecdsa")]
+            PublicKey::Ecdsa(EcdsaKey::P256(key)) => {
+                compressed_point(key.to_encoded_point(true).as_bytes())
+                    .map(schema::PublicKey::EcdsaP256)
+            }
+            #[cfg(feature = "secp256k1")]
+            PublicKey::Ecdsa(EcdsaKey::Secp256k1(key)) => {
+                compressed_point(key.to_encoded_point(true).as_bytes())
+                    .map(schema::PublicKey::EcdsaSecp256k1)
+            }
         }
     }
 }
 
 /// ECDSA public keys
-#[derive(Copy, Clone, Debug)]
+#[cfg(feature = "ecdsa")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum EcdsaKey {
     /// NIST P-256 public keys
-    #[cfg(feature = "ecdsa")]
-    P256(ecdsa::curve::nistp256::PublicKey),
+    P256(p256::ecdsa::VerifyingKey),
+
+    /// secp256k1 public keys
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(k256::ecdsa::VerifyingKey),
+}
+
+#[cfg(feature = "ecdsa")]
+impl EcdsaKey {
+    /// Verify an ECDSA signature over the given message (hashed with SHA-256)
+    pub fn verify(&self, msg: &[u8], signature: &schema::Signature) -> Result<(), Error> {
+        use p256::ecdsa::signature::Verifier;
+
+        match (self, signature) {
+            (EcdsaKey::P256(key), schema::Signature::EcdsaP256(signature)) => {
+                let signature =
+                    p256::ecdsa::Signature::from_slice(signature).map_err(|_| Error::Crypto)?;
+
+                key.verify(msg, &signature).map_err(|_| Error::Crypto)
+            }
+            #[cfg(feature = "secp256k1")]
+            (EcdsaKey::Secp256k1(key), schema::Signature::EcdsaSecp256k1(signature)) => {
+                let signature =
+                    k256::ecdsa::Signature::from_slice(signature).map_err(|_| Error::Crypto)?;
+
+                key.verify(msg, &signature).map_err(|_| Error::Crypto)
+            }
+            _ => Err(Error::Crypto),
+        }
+    }
+}
+
+/// Convert a compressed SEC1-encoded elliptic curve point into an array
+#[cfg(feature = "ecdsa")]
+fn compressed_point(bytes: &[u8]) -> Result<[u8; 33], Error> {
+    if bytes.len() != 33 {
+        return Err(Error::Crypto);
+    }
+
+    let mut point = [0u8; 33];
+    point.copy_from_slice(bytes);
+    Ok(point)
 }
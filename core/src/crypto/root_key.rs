@@ -2,5 +2,9 @@
 
 use aes_gcm_siv::AesGcmSiv;
 
-/// Root AES-GCM-SIV key
+/// Root AES-GCM-SIV key: seals device state persisted to storage, with the
+/// value of a [`MonotonicCounter`] bound into the associated data to prevent
+/// rollback
+///
+/// [`MonotonicCounter`]: crate::counter::MonotonicCounter
 pub type RootKey<B> = AesGcmSiv<B>;
@@ -0,0 +1,288 @@
+//! Signing keys
+
+use super::PublicKey;
+use crate::{
+    error::Error,
+    schema::{self, key::Algorithm},
+};
+use core::{convert::TryFrom, fmt};
+use ed25519_dalek::Signer;
+use rand_core::{CryptoRng, RngCore};
+
+#[cfg(feature = "bls")]
+use super::bls;
+
+#[cfg(feature = "ecdsa")]
+use {
+    super::public_key::EcdsaKey,
+    p256::ecdsa::signature::{hazmat::PrehashSigner, Signer as _},
+};
+
+/// Key algorithms supported by this build (i.e. enabled via cargo features)
+pub const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::Ed25519,
+    #[cfg(feature = "ecdsa")]
+    Algorithm::EcdsaP256,
+    #[cfg(feature = "secp256k1")]
+    Algorithm::EcdsaSecp256k1,
+    #[cfg(feature = "bls")]
+    Algorithm::Bls12381,
+];
+
+/// Signing keys (i.e. private keys)
+pub enum SigningKey {
+    /// BLS12-381 signing keys
+    #[cfg(feature = "bls")]
+    Bls12381(bls::SecretKey),
+
+    /// ECDSA/P-256 signing keys
+    #[cfg(feature = "ecdsa")]
+    EcdsaP256(p256::ecdsa::SigningKey),
+
+    /// ECDSA/secp256k1 signing keys
+    #[cfg(feature = "secp256k1")]
+    EcdsaSecp256k1(k256::ecdsa::SigningKey),
+
+    /// Ed25519 signing keys
+    Ed25519(ed25519_dalek::Keypair),
+}
+
+impl SigningKey {
+    /// Generate a new random [`SigningKey`] for the given [`Algorithm`].
+    ///
+    /// Returns [`Error::Crypto`] if support for the algorithm is disabled.
+    pub fn generate(
+        algorithm: Algorithm,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self, Error> {
+        match algorithm {
+            Algorithm::Ed25519 => {
+                let mut bytes = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+                rng.fill_bytes(&mut bytes);
+
+                // Only fails if the input is the wrong length
+                let secret = ed25519_dalek::SecretKey::from_bytes(&bytes).unwrap();
+                let public = ed25519_dalek::PublicKey::from(&secret);
+                Ok(SigningKey::Ed25519(ed25519_dalek::Keypair {
+                    secret,
+                    public,
+                }))
+            }
+            #[cfg(feature = "bls")]
+            Algorithm::Bls12381 => Ok(SigningKey::Bls12381(bls::SecretKey::generate(rng))),
+            #[cfg(feature = "ecdsa")]
+            Algorithm::EcdsaP256 => Ok(SigningKey::EcdsaP256(random_scalar(rng, |bytes| {
+                p256::ecdsa::SigningKey::from_slice(bytes).ok()
+            }))),
+            #[cfg(feature = "secp256k1")]
+            Algorithm::EcdsaSecp256k1 => {
+                Ok(SigningKey::EcdsaSecp256k1(random_scalar(rng, |bytes| {
+                    k256::ecdsa::SigningKey::from_slice(bytes).ok()
+                })))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::Crypto),
+        }
+    }
+
+    /// Get the [`Algorithm`] of this key
+    pub fn algorithm(&self) -> Algorithm {
+        match self {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(_) => Algorithm::Bls12381,
+            #[cfg(feature = "ecdsa")]
+            SigningKey::EcdsaP256(_) => Algorithm::EcdsaP256,
+            #[cfg(feature = "secp256k1")]
+            SigningKey::EcdsaSecp256k1(_) => Algorithm::EcdsaSecp256k1,
+            SigningKey::Ed25519(_) => Algorithm::Ed25519,
+        }
+    }
+
+    /// Get the [`PublicKey`] which corresponds to this key
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(key) => PublicKey::Bls12381(key.public_key()),
+            #[cfg(feature = "ecdsa")]
+            SigningKey::EcdsaP256(key) => PublicKey::Ecdsa(EcdsaKey::P256(*key.verifying_key())),
+            #[cfg(feature = "secp256k1")]
+            SigningKey::EcdsaSecp256k1(key) => {
+                PublicKey::Ecdsa(EcdsaKey::Secp256k1(*key.verifying_key()))
+            }
+            SigningKey::Ed25519(keypair) => PublicKey::Ed25519(keypair.public.to_bytes()),
+        }
+    }
+
+    /// Sign the given message, hashing it as specified by the signature
+    /// algorithm (SHA-256 in the case of ECDSA)
+    pub fn sign(&self, msg: &[u8]) -> Result<schema::Signature, Error> {
+        match self {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(key) => Ok(schema::Signature::Bls12381(key.sign(msg).to_bytes())),
+            #[cfg(feature = "ecdsa")]
+            SigningKey::EcdsaP256(key) => {
+                let signature: p256::ecdsa::Signature =
+                    key.try_sign(msg).map_err(|_| Error::Crypto)?;
+                Ok(schema::Signature::EcdsaP256(signature.to_bytes().into()))
+            }
+            #[cfg(feature = "secp256k1")]
+            SigningKey::EcdsaSecp256k1(key) => {
+                let signature: k256::ecdsa::Signature =
+                    key.try_sign(msg).map_err(|_| Error::Crypto)?;
+                Ok(schema::Signature::EcdsaSecp256k1(
+                    signature.to_bytes().into(),
+                ))
+            }
+            SigningKey::Ed25519(keypair) => keypair
+                .try_sign(msg)
+                .map(|signature| schema::Signature::Ed25519(signature.to_bytes()))
+                .map_err(|_| Error::Crypto),
+        }
+    }
+
+    /// Sign the SHA-256 digest of a message which has been hashed in advance.
+    ///
+    /// Only supported by ECDSA keys: returns [`Error::Crypto`] otherwise.
+    #[cfg_attr(not(feature = "ecdsa"), allow(unused_variables))]
+    pub fn sign_prehashed(&self, digest: &[u8; 32]) -> Result<schema::Signature, Error> {
+        match self {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(_) => Err(Error::Crypto),
+            #[cfg(feature = "ecdsa")]
+            SigningKey::EcdsaP256(key) => {
+                let signature: p256::ecdsa::Signature =
+                    key.sign_prehash(digest).map_err(|_| Error::Crypto)?;
+                Ok(schema::Signature::EcdsaP256(signature.to_bytes().into()))
+            }
+            #[cfg(feature = "secp256k1")]
+            SigningKey::EcdsaSecp256k1(key) => {
+                let signature: k256::ecdsa::Signature =
+                    key.sign_prehash(digest).map_err(|_| Error::Crypto)?;
+                Ok(schema::Signature::EcdsaSecp256k1(
+                    signature.to_bytes().into(),
+                ))
+            }
+            SigningKey::Ed25519(_) => Err(Error::Crypto),
+        }
+    }
+
+    /// Produce a proof of possession of this key.
+    ///
+    /// Only supported by BLS keys: returns [`Error::Crypto`] otherwise.
+    pub fn prove_possession(&self) -> Result<schema::Signature, Error> {
+        match self {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(key) => Ok(schema::Signature::Bls12381(
+                key.prove_possession().to_bytes(),
+            )),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::Crypto),
+        }
+    }
+}
+
+impl TryFrom<&schema::state::SecretKey> for SigningKey {
+    type Error = Error;
+
+    fn try_from(key: &schema::state::SecretKey) -> Result<Self, Error> {
+        match key {
+            schema::state::SecretKey::Ed25519(bytes) => {
+                let secret =
+                    ed25519_dalek::SecretKey::from_bytes(bytes).map_err(|_| Error::Crypto)?;
+                let public = ed25519_dalek::PublicKey::from(&secret);
+                Ok(SigningKey::Ed25519(ed25519_dalek::Keypair {
+                    secret,
+                    public,
+                }))
+            }
+            #[cfg(feature = "ecdsa")]
+            schema::state::SecretKey::EcdsaP256(bytes) => {
+                p256::ecdsa::SigningKey::from_slice(bytes)
+                    .map(SigningKey::EcdsaP256)
+                    .map_err(|_| Error::Crypto)
+            }
+            #[cfg(feature = "secp256k1")]
+            schema::state::SecretKey::EcdsaSecp256k1(bytes) => {
+                k256::ecdsa::SigningKey::from_slice(bytes)
+                    .map(SigningKey::EcdsaSecp256k1)
+                    .map_err(|_| Error::Crypto)
+            }
+            #[cfg(feature = "bls")]
+            schema::state::SecretKey::Bls12381(bytes) => {
+                bls::SecretKey::from_bytes(bytes).map(SigningKey::Bls12381)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::Crypto),
+        }
+    }
+}
+
+impl From<&SigningKey> for schema::state::SecretKey {
+    fn from(key: &SigningKey) -> schema::state::SecretKey {
+        match key {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(key) => schema::state::SecretKey::Bls12381(key.to_bytes()),
+            #[cfg(feature = "ecdsa")]
+            SigningKey::EcdsaP256(key) => {
+                schema::state::SecretKey::EcdsaP256(key.to_bytes().into())
+            }
+            #[cfg(feature = "secp256k1")]
+            SigningKey::EcdsaSecp256k1(key) => {
+                schema::state::SecretKey::EcdsaSecp256k1(key.to_bytes().into())
+            }
+            SigningKey::Ed25519(keypair) => {
+                schema::state::SecretKey::Ed25519(keypair.secret.to_bytes())
+            }
+        }
+    }
+}
+
+impl Clone for SigningKey {
+    fn clone(&self) -> Self {
+        match self {
+            #[cfg(feature = "bls")]
+            SigningKey::Bls12381(key) => SigningKey::Bls12381(key.clone()),
+            #[cfg(feature = "ecdsa")]
+            SigningKey::EcdsaP256(key) => SigningKey::EcdsaP256(key.clone()),
+            #[cfg(feature = "secp256k1")]
+            SigningKey::EcdsaSecp256k1(key) => SigningKey::EcdsaSecp256k1(key.clone()),
+            SigningKey::Ed25519(keypair) => {
+                // Only fails if the input is the wrong length
+                let secret =
+                    ed25519_dalek::SecretKey::from_bytes(keypair.secret.as_bytes()).unwrap();
+
+                SigningKey::Ed25519(ed25519_dalek::Keypair {
+                    secret,
+                    public: keypair.public,
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid leaking secret key material via `Debug`
+        f.debug_struct("SigningKey")
+            .field("public_key", &self.public_key())
+            .finish()
+    }
+}
+
+/// Generate a random ECDSA secret scalar, retrying in the (astronomically
+/// unlikely) event the random bytes are out of range for the curve
+#[cfg(feature = "ecdsa")]
+fn random_scalar<K>(
+    rng: &mut (impl CryptoRng + RngCore),
+    from_bytes: impl Fn(&[u8]) -> Option<K>,
+) -> K {
+    let mut bytes = [0u8; 32];
+
+    loop {
+        rng.fill_bytes(&mut bytes);
+
+        if let Some(key) = from_bytes(&bytes) {
+            return key;
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//! Domains: namespaced containers for keys
+//!
+//! Each domain is administered by its own threshold key set and has a policy
+//! which constrains the keys it contains. Domains are created and deleted by
+//! the root role.
+
+use crate::{
+    crypto::SigningKey,
+    error::Error,
+    policy::{self, Context, Denial, Usage},
+    program::{self, Program, Verdict},
+    schema::{
+        self,
+        key::{Algorithm, Payload},
+    },
+    threshold::ThresholdKeySet,
+};
+use block_cipher::generic_array::typenum::Unsigned;
+use core::{convert::TryFrom, slice};
+use heapless::Vec;
+use rand_core::{CryptoRng, RngCore};
+
+/// Domain identifiers
+pub type Id = schema::domain::Id;
+
+/// Maximum number of domains
+pub type MaxDomains = heapless::consts::U8;
+
+/// Maximum number of key slots in a domain
+pub type MaxKeys = heapless::consts::U8;
+
+/// Key slot numbers (i.e. identifiers for keys within a domain)
+pub type Slot = schema::key::Slot;
+
+/// Domain: namespaced container for keys
+#[derive(Clone, Debug)]
+pub struct Domain {
+    /// Domain identifier
+    id: Id,
+
+    /// Threshold key set which administers this domain
+    admins: ThresholdKeySet,
+
+    /// Policy for keys within this domain
+    policy: Policy,
+
+    /// Keys within this domain, indexed by slot
+    keys: Vec<Key, MaxKeys>,
+
+    /// Authorization program run before each use of a key (if installed)
+    program: Option<Program>,
+
+    /// Authorization counter of the most recent request authorized for this
+    /// domain
+    authorization_counter: u64,
+}
+
+impl Domain {
+    /// Create a new [`Domain`] which does not yet contain any keys
+    pub fn new(id: Id, admins: ThresholdKeySet, policy: Policy) -> Self {
+        Domain {
+            id,
+            admins,
+            policy,
+            keys: Vec::new(),
+            program: None,
+            authorization_counter: 0,
+        }
+    }
+
+    /// Get the domain identifier
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Get the threshold key set which administers this domain
+    pub fn admins(&self) -> &ThresholdKeySet {
+        &self.admins
+    }
+
+    /// Get the policy for keys within this domain
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Get the authorization program installed in this domain, if any
+    pub fn program(&self) -> Option<&Program> {
+        self.program.as_ref()
+    }
+
+    /// Get the authorization counter of the most recent request authorized
+    /// for this domain. The next must be bound to the counter following it.
+    pub fn authorization_counter(&self) -> u64 {
+        self.authorization_counter
+    }
+
+    /// Get the key in the given slot, if it exists
+    pub fn key(&self, slot: Slot) -> Option<&SigningKey> {
+        self.keys.get(slot as usize).map(|key| &key.signing_key)
+    }
+
+    /// Get the policy for the key in the given slot, if it exists
+    pub fn key_policy(&self, slot: Slot) -> Option<&policy::Policy> {
+        self.keys.get(slot as usize).map(|key| &key.policy)
+    }
+
+    /// Iterate over the keys in this domain (in slot order)
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &SigningKey> {
+        self.keys.iter().map(|key| &key.signing_key)
+    }
+
+    /// Get the key in the given slot for the given use, checking both its
+    /// policy and this domain's authorization program allow it. The use must
+    /// then be recorded with [`Domain::record_use`].
+    ///
+    /// Fails with [`Error::Policy`] if either denies it.
+    pub(crate) fn check_key(
+        &self,
+        slot: Slot,
+        payload: &Payload,
+        context: &Context<'_>,
+    ) -> Result<&SigningKey, Error> {
+        let key = self.keys.get(slot as usize).ok_or(Error::NotFound)?;
+        key.policy.check(&key.usage, payload, context)?;
+
+        if let Some(program) = &self.program {
+            let input = program::Input {
+                domain: self.id,
+                slot,
+                algorithm: key.signing_key.algorithm(),
+                payload,
+                request_count: context.request_count,
+                approvals: context.approvals,
+                authenticated: context.caller.is_some(),
+            };
+
+            match program.run(&input) {
+                Ok(Verdict::Allow) => (),
+                Ok(Verdict::Deny) => return Err(Denial::Program.into()),
+                Err(fault) => return Err(Denial::ProgramFault(fault).into()),
+            }
+        }
+
+        Ok(&key.signing_key)
+    }
+
+    /// Record a use of the key in the given slot as of this domain's
+    /// authorization counter, against which its rate limit is checked
+    pub(crate) fn record_use(&mut self, slot: Slot) -> Result<(), Error> {
+        let key = self.keys.get_mut(slot as usize).ok_or(Error::NotFound)?;
+        key.usage.record(&key.policy, self.authorization_counter);
+        Ok(())
+    }
+
+    /// Replace this domain's administrators and policy with those of the
+    /// given updated domain.
+    ///
+    /// Fails if the updated policy has fewer key slots than are in use.
+    pub(crate) fn update(&mut self, updated: Domain) -> Result<(), Error> {
+        debug_assert_eq!(self.id, updated.id);
+
+        if updated.policy.max_keys < self.keys.len() {
+            return Err(Error::Capacity);
+        }
+
+        self.admins = updated.admins;
+        self.policy = updated.policy;
+        Ok(())
+    }
+
+    /// Record the authorization counter of a request authorized for this
+    /// domain
+    pub(crate) fn set_authorization_counter(&mut self, counter: u64) {
+        self.authorization_counter = counter;
+    }
+
+    /// Install an authorization program, replacing any installed previously
+    /// (or remove it, if `None`)
+    pub(crate) fn set_program(&mut self, program: Option<Program>) {
+        self.program = program;
+    }
+
+    /// Generate a new key governed by the given policy in the next free
+    /// slot, returning the slot number
+    pub(crate) fn generate_key(
+        &mut self,
+        algorithm: Algorithm,
+        policy: policy::Policy,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Slot, Error> {
+        if self.keys.len() >= self.policy.max_keys {
+            return Err(Error::Capacity);
+        }
+
+        self.insert_key(SigningKey::generate(algorithm, rng)?, policy)
+    }
+
+    /// Add an existing key (e.g. an imported one) governed by the given
+    /// policy to the next free slot, returning the slot number.
+    ///
+    /// Fails with [`Error::Duplicate`] if the domain already contains the
+    /// same key, which would otherwise be usable (and rate limited)
+    /// separately in each slot.
+    pub(crate) fn insert_key(
+        &mut self,
+        signing_key: SigningKey,
+        policy: policy::Policy,
+    ) -> Result<Slot, Error> {
+        if self.keys.len() >= self.policy.max_keys {
+            return Err(Error::Capacity);
+        }
+
+        let public_key = signing_key.public_key();
+
+        if self
+            .keys
+            .iter()
+            .any(|key| key.signing_key.public_key() == public_key)
+        {
+            return Err(Error::Duplicate);
+        }
+
+        let slot = self.keys.len() as Slot;
+        self.keys
+            .push(Key::new(signing_key, policy))
+            .map_err(|_| Error::Capacity)?;
+        Ok(slot)
+    }
+}
+
+/// Key stored in a domain's slot, along with its policy
+#[derive(Clone, Debug)]
+struct Key {
+    /// Signing key
+    signing_key: SigningKey,
+
+    /// Policy governing use of the key
+    policy: policy::Policy,
+
+    /// Record of the key's recent use
+    usage: Usage,
+}
+
+impl Key {
+    /// Create a new [`Key`] which hasn't yet been used
+    fn new(signing_key: SigningKey, policy: policy::Policy) -> Self {
+        Key {
+            signing_key,
+            policy,
+            usage: Usage::default(),
+        }
+    }
+}
+
+impl TryFrom<&schema::domain::Config> for Domain {
+    type Error = Error;
+
+    fn try_from(config: &schema::domain::Config) -> Result<Self, Error> {
+        Ok(Domain::new(
+            config.id,
+            ThresholdKeySet::try_from(&config.admins)?,
+            Policy::try_from(&config.policy)?,
+        ))
+    }
+}
+
+impl TryFrom<&Domain> for schema::domain::Config {
+    type Error = Error;
+
+    fn try_from(domain: &Domain) -> Result<Self, Error> {
+        Ok(schema::domain::Config {
+            id: domain.id,
+            admins: schema::ThresholdKeySet::try_from(&domain.admins)?,
+            policy: schema::domain::Policy::from(&domain.policy),
+        })
+    }
+}
+
+impl TryFrom<&schema::state::Domain> for Domain {
+    type Error = Error;
+
+    fn try_from(state: &schema::state::Domain) -> Result<Self, Error> {
+        let mut domain = Domain::try_from(&state.config)?;
+
+        if state.keys.len() > domain.policy.max_keys {
+            return Err(Error::Capacity);
+        }
+
+        for key in &state.keys {
+            domain
+                .keys
+                .push(Key {
+                    signing_key: SigningKey::try_from(&key.secret_key)?,
+                    policy: policy::Policy::try_from(&key.policy)?,
+                    usage: Usage::from(&key.usage),
+                })
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        if !state.program.code.is_empty() {
+            domain.program = Some(Program::try_from(&state.program)?);
+        }
+
+        domain.authorization_counter = state.authorization_counter;
+        Ok(domain)
+    }
+}
+
+impl TryFrom<&Domain> for schema::state::Domain {
+    type Error = Error;
+
+    fn try_from(domain: &Domain) -> Result<Self, Error> {
+        let mut keys = schema::state::Keys::new();
+
+        for key in &domain.keys {
+            keys.push(schema::state::Key {
+                secret_key: (&key.signing_key).into(),
+                policy: schema::policy::Policy::try_from(&key.policy)?,
+                usage: (&key.usage).into(),
+            })
+            .map_err(|_| Error::Capacity)?;
+        }
+
+        Ok(schema::state::Domain {
+            config: schema::domain::Config::try_from(domain)?,
+            keys,
+            program: domain.program.as_ref().map(Into::into).unwrap_or_default(),
+            authorization_counter: domain.authorization_counter,
+        })
+    }
+}
+
+/// Domain policy: constraints on the keys within a domain
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Policy {
+    /// Maximum number of key slots available in this domain
+    max_keys: usize,
+
+    /// Number of requests after which challenges for operations in this
+    /// domain expire (zero if operations don't require challenges)
+    challenge_lifetime: u64,
+}
+
+impl Policy {
+    /// Create a new domain [`Policy`]
+    pub fn new(max_keys: usize, challenge_lifetime: u64) -> Result<Self, Error> {
+        if max_keys > MaxKeys::to_usize() {
+            return Err(Error::Capacity);
+        }
+
+        Ok(Policy {
+            max_keys,
+            challenge_lifetime,
+        })
+    }
+
+    /// Get the maximum number of key slots available in this domain
+    pub fn max_keys(&self) -> usize {
+        self.max_keys
+    }
+
+    /// Get the number of requests after which challenges for operations in
+    /// this domain expire, or `None` if operations don't require challenges
+    pub fn challenge_lifetime(&self) -> Option<u64> {
+        match self.challenge_lifetime {
+            0 => None,
+            lifetime => Some(lifetime),
+        }
+    }
+}
+
+impl TryFrom<&schema::domain::Policy> for Policy {
+    type Error = Error;
+
+    fn try_from(policy: &schema::domain::Policy) -> Result<Self, Error> {
+        Policy::new(policy.max_keys as usize, policy.challenge_lifetime)
+    }
+}
+
+impl From<&Policy> for schema::domain::Policy {
+    fn from(policy: &Policy) -> schema::domain::Policy {
+        schema::domain::Policy {
+            max_keys: policy.max_keys as u64,
+            challenge_lifetime: policy.challenge_lifetime,
+        }
+    }
+}
+
+/// Collection of all domains on the device
+#[derive(Debug, Default)]
+pub struct Domains(Vec<Domain, MaxDomains>);
+
+impl Domains {
+    /// Get the domain with the given ID, if it exists
+    pub fn get(&self, id: Id) -> Option<&Domain> {
+        self.0.iter().find(|domain| domain.id == id)
+    }
+
+    /// Get a mutable reference to the domain with the given ID, if it exists
+    pub(crate) fn get_mut(&mut self, id: Id) -> Option<&mut Domain> {
+        self.0.iter_mut().find(|domain| domain.id == id)
+    }
+
+    /// Iterate over all domains
+    pub fn iter(&self) -> slice::Iter<'_, Domain> {
+        self.0.iter()
+    }
+
+    /// Get the number of domains
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Are there presently no domains?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Add a new domain, ensuring its ID is unique
+    pub(crate) fn insert(&mut self, domain: Domain) -> Result<(), Error> {
+        if self.get(domain.id).is_some() {
+            return Err(Error::Duplicate);
+        }
+
+        self.0.push(domain).map_err(|_| Error::Capacity)
+    }
+
+    /// Add a domain, replacing any existing one with the same ID
+    pub(crate) fn replace(&mut self, domain: Domain) -> Result<(), Error> {
+        match self.get_mut(domain.id) {
+            Some(existing) => {
+                *existing = domain;
+                Ok(())
+            }
+            None => self.0.push(domain).map_err(|_| Error::Capacity),
+        }
+    }
+
+    /// Remove the domain with the given ID
+    pub(crate) fn remove(&mut self, id: Id) -> Result<Domain, Error> {
+        let index = self
+            .0
+            .iter()
+            .position(|domain| domain.id == id)
+            .ok_or(Error::NotFound)?;
+
+        Ok(self.0.swap_remove(index))
+    }
+}
@@ -1,18 +1,84 @@
 //! Error type
 
+use crate::{policy::Denial, schema};
 use displaydoc::Display;
 
 /// Types of errors
 #[derive(Copy, Clone, Debug, Display, Eq, PartialEq)]
 pub enum Error {
+    /// Capacity exceeded
+    Capacity,
+
+    /// Missing or expired challenge
+    Challenge,
+
     /// Crypto error
     Crypto,
 
-    /// Provisioning error
-    Provision,
+    /// Duplicate entry
+    Duplicate,
+
+    /// Not found
+    NotFound,
+
+    /// Denied by key policy: {0}
+    Policy(Denial),
+
+    /// Already provisioned
+    Provisioned,
+
+    /// Stale or replayed request
+    Replay,
+
+    /// Encrypted session required
+    Session,
+
+    /// Storage error
+    Storage,
 
     /// Threshold invalid
     Threshold,
+
+    /// Insufficient valid signatures
+    Unauthorized,
+
+    /// Not yet provisioned
+    Unprovisioned,
+
+    /// Version invalid
+    Version,
+}
+
+impl From<Error> for schema::error::Code {
+    fn from(error: Error) -> schema::error::Code {
+        match error {
+            Error::Capacity => schema::error::Code::Capacity,
+            Error::Challenge => schema::error::Code::Challenge,
+            Error::Crypto => schema::error::Code::Crypto,
+            Error::Duplicate => schema::error::Code::Duplicate,
+            Error::NotFound => schema::error::Code::NotFound,
+            Error::Policy(_) => schema::error::Code::Policy,
+            Error::Provisioned => schema::error::Code::Provisioned,
+            Error::Replay => schema::error::Code::Replay,
+            Error::Session => schema::error::Code::Session,
+            Error::Storage => schema::error::Code::Storage,
+            Error::Threshold => schema::error::Code::Threshold,
+            Error::Unauthorized => schema::error::Code::Unauthorized,
+            Error::Unprovisioned => schema::error::Code::Unprovisioned,
+            Error::Version => schema::error::Code::Version,
+        }
+    }
+}
+
+impl From<Error> for schema::Response {
+    fn from(error: Error) -> schema::Response {
+        match error {
+            Error::Policy(denial) => {
+                schema::error::Response::new(schema::error::Code::Policy, denial.as_str()).into()
+            }
+            _ => schema::error::Response::from(schema::error::Code::from(error)).into(),
+        }
+    }
 }
 
 #[cfg(feature = "std")]
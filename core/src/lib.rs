@@ -9,13 +9,27 @@
 #[cfg(feature = "std")]
 extern crate std;
 
+pub mod approval;
 mod armistice;
+pub mod challenge;
+pub mod counter;
 pub mod crypto;
+pub mod domain;
 mod error;
+pub mod policy;
+pub mod program;
 pub mod root;
+mod state;
+pub mod storage;
+pub mod threshold;
+mod wrap;
 
 pub use armistice_schema as schema;
+pub use block_cipher;
 pub use heapless::{self, String, Vec};
+pub use rand_core;
 
-pub use armistice::Armistice;
+pub use armistice::{Armistice, DeviceId, MAX_MESSAGE_SIZE, MAX_SESSIONS};
+pub use counter::MonotonicCounter;
 pub use error::Error;
+pub use storage::Storage;
@@ -0,0 +1,359 @@
+//! Key policies: constraints on how individual keys may be used
+//!
+//! A [`Policy`] is attached to each key when it's generated or imported (see
+//! [`schema::policy`] for its encoding), and is evaluated before every
+//! operation which uses the key's secret (presently signing). Denials carry
+//! a [`Denial`] describing which constraint wasn't satisfied, which is
+//! returned to the client in the error response.
+//!
+//! As the device has no trusted clock, signature rate limit windows are
+//! measured in the authorization counter of the key's domain, which only
+//! advances when its administrators authorize an operation in it: callers
+//! can't advance it by using keys (this or any other), so a key may make at
+//! most its maximum number of signatures until the domain's administrators
+//! have authorized the window's number of operations. Each key counts its
+//! own signatures in the current window, which are persisted once a
+//! signature has been made (and before it's returned), so restarting the
+//! device doesn't reset its window.
+
+use crate::{
+    crypto::PublicKey,
+    error::Error,
+    program::Fault,
+    schema::{
+        self,
+        key::Payload,
+        policy::{hash_algorithm_mask, HashAlgorithm, MaxCallers, Prefixes},
+    },
+    threshold,
+};
+use block_cipher::generic_array::typenum::Unsigned;
+use core::{convert::TryFrom, fmt};
+use heapless::Vec;
+
+/// Reasons a policy denies an operation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Denial {
+    /// Policy is malformed or unsatisfiable
+    Invalid,
+
+    /// Caller's session identity isn't allowed
+    Caller,
+
+    /// Operation hasn't been approved by enough administrators
+    Approvals,
+
+    /// Hash algorithm isn't allowed
+    HashAlgorithm,
+
+    /// Message doesn't begin with an allowed prefix
+    Prefix,
+
+    /// Message is too short or too long
+    Length,
+
+    /// Maximum number of signatures in the current window reached
+    RateLimit,
+
+    /// Domain's authorization program denied the operation
+    Program,
+
+    /// Domain's authorization program faulted
+    ProgramFault(Fault),
+}
+
+impl Denial {
+    /// Get a description of this denial
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Denial::Invalid => "invalid policy",
+            Denial::Caller => "caller not allowed",
+            Denial::Approvals => "insufficient approvals",
+            Denial::HashAlgorithm => "hash algorithm not allowed",
+            Denial::Prefix => "message prefix not allowed",
+            Denial::Length => "message length not allowed",
+            Denial::RateLimit => "signature rate limit exceeded",
+            Denial::Program => "denied by authorization program",
+            Denial::ProgramFault(fault) => fault.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<Denial> for Error {
+    fn from(denial: Denial) -> Error {
+        Error::Policy(denial)
+    }
+}
+
+/// Circumstances under which a key is being used
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Context<'a> {
+    /// Number of requests the device has received since it started
+    pub request_count: u64,
+
+    /// Authorization counter of the key's domain, against which rate limit
+    /// windows are measured
+    pub authorization_counter: u64,
+
+    /// Number of domain administrators who have approved the operation
+    pub approvals: usize,
+
+    /// Session identity of the caller (`None` if anonymous)
+    pub caller: Option<&'a PublicKey>,
+}
+
+/// Policy for an individual key
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Policy {
+    /// Prefixes one of which messages must begin with (any if empty)
+    prefixes: Prefixes,
+
+    /// Minimum length of messages
+    min_length: usize,
+
+    /// Maximum length of messages, if limited
+    max_length: Option<usize>,
+
+    /// Bitmask of allowed hash algorithms
+    hash_algorithms: u64,
+
+    /// Maximum number of signatures and the window they're counted over (in
+    /// increments of the domain's authorization counter), if limited
+    rate_limit: Option<(u64, u64)>,
+
+    /// Number of administrator approvals required to use the key
+    required_approvals: usize,
+
+    /// Session identities of the callers allowed to use the key (only with
+    /// the approval of the domain's administrators if empty)
+    callers: Vec<PublicKey, MaxCallers>,
+}
+
+impl Policy {
+    /// Get the number of domain administrators who must approve uses of the
+    /// key (zero if it may be used without their approval)
+    pub fn required_approvals(&self) -> usize {
+        self.required_approvals
+    }
+
+    /// Is the given session identity one of the callers this policy names?
+    pub fn names_caller(&self, caller: &PublicKey) -> bool {
+        self.callers.contains(caller)
+    }
+
+    /// Does this policy limit the rate of signatures (in which case each use
+    /// of the key must be persisted)?
+    pub fn is_rate_limited(&self) -> bool {
+        self.rate_limit.is_some()
+    }
+
+    /// Evaluate whether this policy allows signing the given payload, given
+    /// the key's prior usage
+    pub fn check(
+        &self,
+        usage: &Usage,
+        payload: &Payload,
+        context: &Context<'_>,
+    ) -> Result<(), Denial> {
+        // Keys are denied to everyone by default: callers must be named by
+        // the policy, or if it names none, have their use approved by the
+        // domain's administrators
+        let allowed = if self.callers.is_empty() {
+            context.approvals > 0
+        } else {
+            context
+                .caller
+                .map(|caller| self.callers.contains(caller))
+                .unwrap_or(false)
+        };
+
+        if !allowed {
+            return Err(Denial::Caller);
+        }
+
+        if context.approvals < self.required_approvals {
+            return Err(Denial::Approvals);
+        }
+
+        if self.hash_algorithms & hash_algorithm_mask(&[HashAlgorithm::from(payload)]) == 0 {
+            return Err(Denial::HashAlgorithm);
+        }
+
+        if self.constrains_messages() {
+            let message = match payload {
+                Payload::Message(message) => message,
+                // Prehashed payloads can't be checked against constraints
+                // on the message itself
+                Payload::Sha256(_) => return Err(Denial::HashAlgorithm),
+            };
+
+            if !self.prefixes.is_empty()
+                && !self
+                    .prefixes
+                    .iter()
+                    .any(|prefix| message.starts_with(&prefix.bytes))
+            {
+                return Err(Denial::Prefix);
+            }
+
+            if message.len() < self.min_length
+                || self
+                    .max_length
+                    .map(|max| message.len() > max)
+                    .unwrap_or(false)
+            {
+                return Err(Denial::Length);
+            }
+        }
+
+        if let Some((max_signatures, window)) = self.rate_limit {
+            if usage.signatures_in_window(window, context.authorization_counter) >= max_signatures {
+                return Err(Denial::RateLimit);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Does this policy constrain the contents of messages?
+    fn constrains_messages(&self) -> bool {
+        !self.prefixes.is_empty() || self.min_length > 0 || self.max_length.is_some()
+    }
+}
+
+impl TryFrom<&schema::policy::Policy> for Policy {
+    type Error = Error;
+
+    fn try_from(policy: &schema::policy::Policy) -> Result<Self, Error> {
+        let all_hash_algorithms =
+            hash_algorithm_mask(&[HashAlgorithm::Intrinsic, HashAlgorithm::Sha256]);
+
+        let hash_algorithms = match policy.hash_algorithms {
+            0 => all_hash_algorithms,
+            mask if mask & !all_hash_algorithms == 0 => mask,
+            _ => return Err(Denial::Invalid.into()),
+        };
+
+        let max_length = match policy.max_length {
+            0 => None,
+            max if max >= policy.min_length => Some(max as usize),
+            _ => return Err(Denial::Invalid.into()),
+        };
+
+        let rate_limit = match (policy.max_signatures, policy.window) {
+            (0, _) => None,
+            (_, 0) => return Err(Denial::Invalid.into()),
+            limit => Some(limit),
+        };
+
+        // Approvals are counted from the domain's administrators
+        if policy.required_approvals > threshold::MaxKeys::U64 {
+            return Err(Denial::Invalid.into());
+        }
+
+        let mut callers = Vec::new();
+
+        for caller in &policy.callers {
+            callers
+                .push(PublicKey::try_from(caller)?)
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        Ok(Policy {
+            prefixes: policy.prefixes.clone(),
+            min_length: policy.min_length as usize,
+            max_length,
+            hash_algorithms,
+            rate_limit,
+            required_approvals: policy.required_approvals as usize,
+            callers,
+        })
+    }
+}
+
+impl TryFrom<&Policy> for schema::policy::Policy {
+    type Error = Error;
+
+    fn try_from(policy: &Policy) -> Result<Self, Error> {
+        let mut callers = schema::policy::Callers::new();
+
+        for caller in &policy.callers {
+            callers
+                .push(schema::PublicKey::try_from(caller)?)
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        let (max_signatures, window) = policy.rate_limit.unwrap_or((0, 0));
+
+        Ok(schema::policy::Policy {
+            prefixes: policy.prefixes.clone(),
+            min_length: policy.min_length as u64,
+            max_length: policy.max_length.unwrap_or(0) as u64,
+            hash_algorithms: policy.hash_algorithms,
+            max_signatures,
+            window,
+            required_approvals: policy.required_approvals as u64,
+            callers,
+        })
+    }
+}
+
+/// Record of a key's recent usage, against which rate limits are checked
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Usage {
+    /// Domain authorization counter at which the current window began
+    window_start: u64,
+
+    /// Number of signatures made in the current window
+    signatures: u64,
+}
+
+impl Usage {
+    /// Record that the key has been used to sign under the given policy as
+    /// of the given domain authorization counter
+    pub(crate) fn record(&mut self, policy: &Policy, authorization_counter: u64) {
+        if let Some((_, window)) = policy.rate_limit {
+            let signatures = self.signatures_in_window(window, authorization_counter);
+
+            if signatures == 0 {
+                self.window_start = authorization_counter;
+            }
+
+            self.signatures = signatures + 1;
+        }
+    }
+
+    /// Get the number of signatures made in the window of the given length
+    /// which is current as of the given domain authorization counter
+    fn signatures_in_window(&self, window: u64, authorization_counter: u64) -> u64 {
+        if authorization_counter.saturating_sub(self.window_start) < window {
+            self.signatures
+        } else {
+            0
+        }
+    }
+}
+
+impl From<&schema::state::Usage> for Usage {
+    fn from(usage: &schema::state::Usage) -> Usage {
+        Usage {
+            window_start: usage.window_start,
+            signatures: usage.signatures,
+        }
+    }
+}
+
+impl From<&Usage> for schema::state::Usage {
+    fn from(usage: &Usage) -> schema::state::Usage {
+        schema::state::Usage {
+            window_start: usage.window_start,
+            signatures: usage.signatures,
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Authorization programs: a deterministic, gas-metered bytecode interpreter
+//!
+//! See [`schema::program`] for the instruction set. Programs are validated
+//! when they're installed in a domain, and run before every use of the
+//! domain's keys with an [`Input`] describing the operation. They run in a
+//! fixed amount of memory and halt within [`GAS_LIMIT`] instructions, so a
+//! faulty or malicious program can only deny operations in its own domain.
+
+use crate::{
+    error::Error,
+    policy::Denial,
+    schema::{
+        self,
+        key::{Algorithm, Payload, Slot},
+        program::{Code, Field, MaxCodeSize, MaxStackDepth, Opcode},
+    },
+};
+use block_cipher::generic_array::typenum::Unsigned;
+use core::{convert::TryFrom, fmt};
+use heapless::Vec;
+
+pub use schema::program::GAS_LIMIT;
+
+/// Maximum size of a program's code
+pub const MAX_CODE_SIZE: usize = <MaxCodeSize as Unsigned>::USIZE;
+
+/// Maximum number of words on a program's stack: pushing another faults
+pub const MAX_STACK_DEPTH: usize = <MaxStackDepth as Unsigned>::USIZE;
+
+/// Program stack
+type Stack = Vec<u64, MaxStackDepth>;
+
+/// Faults which halt a program, denying the operation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// Gas exhausted
+    OutOfGas,
+
+    /// Stack overflowed
+    StackOverflow,
+
+    /// Stack underflowed
+    StackUnderflow,
+
+    /// Arithmetic overflowed
+    Overflow,
+
+    /// Payload index out of bounds
+    OutOfBounds,
+
+    /// Unknown opcode or incomplete immediate
+    InvalidInstruction,
+
+    /// Ran past the end of the code
+    EndOfCode,
+}
+
+impl Fault {
+    /// Get a description of this fault
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Fault::OutOfGas => "authorization program out of gas",
+            Fault::StackOverflow => "authorization program stack overflow",
+            Fault::StackUnderflow => "authorization program stack underflow",
+            Fault::Overflow => "authorization program arithmetic overflow",
+            Fault::OutOfBounds => "authorization program payload index out of bounds",
+            Fault::InvalidInstruction => "authorization program invalid instruction",
+            Fault::EndOfCode => "authorization program ran past end of code",
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Decisions programs reach
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    /// Operation is allowed
+    Allow,
+
+    /// Operation is denied
+    Deny,
+}
+
+/// Operation a program decides on, and the key it uses
+#[derive(Copy, Clone, Debug)]
+pub struct Input<'a> {
+    /// Domain the key is in
+    pub domain: schema::domain::Id,
+
+    /// Slot the key is in
+    pub slot: Slot,
+
+    /// Algorithm of the key
+    pub algorithm: Algorithm,
+
+    /// Payload to be signed
+    pub payload: &'a Payload,
+
+    /// Number of requests the device has received since it started
+    pub request_count: u64,
+
+    /// Number of administrators who approved the operation
+    pub approvals: usize,
+
+    /// Did the caller authenticate within their session?
+    pub authenticated: bool,
+}
+
+impl<'a> Input<'a> {
+    /// Get the payload's bytes (the message, or the digest if prehashed)
+    fn payload_bytes(&self) -> &'a [u8] {
+        match self.payload {
+            Payload::Message(message) => message,
+            Payload::Sha256(digest) => digest,
+        }
+    }
+
+    /// Get the value of the given field
+    fn field(&self, field: Field) -> u64 {
+        match field {
+            Field::Domain => self.domain,
+            Field::Slot => self.slot,
+            Field::Algorithm => self.algorithm.into(),
+            Field::HashAlgorithm => schema::policy::HashAlgorithm::from(self.payload).into(),
+            Field::PayloadLength => self.payload_bytes().len() as u64,
+            Field::RequestCount => self.request_count,
+            Field::Approvals => self.approvals as u64,
+            Field::Authenticated => self.authenticated as u64,
+        }
+    }
+}
+
+/// Validated authorization program
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Program {
+    /// Program bytecode
+    code: Code,
+}
+
+impl Program {
+    /// Validate the given code, ensuring every opcode is known, every
+    /// immediate is complete, and every jump lands on an instruction.
+    ///
+    /// Fails with [`Error::Policy`] if the code is invalid or empty.
+    pub fn new(code: &[u8]) -> Result<Self, Error> {
+        let invalid = Error::Policy(Denial::Invalid);
+
+        if code.is_empty() || code.len() > MAX_CODE_SIZE {
+            return Err(invalid);
+        }
+
+        let mut instructions = [false; MAX_CODE_SIZE];
+        let mut pc = 0;
+
+        while pc < code.len() {
+            instructions[pc] = true;
+            let (_, next) = decode(code, pc).map_err(|_| invalid)?;
+            pc = next;
+        }
+
+        pc = 0;
+
+        while pc < code.len() {
+            let (instruction, next) = decode(code, pc).map_err(|_| invalid)?;
+
+            if let Instruction::Jump(target) | Instruction::JumpIf(target) = instruction {
+                if !instructions.get(target).cloned().unwrap_or(false) {
+                    return Err(invalid);
+                }
+            }
+
+            pc = next;
+        }
+
+        let mut program = Program { code: Code::new() };
+        program.code.extend_from_slice(code).map_err(|_| invalid)?;
+
+        Ok(program)
+    }
+
+    /// Get this program's bytecode
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Run this program with the given input, returning its verdict, or the
+    /// fault which halted it
+    pub fn run(&self, input: &Input<'_>) -> Result<Verdict, Fault> {
+        let mut stack = Stack::new();
+        let mut gas = GAS_LIMIT;
+        let mut pc = 0;
+
+        loop {
+            gas = gas.checked_sub(1).ok_or(Fault::OutOfGas)?;
+
+            if pc >= self.code.len() {
+                return Err(Fault::EndOfCode);
+            }
+
+            let (instruction, next) = decode(&self.code, pc)?;
+            pc = next;
+
+            match instruction {
+                Instruction::Halt(verdict) => return Ok(verdict),
+                Instruction::Push(value) => push(&mut stack, value)?,
+                Instruction::Simple(Opcode::Pop) => {
+                    pop(&mut stack)?;
+                }
+                Instruction::Simple(Opcode::Dup) => {
+                    let a = pop(&mut stack)?;
+                    push(&mut stack, a)?;
+                    push(&mut stack, a)?;
+                }
+                Instruction::Simple(Opcode::Swap) => {
+                    let b = pop(&mut stack)?;
+                    let a = pop(&mut stack)?;
+                    push(&mut stack, b)?;
+                    push(&mut stack, a)?;
+                }
+                Instruction::Simple(Opcode::Not) => {
+                    let a = pop(&mut stack)?;
+                    push(&mut stack, (a == 0) as u64)?;
+                }
+                Instruction::Simple(Opcode::PayloadByte) => {
+                    let index = pop(&mut stack)?;
+                    let byte = input
+                        .payload_bytes()
+                        .get(index as usize)
+                        .ok_or(Fault::OutOfBounds)?;
+
+                    push(&mut stack, u64::from(*byte))?;
+                }
+                Instruction::Simple(opcode) => {
+                    let b = pop(&mut stack)?;
+                    let a = pop(&mut stack)?;
+                    push(&mut stack, binary(opcode, a, b)?)?;
+                }
+                Instruction::Jump(target) => pc = target,
+                Instruction::JumpIf(target) => {
+                    if pop(&mut stack)? != 0 {
+                        pc = target;
+                    }
+                }
+                Instruction::Load(field) => push(&mut stack, input.field(field))?,
+                Instruction::PayloadPrefix(prefix) => {
+                    // Comparing each byte costs a unit of gas
+                    gas = gas
+                        .checked_sub(prefix.len() as u64)
+                        .ok_or(Fault::OutOfGas)?;
+
+                    let matches = input.payload_bytes().starts_with(prefix);
+                    push(&mut stack, matches as u64)?;
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<&schema::program::Program> for Program {
+    type Error = Error;
+
+    fn try_from(program: &schema::program::Program) -> Result<Self, Error> {
+        Program::new(&program.code)
+    }
+}
+
+impl From<&Program> for schema::program::Program {
+    fn from(program: &Program) -> schema::program::Program {
+        schema::program::Program {
+            code: program.code.clone(),
+        }
+    }
+}
+
+/// Decoded instruction
+enum Instruction<'a> {
+    /// Halt with a verdict
+    Halt(Verdict),
+
+    /// Push an immediate
+    Push(u64),
+
+    /// Instruction without immediates
+    Simple(Opcode),
+
+    /// Jump to an offset
+    Jump(usize),
+
+    /// Pop a condition and jump to an offset if it's non-zero
+    JumpIf(usize),
+
+    /// Push a field of the input
+    Load(Field),
+
+    /// Push whether the payload begins with the given bytes
+    PayloadPrefix(&'a [u8]),
+}
+
+/// Decode the instruction at the given offset, returning it along with the
+/// offset of the next instruction
+fn decode(code: &[u8], pc: usize) -> Result<(Instruction<'_>, usize), Fault> {
+    let opcode = code
+        .get(pc)
+        .and_then(|&byte| Opcode::from_u8(byte))
+        .ok_or(Fault::InvalidInstruction)?;
+
+    let start = pc + 1;
+
+    let immediate = |len: usize| -> Result<&[u8], Fault> {
+        code.get(start..start + len)
+            .ok_or(Fault::InvalidInstruction)
+    };
+
+    Ok(match opcode {
+        Opcode::Deny => (Instruction::Halt(Verdict::Deny), start),
+        Opcode::Allow => (Instruction::Halt(Verdict::Allow), start),
+        Opcode::Push => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(immediate(8)?);
+            (Instruction::Push(u64::from_le_bytes(bytes)), start + 8)
+        }
+        Opcode::PushByte => (Instruction::Push(u64::from(immediate(1)?[0])), start + 1),
+        Opcode::Jump | Opcode::JumpIf => {
+            let bytes = immediate(2)?;
+            let target = usize::from(u16::from_le_bytes([bytes[0], bytes[1]]));
+
+            if opcode == Opcode::Jump {
+                (Instruction::Jump(target), start + 2)
+            } else {
+                (Instruction::JumpIf(target), start + 2)
+            }
+        }
+        Opcode::Load => {
+            let field = Field::from_u8(immediate(1)?[0]).ok_or(Fault::InvalidInstruction)?;
+            (Instruction::Load(field), start + 1)
+        }
+        Opcode::PayloadPrefix => {
+            let len = usize::from(immediate(1)?[0]);
+            let prefix = code
+                .get(start + 1..start + 1 + len)
+                .ok_or(Fault::InvalidInstruction)?;
+
+            (Instruction::PayloadPrefix(prefix), start + 1 + len)
+        }
+        _ => (Instruction::Simple(opcode), start),
+    })
+}
+
+/// Evaluate a binary operation
+fn binary(opcode: Opcode, a: u64, b: u64) -> Result<u64, Fault> {
+    match opcode {
+        Opcode::Add => a.checked_add(b).ok_or(Fault::Overflow),
+        Opcode::Sub => a.checked_sub(b).ok_or(Fault::Overflow),
+        Opcode::Eq => Ok((a == b) as u64),
+        Opcode::Lt => Ok((a < b) as u64),
+        Opcode::Gt => Ok((a > b) as u64),
+        Opcode::And => Ok(a & b),
+        Opcode::Or => Ok(a | b),
+        _ => Err(Fault::InvalidInstruction),
+    }
+}
+
+/// Push a word onto the stack
+fn push(stack: &mut Stack, value: u64) -> Result<(), Fault> {
+    stack.push(value).map_err(|_| Fault::StackOverflow)
+}
+
+/// Pop a word from the stack
+fn pop(stack: &mut Stack) -> Result<u64, Fault> {
+    stack.pop().ok_or(Fault::StackUnderflow)
+}
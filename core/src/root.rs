@@ -5,62 +5,83 @@
 //!
 //! <https://github.com/theupdateframework/specification/blob/master/tuf-spec.md#4-document-formats>
 
-use crate::{crypto::PublicKey, error::Error, schema::Uuid};
-use heapless::Vec;
-
-/// Maximum number of keys allowed for root role
-pub(crate) type MaxKeys = heapless::consts::U8;
+use crate::{
+    error::Error,
+    schema::{self, Signature, Uuid},
+    threshold::ThresholdKeySet,
+};
+use core::convert::TryFrom;
 
 /// Root configuration: controls sensitive administrative authority
-// TODO(tarcieri): extract type for threshold key sets
 #[derive(Debug, Default)]
 pub struct Config {
-    /// Threshold for number of keys required to perform a root action
-    threshold: usize,
+    /// UUID which identifies this root configuration
+    uuid: Option<Uuid>,
+
+    /// Root version number (incremented each time the root keys are rotated)
+    version: u64,
 
-    /// Public keys for the root role
-    public_keys: Vec<PublicKey, MaxKeys>,
+    /// Threshold key set for the root role
+    key_set: ThresholdKeySet,
 }
 
 impl Config {
     /// Create new [`Root`] configuration
-    pub fn new(threshold: usize, keys: impl IntoIterator<Item = PublicKey>) -> Result<Self, Error> {
-        let mut public_keys = Vec::new();
-
-        for key in keys.into_iter() {
-            public_keys.push(key).map_err(|_| Error::Threshold)?;
-        }
-
-        if threshold < 1 || threshold > public_keys.len() {
-            return Err(Error::Threshold);
+    pub fn new(uuid: Uuid, version: u64, key_set: ThresholdKeySet) -> Self {
+        Config {
+            uuid: Some(uuid),
+            version,
+            key_set,
         }
-
-        Ok(Config {
-            threshold,
-            public_keys,
-        })
     }
 
     /// Is the root [`Config`] presently empty? (i.e. unprovisioned)
     pub fn is_empty(&self) -> bool {
-        self.threshold == 0
+        self.key_set.is_empty()
+    }
+
+    /// Get the root version number (zero if empty)
+    pub fn version(&self) -> u64 {
+        self.version
     }
 
-    /// Get the threshold of required keys
-    #[allow(dead_code)]
-    pub fn threshold(&self) -> usize {
-        self.threshold
+    /// Get the threshold key set for the root role
+    pub fn key_set(&self) -> &ThresholdKeySet {
+        &self.key_set
     }
 
-    /// Get the public keys which are members of the root role
-    #[allow(dead_code)]
-    pub fn public_keys(&self) -> &[PublicKey] {
-        self.public_keys.as_ref()
+    /// Verify that a threshold of the root keys have produced valid signatures
+    /// over the given message digest
+    pub fn verify(&self, digest: &[u8], signatures: &[Signature]) -> Result<(), Error> {
+        self.key_set.verify(digest, signatures)
     }
 
-    /// Get a UUID which represents this root configuration
+    /// Get a UUID which represents this root configuration (nil if empty)
     pub fn uuid(&self) -> Uuid {
-        // TODO(tarcieri): stub!
-        Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()
+        self.uuid.unwrap_or_else(Uuid::nil)
+    }
+}
+
+impl TryFrom<&schema::state::RootConfig> for Config {
+    type Error = Error;
+
+    fn try_from(config: &schema::state::RootConfig) -> Result<Self, Error> {
+        Ok(Config::new(
+            config.uuid,
+            config.version,
+            ThresholdKeySet::try_from(&config.key_set)?,
+        ))
+    }
+}
+
+impl TryFrom<&Config> for schema::state::RootConfig {
+    type Error = Error;
+
+    fn try_from(config: &Config) -> Result<Self, Error> {
+        Ok(schema::state::RootConfig {
+            uuid: config.uuid.ok_or(Error::Unprovisioned)?,
+            version: config.version,
+            key_set: schema::ThresholdKeySet::try_from(&config.key_set)?,
+        })
     }
 }
@@ -0,0 +1,244 @@
+//! Sealed state: device state encrypted under the root key and persisted
+//! to [`Storage`] records.
+//!
+//! Each record has the following layout:
+//!
+//! ```text
+//! [length: u32][version: u64][counter: u64][nonce: 12 bytes][tag: 16 bytes][ciphertext]
+//! ```
+//!
+//! The version counter is authenticated as associated data and must match
+//! the version within the encrypted state. States are written alternately to
+//! each of the [`NUM_RECORDS`] records, erasing the previous one afterwards,
+//! so an interrupted write leaves the previous state intact.
+//!
+//! The value of the [`MonotonicCounter`] is also authenticated as associated
+//! data. Each state is sealed with the next counter value and the counter is
+//! incremented once it has been written, so states which are behind the
+//! counter (i.e. rolled back) are refused.
+
+use crate::{
+    counter::MonotonicCounter,
+    error::Error,
+    schema::{state::State, veriform::Decoder, Message},
+    storage::{Record, Storage, NUM_RECORDS, RECORD_SIZE},
+};
+use aes_gcm_siv::aead::{
+    consts::{U12, U16},
+    generic_array::GenericArray,
+    AeadInPlace,
+};
+use core::convert::TryInto;
+use rand_core::{CryptoRng, RngCore};
+
+/// Domain separation prefix for the associated data
+const AAD_PREFIX: &[u8] = b"armistice.state";
+
+/// Size of the length field
+const LENGTH_SIZE: usize = 4;
+
+/// Size of the version counter field
+const VERSION_SIZE: usize = 8;
+
+/// Size of the monotonic counter field
+const COUNTER_SIZE: usize = 8;
+
+/// Size of an AES-GCM-SIV nonce
+const NONCE_SIZE: usize = 12;
+
+/// Size of an AES-GCM-SIV tag
+const TAG_SIZE: usize = 16;
+
+/// Offset of the version counter
+const VERSION_OFFSET: usize = LENGTH_SIZE;
+
+/// Offset of the monotonic counter
+const COUNTER_OFFSET: usize = VERSION_OFFSET + VERSION_SIZE;
+
+/// Offset of the nonce
+const NONCE_OFFSET: usize = COUNTER_OFFSET + COUNTER_SIZE;
+
+/// Offset of the tag
+const TAG_OFFSET: usize = NONCE_OFFSET + NONCE_SIZE;
+
+/// Size of the record header (i.e. offset of the ciphertext)
+const HEADER_SIZE: usize = TAG_OFFSET + TAG_SIZE;
+
+/// Size of the associated data
+const AAD_SIZE: usize = AAD_PREFIX.len() + VERSION_SIZE + COUNTER_SIZE;
+
+/// Load the most recent state from storage.
+///
+/// Returns `None` if no state has been persisted. Records which fail to
+/// unseal or are behind the monotonic counter are ignored so long as another
+/// record contains a valid state.
+pub(crate) fn load<A, S, C>(
+    aead: &A,
+    storage: &mut S,
+    counter: &mut C,
+) -> Result<Option<State>, Error>
+where
+    A: AeadInPlace<NonceSize = U12, TagSize = U16>,
+    S: Storage,
+    C: MonotonicCounter,
+{
+    let current = counter.get()?;
+    let mut record = [0u8; RECORD_SIZE];
+    let mut latest: Option<(State, u64)> = None;
+    let mut result = Ok(());
+
+    for index in 0..NUM_RECORDS {
+        if !storage.read(index, &mut record)? {
+            continue;
+        }
+
+        match unseal(aead, &record) {
+            Ok((_, sealed_counter)) if sealed_counter < current => result = Err(Error::Version),
+            Ok((state, sealed_counter)) => {
+                let newer = match &latest {
+                    Some((previous, _)) => state.version > previous.version,
+                    None => true,
+                };
+
+                if newer {
+                    latest = Some((state, sealed_counter));
+                }
+            }
+            Err(e) => result = Err(e),
+        }
+    }
+
+    match latest {
+        Some((state, sealed_counter)) => {
+            // Catch up if interrupted after writing state but prior to
+            // incrementing the counter
+            advance_counter(counter, sealed_counter)?;
+            Ok(Some(state))
+        }
+        // State has been persisted previously but is now missing
+        None if current > 0 => result.and(Err(Error::Version)),
+        None => result.map(|_| None),
+    }
+}
+
+/// Seal the given state and persist it to storage, erasing the record
+/// containing the previous state and incrementing the monotonic counter
+/// afterwards
+pub(crate) fn save<A, S, C>(
+    aead: &A,
+    rng: &mut (impl CryptoRng + RngCore),
+    storage: &mut S,
+    counter: &mut C,
+    state: &State,
+) -> Result<(), Error>
+where
+    A: AeadInPlace<NonceSize = U12, TagSize = U16>,
+    S: Storage,
+    C: MonotonicCounter,
+{
+    let next_counter = counter.get()?.checked_add(1).ok_or(Error::Capacity)?;
+    let index = (state.version % NUM_RECORDS as u64) as usize;
+    let record = seal(aead, rng, state, next_counter)?;
+    storage.write(index, &record)?;
+
+    for other in (0..NUM_RECORDS).filter(|&i| i != index) {
+        storage.erase(other)?;
+    }
+
+    advance_counter(counter, next_counter)
+}
+
+/// Increment the monotonic counter until it reaches the given value
+fn advance_counter(counter: &mut impl MonotonicCounter, value: u64) -> Result<(), Error> {
+    while counter.get()? < value {
+        counter.increment()?;
+    }
+
+    Ok(())
+}
+
+/// Seal the given state into a storage record
+fn seal<A>(
+    aead: &A,
+    rng: &mut (impl CryptoRng + RngCore),
+    state: &State,
+    counter: u64,
+) -> Result<Record, Error>
+where
+    A: AeadInPlace<NonceSize = U12, TagSize = U16>,
+{
+    let mut record = [0u8; RECORD_SIZE];
+
+    let length = state
+        .encode(&mut record[HEADER_SIZE..])
+        .map_err(|_| Error::Capacity)?
+        .len();
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+
+    let tag = aead
+        .encrypt_in_place_detached(
+            GenericArray::from_slice(&nonce),
+            &associated_data(state.version, counter),
+            &mut record[HEADER_SIZE..(HEADER_SIZE + length)],
+        )
+        .map_err(|_| Error::Crypto)?;
+
+    record[..VERSION_OFFSET].copy_from_slice(&(length as u32).to_be_bytes());
+    record[VERSION_OFFSET..COUNTER_OFFSET].copy_from_slice(&state.version.to_be_bytes());
+    record[COUNTER_OFFSET..NONCE_OFFSET].copy_from_slice(&counter.to_be_bytes());
+    record[NONCE_OFFSET..TAG_OFFSET].copy_from_slice(&nonce);
+    record[TAG_OFFSET..HEADER_SIZE].copy_from_slice(&tag);
+
+    Ok(record)
+}
+
+/// Unseal the state contained in the given storage record, returning it
+/// along with the monotonic counter value it was sealed with
+fn unseal<A>(aead: &A, record: &Record) -> Result<(State, u64), Error>
+where
+    A: AeadInPlace<NonceSize = U12, TagSize = U16>,
+{
+    let length = u32::from_be_bytes(record[..VERSION_OFFSET].try_into().unwrap()) as usize;
+
+    if length > RECORD_SIZE - HEADER_SIZE {
+        return Err(Error::Storage);
+    }
+
+    let version = u64::from_be_bytes(record[VERSION_OFFSET..COUNTER_OFFSET].try_into().unwrap());
+    let counter = u64::from_be_bytes(record[COUNTER_OFFSET..NONCE_OFFSET].try_into().unwrap());
+    let mut buffer = *record;
+
+    aead.decrypt_in_place_detached(
+        GenericArray::from_slice(&record[NONCE_OFFSET..TAG_OFFSET]),
+        &associated_data(version, counter),
+        &mut buffer[HEADER_SIZE..(HEADER_SIZE + length)],
+        GenericArray::from_slice(&record[TAG_OFFSET..HEADER_SIZE]),
+    )
+    .map_err(|_| Error::Crypto)?;
+
+    let state = State::decode(
+        &mut Decoder::new(),
+        &buffer[HEADER_SIZE..(HEADER_SIZE + length)],
+    )
+    .map_err(|_| Error::Storage)?;
+
+    if state.version != version {
+        return Err(Error::Version);
+    }
+
+    Ok((state, counter))
+}
+
+/// Compute the associated data for a sealed state with the given version
+/// and monotonic counter value
+fn associated_data(version: u64, counter: u64) -> [u8; AAD_SIZE] {
+    let mut aad = [0u8; AAD_SIZE];
+    let (prefix, rest) = aad.split_at_mut(AAD_PREFIX.len());
+    let (version_bytes, counter_bytes) = rest.split_at_mut(VERSION_SIZE);
+    prefix.copy_from_slice(AAD_PREFIX);
+    version_bytes.copy_from_slice(&version.to_be_bytes());
+    counter_bytes.copy_from_slice(&counter.to_be_bytes());
+    aad
+}
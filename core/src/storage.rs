@@ -0,0 +1,47 @@
+//! Persistent storage for sealed device state
+//!
+//! Storage is modeled as a small number of fixed-size records which can be
+//! read, written, and erased individually (e.g. blocks of flash memory).
+
+mod memory;
+
+#[cfg(feature = "std")]
+mod file;
+
+pub use self::memory::MemoryStorage;
+
+#[cfg(feature = "std")]
+pub use self::file::FileStorage;
+
+use crate::error::Error;
+
+/// Size of a storage record in bytes.
+///
+/// This is smaller than the largest state the device's limits permit (e.g.
+/// every domain filled with keys carrying maximal policies). Changes which
+/// would result in a state that doesn't fit in a record are refused with
+/// [`Error::Capacity`] before they're applied.
+pub const RECORD_SIZE: usize = 8192;
+
+/// Number of records used to store device state.
+///
+/// State is written alternately to each record so the previous state
+/// remains intact if writing the new one is interrupted.
+pub const NUM_RECORDS: usize = 2;
+
+/// Storage records
+pub type Record = [u8; RECORD_SIZE];
+
+/// Persistent storage for fixed-size records
+pub trait Storage {
+    /// Read the record with the given index into the provided buffer.
+    ///
+    /// Returns `false` if the record has been erased (or never written).
+    fn read(&mut self, index: usize, record: &mut Record) -> Result<bool, Error>;
+
+    /// Write the record with the given index
+    fn write(&mut self, index: usize, record: &Record) -> Result<(), Error>;
+
+    /// Erase the record with the given index
+    fn erase(&mut self, index: usize) -> Result<(), Error>;
+}
@@ -0,0 +1,75 @@
+//! File-backed storage (for host testing and simulation)
+
+use super::{Record, Storage, NUM_RECORDS};
+use crate::error::Error;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// File-backed storage: each record is stored in its own file within a
+/// directory
+#[derive(Clone, Debug)]
+pub struct FileStorage {
+    /// Directory containing the record files
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Open file-backed storage in the given directory, creating it if it
+    /// doesn't already exist
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|_| Error::Storage)?;
+        Ok(FileStorage { dir })
+    }
+
+    /// Get the directory containing the record files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the path to the file for the record with the given index
+    fn record_path(&self, index: usize) -> Result<PathBuf, Error> {
+        if index >= NUM_RECORDS {
+            return Err(Error::Storage);
+        }
+
+        Ok(self.dir.join(std::format!("record-{}.bin", index)))
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&mut self, index: usize, record: &mut Record) -> Result<bool, Error> {
+        let bytes = match fs::read(self.record_path(index)?) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(_) => return Err(Error::Storage),
+        };
+
+        if bytes.len() != record.len() {
+            return Err(Error::Storage);
+        }
+
+        record.copy_from_slice(&bytes);
+        Ok(true)
+    }
+
+    fn write(&mut self, index: usize, record: &Record) -> Result<(), Error> {
+        let path = self.record_path(index)?;
+
+        // Write to a temporary file and rename it into place so a partially
+        // written record is never observed
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, &record[..]).map_err(|_| Error::Storage)?;
+        fs::rename(&tmp_path, &path).map_err(|_| Error::Storage)
+    }
+
+    fn erase(&mut self, index: usize) -> Result<(), Error> {
+        match fs::remove_file(self.record_path(index)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(_) => Err(Error::Storage),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! In-memory storage (lost on reset; useful for host testing)
+
+use super::{Record, Storage, NUM_RECORDS};
+use crate::error::Error;
+use core::fmt;
+
+/// In-memory storage
+#[derive(Clone)]
+pub struct MemoryStorage {
+    /// Record contents (`None` if erased)
+    records: [Option<Record>; NUM_RECORDS],
+}
+
+impl MemoryStorage {
+    /// Create new (empty) in-memory storage
+    pub fn new() -> Self {
+        MemoryStorage {
+            records: [None; NUM_RECORDS],
+        }
+    }
+
+    /// Get the raw contents of the record with the given index, if present
+    pub fn record(&self, index: usize) -> Option<&Record> {
+        self.records.get(index).and_then(Option::as_ref)
+    }
+
+    /// Get a mutable reference to the raw contents of the record with the
+    /// given index, if present (e.g. to simulate corruption)
+    pub fn record_mut(&mut self, index: usize) -> Option<&mut Record> {
+        self.records.get_mut(index).and_then(Option::as_mut)
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&mut self, index: usize, record: &mut Record) -> Result<bool, Error> {
+        match self.records.get(index).ok_or(Error::Storage)? {
+            Some(stored) => {
+                record.copy_from_slice(stored);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn write(&mut self, index: usize, record: &Record) -> Result<(), Error> {
+        *self.records.get_mut(index).ok_or(Error::Storage)? = Some(*record);
+        Ok(())
+    }
+
+    fn erase(&mut self, index: usize) -> Result<(), Error> {
+        *self.records.get_mut(index).ok_or(Error::Storage)? = None;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MemoryStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Records are large: only show which ones are present
+        let mut list = f.debug_list();
+
+        for record in &self.records {
+            list.entry(&record.is_some());
+        }
+
+        list.finish()
+    }
+}
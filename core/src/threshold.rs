@@ -0,0 +1,136 @@
+//! Threshold key sets: M-of-N sets of public keys which authorize actions
+//!
+//! These are used by the root role as well as any other administrative roles
+//! which require signatures from multiple parties to perform an action.
+
+use crate::{crypto::PublicKey, error::Error, schema};
+use core::convert::TryFrom;
+use heapless::Vec;
+
+/// Maximum number of keys allowed in a threshold key set
+pub type MaxKeys = heapless::consts::U8;
+
+/// Threshold key set: actions are authorized by signatures from `threshold`
+/// of its public keys
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ThresholdKeySet {
+    /// Number of signatures required to authorize an action
+    threshold: usize,
+
+    /// Public keys which are members of this key set (all distinct)
+    public_keys: Vec<PublicKey, MaxKeys>,
+}
+
+impl ThresholdKeySet {
+    /// Create a new [`ThresholdKeySet`] from the given threshold and keys.
+    ///
+    /// Fails with [`Error::Threshold`] if any key is listed more than once,
+    /// or if the threshold isn't satisfiable by the given keys.
+    pub fn new(threshold: usize, keys: impl IntoIterator<Item = PublicKey>) -> Result<Self, Error> {
+        let mut public_keys = Vec::new();
+
+        for key in keys.into_iter() {
+            if public_keys.contains(&key) {
+                return Err(Error::Threshold);
+            }
+
+            public_keys.push(key).map_err(|_| Error::Threshold)?;
+        }
+
+        if threshold < 1 || threshold > public_keys.len() {
+            return Err(Error::Threshold);
+        }
+
+        Ok(ThresholdKeySet {
+            threshold,
+            public_keys,
+        })
+    }
+
+    /// Create a new [`ThresholdKeySet`] from a threshold and the
+    /// [`schema::PublicKey`] encodings of its members
+    pub fn from_schema(threshold: u64, keys: &[schema::PublicKey]) -> Result<Self, Error> {
+        let mut public_keys = Vec::<PublicKey, MaxKeys>::new();
+
+        for key in keys {
+            public_keys
+                .push(PublicKey::try_from(key)?)
+                .map_err(|_| Error::Threshold)?;
+        }
+
+        ThresholdKeySet::new(threshold as usize, public_keys)
+    }
+
+    /// Is this key set empty?
+    pub fn is_empty(&self) -> bool {
+        self.public_keys.is_empty()
+    }
+
+    /// Get the threshold of required signatures
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Get the public keys which are members of this key set
+    pub fn public_keys(&self) -> &[PublicKey] {
+        self.public_keys.as_ref()
+    }
+
+    /// Is the given key a member of this key set?
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.public_keys.contains(public_key)
+    }
+
+    /// Count the number of distinct member keys which have produced a valid
+    /// signature over the given message digest
+    pub fn count_signers(&self, digest: &[u8], signatures: &[schema::Signature]) -> usize {
+        self.public_keys
+            .iter()
+            .filter(|key| {
+                signatures
+                    .iter()
+                    .any(|signature| key.verify(digest, signature).is_ok())
+            })
+            .count()
+    }
+
+    /// Verify that a threshold of member keys have produced valid signatures
+    /// over the given message digest.
+    ///
+    /// Each key is counted at most once regardless of how many of the
+    /// provided signatures it has produced.
+    pub fn verify(&self, digest: &[u8], signatures: &[schema::Signature]) -> Result<(), Error> {
+        if !self.is_empty() && self.count_signers(digest, signatures) >= self.threshold {
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+}
+
+impl TryFrom<&schema::ThresholdKeySet> for ThresholdKeySet {
+    type Error = Error;
+
+    fn try_from(key_set: &schema::ThresholdKeySet) -> Result<Self, Error> {
+        ThresholdKeySet::from_schema(key_set.threshold, &key_set.public_keys)
+    }
+}
+
+impl TryFrom<&ThresholdKeySet> for schema::ThresholdKeySet {
+    type Error = Error;
+
+    fn try_from(key_set: &ThresholdKeySet) -> Result<Self, Error> {
+        let mut public_keys = schema::threshold::PublicKeys::new();
+
+        for key in key_set.public_keys() {
+            public_keys
+                .push(schema::PublicKey::try_from(key)?)
+                .map_err(|_| Error::Capacity)?;
+        }
+
+        Ok(schema::ThresholdKeySet {
+            threshold: key_set.threshold as u64,
+            public_keys,
+        })
+    }
+}
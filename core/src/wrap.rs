@@ -0,0 +1,164 @@
+//! Key wrapping: secret keys exported from a domain are sealed under the
+//! root key so they can be backed up off-device and imported again later.
+//!
+//! Wrapped keys have the following layout:
+//!
+//! ```text
+//! [nonce: 12 bytes][tag: 16 bytes][ciphertext]
+//! ```
+//!
+//! The UUID of the root configuration, the ID of the domain the key was
+//! exported from and the key's policy are authenticated as associated data,
+//! so keys can only be imported by the device (and root configuration) which
+//! exported them, into the same domain and with the same policy.
+//! Re-provisioning a device invalidates its wrapped keys.
+
+use crate::{
+    crypto::SigningKey,
+    domain,
+    error::Error,
+    schema::{
+        key::{MaxWrappedKeySize, WrappedKey},
+        policy::Policy,
+        state::SecretKey,
+        veriform::Decoder,
+        Message, Uuid,
+    },
+};
+use aes_gcm_siv::aead::{
+    consts::{U12, U16},
+    generic_array::GenericArray,
+    AeadInPlace,
+};
+use block_cipher::generic_array::typenum::Unsigned;
+use core::convert::TryFrom;
+use rand_core::{CryptoRng, RngCore};
+
+/// Domain separation prefix for the associated data
+const AAD_PREFIX: &[u8] = b"armistice.key";
+
+/// Size of an AES-GCM-SIV nonce
+const NONCE_SIZE: usize = 12;
+
+/// Size of an AES-GCM-SIV tag
+const TAG_SIZE: usize = 16;
+
+/// Size of the wrapped key header (i.e. offset of the ciphertext)
+const HEADER_SIZE: usize = NONCE_SIZE + TAG_SIZE;
+
+/// Maximum size of a wrapped key
+const MAX_SIZE: usize = <MaxWrappedKeySize as Unsigned>::USIZE;
+
+/// Size of a UUID
+const UUID_SIZE: usize = 16;
+
+/// Size of an encoded domain ID
+const DOMAIN_ID_SIZE: usize = 8;
+
+/// Maximum size of an encoded key policy
+const MAX_POLICY_SIZE: usize = 512;
+
+/// Maximum size of the associated data
+const MAX_AAD_SIZE: usize = AAD_PREFIX.len() + UUID_SIZE + DOMAIN_ID_SIZE + MAX_POLICY_SIZE;
+
+/// Wrap the given key under the root key, binding it to the given root
+/// configuration UUID, the domain it's exported from and its policy
+pub(crate) fn wrap<A>(
+    aead: &A,
+    rng: &mut (impl CryptoRng + RngCore),
+    uuid: &Uuid,
+    domain: domain::Id,
+    policy: &Policy,
+    key: &SigningKey,
+) -> Result<WrappedKey, Error>
+where
+    A: AeadInPlace<NonceSize = U12, TagSize = U16>,
+{
+    let mut buffer = [0u8; MAX_SIZE];
+
+    let length = SecretKey::from(key)
+        .encode(&mut buffer[HEADER_SIZE..])
+        .map_err(|_| Error::Capacity)?
+        .len();
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+
+    let mut aad = [0u8; MAX_AAD_SIZE];
+    let tag = aead
+        .encrypt_in_place_detached(
+            GenericArray::from_slice(&nonce),
+            associated_data(&mut aad, uuid, domain, policy)?,
+            &mut buffer[HEADER_SIZE..(HEADER_SIZE + length)],
+        )
+        .map_err(|_| Error::Crypto)?;
+
+    buffer[..NONCE_SIZE].copy_from_slice(&nonce);
+    buffer[NONCE_SIZE..HEADER_SIZE].copy_from_slice(&tag);
+
+    let mut wrapped_key = WrappedKey::new();
+    wrapped_key
+        .extend_from_slice(&buffer[..(HEADER_SIZE + length)])
+        .map_err(|_| Error::Capacity)?;
+
+    Ok(wrapped_key)
+}
+
+/// Unwrap a key which was wrapped under the root key and bound to the given
+/// root configuration UUID, domain and policy
+pub(crate) fn unwrap<A>(
+    aead: &A,
+    uuid: &Uuid,
+    domain: domain::Id,
+    policy: &Policy,
+    wrapped_key: &[u8],
+) -> Result<SigningKey, Error>
+where
+    A: AeadInPlace<NonceSize = U12, TagSize = U16>,
+{
+    if wrapped_key.len() <= HEADER_SIZE {
+        return Err(Error::Crypto);
+    }
+
+    let mut buffer = WrappedKey::new();
+    buffer
+        .extend_from_slice(&wrapped_key[HEADER_SIZE..])
+        .map_err(|_| Error::Capacity)?;
+
+    let mut aad = [0u8; MAX_AAD_SIZE];
+    aead.decrypt_in_place_detached(
+        GenericArray::from_slice(&wrapped_key[..NONCE_SIZE]),
+        associated_data(&mut aad, uuid, domain, policy)?,
+        &mut buffer,
+        GenericArray::from_slice(&wrapped_key[NONCE_SIZE..HEADER_SIZE]),
+    )
+    .map_err(|_| Error::Crypto)?;
+
+    let secret_key = SecretKey::decode(&mut Decoder::new(), &buffer).map_err(|_| Error::Crypto)?;
+    SigningKey::try_from(&secret_key)
+}
+
+/// Compute the associated data for a key bound to the given root
+/// configuration UUID, domain and policy in the given buffer
+fn associated_data<'a>(
+    aad: &'a mut [u8; MAX_AAD_SIZE],
+    uuid: &Uuid,
+    domain: domain::Id,
+    policy: &Policy,
+) -> Result<&'a [u8], Error> {
+    let (prefix, rest) = aad.split_at_mut(AAD_PREFIX.len());
+    prefix.copy_from_slice(AAD_PREFIX);
+
+    let (uuid_bytes, rest) = rest.split_at_mut(UUID_SIZE);
+    uuid_bytes.copy_from_slice(uuid.as_bytes());
+
+    let (domain_bytes, policy_bytes) = rest.split_at_mut(DOMAIN_ID_SIZE);
+    domain_bytes.copy_from_slice(&domain.to_be_bytes());
+
+    let policy_length = policy
+        .encode(policy_bytes)
+        .map_err(|_| Error::Capacity)?
+        .len();
+
+    Ok(&aad[..(MAX_AAD_SIZE - MAX_POLICY_SIZE + policy_length)])
+}
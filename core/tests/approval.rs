@@ -0,0 +1,505 @@
+//! Approval queue integration test
+
+mod support;
+
+use armistice_core::{
+    approval::{MAX_PENDING, PENDING_LIFETIME},
+    crypto::PublicKey,
+    Error,
+};
+use armistice_schema::{
+    approval, domain, info, key, policy, threshold, veriform::Sha256Digest, Response, Signature,
+    ThresholdKeySet,
+};
+use core::convert::TryFrom;
+use ed25519_dalek::{Keypair, Signer};
+use support::{
+    binding, domain_binding, keypair, provisioned_armistice, public_key, root_encryption_key,
+    round_trip, sign, timestamp, Armistice,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Create a domain configuration administered by a threshold of the given
+/// keys
+fn domain_config(threshold: u64, admins: &[&Keypair]) -> domain::Config {
+    let mut public_keys = threshold::PublicKeys::new();
+
+    for admin in admins {
+        public_keys.push(public_key(admin)).unwrap();
+    }
+
+    domain::Config {
+        id: DOMAIN_ID,
+        admins: ThresholdKeySet {
+            threshold,
+            public_keys,
+        },
+        policy: domain::Policy {
+            max_keys: 4,
+            challenge_lifetime: 0,
+        },
+    }
+}
+
+/// Provision a device with a domain administered by 2-of-3 keys containing a
+/// single key, returning it along with the root and administrator keys
+fn armistice_with_domain() -> (Armistice, Keypair, [Keypair; 3]) {
+    let root_key = keypair(1);
+    let admins = [keypair(2), keypair(3), keypair(4)];
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain_config(2, &[&admins[0], &admins[1], &admins[2]]),
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let create_binding = binding(&armistice);
+    armistice
+        .create_domain(&domain::SignedCreateRequest {
+            signatures: sign(
+                &create_binding.digest(&request.digest.unwrap()),
+                &[&root_key],
+            ),
+            request,
+            binding: create_binding,
+        })
+        .unwrap();
+
+    let request = generate_request();
+    let generate_binding = domain_binding(&armistice, DOMAIN_ID);
+    armistice
+        .generate_key(&key::SignedGenerateRequest {
+            signatures: sign(
+                &generate_binding.digest(&request.digest.unwrap()),
+                &[&admins[0], &admins[1]],
+            ),
+            request,
+            binding: generate_binding,
+        })
+        .unwrap();
+
+    (armistice, root_key, admins)
+}
+
+/// Create a key generation request in the test domain
+fn generate_request() -> key::GenerateRequest {
+    round_trip(&key::GenerateRequest {
+        domain: DOMAIN_ID,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: policy::Policy::default(),
+        digest: None,
+    })
+}
+
+/// Create a submission request for signing the given message with the key
+/// in slot 0 of the test domain
+fn sign_submission(message: &[u8]) -> approval::SubmitRequest {
+    slot_sign_submission(0, message)
+}
+
+/// Create a submission request for signing the given message with the key
+/// in the given slot of the test domain
+fn slot_sign_submission(slot: key::Slot, message: &[u8]) -> approval::SubmitRequest {
+    let mut payload = key::MessageBytes::new();
+    payload.extend_from_slice(message).unwrap();
+
+    round_trip(&approval::SubmitRequest {
+        operation: approval::Operation::Sign(key::SignRequest {
+            domain: DOMAIN_ID,
+            slot,
+            payload: key::Payload::Message(payload),
+        }),
+        timestamp: timestamp(),
+        digest: None,
+    })
+}
+
+/// Submit an operation on behalf of the given caller
+fn submit(
+    armistice: &mut Armistice,
+    request: &approval::SubmitRequest,
+    caller: &Keypair,
+) -> Result<approval::SubmitResponse, Error> {
+    let caller = PublicKey::try_from(&public_key(caller)).unwrap();
+    armistice.submit_operation_as(request, Some(&caller))
+}
+
+/// Sign the given digest with a single keypair
+fn sign_digest(digest: &Sha256Digest, signer: &Keypair) -> Signature {
+    Signature::Ed25519(signer.sign(digest).to_bytes())
+}
+
+/// Approve a pending operation submitted with the given request
+fn approve(
+    armistice: &mut Armistice,
+    request: &approval::SubmitRequest,
+    submitted: &approval::SubmitResponse,
+    approver: &Keypair,
+) -> Result<Response, Error> {
+    let digest = approval::approval_digest(&submitted.nonce, &request.digest.unwrap());
+
+    armistice.approve(&approval::ApproveRequest {
+        id: submitted.id,
+        signature: sign_digest(&digest, approver),
+    })
+}
+
+#[test]
+fn operation_performed_once_threshold_approves() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+    assert_eq!(submitted.threshold, 2);
+
+    let response = approve(&mut armistice, &request, &submitted, &admins[0]).unwrap();
+    assert_eq!(
+        response.approve(),
+        Some(&approval::ApproveResponse {
+            id: submitted.id,
+            approvals: 1,
+            threshold: 2,
+        })
+    );
+
+    // Approvers need not be online at the same time: the next one discovers
+    // the pending operation by listing them
+    let pending = armistice.list_pending().unwrap().pending;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, submitted.id);
+    assert_eq!(pending[0].digest, request.digest.unwrap());
+    assert_eq!(pending[0].approvals, 1);
+
+    let response = approve(&mut armistice, &request, &submitted, &admins[2]).unwrap();
+    assert!(response.sign().is_some());
+    assert!(armistice.approvals().is_empty());
+}
+
+#[test]
+fn duplicate_approval_rejected() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+
+    approve(&mut armistice, &request, &submitted, &admins[0]).unwrap();
+    assert_eq!(
+        approve(&mut armistice, &request, &submitted, &admins[0]),
+        Err(Error::Duplicate)
+    );
+    assert_eq!(armistice.list_pending().unwrap().pending[0].approvals, 1);
+}
+
+#[test]
+fn approvals_must_be_by_admins_and_cover_nonce() {
+    let (mut armistice, root_key, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+
+    assert_eq!(
+        approve(&mut armistice, &request, &submitted, &root_key),
+        Err(Error::Unauthorized)
+    );
+
+    // Signatures over the request digest alone aren't approvals
+    let signature = sign_digest(&request.digest.unwrap(), &admins[0]);
+    assert_eq!(
+        armistice.approve(&approval::ApproveRequest {
+            id: submitted.id,
+            signature,
+        }),
+        Err(Error::Unauthorized)
+    );
+
+    assert_eq!(armistice.list_pending().unwrap().pending[0].approvals, 0);
+}
+
+#[test]
+fn resubmitted_operation_requires_fresh_approvals() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    let first = submit(&mut armistice, &request, &admins[0]).unwrap();
+    approve(&mut armistice, &request, &first, &admins[0]).unwrap();
+    approve(&mut armistice, &request, &first, &admins[1]).unwrap();
+
+    let second = submit(&mut armistice, &request, &admins[0]).unwrap();
+    assert_ne!(first.id, second.id);
+    assert_ne!(first.nonce, second.nonce);
+
+    // Approvals of the first submission can't be replayed for the second
+    let digest = approval::approval_digest(&first.nonce, &request.digest.unwrap());
+    assert_eq!(
+        armistice.approve(&approval::ApproveRequest {
+            id: second.id,
+            signature: sign_digest(&digest, &admins[0]),
+        }),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn cancelled_by_any_single_admin() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+    approve(&mut armistice, &request, &submitted, &admins[0]).unwrap();
+
+    // Approvals aren't cancellations
+    let digest = approval::approval_digest(&submitted.nonce, &request.digest.unwrap());
+    assert_eq!(
+        armistice.cancel_operation(&approval::CancelRequest {
+            id: submitted.id,
+            signature: sign_digest(&digest, &admins[1]),
+        }),
+        Err(Error::Unauthorized)
+    );
+
+    let digest = approval::cancellation_digest(&submitted.nonce, &request.digest.unwrap());
+    armistice
+        .cancel_operation(&approval::CancelRequest {
+            id: submitted.id,
+            signature: sign_digest(&digest, &admins[1]),
+        })
+        .unwrap();
+
+    assert!(armistice.approvals().is_empty());
+    assert_eq!(
+        approve(&mut armistice, &request, &submitted, &admins[2]),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn pending_operations_bounded() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    submit(&mut armistice, &request, &admins[0]).unwrap();
+
+    assert_eq!(
+        submit(&mut armistice, &request, &admins[1]),
+        Err(Error::Duplicate)
+    );
+
+    // Only callers the domain recognizes may submit operations...
+    let caller = keypair(10);
+    assert_eq!(
+        submit(&mut armistice, &sign_submission(b"another"), &caller),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(
+        armistice.submit_operation_as(&sign_submission(b"another"), None),
+        Err(Error::Unauthorized)
+    );
+
+    // ...and each may only have one pending
+    assert_eq!(
+        submit(&mut armistice, &sign_submission(b"another"), &admins[0]),
+        Err(Error::Capacity)
+    );
+
+    for (i, admin) in admins.iter().enumerate().skip(1) {
+        submit(&mut armistice, &sign_submission(&[i as u8]), admin).unwrap();
+    }
+
+    // Callers named by a key's policy may submit operations which use it
+    let mut callers = policy::Callers::new();
+    callers.push(public_key(&caller)).unwrap();
+
+    let request = round_trip(&key::GenerateRequest {
+        policy: policy::Policy {
+            callers,
+            ..policy::Policy::default()
+        },
+        ..generate_request()
+    });
+    let binding = domain_binding(&armistice, DOMAIN_ID);
+    armistice
+        .generate_key(&key::SignedGenerateRequest {
+            signatures: sign(
+                &binding.digest(&request.digest.unwrap()),
+                &[&admins[0], &admins[1]],
+            ),
+            request,
+            binding,
+        })
+        .unwrap();
+
+    assert_eq!(
+        submit(&mut armistice, &sign_submission(b"another"), &caller),
+        Err(Error::Unauthorized)
+    );
+    submit(
+        &mut armistice,
+        &slot_sign_submission(1, b"another"),
+        &caller,
+    )
+    .unwrap();
+
+    let pending = armistice.list_pending().unwrap().pending;
+    assert_eq!(pending.len(), MAX_PENDING);
+    assert!(pending.windows(2).all(|pair| pair[0].id < pair[1].id));
+}
+
+#[test]
+fn pending_operations_expire() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+
+    for _ in 0..PENDING_LIFETIME {
+        armistice.handle_request(info::Request {}.into()).unwrap();
+    }
+
+    assert_eq!(armistice.approvals().len(), 1);
+    armistice.handle_request(info::Request {}.into()).unwrap();
+    assert!(armistice.approvals().is_empty());
+
+    assert_eq!(
+        approve(&mut armistice, &request, &submitted, &admins[0]),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn approved_key_generation_persisted() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = round_trip(&approval::SubmitRequest {
+        operation: approval::Operation::GenerateKey(generate_request()),
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+    approve(&mut armistice, &request, &submitted, &admins[1]).unwrap();
+
+    let counter = armistice
+        .domains()
+        .get(DOMAIN_ID)
+        .unwrap()
+        .authorization_counter();
+    let response = approve(&mut armistice, &request, &submitted, &admins[2]).unwrap();
+    assert_eq!(response.generate_key().unwrap().slot, 1);
+    assert_eq!(
+        armistice
+            .domains()
+            .get(DOMAIN_ID)
+            .unwrap()
+            .authorization_counter(),
+        counter + 1
+    );
+
+    let restarted = Armistice::new(
+        root_encryption_key(),
+        support::rng(),
+        armistice.storage().clone(),
+        armistice.counter().clone(),
+    )
+    .unwrap();
+
+    assert_eq!(restarted.domains().get(DOMAIN_ID).unwrap().keys().len(), 2);
+}
+
+#[test]
+fn approved_key_generation_bound_to_counter() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = round_trip(&approval::SubmitRequest {
+        operation: approval::Operation::GenerateKey(generate_request()),
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+    approve(&mut armistice, &request, &submitted, &admins[1]).unwrap();
+
+    // Another operation authorized in the meantime invalidates the pending one
+    let generate = generate_request();
+    let binding = domain_binding(&armistice, DOMAIN_ID);
+    armistice
+        .generate_key(&key::SignedGenerateRequest {
+            signatures: sign(
+                &binding.digest(&generate.digest.unwrap()),
+                &[&admins[0], &admins[1]],
+            ),
+            request: generate,
+            binding,
+        })
+        .unwrap();
+
+    assert_eq!(
+        approve(&mut armistice, &request, &submitted, &admins[2]),
+        Err(Error::Replay)
+    );
+    assert_eq!(armistice.domains().get(DOMAIN_ID).unwrap().keys().len(), 2);
+}
+
+#[test]
+fn removed_admins_approvals_not_counted() {
+    let (mut armistice, _, admins) = armistice_with_domain();
+    let request = sign_submission(b"hello, world");
+    let submitted = submit(&mut armistice, &request, &admins[0]).unwrap();
+    approve(&mut armistice, &request, &submitted, &admins[0]).unwrap();
+
+    // Replace the first administrator
+    let new_admin = keypair(5);
+    let update = round_trip(&domain::UpdateRequest {
+        config: domain_config(2, &[&new_admin, &admins[1], &admins[2]]),
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = domain_binding(&armistice, DOMAIN_ID);
+    armistice
+        .update_domain(&domain::SignedUpdateRequest {
+            signatures: sign(
+                &binding.digest(&update.digest.unwrap()),
+                &[&admins[1], &admins[2]],
+            ),
+            request: update,
+            binding,
+        })
+        .unwrap();
+
+    assert_eq!(armistice.list_pending().unwrap().pending[0].approvals, 0);
+
+    let response = approve(&mut armistice, &request, &submitted, &admins[1]).unwrap();
+    assert_eq!(response.approve().unwrap().approvals, 1);
+}
+
+#[test]
+fn domain_deletion_discards_pending_operations() {
+    let (mut armistice, root_key, admins) = armistice_with_domain();
+    submit(
+        &mut armistice,
+        &sign_submission(b"hello, world"),
+        &admins[0],
+    )
+    .unwrap();
+
+    let request = round_trip(&domain::DeleteRequest {
+        id: DOMAIN_ID,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(&armistice);
+    armistice
+        .delete_domain(&domain::SignedDeleteRequest {
+            signatures: sign(&binding.digest(&request.digest.unwrap()), &[&root_key]),
+            request,
+            binding,
+        })
+        .unwrap();
+
+    assert!(armistice.approvals().is_empty());
+    assert_eq!(
+        submit(
+            &mut armistice,
+            &sign_submission(b"hello, world"),
+            &admins[0]
+        ),
+        Err(Error::NotFound)
+    );
+}
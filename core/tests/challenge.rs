@@ -0,0 +1,351 @@
+//! Challenge-response authorization integration test
+
+mod support;
+
+use armistice_core::{challenge::MAX_CHALLENGES, Error};
+use armistice_schema::{challenge, domain, info, key, policy, threshold, ThresholdKeySet};
+use ed25519_dalek::Keypair;
+use support::{
+    binding, domain_binding, keypair, provisioned_armistice, public_key, round_trip, sign,
+    timestamp, Armistice,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Number of requests after which challenges in the test domain expire
+const CHALLENGE_LIFETIME: u64 = 2;
+
+/// Create a domain with the given ID administered by the given key whose
+/// operations require challenges (if `challenge_lifetime` is non-zero)
+fn create_domain(
+    armistice: &mut Armistice,
+    id: domain::Id,
+    root_key: &Keypair,
+    admin: &Keypair,
+    challenge_lifetime: u64,
+) {
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys.push(public_key(admin)).unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+
+    armistice
+        .create_domain(&domain::SignedCreateRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+}
+
+/// Provision a device with a domain whose operations require challenges,
+/// returning it along with the root and administrator keys
+fn challenged_armistice() -> (Armistice, Keypair, Keypair) {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(
+        &mut armistice,
+        DOMAIN_ID,
+        &root_key,
+        &admin,
+        CHALLENGE_LIFETIME,
+    );
+    (armistice, root_key, admin)
+}
+
+/// Create a key generation request in the test domain
+fn generate_request() -> key::GenerateRequest {
+    round_trip(&key::GenerateRequest {
+        domain: DOMAIN_ID,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: policy::Policy::default(),
+        digest: None,
+    })
+}
+
+/// Request a challenge for the given operation in the test domain
+fn get_challenge(
+    armistice: &mut Armistice,
+    operation: &[u8; 32],
+) -> Result<challenge::Response, Error> {
+    armistice.get_challenge(&challenge::Request {
+        domain: DOMAIN_ID,
+        operation: *operation,
+    })
+}
+
+/// Sign a key generation request in response to the given challenge (or
+/// without one)
+fn sign_generate_request(
+    armistice: &Armistice,
+    request: key::GenerateRequest,
+    challenge: Option<&challenge::Response>,
+    signers: &[&Keypair],
+) -> key::SignedGenerateRequest {
+    let binding = domain_binding(armistice, DOMAIN_ID);
+    let mut digest = binding.digest(&request.digest.unwrap());
+
+    if let Some(challenge) = challenge {
+        digest = challenge.digest(&digest);
+    }
+
+    key::SignedGenerateRequest {
+        request,
+        signatures: sign(&digest, signers),
+        binding,
+    }
+}
+
+/// Have the device receive a request which doesn't concern challenges
+fn send_unrelated_request(armistice: &mut Armistice) {
+    armistice.handle_request(info::Request {}.into()).unwrap();
+}
+
+#[test]
+fn challenge_response_happy_path() {
+    let (mut armistice, _, admin) = challenged_armistice();
+    let request = generate_request();
+
+    let challenge = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+    assert_eq!(challenge.expires_in, CHALLENGE_LIFETIME);
+    assert_eq!(armistice.challenges().len(), 1);
+
+    let signed_request = sign_generate_request(&armistice, request, Some(&challenge), &[&admin]);
+    assert_eq!(armistice.generate_key(&signed_request).unwrap().slot, 0);
+    assert!(armistice.challenges().is_empty());
+}
+
+#[test]
+fn challenge_required() {
+    let (mut armistice, _, admin) = challenged_armistice();
+
+    let signed_request = sign_generate_request(&armistice, generate_request(), None, &[&admin]);
+    assert_eq!(
+        armistice.generate_key(&signed_request),
+        Err(Error::Challenge)
+    );
+    assert_eq!(armistice.domains().get(DOMAIN_ID).unwrap().keys().len(), 0);
+}
+
+#[test]
+fn signatures_must_cover_challenge() {
+    let (mut armistice, _, admin) = challenged_armistice();
+    let request = generate_request();
+    get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+
+    let signed_request = sign_generate_request(&armistice, request, None, &[&admin]);
+    assert_eq!(
+        armistice.generate_key(&signed_request),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn challenge_bound_to_operation() {
+    let (mut armistice, _, admin) = challenged_armistice();
+
+    // Challenge issued for a different operation
+    let challenge = get_challenge(&mut armistice, &[0; 32]).unwrap();
+
+    let signed_request =
+        sign_generate_request(&armistice, generate_request(), Some(&challenge), &[&admin]);
+    assert_eq!(
+        armistice.generate_key(&signed_request),
+        Err(Error::Challenge)
+    );
+    assert_eq!(armistice.challenges().len(), 1);
+}
+
+#[test]
+fn challenge_single_use() {
+    let (mut armistice, _, admin) = challenged_armistice();
+    let other_admin = keypair(3);
+    let request = generate_request();
+    let challenge = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+
+    // An attempt with invalid signatures leaves the challenge outstanding...
+    let signed_request = sign_generate_request(
+        &armistice,
+        request.clone(),
+        Some(&challenge),
+        &[&other_admin],
+    );
+    assert_eq!(
+        armistice.generate_key(&signed_request),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(armistice.challenges().len(), 1);
+
+    // ...but it's consumed once approved
+    let signed_request = sign_generate_request(&armistice, request, Some(&challenge), &[&admin]);
+    armistice.generate_key(&signed_request).unwrap();
+    assert!(armistice.challenges().is_empty());
+
+    let signed_request = key::SignedGenerateRequest {
+        binding: domain_binding(&armistice, DOMAIN_ID),
+        ..signed_request
+    };
+    assert_eq!(
+        armistice.generate_key(&signed_request),
+        Err(Error::Challenge)
+    );
+}
+
+#[test]
+fn challenge_outstanding_after_failure() {
+    let (mut armistice, _, admin) = challenged_armistice();
+    let request = round_trip(&key::GenerateRequest {
+        algorithm: 0xff,
+        ..generate_request()
+    });
+    let challenge = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+
+    // Challenges are only consumed once approved operations are performed
+    let signed_request = sign_generate_request(&armistice, request, Some(&challenge), &[&admin]);
+    assert_eq!(armistice.generate_key(&signed_request), Err(Error::Crypto));
+    assert_eq!(armistice.challenges().len(), 1);
+}
+
+#[test]
+fn challenge_expires() {
+    let (mut armistice, _, admin) = challenged_armistice();
+    let request = generate_request();
+    let challenge = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+
+    for _ in 0..CHALLENGE_LIFETIME {
+        send_unrelated_request(&mut armistice);
+    }
+
+    assert_eq!(armistice.challenges().len(), 1);
+    send_unrelated_request(&mut armistice);
+    assert!(armistice.challenges().is_empty());
+
+    let signed_request = sign_generate_request(&armistice, request, Some(&challenge), &[&admin]);
+    assert_eq!(
+        armistice.generate_key(&signed_request),
+        Err(Error::Challenge)
+    );
+}
+
+#[test]
+fn reissued_challenge_unchanged() {
+    let (mut armistice, _, admin) = challenged_armistice();
+    let request = generate_request();
+    let first = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+    send_unrelated_request(&mut armistice);
+
+    let second = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+    assert_eq!(first.nonce, second.nonce);
+    assert_eq!(second.expires_in, CHALLENGE_LIFETIME - 1);
+    assert_eq!(armistice.challenges().len(), 1);
+
+    let signed_request = sign_generate_request(&armistice, request, Some(&first), &[&admin]);
+    armistice.generate_key(&signed_request).unwrap();
+}
+
+#[test]
+fn outstanding_challenges_bounded() {
+    let (mut armistice, root_key, admin) = challenged_armistice();
+    let request = generate_request();
+    let challenge = get_challenge(&mut armistice, &request.digest.unwrap()).unwrap();
+
+    // Outstanding challenges are never replaced by new ones for the same
+    // domain...
+    for i in 1..MAX_CHALLENGES as u8 {
+        get_challenge(&mut armistice, &[i; 32]).unwrap();
+    }
+
+    assert_eq!(armistice.challenges().len(), MAX_CHALLENGES);
+    assert_eq!(
+        get_challenge(&mut armistice, &[0; 32]),
+        Err(Error::Capacity)
+    );
+
+    // ...but other domains' challenges are limited separately
+    create_domain(
+        &mut armistice,
+        DOMAIN_ID + 1,
+        &root_key,
+        &admin,
+        CHALLENGE_LIFETIME,
+    );
+    armistice
+        .get_challenge(&challenge::Request {
+            domain: DOMAIN_ID + 1,
+            operation: [0; 32],
+        })
+        .unwrap();
+
+    let signed_request = sign_generate_request(&armistice, request, Some(&challenge), &[&admin]);
+    armistice.generate_key(&signed_request).unwrap();
+    get_challenge(&mut armistice, &[0; 32]).unwrap();
+}
+
+#[test]
+fn domain_deletion_requires_challenge() {
+    let (mut armistice, root_key, _) = challenged_armistice();
+
+    let request = round_trip(&domain::DeleteRequest {
+        id: DOMAIN_ID,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(&armistice);
+    let digest = binding.digest(&request.digest.unwrap());
+    let mut signed_request = domain::SignedDeleteRequest {
+        request,
+        signatures: sign(&digest, &[&root_key]),
+        binding,
+    };
+
+    assert_eq!(
+        armistice.delete_domain(&signed_request),
+        Err(Error::Challenge)
+    );
+
+    let challenge = get_challenge(&mut armistice, &signed_request.request.digest.unwrap()).unwrap();
+    signed_request.signatures = sign(&challenge.digest(&digest), &[&root_key]);
+    armistice.delete_domain(&signed_request).unwrap();
+    assert!(armistice.domains().is_empty());
+}
+
+#[test]
+fn challenges_only_issued_when_required() {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    assert_eq!(
+        get_challenge(&mut armistice, &[0; 32]),
+        Err(Error::NotFound)
+    );
+
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin, 0);
+    assert_eq!(
+        get_challenge(&mut armistice, &[0; 32]),
+        Err(Error::Challenge)
+    );
+
+    // Operations in the domain are authorized without challenges
+    let signed_request = sign_generate_request(&armistice, generate_request(), None, &[&admin]);
+    armistice.generate_key(&signed_request).unwrap();
+}
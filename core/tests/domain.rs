@@ -0,0 +1,268 @@
+//! Domain management integration test
+
+mod support;
+
+use armistice_core::{domain::Policy, Error};
+use armistice_schema::{domain, threshold, ThresholdKeySet};
+use ed25519_dalek::Keypair;
+use support::{
+    binding, domain_binding, keypair, provisioned_armistice, public_key, round_trip, sign,
+    timestamp, Armistice,
+};
+
+/// Create a domain configuration administered by the given keys
+fn domain_config(id: domain::Id, threshold: u64, admins: &[&Keypair]) -> domain::Config {
+    let mut public_keys = threshold::PublicKeys::new();
+
+    for admin in admins {
+        public_keys.push(public_key(admin)).unwrap();
+    }
+
+    domain::Config {
+        id,
+        admins: ThresholdKeySet {
+            threshold,
+            public_keys,
+        },
+        policy: domain::Policy {
+            max_keys: 4,
+            challenge_lifetime: 0,
+        },
+    }
+}
+
+/// Create a domain with the given configuration, signed by the given keys
+fn create_domain(
+    armistice: &mut Armistice,
+    config: domain::Config,
+    signers: &[&Keypair],
+) -> Result<domain::Id, Error> {
+    let request = round_trip(&domain::CreateRequest {
+        config,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+    let signed_request = domain::SignedCreateRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice
+        .create_domain(&signed_request)
+        .map(|response| response.id)
+}
+
+/// Update a domain to the given configuration, signed by the given keys
+fn update_domain(
+    armistice: &mut Armistice,
+    config: domain::Config,
+    signers: &[&Keypair],
+) -> Result<domain::Id, Error> {
+    let request = round_trip(&domain::UpdateRequest {
+        config,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, request.config.id);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+    let signed_request = domain::SignedUpdateRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice
+        .update_domain(&signed_request)
+        .map(|response| response.id)
+}
+
+/// Delete a domain, signed by the given keys
+fn delete_domain(
+    armistice: &mut Armistice,
+    id: domain::Id,
+    signers: &[&Keypair],
+) -> Result<domain::Id, Error> {
+    let request = round_trip(&domain::DeleteRequest {
+        id,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+    let signed_request = domain::SignedDeleteRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice
+        .delete_domain(&signed_request)
+        .map(|response| response.id)
+}
+
+/// List the configurations of all domains
+fn list_domains(armistice: &mut Armistice) -> domain::Configs {
+    armistice.list_domains().unwrap().domains
+}
+
+#[test]
+fn domain_lifecycle() {
+    let root_key = keypair(1);
+    let (admin_1, admin_2, admin_3) = (keypair(2), keypair(3), keypair(4));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let config = domain_config(42, 1, &[&admin_1]);
+    assert_eq!(
+        create_domain(&mut armistice, config.clone(), &[&root_key]),
+        Ok(42)
+    );
+    assert_eq!(&list_domains(&mut armistice)[..], &[config]);
+
+    let updated_config = domain_config(42, 2, &[&admin_2, &admin_3]);
+    assert_eq!(
+        update_domain(&mut armistice, updated_config.clone(), &[&admin_1]),
+        Ok(42)
+    );
+    assert_eq!(&list_domains(&mut armistice)[..], &[updated_config]);
+
+    let domain = armistice.domains().get(42).unwrap();
+    assert_eq!(domain.admins().threshold(), 2);
+
+    assert_eq!(delete_domain(&mut armistice, 42, &[&root_key]), Ok(42));
+    assert!(armistice.domains().is_empty());
+    assert!(list_domains(&mut armistice).is_empty());
+}
+
+#[test]
+fn create_requires_root_threshold() {
+    let (root_key_1, root_key_2, admin) = (keypair(1), keypair(2), keypair(3));
+    let mut armistice = provisioned_armistice(2, &[&root_key_1, &root_key_2]);
+
+    assert_eq!(
+        create_domain(
+            &mut armistice,
+            domain_config(1, 1, &[&admin]),
+            &[&root_key_1]
+        ),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(
+        create_domain(&mut armistice, domain_config(1, 1, &[&admin]), &[&admin]),
+        Err(Error::Unauthorized)
+    );
+    assert!(armistice.domains().is_empty());
+}
+
+#[test]
+fn create_requires_provisioning() {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = support::armistice();
+
+    assert_eq!(
+        create_domain(&mut armistice, domain_config(1, 1, &[&admin]), &[&root_key]),
+        Err(Error::Unprovisioned)
+    );
+}
+
+#[test]
+fn duplicate_domain_ids_rejected() {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    create_domain(&mut armistice, domain_config(1, 1, &[&admin]), &[&root_key]).unwrap();
+
+    assert_eq!(
+        create_domain(&mut armistice, domain_config(1, 1, &[&admin]), &[&root_key]),
+        Err(Error::Duplicate)
+    );
+}
+
+#[test]
+fn domain_capacity() {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    for id in 0..8 {
+        create_domain(
+            &mut armistice,
+            domain_config(id, 1, &[&admin]),
+            &[&root_key],
+        )
+        .unwrap();
+    }
+
+    assert_eq!(
+        create_domain(&mut armistice, domain_config(8, 1, &[&admin]), &[&root_key]),
+        Err(Error::Capacity)
+    );
+    assert_eq!(armistice.domains().len(), 8);
+}
+
+#[test]
+fn policy_key_slot_limit() {
+    assert_eq!(Policy::new(8, 0).unwrap().max_keys(), 8);
+    assert_eq!(Policy::new(9, 0), Err(Error::Capacity));
+}
+
+#[test]
+fn update_requires_domain_admins() {
+    let (root_key, admin, other_admin) = (keypair(1), keypair(2), keypair(3));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    create_domain(&mut armistice, domain_config(1, 1, &[&admin]), &[&root_key]).unwrap();
+
+    // Root keys don't administer domains
+    assert_eq!(
+        update_domain(
+            &mut armistice,
+            domain_config(1, 1, &[&other_admin]),
+            &[&root_key]
+        ),
+        Err(Error::Unauthorized)
+    );
+
+    // Incoming administrators can't authorize their own appointment
+    assert_eq!(
+        update_domain(
+            &mut armistice,
+            domain_config(1, 1, &[&other_admin]),
+            &[&other_admin]
+        ),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn update_and_delete_unknown_domain() {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    assert_eq!(
+        update_domain(&mut armistice, domain_config(7, 1, &[&admin]), &[&admin]),
+        Err(Error::NotFound)
+    );
+    assert_eq!(
+        delete_domain(&mut armistice, 7, &[&root_key]),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn delete_requires_root_threshold() {
+    let (root_key, admin) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    create_domain(&mut armistice, domain_config(1, 1, &[&admin]), &[&root_key]).unwrap();
+
+    assert_eq!(
+        delete_domain(&mut armistice, 1, &[&admin]),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(armistice.domains().len(), 1);
+}
@@ -0,0 +1,68 @@
+//! Fragmented request/response integration test
+
+mod support;
+
+use armistice_core::MAX_MESSAGE_SIZE;
+use armistice_schema::{
+    framing::{self, Reassembler},
+    veriform::Decoder,
+    Message, Request, Response,
+};
+use ed25519_dalek::Keypair;
+use support::{armistice, keypair, provision_request, sign_provision_request};
+
+/// Packet size of the USB armory's bulk endpoints
+const PACKET_SIZE: usize = 512;
+
+/// Split a message into packets and reassemble them, returning the number
+/// of packets it took along with the reassembled message
+fn transfer(message: &[u8], reassembler: &mut Reassembler) -> (usize, Vec<u8>) {
+    let mut count = 0;
+
+    for fragment in framing::fragments(message, PACKET_SIZE).unwrap() {
+        let mut packet = [0u8; PACKET_SIZE];
+        count += 1;
+
+        if let Some(reassembled) = reassembler
+            .push(fragment.encode(&mut packet).unwrap())
+            .unwrap()
+        {
+            return (count, reassembled.to_vec());
+        }
+    }
+
+    panic!("message was not reassembled");
+}
+
+#[test]
+fn provision_with_eight_root_keys() {
+    let mut armistice = armistice();
+    let root_keys: Vec<Keypair> = (1..=8).map(keypair).collect();
+    let signers: Vec<&Keypair> = root_keys.iter().collect();
+
+    let request: Request =
+        sign_provision_request(&armistice, provision_request(8, &signers), &signers).into();
+
+    let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+    let encoded = request.encode(&mut buffer).unwrap();
+
+    let mut device_reassembler = Reassembler::new();
+    let (packets, reassembled) = transfer(encoded, &mut device_reassembler);
+    assert!(packets > 1);
+
+    let request = Request::decode(&mut Decoder::new(), &reassembled).unwrap();
+    let response = armistice.handle_request(request).unwrap();
+
+    let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+    let encoded = response.encode(&mut buffer).unwrap();
+
+    let mut client_reassembler = Reassembler::new();
+    let (_, reassembled) = transfer(encoded, &mut client_reassembler);
+    let response = Response::decode(&mut Decoder::new(), &reassembled).unwrap();
+
+    assert!(armistice.is_provisioned());
+    assert_eq!(
+        response.provision().unwrap().uuid,
+        armistice.root_config().uuid()
+    );
+}
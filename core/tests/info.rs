@@ -0,0 +1,108 @@
+//! Device information integration test
+
+mod support;
+
+use armistice_core::{crypto::signing_key::ALGORITHMS, MAX_MESSAGE_SIZE};
+use armistice_schema::{domain, info, key, policy, threshold, ThresholdKeySet, Uuid};
+use support::{armistice, keypair, provisioned_armistice, public_key, round_trip, sign, timestamp};
+
+#[test]
+fn unprovisioned_device_info() {
+    let mut armistice = armistice();
+
+    let response = armistice.handle_request(info::Request {}.into()).unwrap();
+
+    let info = response.get_info().unwrap();
+    assert_eq!(info.firmware_version(), Some(env!("CARGO_PKG_VERSION")));
+    assert!(info.is_compatible());
+    assert!(!info.is_provisioned());
+    assert_eq!(info.uuid, Uuid::nil());
+    assert!(info.slots.is_empty());
+    assert_eq!(info.max_message_size, MAX_MESSAGE_SIZE as u64);
+
+    for &algorithm in ALGORITHMS {
+        assert!(info.supports(algorithm));
+    }
+
+    assert!(info.supports(key::Algorithm::Ed25519));
+    assert_eq!(
+        info.supports(key::Algorithm::Bls12381),
+        cfg!(feature = "bls")
+    );
+}
+
+#[test]
+fn provisioned_device_info() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys.push(public_key(&admin_key)).unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id: 7,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = support::binding(&armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[&root_key]);
+    armistice
+        .create_domain(&domain::SignedCreateRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+
+    let request = round_trip(&key::GenerateRequest {
+        domain: 7,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: policy::Policy::default(),
+        digest: None,
+    });
+
+    let binding = support::domain_binding(&armistice, 7);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[&admin_key]);
+    armistice
+        .generate_key(&key::SignedGenerateRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+
+    let info = armistice.info().unwrap();
+    assert!(info.is_provisioned());
+    assert_eq!(info.uuid, armistice.root_config().uuid());
+    assert_eq!(info.root_version, 1);
+    assert_eq!(info.device_id, *armistice.device_id());
+    assert_eq!(info.authorization_counter, 2);
+    assert_eq!(
+        &info.domain_counters[..],
+        &[info::DomainCounter {
+            domain: 7,
+            counter: 1,
+        }]
+    );
+    assert_eq!(
+        &info.slots[..],
+        &[info::SlotUsage {
+            domain: 7,
+            used: 1,
+            max: 4,
+        }]
+    );
+}
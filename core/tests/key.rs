@@ -0,0 +1,606 @@
+//! Key generation and signing integration test
+
+mod support;
+
+use armistice_core::{crypto::PublicKey, policy::Denial, Error, Vec};
+use armistice_schema::{domain, key, policy, threshold, Signature, ThresholdKeySet};
+use core::convert::TryFrom;
+use ed25519_dalek::Keypair;
+use support::{
+    binding, caller_policy, domain_binding, keypair, provisioned_armistice, public_key, round_trip,
+    sign, sign_as_caller, timestamp, Armistice,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Create a domain with the given ID administered by the given key, signed by
+/// the given root key
+fn create_domain(
+    armistice: &mut Armistice,
+    id: domain::Id,
+    root_key: &Keypair,
+    admin: &Keypair,
+    max_keys: u64,
+) {
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys.push(public_key(admin)).unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+    let signed_request = domain::SignedCreateRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice.create_domain(&signed_request).unwrap();
+}
+
+/// Delete the given domain, signed by the given root key
+fn delete_domain(armistice: &mut Armistice, id: domain::Id, root_key: &Keypair) {
+    let request = round_trip(&domain::DeleteRequest {
+        id,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+    let signed_request = domain::SignedDeleteRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice.delete_domain(&signed_request).unwrap();
+}
+
+/// Generate a key in the given domain, signed by the given keys
+fn generate_key(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    algorithm: u64,
+    signers: &[&Keypair],
+) -> Result<key::GenerateResponse, Error> {
+    generate_key_with_policy(armistice, domain, algorithm, caller_policy(), signers)
+}
+
+/// Generate a key governed by the given policy in the given domain, signed
+/// by the given keys
+fn generate_key_with_policy(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    algorithm: u64,
+    policy: policy::Policy,
+    signers: &[&Keypair],
+) -> Result<key::GenerateResponse, Error> {
+    let request = round_trip(&key::GenerateRequest {
+        domain,
+        algorithm,
+        timestamp: timestamp(),
+        policy,
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, domain);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+    let signed_request = key::SignedGenerateRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice.generate_key(&signed_request)
+}
+
+/// Sign the given payload with the key in the given domain and slot
+fn sign_payload(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    slot: key::Slot,
+    payload: key::Payload,
+) -> Result<Signature, Error> {
+    sign_as_caller(
+        armistice,
+        &key::SignRequest {
+            domain,
+            slot,
+            payload,
+        },
+    )
+    .map(|response| response.signature)
+}
+
+/// Sign a raw message with the key in the given domain and slot
+fn sign_message(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    slot: key::Slot,
+    msg: &[u8],
+) -> Result<Signature, Error> {
+    let mut message = Vec::new();
+    message.extend_from_slice(msg).unwrap();
+    sign_payload(armistice, domain, slot, key::Payload::Message(message))
+}
+
+#[test]
+fn generate_and_sign() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    let response_1 = generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+    let response_2 = generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+
+    assert_eq!(response_1.slot, 0);
+    assert_eq!(response_2.slot, 1);
+    assert_ne!(response_1.public_key, response_2.public_key);
+
+    let msg = b"example message";
+    let signature = sign_message(&mut armistice, DOMAIN_ID, 1, msg).unwrap();
+
+    PublicKey::try_from(&response_2.public_key)
+        .unwrap()
+        .verify(msg, &signature)
+        .unwrap();
+
+    assert_eq!(
+        PublicKey::try_from(&response_1.public_key)
+            .unwrap()
+            .verify(msg, &signature),
+        Err(Error::Crypto)
+    );
+
+    // Ed25519 keys can't sign prehashed messages
+    assert_eq!(
+        sign_payload(&mut armistice, DOMAIN_ID, 1, key::Payload::Sha256([0; 32])),
+        Err(Error::Crypto)
+    );
+}
+
+/// Generate a key with the given ECDSA algorithm and check raw and
+/// prehashed signatures produced by it verify
+#[cfg(feature = "ecdsa")]
+fn generate_and_sign_ecdsa(algorithm: key::Algorithm) {
+    use sha2::{Digest, Sha256};
+
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let response =
+        generate_key(&mut armistice, DOMAIN_ID, algorithm.into(), &[&admin_key]).unwrap();
+    let public_key = PublicKey::try_from(&response.public_key).unwrap();
+
+    let msg = b"example message";
+    let signature = sign_message(&mut armistice, DOMAIN_ID, response.slot, msg).unwrap();
+    public_key.verify(msg, &signature).unwrap();
+
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&Sha256::digest(msg));
+
+    let signature = sign_payload(
+        &mut armistice,
+        DOMAIN_ID,
+        response.slot,
+        key::Payload::Sha256(digest),
+    )
+    .unwrap();
+
+    public_key.verify(msg, &signature).unwrap();
+}
+
+#[test]
+#[cfg(feature = "ecdsa")]
+fn generate_and_sign_ecdsa_p256() {
+    generate_and_sign_ecdsa(key::Algorithm::EcdsaP256);
+}
+
+#[test]
+#[cfg(feature = "secp256k1")]
+fn generate_and_sign_ecdsa_secp256k1() {
+    generate_and_sign_ecdsa(key::Algorithm::EcdsaSecp256k1);
+}
+
+/// Request a proof of possession for the key in the given domain and slot
+fn prove_possession(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    slot: key::Slot,
+) -> Result<Signature, Error> {
+    armistice
+        .prove_possession(&key::PossessionRequest { domain, slot })
+        .map(|response| response.proof)
+}
+
+#[test]
+#[cfg(feature = "bls")]
+fn generate_and_sign_bls12381() {
+    use armistice_core::crypto::bls;
+
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let msg = b"example message";
+    let mut public_keys = std::vec::Vec::new();
+    let mut signatures = std::vec::Vec::new();
+
+    for _ in 0..2 {
+        let algorithm = key::Algorithm::Bls12381.into();
+        let response = generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+
+        let public_key = match response.public_key {
+            armistice_schema::PublicKey::Bls12381(bytes) => bls::PublicKey::from_bytes(&bytes),
+            other => panic!("unexpected public key: {:?}", other),
+        }
+        .unwrap();
+
+        let proof = match prove_possession(&mut armistice, DOMAIN_ID, response.slot).unwrap() {
+            Signature::Bls12381(bytes) => bls::Signature::from_bytes(&bytes).unwrap(),
+            other => panic!("unexpected proof: {:?}", other),
+        };
+
+        public_key.verify_possession(&proof).unwrap();
+
+        // Proofs of possession are not valid signatures over the public key
+        assert_eq!(
+            public_key.verify(&public_key.to_bytes(), &proof),
+            Err(Error::Crypto)
+        );
+
+        let signature = sign_message(&mut armistice, DOMAIN_ID, response.slot, msg).unwrap();
+
+        PublicKey::try_from(&response.public_key)
+            .unwrap()
+            .verify(msg, &signature)
+            .unwrap();
+
+        match signature {
+            Signature::Bls12381(bytes) => {
+                signatures.push(bls::Signature::from_bytes(&bytes).unwrap())
+            }
+            other => panic!("unexpected signature: {:?}", other),
+        }
+
+        public_keys.push(public_key);
+    }
+
+    let aggregate_key = bls::PublicKey::aggregate(&public_keys).unwrap();
+    let aggregate_signature = bls::Signature::aggregate(&signatures).unwrap();
+    aggregate_key.verify(msg, &aggregate_signature).unwrap();
+
+    assert_eq!(
+        public_keys[0].verify(msg, &aggregate_signature),
+        Err(Error::Crypto)
+    );
+
+    // BLS keys can't sign prehashed messages
+    assert_eq!(
+        sign_payload(&mut armistice, DOMAIN_ID, 0, key::Payload::Sha256([0; 32])),
+        Err(Error::Crypto)
+    );
+}
+
+#[test]
+fn prove_possession_requires_bls_key() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    let response = generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+
+    assert_eq!(
+        prove_possession(&mut armistice, DOMAIN_ID, response.slot),
+        Err(Error::Crypto)
+    );
+    assert_eq!(
+        prove_possession(&mut armistice, DOMAIN_ID, response.slot + 1),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn generate_requires_domain_admins() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+
+    // Root keys have no authority over keys within domains
+    assert_eq!(
+        generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&root_key]),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn generate_in_unknown_domain() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    assert_eq!(
+        generate_key(
+            &mut armistice,
+            DOMAIN_ID,
+            key::Algorithm::Ed25519.into(),
+            &[&admin_key]
+        ),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn generate_unknown_algorithm() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    assert_eq!(
+        generate_key(&mut armistice, DOMAIN_ID, 42, &[&admin_key]),
+        Err(Error::Crypto)
+    );
+}
+
+#[test]
+fn generate_enforces_max_keys() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 1);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+
+    assert_eq!(
+        generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]),
+        Err(Error::Capacity)
+    );
+}
+
+#[test]
+fn sign_with_empty_slot() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    assert_eq!(
+        sign_message(&mut armistice, DOMAIN_ID, 0, b"example message"),
+        Err(Error::NotFound)
+    );
+}
+
+/// Get the public key of the key in the given domain and slot
+fn get_public_key(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    slot: key::Slot,
+) -> Result<armistice_schema::PublicKey, Error> {
+    armistice
+        .public_key(&key::PublicKeyRequest { domain, slot })
+        .map(|response| response.public_key)
+}
+
+/// Export the key in the given domain and slot, signed by the given keys
+fn export_key(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    slot: key::Slot,
+    signers: &[&Keypair],
+) -> Result<key::ExportResponse, Error> {
+    let request = round_trip(&key::ExportRequest {
+        domain,
+        slot,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, domain);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+    let signed_request = key::SignedExportRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice.export_key(&signed_request)
+}
+
+/// Import an exported key into the given domain, signed by the given keys
+fn import_key(
+    armistice: &mut Armistice,
+    domain: domain::Id,
+    exported: key::ExportResponse,
+    signers: &[&Keypair],
+) -> Result<key::ImportResponse, Error> {
+    let request = round_trip(&key::ImportRequest {
+        domain,
+        wrapped_key: exported.wrapped_key,
+        timestamp: timestamp(),
+        policy: exported.policy,
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, domain);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+    let signed_request = key::SignedImportRequest {
+        request,
+        signatures,
+        binding,
+    };
+
+    armistice.import_key(&signed_request)
+}
+
+#[test]
+fn get_public_key_of_slot() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    let response = generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+
+    assert_eq!(
+        get_public_key(&mut armistice, DOMAIN_ID, response.slot),
+        Ok(response.public_key)
+    );
+
+    assert_eq!(
+        get_public_key(&mut armistice, DOMAIN_ID, response.slot + 1),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn export_and_import() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    let policy = policy::Policy {
+        max_length: 32,
+        ..caller_policy()
+    };
+    let generated =
+        generate_key_with_policy(&mut armistice, DOMAIN_ID, algorithm, policy, &[&admin_key])
+            .unwrap();
+    let exported = export_key(&mut armistice, DOMAIN_ID, 0, &[&admin_key]).unwrap();
+    assert_eq!(exported.policy.max_length, 32);
+
+    // The same key can't occupy two slots...
+    assert_eq!(
+        import_key(&mut armistice, DOMAIN_ID, exported.clone(), &[&admin_key]),
+        Err(Error::Duplicate)
+    );
+
+    // ...but can be restored once the domain has been recreated
+    delete_domain(&mut armistice, DOMAIN_ID, &root_key);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let imported = import_key(&mut armistice, DOMAIN_ID, exported, &[&admin_key]).unwrap();
+    assert_eq!(imported.slot, 0);
+    assert_eq!(imported.public_key, generated.public_key);
+
+    let msg = b"example message";
+    let signature = sign_message(&mut armistice, DOMAIN_ID, imported.slot, msg).unwrap();
+
+    PublicKey::try_from(&generated.public_key)
+        .unwrap()
+        .verify(msg, &signature)
+        .unwrap();
+
+    // Imported keys are governed by the policy they were exported with
+    assert_eq!(
+        sign_message(&mut armistice, DOMAIN_ID, imported.slot, &[0; 33]),
+        Err(Error::Policy(Denial::Length))
+    );
+}
+
+#[test]
+fn export_and_import_require_domain_admins() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+
+    assert_eq!(
+        export_key(&mut armistice, DOMAIN_ID, 0, &[&root_key]),
+        Err(Error::Unauthorized)
+    );
+
+    let exported = export_key(&mut armistice, DOMAIN_ID, 0, &[&admin_key]).unwrap();
+
+    assert_eq!(
+        import_key(&mut armistice, DOMAIN_ID, exported, &[&root_key]),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn import_rejects_tampered_or_foreign_keys() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    create_domain(&mut armistice, DOMAIN_ID, &root_key, &admin_key, 4);
+
+    let algorithm = key::Algorithm::Ed25519.into();
+    generate_key(&mut armistice, DOMAIN_ID, algorithm, &[&admin_key]).unwrap();
+    let exported = export_key(&mut armistice, DOMAIN_ID, 0, &[&admin_key]).unwrap();
+
+    let mut tampered = exported.clone();
+    let last = tampered.wrapped_key.len() - 1;
+    tampered.wrapped_key[last] ^= 1;
+
+    assert_eq!(
+        import_key(&mut armistice, DOMAIN_ID, tampered, &[&admin_key]),
+        Err(Error::Crypto)
+    );
+
+    // Wrapped keys are bound to the policy they were exported with...
+    let mut tampered = exported.clone();
+    tampered.policy.max_signatures = 1;
+    tampered.policy.window = 1;
+
+    assert_eq!(
+        import_key(&mut armistice, DOMAIN_ID, tampered, &[&admin_key]),
+        Err(Error::Crypto)
+    );
+
+    // ...the domain which exported them...
+    create_domain(&mut armistice, DOMAIN_ID + 1, &root_key, &admin_key, 4);
+
+    assert_eq!(
+        import_key(
+            &mut armistice,
+            DOMAIN_ID + 1,
+            exported.clone(),
+            &[&admin_key]
+        ),
+        Err(Error::Crypto)
+    );
+
+    // ...and the root configuration which exported them
+    let other_root_key = keypair(3);
+    let mut other = provisioned_armistice(1, &[&other_root_key]);
+    create_domain(&mut other, DOMAIN_ID, &other_root_key, &admin_key, 4);
+
+    assert_eq!(
+        import_key(&mut other, DOMAIN_ID, exported, &[&admin_key]),
+        Err(Error::Crypto)
+    );
+}
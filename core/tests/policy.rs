@@ -0,0 +1,498 @@
+//! Key policy integration test
+
+mod support;
+
+use armistice_core::{policy::Denial, Error, MAX_MESSAGE_SIZE};
+use armistice_schema::{
+    approval, domain, error, info, key,
+    policy::{hash_algorithm_mask, HashAlgorithm, Policy, Prefix},
+    session::{self, Initiator, Session},
+    threshold,
+    veriform::Decoder,
+    Message, Request, Response, Signature, ThresholdKeySet,
+};
+use core::convert::TryFrom;
+use ed25519_dalek::{Keypair, Signer};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use support::{
+    binding, caller, caller_policy, domain_binding, keypair, provisioned_armistice, public_key,
+    root_encryption_key, round_trip, sign, sign_as_caller, timestamp, Armistice,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Provision a device with a domain administered by 2-of-3 keys, containing
+/// a single key governed by the given policy
+fn armistice_with_key(policy: Policy) -> (Armistice, [Keypair; 3]) {
+    let root_key = keypair(1);
+    let admins = [keypair(2), keypair(3), keypair(4)];
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let mut public_keys = threshold::PublicKeys::new();
+
+    for admin in &admins {
+        public_keys.push(public_key(admin)).unwrap();
+    }
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id: DOMAIN_ID,
+            admins: ThresholdKeySet {
+                threshold: 2,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let create_binding = binding(&armistice);
+    armistice
+        .create_domain(&domain::SignedCreateRequest {
+            signatures: sign(
+                &create_binding.digest(&request.digest.unwrap()),
+                &[&root_key],
+            ),
+            request,
+            binding: create_binding,
+        })
+        .unwrap();
+
+    generate_key(&mut armistice, policy, &admins).unwrap();
+    (armistice, admins)
+}
+
+/// Generate a key governed by the given policy in the test domain
+fn generate_key(
+    armistice: &mut Armistice,
+    policy: Policy,
+    admins: &[Keypair; 3],
+) -> Result<key::GenerateResponse, Error> {
+    let request = round_trip(&key::GenerateRequest {
+        domain: DOMAIN_ID,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy,
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, DOMAIN_ID);
+    armistice.generate_key(&key::SignedGenerateRequest {
+        signatures: sign(
+            &binding.digest(&request.digest.unwrap()),
+            &[&admins[0], &admins[1]],
+        ),
+        request,
+        binding,
+    })
+}
+
+/// Create a request to sign the given message with the key in slot 0
+fn sign_request(message: &[u8]) -> key::SignRequest {
+    let mut payload = key::MessageBytes::new();
+    payload.extend_from_slice(message).unwrap();
+
+    key::SignRequest {
+        domain: DOMAIN_ID,
+        slot: 0,
+        payload: key::Payload::Message(payload),
+    }
+}
+
+/// Create a request to sign a SHA-256 digest with the key in slot 0
+fn sign_prehashed_request() -> key::SignRequest {
+    key::SignRequest {
+        domain: DOMAIN_ID,
+        slot: 0,
+        payload: key::Payload::Sha256([0x42; 32]),
+    }
+}
+
+/// Create a policy which only allows messages with the given prefix
+fn prefix_policy(prefix: &[u8]) -> Policy {
+    let mut policy = caller_policy();
+    let mut allowed = Prefix::default();
+    allowed.bytes.extend_from_slice(prefix).unwrap();
+    policy.prefixes.push(allowed).unwrap();
+    policy
+}
+
+/// Have the device receive a request which doesn't use any keys
+fn send_unrelated_request(armistice: &mut Armistice) {
+    armistice.handle_request(info::Request {}.into()).unwrap();
+}
+
+/// Establish a session with the given device
+fn establish_session(armistice: &mut Armistice) -> Session {
+    let (initiator, request) = Initiator::new(
+        &armistice.session_public_key(),
+        ChaCha20Rng::from_seed([1u8; 32]),
+    )
+    .unwrap();
+
+    let response = armistice.handle_request(request.into()).unwrap();
+    initiator.finish(response.session_init().unwrap()).unwrap()
+}
+
+/// Send a request to the device within the given session
+fn send_request(armistice: &mut Armistice, session: &mut Session, request: Request) -> Response {
+    let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+    let encrypted = session
+        .encrypt(request.encode(&mut buffer).unwrap())
+        .unwrap();
+
+    let response = armistice.handle_request(encrypted.into()).unwrap();
+    let plaintext = session.decrypt(response.session().unwrap()).unwrap();
+    Response::decode(&mut Decoder::new(), &plaintext).unwrap()
+}
+
+#[test]
+fn default_policy_denies_use() {
+    let (mut armistice, admins) = armistice_with_key(Policy::default());
+
+    let request = sign_request(b"hello");
+    assert_eq!(
+        sign_as_caller(&mut armistice, &request),
+        Err(Error::Policy(Denial::Caller))
+    );
+    assert_eq!(
+        armistice.sign_as(&request, None),
+        Err(Error::Policy(Denial::Caller))
+    );
+
+    // Keys whose policies name no callers can still be used with the
+    // approval of the domain's administrators
+    let submission = round_trip(&approval::SubmitRequest {
+        operation: approval::Operation::Sign(request),
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let submitter = armistice_core::crypto::PublicKey::try_from(&public_key(&admins[0])).unwrap();
+    let submitted = armistice
+        .submit_operation_as(&submission, Some(&submitter))
+        .unwrap();
+
+    let digest = approval::approval_digest(&submitted.nonce, &submission.digest.unwrap());
+    let mut responses = admins[..2].iter().map(|admin| {
+        armistice
+            .approve(&approval::ApproveRequest {
+                id: submitted.id,
+                signature: Signature::Ed25519(admin.sign(&digest).to_bytes()),
+            })
+            .unwrap()
+    });
+
+    assert!(responses.next().unwrap().approve().is_some());
+    assert!(responses.next().unwrap().sign().is_some());
+}
+
+#[test]
+fn named_caller_allowed_any_use() {
+    let (mut armistice, _) = armistice_with_key(caller_policy());
+
+    sign_as_caller(&mut armistice, &sign_request(b"")).unwrap();
+
+    // Allowed by the policy, but Ed25519 keys don't sign prehashed payloads
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_prehashed_request()),
+        Err(Error::Crypto)
+    );
+}
+
+#[test]
+fn message_prefix_enforced() {
+    let (mut armistice, _) = armistice_with_key(prefix_policy(b"armistice:"));
+
+    sign_as_caller(&mut armistice, &sign_request(b"armistice:hello")).unwrap();
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_request(b"hello")),
+        Err(Error::Policy(Denial::Prefix))
+    );
+
+    // Prehashed payloads can't be checked against the prefix
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_prehashed_request()),
+        Err(Error::Policy(Denial::HashAlgorithm))
+    );
+}
+
+#[test]
+fn message_length_enforced() {
+    let (mut armistice, _) = armistice_with_key(Policy {
+        min_length: 4,
+        max_length: 8,
+        ..caller_policy()
+    });
+
+    sign_as_caller(&mut armistice, &sign_request(b"1234")).unwrap();
+    sign_as_caller(&mut armistice, &sign_request(b"12345678")).unwrap();
+
+    for message in &[&b"123"[..], &b"123456789"[..]] {
+        assert_eq!(
+            sign_as_caller(&mut armistice, &sign_request(message)),
+            Err(Error::Policy(Denial::Length))
+        );
+    }
+}
+
+#[test]
+fn hash_algorithm_enforced() {
+    let (mut armistice, _) = armistice_with_key(Policy {
+        hash_algorithms: hash_algorithm_mask(&[HashAlgorithm::Sha256]),
+        ..caller_policy()
+    });
+
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_prehashed_request()),
+        Err(Error::Crypto)
+    );
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_request(b"hello")),
+        Err(Error::Policy(Denial::HashAlgorithm))
+    );
+}
+
+#[test]
+fn signature_rate_limited_per_window() {
+    let (mut armistice, admins) = armistice_with_key(Policy {
+        max_signatures: 2,
+        window: 2,
+        ..caller_policy()
+    });
+
+    sign_as_caller(&mut armistice, &sign_request(b"one")).unwrap();
+    sign_as_caller(&mut armistice, &sign_request(b"two")).unwrap();
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_request(b"three")),
+        Err(Error::Policy(Denial::RateLimit))
+    );
+
+    // Neither unauthenticated requests nor restarting the device advance
+    // the window
+    for _ in 0..4 {
+        send_unrelated_request(&mut armistice);
+    }
+
+    let mut armistice = Armistice::new(
+        root_encryption_key(),
+        support::rng(),
+        armistice.storage().clone(),
+        armistice.counter().clone(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_request(b"three")),
+        Err(Error::Policy(Denial::RateLimit))
+    );
+
+    // Nor do other keys' signatures...
+    let rate_limited = Policy {
+        max_signatures: 1,
+        window: 1,
+        ..caller_policy()
+    };
+    let slot = generate_key(&mut armistice, rate_limited, &admins)
+        .unwrap()
+        .slot;
+
+    let other = key::SignRequest {
+        slot,
+        ..sign_request(b"other")
+    };
+    sign_as_caller(&mut armistice, &other).unwrap();
+    assert_eq!(
+        sign_as_caller(&mut armistice, &other),
+        Err(Error::Policy(Denial::RateLimit))
+    );
+    assert_eq!(
+        sign_as_caller(&mut armistice, &sign_request(b"three")),
+        Err(Error::Policy(Denial::RateLimit))
+    );
+
+    // ...as it's measured in operations the domain's administrators authorize
+    generate_key(&mut armistice, Policy::default(), &admins).unwrap();
+    sign_as_caller(&mut armistice, &sign_request(b"three")).unwrap();
+}
+
+#[test]
+fn required_approvals_collected_via_queue() {
+    let (mut armistice, admins) = armistice_with_key(Policy {
+        required_approvals: 3,
+        ..caller_policy()
+    });
+
+    let request = sign_request(b"hello");
+    assert_eq!(
+        sign_as_caller(&mut armistice, &request),
+        Err(Error::Policy(Denial::Approvals))
+    );
+
+    let submission = round_trip(&approval::SubmitRequest {
+        operation: approval::Operation::Sign(request),
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    // The key's policy raises the domain's 2-of-3 threshold
+    let submitter = armistice_core::crypto::PublicKey::try_from(&public_key(&caller())).unwrap();
+    let submitted = armistice
+        .submit_operation_as(&submission, Some(&submitter))
+        .unwrap();
+    assert_eq!(submitted.threshold, 3);
+
+    let digest = approval::approval_digest(&submitted.nonce, &submission.digest.unwrap());
+
+    for (i, admin) in admins.iter().enumerate() {
+        let response = armistice
+            .approve(&approval::ApproveRequest {
+                id: submitted.id,
+                signature: Signature::Ed25519(admin.sign(&digest).to_bytes()),
+            })
+            .unwrap();
+
+        if i < 2 {
+            assert_eq!(response.approve().unwrap().threshold, 3);
+        } else {
+            assert!(response.sign().is_some());
+        }
+    }
+}
+
+#[test]
+fn callers_authenticated_within_session() {
+    let caller = keypair(9);
+    let mut policy = Policy::default();
+    policy.callers.push(public_key(&caller)).unwrap();
+
+    let (mut armistice, _) = armistice_with_key(policy);
+    assert_eq!(
+        armistice.sign_as(&sign_request(b"hello"), None),
+        Err(Error::Policy(Denial::Caller))
+    );
+
+    let mut session = establish_session(&mut armistice);
+    let response = send_request(&mut armistice, &mut session, sign_request(b"hello").into());
+
+    // Denials are returned to the client along with their reason
+    let error = response.error().unwrap();
+    assert_eq!(error.code(), error::Code::Policy);
+    assert_eq!(error.detail(), Some(Denial::Caller.as_str()));
+
+    // Authentication must be signed by the caller's key over this session
+    let digest = session.authentication_digest();
+    let response = send_request(
+        &mut armistice,
+        &mut session,
+        session::AuthenticateRequest {
+            public_key: public_key(&caller),
+            signature: Signature::Ed25519(keypair(10).sign(&digest).to_bytes()),
+        }
+        .into(),
+    );
+    assert_eq!(response.error().unwrap().code(), error::Code::Unauthorized);
+
+    let response = send_request(
+        &mut armistice,
+        &mut session,
+        session::AuthenticateRequest {
+            public_key: public_key(&caller),
+            signature: Signature::Ed25519(caller.sign(&digest).to_bytes()),
+        }
+        .into(),
+    );
+    assert_eq!(
+        response.session_authenticate().unwrap().public_key,
+        public_key(&caller)
+    );
+
+    let response = send_request(&mut armistice, &mut session, sign_request(b"hello").into());
+    assert!(response.sign().is_some());
+
+    // Authentication doesn't carry over to other sessions
+    let mut other_session = establish_session(&mut armistice);
+    let response = send_request(
+        &mut armistice,
+        &mut other_session,
+        sign_request(b"hello").into(),
+    );
+    assert_eq!(response.error().unwrap().code(), error::Code::Policy);
+}
+
+#[test]
+fn invalid_policy_rejected() {
+    let (mut armistice, admins) = armistice_with_key(Policy::default());
+
+    for policy in &[
+        Policy {
+            min_length: 8,
+            max_length: 4,
+            ..caller_policy()
+        },
+        Policy {
+            max_signatures: 1,
+            ..caller_policy()
+        },
+        Policy {
+            hash_algorithms: 0b100,
+            ..caller_policy()
+        },
+    ] {
+        assert_eq!(
+            generate_key(&mut armistice, policy.clone(), &admins),
+            Err(Error::Policy(Denial::Invalid))
+        );
+    }
+
+    assert_eq!(armistice.domains().get(DOMAIN_ID).unwrap().keys().len(), 1);
+}
+
+#[test]
+fn policy_persisted() {
+    let (armistice, _) = armistice_with_key(prefix_policy(b"armistice:"));
+
+    let mut restarted = Armistice::new(
+        root_encryption_key(),
+        support::rng(),
+        armistice.storage().clone(),
+        armistice.counter().clone(),
+    )
+    .unwrap();
+
+    let policy = restarted
+        .domains()
+        .get(DOMAIN_ID)
+        .unwrap()
+        .key_policy(0)
+        .unwrap();
+
+    assert_eq!(
+        policy,
+        &armistice_core::policy::Policy::try_from(&prefix_policy(b"armistice:")).unwrap()
+    );
+    assert_eq!(
+        sign_as_caller(&mut restarted, &sign_request(b"hello")),
+        Err(Error::Policy(Denial::Prefix))
+    );
+}
+
+#[test]
+fn policy_conversion_round_trip() {
+    let mut policy = prefix_policy(b"armistice:");
+    policy.callers.push(public_key(&keypair(9))).unwrap();
+    policy.max_signatures = 10;
+    policy.window = 100;
+
+    let parsed = armistice_core::policy::Policy::try_from(&policy).unwrap();
+
+    // Allowing all hash algorithms is normalized to an explicit mask
+    policy.hash_algorithms =
+        hash_algorithm_mask(&[HashAlgorithm::Intrinsic, HashAlgorithm::Sha256]);
+    assert_eq!(Policy::try_from(&parsed).unwrap(), policy);
+}
@@ -0,0 +1,418 @@
+//! Authorization program integration test
+
+mod support;
+
+use armistice_core::{
+    policy::Denial,
+    program::{Fault, Input, Program, Verdict, MAX_STACK_DEPTH},
+    Error,
+};
+use armistice_schema::{
+    domain, key,
+    program::{self, Field, Opcode},
+    threshold, ThresholdKeySet,
+};
+use ed25519_dalek::Keypair;
+use support::{
+    binding, caller_policy, domain_binding, keypair, provisioned_armistice, public_key,
+    root_encryption_key, round_trip, sign, sign_as_caller, timestamp, Armistice,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Program which only allows signing messages beginning with "ok:"
+const PREFIX_PROGRAM: &[u8] = &[0x32, 3, b'o', b'k', b':', 0x21, 9, 0, 0x00, 0x01];
+
+/// Encode an opcode
+fn op(opcode: Opcode) -> u8 {
+    opcode.into()
+}
+
+/// Create a message payload
+fn message(bytes: &[u8]) -> key::Payload {
+    let mut message = key::MessageBytes::new();
+    message.extend_from_slice(bytes).unwrap();
+    key::Payload::Message(message)
+}
+
+/// Run the given code with a message payload
+fn run(code: &[u8], payload: &key::Payload) -> Result<Verdict, Fault> {
+    Program::new(code).unwrap().run(&Input {
+        domain: DOMAIN_ID,
+        slot: 2,
+        algorithm: key::Algorithm::Ed25519,
+        payload,
+        request_count: 42,
+        approvals: 1,
+        authenticated: false,
+    })
+}
+
+/// Provision a device with a domain administered by a single key, containing
+/// a single key, returning it along with the root and administrator keys
+fn armistice_with_domain() -> (Armistice, Keypair, Keypair) {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys.push(public_key(&admin_key)).unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id: DOMAIN_ID,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let create_binding = binding(&armistice);
+    armistice
+        .create_domain(&domain::SignedCreateRequest {
+            signatures: sign(
+                &create_binding.digest(&request.digest.unwrap()),
+                &[&root_key],
+            ),
+            request,
+            binding: create_binding,
+        })
+        .unwrap();
+
+    let request = round_trip(&key::GenerateRequest {
+        domain: DOMAIN_ID,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: caller_policy(),
+        digest: None,
+    });
+
+    let generate_binding = domain_binding(&armistice, DOMAIN_ID);
+    armistice
+        .generate_key(&key::SignedGenerateRequest {
+            signatures: sign(
+                &generate_binding.digest(&request.digest.unwrap()),
+                &[&admin_key],
+            ),
+            request,
+            binding: generate_binding,
+        })
+        .unwrap();
+
+    (armistice, root_key, admin_key)
+}
+
+/// Install the given code in the test domain, signed by the given keys
+fn install(
+    armistice: &mut Armistice,
+    code: &[u8],
+    signers: &[&Keypair],
+) -> Result<program::InstallResponse, Error> {
+    let mut program = program::Program::default();
+    program.code.extend_from_slice(code).unwrap();
+
+    let request = round_trip(&program::InstallRequest {
+        domain: DOMAIN_ID,
+        program,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, DOMAIN_ID);
+    armistice.install_program(&program::SignedInstallRequest {
+        signatures: sign(&binding.digest(&request.digest.unwrap()), signers),
+        request,
+        binding,
+    })
+}
+
+/// Sign the given message with the key in slot 0 of the test domain
+fn sign_message(armistice: &mut Armistice, bytes: &[u8]) -> Result<key::SignResponse, Error> {
+    sign_as_caller(
+        armistice,
+        &key::SignRequest {
+            domain: DOMAIN_ID,
+            slot: 0,
+            payload: message(bytes),
+        },
+    )
+}
+
+#[test]
+fn verdicts() {
+    let payload = message(b"hello");
+    assert_eq!(run(&[op(Opcode::Allow)], &payload), Ok(Verdict::Allow));
+    assert_eq!(run(&[op(Opcode::Deny)], &payload), Ok(Verdict::Deny));
+    assert_eq!(run(PREFIX_PROGRAM, &payload), Ok(Verdict::Deny));
+    assert_eq!(
+        run(PREFIX_PROGRAM, &message(b"ok:hello")),
+        Ok(Verdict::Allow)
+    );
+}
+
+#[test]
+fn arithmetic_and_comparison() {
+    // Allow if 2 + 3 == 5 and 7 - 5 < 3
+    let code = [
+        op(Opcode::PushByte),
+        2,
+        op(Opcode::PushByte),
+        3,
+        op(Opcode::Add),
+        op(Opcode::PushByte),
+        5,
+        op(Opcode::Eq),
+        op(Opcode::PushByte),
+        7,
+        op(Opcode::PushByte),
+        5,
+        op(Opcode::Sub),
+        op(Opcode::PushByte),
+        3,
+        op(Opcode::Lt),
+        op(Opcode::And),
+        op(Opcode::Not),
+        op(Opcode::JumpIf),
+        22,
+        0,
+        op(Opcode::Allow),
+        op(Opcode::Deny),
+    ];
+
+    assert_eq!(run(&code, &message(b"")), Ok(Verdict::Allow));
+}
+
+#[test]
+fn input_fields() {
+    let payload = message(b"hello");
+
+    for &(field, expected) in &[
+        (Field::Domain, DOMAIN_ID),
+        (Field::Slot, 2),
+        (Field::Algorithm, key::Algorithm::Ed25519.into()),
+        (Field::HashAlgorithm, 0),
+        (Field::PayloadLength, 5),
+        (Field::RequestCount, 42),
+        (Field::Approvals, 1),
+        (Field::Authenticated, 0),
+    ] {
+        let mut code = vec![op(Opcode::Load), field.into(), op(Opcode::Push)];
+        code.extend_from_slice(&u64::to_le_bytes(expected));
+        code.extend_from_slice(&[op(Opcode::Eq), op(Opcode::JumpIf), 16, 0]);
+        code.extend_from_slice(&[op(Opcode::Deny), op(Opcode::Allow)]);
+
+        assert_eq!(run(&code, &payload), Ok(Verdict::Allow), "{:?}", field);
+    }
+
+    // Allow if the last byte of the payload is 'o'
+    let code = [
+        op(Opcode::Load),
+        Field::PayloadLength.into(),
+        op(Opcode::PushByte),
+        1,
+        op(Opcode::Sub),
+        op(Opcode::PayloadByte),
+        op(Opcode::PushByte),
+        b'o',
+        op(Opcode::Eq),
+        op(Opcode::JumpIf),
+        13,
+        0,
+        op(Opcode::Deny),
+        op(Opcode::Allow),
+    ];
+
+    assert_eq!(run(&code, &payload), Ok(Verdict::Allow));
+    assert_eq!(run(&code, &message(b"help")), Ok(Verdict::Deny));
+}
+
+#[test]
+fn faults() {
+    let payload = message(b"hello");
+
+    // Loops are bounded by gas
+    assert_eq!(
+        run(&[op(Opcode::Jump), 0, 0], &payload),
+        Err(Fault::OutOfGas)
+    );
+
+    assert_eq!(
+        run(&[op(Opcode::Pop), op(Opcode::Allow)], &payload),
+        Err(Fault::StackUnderflow)
+    );
+
+    let mut code = vec![op(Opcode::PushByte), 0];
+
+    for _ in 0..MAX_STACK_DEPTH {
+        code.push(op(Opcode::Dup));
+    }
+
+    code.push(op(Opcode::Allow));
+    assert_eq!(run(&code, &payload), Err(Fault::StackOverflow));
+
+    assert_eq!(
+        run(
+            &[
+                op(Opcode::PushByte),
+                0,
+                op(Opcode::PushByte),
+                1,
+                op(Opcode::Sub),
+                op(Opcode::Allow)
+            ],
+            &payload
+        ),
+        Err(Fault::Overflow)
+    );
+
+    assert_eq!(
+        run(
+            &[
+                op(Opcode::PushByte),
+                5,
+                op(Opcode::PayloadByte),
+                op(Opcode::Allow)
+            ],
+            &payload
+        ),
+        Err(Fault::OutOfBounds)
+    );
+
+    assert_eq!(
+        run(&[op(Opcode::PushByte), 0], &payload),
+        Err(Fault::EndOfCode)
+    );
+}
+
+#[test]
+fn invalid_code_rejected() {
+    let invalid = Err(Error::Policy(Denial::Invalid));
+
+    for code in &[
+        // Empty
+        &[][..],
+        // Unknown opcode
+        &[0xff][..],
+        // Incomplete immediate
+        &[op(Opcode::Push), 1, 2, 3][..],
+        &[op(Opcode::PayloadPrefix), 4, b'o', b'k'][..],
+        // Unknown field
+        &[op(Opcode::Load), 8, op(Opcode::Allow)][..],
+        // Jump into an immediate
+        &[op(Opcode::PushByte), 0, op(Opcode::Jump), 1, 0][..],
+        // Jump past the end of the code
+        &[op(Opcode::Jump), 3, 0][..],
+    ] {
+        assert_eq!(Program::new(code), invalid, "{:?}", code);
+    }
+
+    assert_eq!(Program::new(&[0; 257]), invalid);
+}
+
+#[test]
+fn program_installed_by_domain_admins() {
+    let (mut armistice, _, admin_key) = armistice_with_domain();
+    sign_message(&mut armistice, b"hello").unwrap();
+
+    install(&mut armistice, PREFIX_PROGRAM, &[&admin_key]).unwrap();
+    assert_eq!(
+        armistice
+            .domains()
+            .get(DOMAIN_ID)
+            .unwrap()
+            .program()
+            .unwrap()
+            .code(),
+        PREFIX_PROGRAM
+    );
+
+    sign_message(&mut armistice, b"ok:hello").unwrap();
+    assert_eq!(
+        sign_message(&mut armistice, b"hello"),
+        Err(Error::Policy(Denial::Program))
+    );
+
+    // Faults deny the operation too
+    install(&mut armistice, &[op(Opcode::Jump), 0, 0], &[&admin_key]).unwrap();
+    assert_eq!(
+        sign_message(&mut armistice, b"ok:hello"),
+        Err(Error::Policy(Denial::ProgramFault(Fault::OutOfGas)))
+    );
+}
+
+#[test]
+fn program_installed_by_root_keys() {
+    let (mut armistice, root_key, _) = armistice_with_domain();
+
+    install(&mut armistice, &[op(Opcode::Deny)], &[&root_key]).unwrap();
+    assert_eq!(
+        sign_message(&mut armistice, b"hello"),
+        Err(Error::Policy(Denial::Program))
+    );
+}
+
+#[test]
+fn installation_requires_authorization() {
+    let (mut armistice, _, admin_key) = armistice_with_domain();
+
+    assert_eq!(
+        install(&mut armistice, &[op(Opcode::Deny)], &[&keypair(3)]),
+        Err(Error::Unauthorized)
+    );
+
+    assert_eq!(
+        install(&mut armistice, &[op(Opcode::Jump), 1, 0], &[&admin_key]),
+        Err(Error::Policy(Denial::Invalid))
+    );
+
+    assert!(armistice
+        .domains()
+        .get(DOMAIN_ID)
+        .unwrap()
+        .program()
+        .is_none());
+    sign_message(&mut armistice, b"hello").unwrap();
+}
+
+#[test]
+fn program_removed_by_installing_empty_program() {
+    let (mut armistice, _, admin_key) = armistice_with_domain();
+    install(&mut armistice, &[op(Opcode::Deny)], &[&admin_key]).unwrap();
+    install(&mut armistice, &[], &[&admin_key]).unwrap();
+
+    assert!(armistice
+        .domains()
+        .get(DOMAIN_ID)
+        .unwrap()
+        .program()
+        .is_none());
+    sign_message(&mut armistice, b"hello").unwrap();
+}
+
+#[test]
+fn program_persisted() {
+    let (mut armistice, _, admin_key) = armistice_with_domain();
+    install(&mut armistice, PREFIX_PROGRAM, &[&admin_key]).unwrap();
+
+    let mut restarted = Armistice::new(
+        root_encryption_key(),
+        support::rng(),
+        armistice.storage().clone(),
+        armistice.counter().clone(),
+    )
+    .unwrap();
+
+    sign_message(&mut restarted, b"ok:hello").unwrap();
+    assert_eq!(
+        sign_message(&mut restarted, b"hello"),
+        Err(Error::Policy(Denial::Program))
+    );
+}
@@ -1,24 +1,45 @@
 //! Provisioning integration test
 
-use aes::{block_cipher::NewBlockCipher, Aes128};
-use armistice_core::Vec;
-use armistice_schema::{provision, public_key::PublicKey, Timestamp, Uuid};
+mod support;
 
-type Armistice = armistice_core::Armistice<Aes128>;
+use aes::{block_cipher::NewBlockCipher, Aes128};
+use armistice_core::{counter::MemoryCounter, storage::MemoryStorage, Error, Vec};
+use armistice_schema::{error, provision, public_key::PublicKey, Response, Uuid};
+use support::{
+    armistice, keypair, provision_request, rng, round_trip, sign_provision_request, timestamp,
+    Armistice,
+};
 
 #[test]
 fn provisioning_happy_path() {
-    let root_encryption_key = Aes128::new(
-        &[
-            0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad,
-            0xbe, 0xef,
-        ]
-        .into(),
+    let mut armistice = armistice();
+    let root_key_1 = keypair(1);
+    let root_key_2 = keypair(2);
+
+    let request = provision_request(1, &[&root_key_1, &root_key_2]);
+    let expected_uuid = request.uuid(armistice.device_id()).unwrap();
+
+    let signed_request = sign_provision_request(&armistice, request, &[&root_key_1]);
+    let response = armistice.handle_request(signed_request.into()).unwrap();
+    assert!(armistice.is_provisioned());
+
+    let uuid = response.provision().unwrap().uuid;
+    assert_eq!(uuid, expected_uuid);
+    assert_eq!(uuid, armistice.root_config().uuid());
+    assert_ne!(uuid, Uuid::nil());
+}
+
+#[test]
+fn device_id_derivation() {
+    assert_eq!(
+        armistice().device_id(),
+        &[97, 21, 196, 77, 192, 41, 107, 198, 229, 231, 218, 45, 90, 240, 45, 73]
     );
+}
 
-    let mut armistice = Armistice::new(root_encryption_key);
+#[test]
+fn uuid_known_answer() {
     let mut root_keys = Vec::new();
-
     root_keys
         .push(PublicKey::Ed25519([
             0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
@@ -32,22 +53,140 @@ fn provisioning_happy_path() {
         ]))
         .unwrap();
 
-    // TAI64N for 2020-05-21
-    let timestamp =
-        Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap();
-
-    let request = provision::Request {
+    let request = round_trip(&provision::Request {
         root_key_threshold: 1,
         root_keys,
-        timestamp,
+        timestamp: timestamp(),
         digest: None,
-    };
+    });
+
+    assert_eq!(
+        request.uuid(armistice().device_id()).unwrap(),
+        Uuid::parse_str("5c9705da-756c-85aa-8844-acec9e2100c8").unwrap()
+    );
+}
+
+#[test]
+fn uuid_is_device_unique() {
+    let root_key = keypair(1);
+    let request = provision_request(1, &[&root_key]);
+
+    let mut armistice_1 = armistice();
+    let mut armistice_2 = Armistice::new(
+        Aes128::new(&[0x42; 16].into()),
+        rng(),
+        MemoryStorage::new(),
+        MemoryCounter::new(),
+    )
+    .unwrap();
+
+    let signed_request = sign_provision_request(&armistice_1, request.clone(), &[&root_key]);
+    let response_1 = armistice_1.handle_request(signed_request.into()).unwrap();
+
+    let signed_request = sign_provision_request(&armistice_2, request, &[&root_key]);
+    let response_2 = armistice_2.handle_request(signed_request.into()).unwrap();
+
+    assert_ne!(
+        response_1.provision().unwrap().uuid,
+        response_2.provision().unwrap().uuid
+    );
+}
+
+#[test]
+fn provisioning_without_signatures() {
+    let mut armistice = armistice();
+    let root_key = keypair(1);
+
+    let request = provision_request(1, &[&root_key]);
+    let signed_request = sign_provision_request(&armistice, request, &[]);
+
+    assert_eq!(
+        armistice.handle_request(signed_request.into()),
+        Err(Error::Unauthorized)
+    );
+    assert!(!armistice.is_provisioned());
+}
+
+#[test]
+fn provisioning_signed_by_non_root_key() {
+    let mut armistice = armistice();
+    let root_key = keypair(1);
+    let other_key = keypair(3);
+
+    let request = provision_request(1, &[&root_key]);
+    let signed_request = sign_provision_request(&armistice, request, &[&other_key]);
+
+    assert_eq!(
+        armistice.handle_request(signed_request.into()),
+        Err(Error::Unauthorized)
+    );
+    assert!(!armistice.is_provisioned());
+}
+
+#[test]
+fn provisioning_with_duplicate_signatures() {
+    let mut armistice = armistice();
+    let root_key_1 = keypair(1);
+    let root_key_2 = keypair(2);
+
+    let request = provision_request(2, &[&root_key_1, &root_key_2]);
+    let signed_request = sign_provision_request(&armistice, request, &[&root_key_1, &root_key_1]);
+
+    assert_eq!(
+        armistice.handle_request(signed_request.into()),
+        Err(Error::Unauthorized)
+    );
+    assert!(!armistice.is_provisioned());
+}
+
+#[test]
+fn provisioning_with_duplicate_root_keys() {
+    let mut armistice = armistice();
+    let root_key = keypair(1);
+
+    let request = provision_request(2, &[&root_key, &root_key]);
+    let signed_request = sign_provision_request(&armistice, request, &[&root_key]);
+
+    assert_eq!(
+        armistice.handle_request(signed_request.into()),
+        Err(Error::Threshold)
+    );
+}
+
+#[test]
+fn provisioning_with_undecoded_request() {
+    let mut armistice = armistice();
+    let root_key = keypair(1);
+
+    let mut request = provision_request(1, &[&root_key]);
+    let mut signed_request = sign_provision_request(&armistice, request.clone(), &[&root_key]);
+    request.digest = None;
+    signed_request.request = request;
+
+    assert_eq!(
+        armistice.handle_request(signed_request.into()),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn provisioning_twice() {
+    let mut armistice = armistice();
+    let root_key = keypair(1);
+
+    let request = provision_request(1, &[&root_key]);
+    let signed_request = sign_provision_request(&armistice, request, &[&root_key]);
+    armistice
+        .handle_request(signed_request.clone().into())
+        .unwrap();
 
-    let response = armistice.handle_request(request.into()).unwrap();
+    let err = armistice.provision(&signed_request).unwrap_err();
+    assert_eq!(err, Error::Provisioned);
+    assert_eq!(armistice.root_config().version(), 1);
 
-    // TODO(tarcieri): stub!
+    // Errors are reported to clients as error responses
     assert_eq!(
-        response.provision().unwrap().uuid,
-        Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()
+        Response::from(err).error().unwrap().code(),
+        error::Code::Provisioned
     );
 }
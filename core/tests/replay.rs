@@ -0,0 +1,270 @@
+//! Replay protection integration test: requests captured in transit (or
+//! otherwise obtained by an attacker) can't be replayed
+
+mod support;
+
+use aes::{block_cipher::NewBlockCipher, Aes128};
+use armistice_core::{counter::MemoryCounter, storage::MemoryStorage, Error};
+use armistice_schema::{authorization::Binding, domain, key, threshold, ThresholdKeySet};
+use ed25519_dalek::Keypair;
+use support::{
+    armistice, binding, domain_binding, keypair, provision_request, provisioned_armistice,
+    public_key, root_encryption_key, round_trip, sign, sign_provision_request, timestamp,
+    Armistice,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Create a signed domain creation request under the given binding, round
+/// tripping it through the encoder as if it were captured on the wire
+fn create_request(binding: Binding, root_key: &Keypair) -> domain::SignedCreateRequest {
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys.push(public_key(&keypair(2))).unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id: DOMAIN_ID,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+
+    round_trip(&domain::SignedCreateRequest {
+        request,
+        signatures,
+        binding,
+    })
+}
+
+/// Create a signed domain deletion request under the given binding
+fn delete_request(binding: Binding, root_key: &Keypair) -> domain::SignedDeleteRequest {
+    let request = round_trip(&domain::DeleteRequest {
+        id: DOMAIN_ID,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+
+    round_trip(&domain::SignedDeleteRequest {
+        request,
+        signatures,
+        binding,
+    })
+}
+
+/// Create a key generation request in the test domain under the given
+/// binding, signed by its administrator
+fn generate_request(binding: Binding) -> key::SignedGenerateRequest {
+    let request = round_trip(&key::GenerateRequest {
+        domain: DOMAIN_ID,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: Default::default(),
+        digest: None,
+    });
+
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[&keypair(2)]);
+
+    round_trip(&key::SignedGenerateRequest {
+        request,
+        signatures,
+        binding,
+    })
+}
+
+/// Instantiate a device with a different root key (and therefore device ID)
+fn other_armistice() -> Armistice {
+    Armistice::new(
+        Aes128::new(&[0x42; 16].into()),
+        support::rng(),
+        MemoryStorage::new(),
+        MemoryCounter::new(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn replayed_request_rejected() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let captured = create_request(binding(&armistice), &root_key);
+    armistice.create_domain(&captured).unwrap();
+
+    let request = delete_request(binding(&armistice), &root_key);
+    armistice.delete_domain(&request).unwrap();
+
+    // Replaying the captured request would otherwise recreate the domain
+    assert_eq!(armistice.create_domain(&captured), Err(Error::Replay));
+    assert!(armistice.domains().get(DOMAIN_ID).is_none());
+    assert_eq!(armistice.authorization_counter(), 3);
+}
+
+#[test]
+fn replayed_provisioning_rejected_by_other_devices() {
+    let root_key = keypair(1);
+    let mut armistice = armistice();
+
+    let captured =
+        sign_provision_request(&armistice, provision_request(1, &[&root_key]), &[&root_key]);
+    let captured = round_trip(&captured);
+    armistice.provision(&captured).unwrap();
+
+    let mut other = other_armistice();
+    assert_ne!(other.device_id(), armistice.device_id());
+    assert_eq!(other.provision(&captured), Err(Error::Replay));
+    assert!(!other.is_provisioned());
+}
+
+#[test]
+fn request_for_other_device_rejected() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    let mut other = other_armistice();
+    other
+        .provision(&sign_provision_request(
+            &other,
+            provision_request(1, &[&root_key]),
+            &[&root_key],
+        ))
+        .unwrap();
+
+    // Both devices share a root key and authorization counter, so only the
+    // device ID distinguishes requests intended for each of them
+    assert_eq!(binding(&armistice).counter, binding(&other).counter);
+
+    let captured = create_request(binding(&armistice), &root_key);
+    assert_eq!(other.create_domain(&captured), Err(Error::Replay));
+    armistice.create_domain(&captured).unwrap();
+}
+
+#[test]
+fn skipped_counter_rejected() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    // Requests must be bound to exactly the next counter, so signing one
+    // ahead of time can't invalidate those signed since
+    let mut late = binding(&armistice);
+    late.counter += 5;
+
+    let request = create_request(late, &root_key);
+    assert_eq!(armistice.create_domain(&request), Err(Error::Replay));
+    assert_eq!(armistice.authorization_counter(), 1);
+
+    let request = create_request(binding(&armistice), &root_key);
+    armistice.create_domain(&request).unwrap();
+    assert_eq!(armistice.authorization_counter(), 2);
+}
+
+#[test]
+fn domain_admins_cannot_lock_out_root() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    armistice
+        .create_domain(&create_request(binding(&armistice), &root_key))
+        .unwrap();
+
+    // A root request signed offline before the domain is used...
+    let delete = delete_request(binding(&armistice), &root_key);
+
+    // ...isn't invalidated by domain administrators exhausting their counter
+    let mut exhausted = domain_binding(&armistice, DOMAIN_ID);
+    exhausted.counter = u64::MAX;
+    assert_eq!(
+        armistice.generate_key(&generate_request(exhausted)),
+        Err(Error::Replay)
+    );
+
+    armistice
+        .generate_key(&generate_request(domain_binding(&armistice, DOMAIN_ID)))
+        .unwrap();
+    assert_eq!(armistice.authorization_counter(), 2);
+    assert_eq!(
+        armistice
+            .domains()
+            .get(DOMAIN_ID)
+            .unwrap()
+            .authorization_counter(),
+        1
+    );
+
+    armistice.delete_domain(&delete).unwrap();
+    assert_eq!(armistice.authorization_counter(), 3);
+}
+
+#[test]
+fn domain_request_not_replayed_to_recreated_domain() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    armistice
+        .create_domain(&create_request(binding(&armistice), &root_key))
+        .unwrap();
+
+    let captured = generate_request(domain_binding(&armistice, DOMAIN_ID));
+    armistice.generate_key(&captured).unwrap();
+
+    armistice
+        .delete_domain(&delete_request(binding(&armistice), &root_key))
+        .unwrap();
+    armistice
+        .create_domain(&create_request(binding(&armistice), &root_key))
+        .unwrap();
+
+    // The new domain's counter continues from the deleted one's
+    assert_eq!(armistice.generate_key(&captured), Err(Error::Replay));
+    assert_eq!(armistice.domains().get(DOMAIN_ID).unwrap().keys().len(), 0);
+}
+
+#[test]
+fn tampered_binding_rejected() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let captured = create_request(binding(&armistice), &root_key);
+    armistice.create_domain(&captured).unwrap();
+    armistice
+        .delete_domain(&delete_request(binding(&armistice), &root_key))
+        .unwrap();
+
+    // Advancing the counter of a captured request invalidates its signatures
+    let mut replayed = captured;
+    replayed.binding = binding(&armistice);
+    assert_eq!(armistice.create_domain(&replayed), Err(Error::Unauthorized));
+    assert_eq!(armistice.authorization_counter(), 3);
+}
+
+#[test]
+fn counter_survives_restart() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let captured = create_request(binding(&armistice), &root_key);
+    armistice.create_domain(&captured).unwrap();
+    armistice
+        .delete_domain(&delete_request(binding(&armistice), &root_key))
+        .unwrap();
+
+    let mut restarted = Armistice::new(
+        root_encryption_key(),
+        support::rng(),
+        armistice.storage().clone(),
+        armistice.counter().clone(),
+    )
+    .unwrap();
+
+    assert_eq!(restarted.authorization_counter(), 3);
+    assert_eq!(restarted.create_domain(&captured), Err(Error::Replay));
+}
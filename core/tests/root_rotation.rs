@@ -0,0 +1,218 @@
+//! Root key rotation integration test
+
+mod support;
+
+use armistice_core::{crypto::PublicKey, Error};
+use armistice_schema::{root, threshold, veriform::Decoder, Message, Signature, ThresholdKeySet};
+use core::convert::TryFrom;
+use ed25519_dalek::{Keypair, Signer};
+use support::{
+    binding, keypair, provisioned_armistice, public_key, round_trip, timestamp, Armistice,
+};
+
+/// Create a root rotation request to the given keys, round tripping it
+/// through the encoder so `veriform` computes its digest
+fn rotate_request(version: u64, threshold: u64, new_keys: &[&Keypair]) -> root::RotateRequest {
+    let mut public_keys = threshold::PublicKeys::new();
+
+    for key in new_keys {
+        public_keys.push(public_key(key)).unwrap();
+    }
+
+    round_trip(&root::RotateRequest {
+        version,
+        key_set: ThresholdKeySet {
+            threshold,
+            public_keys,
+        },
+        timestamp: timestamp(),
+        digest: None,
+    })
+}
+
+/// Sign a root rotation request for the given device with the given keypairs
+fn sign_rotate_request(
+    armistice: &Armistice,
+    request: root::RotateRequest,
+    signers: &[&Keypair],
+) -> root::SignedRotateRequest {
+    let binding = binding(armistice);
+    let digest = binding.digest(&request.digest.unwrap());
+    let mut signatures = root::RotationSignatures::new();
+
+    for signer in signers {
+        signatures
+            .push(Signature::Ed25519(signer.sign(&digest).to_bytes()))
+            .unwrap();
+    }
+
+    root::SignedRotateRequest {
+        request,
+        signatures,
+        binding,
+    }
+}
+
+#[test]
+fn rotation_happy_path() {
+    let (old_key_1, old_key_2) = (keypair(1), keypair(2));
+    let (new_key_1, new_key_2) = (keypair(3), keypair(4));
+    let mut armistice = provisioned_armistice(2, &[&old_key_1, &old_key_2]);
+    let uuid = armistice.root_config().uuid();
+
+    let request = rotate_request(2, 1, &[&new_key_1, &new_key_2]);
+    let signed_request =
+        sign_rotate_request(&armistice, request, &[&old_key_1, &old_key_2, &new_key_2]);
+
+    let response = armistice.rotate_root(&signed_request).unwrap();
+    assert_eq!(response.version, 2);
+
+    let root_config = armistice.root_config();
+    assert_eq!(root_config.version(), 2);
+    assert_eq!(root_config.uuid(), uuid);
+    assert_eq!(root_config.key_set().threshold(), 1);
+    assert_eq!(
+        root_config.key_set().public_keys(),
+        &[
+            PublicKey::try_from(&public_key(&new_key_1)).unwrap(),
+            PublicKey::try_from(&public_key(&new_key_2)).unwrap()
+        ]
+    );
+}
+
+#[test]
+fn rotation_to_disjoint_key_set() {
+    let old_keys = (1..=8).map(keypair).collect::<Vec<_>>();
+    let new_keys = (9..=16).map(keypair).collect::<Vec<_>>();
+    let mut armistice = provisioned_armistice(5, &old_keys.iter().collect::<Vec<_>>());
+
+    // A threshold of each key set signs: 10 signatures in total
+    let signers = old_keys[..5]
+        .iter()
+        .chain(&new_keys[..5])
+        .collect::<Vec<_>>();
+
+    let request = rotate_request(2, 5, &new_keys.iter().collect::<Vec<_>>());
+    let signed_request = sign_rotate_request(&armistice, request, &signers);
+    assert_eq!(signed_request.signatures.len(), 10);
+
+    let mut buffer = [0u8; 2048];
+    let encoded = signed_request.encode(&mut buffer).unwrap();
+    let decoded = root::SignedRotateRequest::decode(&mut Decoder::new(), encoded).unwrap();
+    assert_eq!(decoded.signatures, signed_request.signatures);
+
+    armistice.rotate_root(&decoded).unwrap();
+    assert_eq!(armistice.root_config().version(), 2);
+    assert_eq!(
+        armistice.root_config().key_set().public_keys(),
+        new_keys
+            .iter()
+            .map(|key| PublicKey::try_from(&public_key(key)).unwrap())
+            .collect::<Vec<_>>()
+            .as_slice()
+    );
+}
+
+#[test]
+fn old_keys_lose_authority_after_rotation() {
+    let (old_key, new_key, newer_key) = (keypair(1), keypair(2), keypair(3));
+    let mut armistice = provisioned_armistice(1, &[&old_key]);
+
+    let request = rotate_request(2, 1, &[&new_key]);
+    armistice
+        .rotate_root(&sign_rotate_request(
+            &armistice,
+            request,
+            &[&old_key, &new_key],
+        ))
+        .unwrap();
+
+    let request = rotate_request(3, 1, &[&newer_key]);
+    assert_eq!(
+        armistice.rotate_root(&sign_rotate_request(
+            &armistice,
+            request,
+            &[&old_key, &newer_key]
+        )),
+        Err(Error::Unauthorized)
+    );
+
+    let request = rotate_request(3, 1, &[&newer_key]);
+    armistice
+        .rotate_root(&sign_rotate_request(
+            &armistice,
+            request,
+            &[&new_key, &newer_key],
+        ))
+        .unwrap();
+    assert_eq!(armistice.root_config().version(), 3);
+}
+
+#[test]
+fn rotation_requires_old_key_threshold() {
+    let (old_key_1, old_key_2, new_key) = (keypair(1), keypair(2), keypair(3));
+    let mut armistice = provisioned_armistice(2, &[&old_key_1, &old_key_2]);
+
+    let request = rotate_request(2, 1, &[&new_key]);
+    let signed_request = sign_rotate_request(&armistice, request, &[&old_key_1, &new_key]);
+
+    assert_eq!(
+        armistice.rotate_root(&signed_request),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(armistice.root_config().version(), 1);
+}
+
+#[test]
+fn rotation_requires_new_key_threshold() {
+    let (old_key, new_key_1, new_key_2) = (keypair(1), keypair(2), keypair(3));
+    let mut armistice = provisioned_armistice(1, &[&old_key]);
+
+    let request = rotate_request(2, 2, &[&new_key_1, &new_key_2]);
+    let signed_request = sign_rotate_request(&armistice, request, &[&old_key, &new_key_1]);
+
+    assert_eq!(
+        armistice.rotate_root(&signed_request),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(armistice.root_config().version(), 1);
+}
+
+#[test]
+fn rotation_requires_next_version() {
+    let (old_key, new_key) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&old_key]);
+
+    for &version in &[0, 1, 3] {
+        let request = rotate_request(version, 1, &[&new_key]);
+        let signed_request = sign_rotate_request(&armistice, request, &[&old_key, &new_key]);
+
+        assert_eq!(armistice.rotate_root(&signed_request), Err(Error::Version));
+    }
+}
+
+#[test]
+fn rotation_cannot_be_replayed() {
+    let (old_key, new_key) = (keypair(1), keypair(2));
+    let mut armistice = provisioned_armistice(1, &[&old_key]);
+
+    let request = rotate_request(2, 1, &[&old_key, &new_key]);
+    let signed_request = sign_rotate_request(&armistice, request, &[&old_key, &new_key]);
+    armistice.rotate_root(&signed_request).unwrap();
+
+    assert_eq!(armistice.rotate_root(&signed_request), Err(Error::Replay));
+}
+
+#[test]
+fn rotation_requires_provisioning() {
+    let (old_key, new_key) = (keypair(1), keypair(2));
+    let mut armistice = support::armistice();
+
+    let request = rotate_request(2, 1, &[&new_key]);
+    let signed_request = sign_rotate_request(&armistice, request, &[&old_key, &new_key]);
+
+    assert_eq!(
+        armistice.rotate_root(&signed_request),
+        Err(Error::Unprovisioned)
+    );
+}
@@ -0,0 +1,251 @@
+//! Encrypted session integration test
+
+mod support;
+
+use aes::{block_cipher::NewBlockCipher, Aes128};
+use armistice_core::{
+    counter::MemoryCounter, storage::MemoryStorage, Error, MAX_MESSAGE_SIZE, MAX_SESSIONS,
+};
+use armistice_schema::{
+    domain, error, info,
+    session::{self, Initiator, PublicKey, Session},
+    veriform::Decoder,
+    Message, Request, Response,
+};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use support::{
+    armistice, keypair, provision_request, provisioned_armistice, rng, sign_provision_request,
+    Armistice,
+};
+
+/// Get the device's session public key from its (cleartext) info
+fn device_key(armistice: &mut Armistice) -> PublicKey {
+    let response = armistice.handle_request(info::Request {}.into()).unwrap();
+    PublicKey::from(response.get_info().unwrap().session_key)
+}
+
+/// Establish a session with the given device
+fn establish_session(armistice: &mut Armistice) -> Session {
+    let device_key = device_key(armistice);
+    let (initiator, request) =
+        Initiator::new(&device_key, ChaCha20Rng::from_seed([1u8; 32])).unwrap();
+
+    let response = armistice.handle_request(request.into()).unwrap();
+    initiator.finish(response.session_init().unwrap()).unwrap()
+}
+
+/// Encode and encrypt a request within the given session
+fn encrypt_request(session: &mut Session, request: Request) -> session::Encrypted {
+    let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+    session
+        .encrypt(request.encode(&mut buffer).unwrap())
+        .unwrap()
+}
+
+/// Decrypt and decode a response received within the given session
+fn decrypt_response(session: &mut Session, response: &Response) -> Response {
+    let plaintext = session.decrypt(response.session().unwrap()).unwrap();
+    Response::decode(&mut Decoder::new(), &plaintext).unwrap()
+}
+
+/// Send a request to the device within the given session
+fn send_request(armistice: &mut Armistice, session: &mut Session, request: Request) -> Response {
+    let encrypted = encrypt_request(session, request);
+    let response = armistice.handle_request(encrypted.into()).unwrap();
+    decrypt_response(session, &response)
+}
+
+#[test]
+fn session_key_derived_from_root_key() {
+    let mut armistice = armistice();
+    let session_key = armistice.session_public_key();
+    assert_eq!(device_key(&mut armistice), session_key);
+
+    // The session key is stable across restarts...
+    assert_eq!(support::armistice().session_public_key(), session_key);
+
+    // ...but unique to each root key
+    let other = Armistice::new(
+        Aes128::new(&[0x42; 16].into()),
+        rng(),
+        MemoryStorage::new(),
+        MemoryCounter::new(),
+    )
+    .unwrap();
+    assert_ne!(other.session_public_key(), session_key);
+}
+
+#[test]
+fn provisioning_within_session() {
+    let root_key = keypair(1);
+    let mut armistice = armistice();
+    let mut session = establish_session(&mut armistice);
+
+    let request =
+        sign_provision_request(&armistice, provision_request(1, &[&root_key]), &[&root_key]);
+    let response = send_request(&mut armistice, &mut session, request.into());
+    assert!(response.provision().is_some());
+    assert!(armistice.is_provisioned());
+
+    let response = send_request(
+        &mut armistice,
+        &mut session,
+        domain::ListRequest::default().into(),
+    );
+    assert!(response.domain_list().unwrap().domains.is_empty());
+}
+
+#[test]
+fn cleartext_rejected_once_provisioned() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    assert_eq!(
+        armistice.handle_request(domain::ListRequest::default().into()),
+        Err(Error::Session)
+    );
+
+    let request =
+        sign_provision_request(&armistice, provision_request(1, &[&root_key]), &[&root_key]);
+    assert_eq!(
+        armistice.handle_request(request.into()),
+        Err(Error::Session)
+    );
+
+    // Device info remains available in cleartext so clients can bootstrap
+    assert!(armistice
+        .handle_request(info::Request {}.into())
+        .unwrap()
+        .get_info()
+        .unwrap()
+        .is_provisioned());
+}
+
+#[test]
+fn errors_returned_within_session() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    let mut session = establish_session(&mut armistice);
+
+    let request =
+        sign_provision_request(&armistice, provision_request(1, &[&root_key]), &[&root_key]);
+    let response = send_request(&mut armistice, &mut session, request.into());
+    assert_eq!(response.error().unwrap().code(), error::Code::Provisioned);
+
+    // Requests can't be nested within a session
+    let (_, init) = Initiator::new(&device_key(&mut armistice), rng()).unwrap();
+    let response = send_request(&mut armistice, &mut session, init.into());
+    assert_eq!(response.error().unwrap().code(), error::Code::Session);
+
+    // The session remains usable
+    let response = send_request(
+        &mut armistice,
+        &mut session,
+        domain::ListRequest::default().into(),
+    );
+    assert!(response.domain_list().is_some());
+}
+
+#[test]
+fn session_required() {
+    let mut armistice = provisioned_armistice(1, &[&keypair(1)]);
+    let mut session = establish_session(&mut armistice);
+    let encrypted = encrypt_request(&mut session, domain::ListRequest::default().into());
+
+    // A device which has restarted has no session
+    let mut restarted = provisioned_armistice(1, &[&keypair(1)]);
+    assert_eq!(
+        restarted.handle_request(encrypted.into()),
+        Err(Error::Session)
+    );
+}
+
+#[test]
+fn tampered_request_rejected() {
+    let mut armistice = provisioned_armistice(1, &[&keypair(1)]);
+    let mut session = establish_session(&mut armistice);
+
+    let mut encrypted = encrypt_request(&mut session, domain::ListRequest::default().into());
+    encrypted.ciphertext[0] ^= 1;
+
+    assert_eq!(
+        armistice.handle_request(encrypted.into()),
+        Err(Error::Session)
+    );
+}
+
+#[test]
+fn replayed_request_rejected() {
+    let mut armistice = provisioned_armistice(1, &[&keypair(1)]);
+    let mut session = establish_session(&mut armistice);
+
+    let encrypted = encrypt_request(&mut session, domain::ListRequest::default().into());
+    let response = armistice.handle_request(encrypted.clone().into()).unwrap();
+    decrypt_response(&mut session, &response);
+
+    assert_eq!(
+        armistice.handle_request(encrypted.into()),
+        Err(Error::Session)
+    );
+}
+
+#[test]
+fn session_with_wrong_device_key_fails() {
+    let mut armistice = armistice();
+    let wrong_key = PublicKey::from(&session::StaticSecret::from([7u8; 32]));
+    let (_, request) = Initiator::new(&wrong_key, rng()).unwrap();
+
+    assert_eq!(
+        armistice.handle_request(request.into()),
+        Err(Error::Session)
+    );
+}
+
+#[test]
+fn concurrent_sessions() {
+    let mut armistice = provisioned_armistice(1, &[&keypair(1)]);
+    let mut sessions: Vec<Session> = (0..MAX_SESSIONS)
+        .map(|_| establish_session(&mut armistice))
+        .collect();
+
+    // Once all are established, new sessions replace the least recently
+    // used one, even if it was established more recently than others
+    for session in &mut sessions[1..] {
+        send_request(
+            &mut armistice,
+            session,
+            domain::ListRequest::default().into(),
+        );
+    }
+
+    sessions.push(establish_session(&mut armistice));
+
+    let encrypted = encrypt_request(&mut sessions[0], domain::ListRequest::default().into());
+    assert_eq!(
+        armistice.handle_request(encrypted.into()),
+        Err(Error::Session)
+    );
+
+    for session in &mut sessions[1..] {
+        let response = send_request(
+            &mut armistice,
+            session,
+            domain::ListRequest::default().into(),
+        );
+        assert!(response.domain_list().is_some());
+    }
+
+    // Establishing sessions repeatedly can't prevent others from being
+    // established
+    for _ in 0..MAX_SESSIONS * 2 {
+        establish_session(&mut armistice);
+    }
+
+    let mut session = establish_session(&mut armistice);
+    let response = send_request(
+        &mut armistice,
+        &mut session,
+        domain::ListRequest::default().into(),
+    );
+    assert!(response.domain_list().is_some());
+}
@@ -0,0 +1,478 @@
+//! Sealed state persistence and rollback protection integration test
+
+mod support;
+
+use aes::{block_cipher::NewBlockCipher, Aes128};
+use armistice_core::{
+    counter::{MemoryCounter, MonotonicCounter},
+    crypto::PublicKey,
+    storage::{MemoryStorage, Record, Storage, NUM_RECORDS},
+    Error, Vec,
+};
+use armistice_schema::{domain, key, policy, threshold, ThresholdKeySet};
+use core::convert::TryFrom;
+use ed25519_dalek::Keypair;
+use support::{
+    binding, caller_policy, domain_binding, keypair, provision_request, provisioned_armistice,
+    public_key, rng, root_encryption_key, round_trip, sign, sign_as_caller, sign_provision_request,
+    timestamp, Armistice, ArmisticeWith,
+};
+
+/// Domain ID used by these tests
+const DOMAIN_ID: domain::Id = 1;
+
+/// Storage which fails writes once a given number of them have succeeded
+#[derive(Clone, Debug)]
+struct FailingStorage {
+    /// Storage which successful writes are made to
+    storage: MemoryStorage,
+
+    /// Number of writes which succeed before they begin to fail
+    writes: usize,
+}
+
+impl Storage for FailingStorage {
+    fn read(&mut self, index: usize, record: &mut Record) -> Result<bool, Error> {
+        self.storage.read(index, record)
+    }
+
+    fn write(&mut self, index: usize, record: &Record) -> Result<(), Error> {
+        if self.writes == 0 {
+            return Err(Error::Storage);
+        }
+
+        self.writes -= 1;
+        self.storage.write(index, record)
+    }
+
+    fn erase(&mut self, index: usize) -> Result<(), Error> {
+        self.storage.erase(index)
+    }
+}
+
+/// Create a domain administered by the given key
+fn create_domain<S: Storage>(
+    armistice: &mut ArmisticeWith<S>,
+    root_key: &Keypair,
+    admin_key: &Keypair,
+) {
+    let mut public_keys = threshold::PublicKeys::new();
+    public_keys.push(public_key(admin_key)).unwrap();
+
+    let request = round_trip(&domain::CreateRequest {
+        config: domain::Config {
+            id: DOMAIN_ID,
+            admins: ThresholdKeySet {
+                threshold: 1,
+                public_keys,
+            },
+            policy: domain::Policy {
+                max_keys: 4,
+                challenge_lifetime: 0,
+            },
+        },
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+    armistice
+        .create_domain(&domain::SignedCreateRequest {
+            request,
+            signatures,
+            binding,
+        })
+        .unwrap();
+}
+
+/// Create a request to generate a key in the test domain, signed by the
+/// given administrator key
+fn generate_request<S: Storage>(
+    armistice: &ArmisticeWith<S>,
+    admin_key: &Keypair,
+) -> key::SignedGenerateRequest {
+    let request = round_trip(&key::GenerateRequest {
+        domain: DOMAIN_ID,
+        algorithm: key::Algorithm::Ed25519.into(),
+        timestamp: timestamp(),
+        policy: caller_policy(),
+        digest: None,
+    });
+
+    let binding = domain_binding(armistice, DOMAIN_ID);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[admin_key]);
+
+    key::SignedGenerateRequest {
+        request,
+        signatures,
+        binding,
+    }
+}
+
+/// Create a domain and generate a key within it, returning its public key
+fn populate(armistice: &mut Armistice, root_key: &Keypair, admin_key: &Keypair) -> PublicKey {
+    create_domain(armistice, root_key, admin_key);
+
+    let response = armistice
+        .generate_key(&generate_request(armistice, admin_key))
+        .unwrap();
+
+    PublicKey::try_from(&response.public_key).unwrap()
+}
+
+/// Create domains and fill them with keys carrying maximal policies until
+/// the device refuses a request, returning the error it was refused with
+fn fill_domains(armistice: &mut Armistice, root_key: &Keypair) -> Error {
+    let admin_keys = (2..=9).map(keypair).collect::<std::vec::Vec<_>>();
+
+    let mut key_policy = policy::Policy {
+        min_length: 1,
+        max_length: u64::MAX,
+        max_signatures: u64::MAX,
+        window: u64::MAX,
+        ..Default::default()
+    };
+
+    for seed in 0..4 {
+        let mut prefix = policy::Prefix::default();
+        prefix.bytes.extend_from_slice(&[seed; 32]).unwrap();
+        key_policy.prefixes.push(prefix).unwrap();
+        key_policy
+            .callers
+            .push(public_key(&keypair(seed + 10)))
+            .unwrap();
+    }
+
+    for id in 1..=8 {
+        let mut public_keys = threshold::PublicKeys::new();
+
+        for admin_key in &admin_keys {
+            public_keys.push(public_key(admin_key)).unwrap();
+        }
+
+        let request = round_trip(&domain::CreateRequest {
+            config: domain::Config {
+                id,
+                admins: ThresholdKeySet {
+                    threshold: 1,
+                    public_keys,
+                },
+                policy: domain::Policy {
+                    max_keys: 8,
+                    challenge_lifetime: 0,
+                },
+            },
+            timestamp: timestamp(),
+            digest: None,
+        });
+
+        let binding = binding(armistice);
+        let signatures = sign(&binding.digest(&request.digest.unwrap()), &[root_key]);
+
+        if let Err(e) = armistice.create_domain(&domain::SignedCreateRequest {
+            request,
+            signatures,
+            binding,
+        }) {
+            return e;
+        }
+
+        for _ in 0..8 {
+            let request = round_trip(&key::GenerateRequest {
+                domain: id,
+                algorithm: key::Algorithm::Ed25519.into(),
+                timestamp: timestamp(),
+                policy: key_policy.clone(),
+                digest: None,
+            });
+
+            let binding = domain_binding(armistice, id);
+            let signatures = sign(&binding.digest(&request.digest.unwrap()), &[&admin_keys[0]]);
+
+            if let Err(e) = armistice.generate_key(&key::SignedGenerateRequest {
+                request,
+                signatures,
+                binding,
+            }) {
+                return e;
+            }
+        }
+    }
+
+    panic!("every domain filled without refusing a request");
+}
+
+/// Restart a device with the test root key and the given storage and counter
+fn restart(storage: MemoryStorage, counter: MemoryCounter) -> Result<Armistice, Error> {
+    Armistice::new(root_encryption_key(), rng(), storage, counter)
+}
+
+/// Count the records presently written to the given storage
+fn count_records(storage: &MemoryStorage) -> usize {
+    (0..NUM_RECORDS)
+        .filter(|&i| storage.record(i).is_some())
+        .count()
+}
+
+#[test]
+fn state_survives_restart() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    let public_key = populate(&mut armistice, &root_key, &admin_key);
+
+    // Provisioning, domain creation, and key generation each persist state
+    assert_eq!(armistice.state_version(), 3);
+    assert_eq!(count_records(armistice.storage()), 1);
+
+    let mut restarted = restart(armistice.storage().clone(), armistice.counter().clone()).unwrap();
+
+    assert!(restarted.is_provisioned());
+    assert_eq!(restarted.state_version(), 3);
+    assert_eq!(
+        restarted.root_config().uuid(),
+        armistice.root_config().uuid()
+    );
+    assert_eq!(
+        restarted.root_config().key_set(),
+        armistice.root_config().key_set()
+    );
+
+    let domain = restarted.domains().get(DOMAIN_ID).unwrap();
+    assert_eq!(domain.keys().len(), 1);
+
+    let mut message = Vec::new();
+    message.extend_from_slice(b"example message").unwrap();
+
+    let response = sign_as_caller(
+        &mut restarted,
+        &key::SignRequest {
+            domain: DOMAIN_ID,
+            slot: 0,
+            payload: key::Payload::Message(message.clone()),
+        },
+    )
+    .unwrap();
+
+    public_key.verify(&message, &response.signature).unwrap();
+}
+
+#[test]
+fn unprovisioned_device_has_no_state() {
+    let armistice = restart(MemoryStorage::new(), MemoryCounter::new()).unwrap();
+
+    assert!(!armistice.is_provisioned());
+    assert_eq!(armistice.state_version(), 0);
+    assert_eq!(count_records(armistice.storage()), 0);
+}
+
+#[test]
+fn state_sealed_under_root_key() {
+    let root_key = keypair(1);
+    let armistice = provisioned_armistice(1, &[&root_key]);
+
+    assert_eq!(
+        Armistice::new(
+            Aes128::new(&[0x42; 16].into()),
+            rng(),
+            armistice.storage().clone(),
+            armistice.counter().clone(),
+        )
+        .err(),
+        Some(Error::Crypto)
+    );
+}
+
+#[test]
+fn tampered_state_rejected() {
+    let root_key = keypair(1);
+    let armistice = provisioned_armistice(1, &[&root_key]);
+
+    let mut storage = armistice.storage().clone();
+    let index = (armistice.state_version() as usize) % NUM_RECORDS;
+    storage.record_mut(index).unwrap()[64] ^= 1;
+
+    assert_eq!(
+        restart(storage, armistice.counter().clone()).err(),
+        Some(Error::Crypto)
+    );
+}
+
+#[test]
+fn write_interrupted_before_erase() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    let mut storage = armistice.storage().clone();
+    let counter = armistice.counter().clone();
+
+    create_domain(&mut armistice, &root_key, &admin_key);
+    assert_eq!(armistice.state_version(), 2);
+
+    // Simulate an interruption after writing the new state but before
+    // erasing the previous one or incrementing the counter: the newest
+    // state should be loaded and the counter brought up to date
+    let index = 2 % NUM_RECORDS;
+    let mut record = *armistice.storage().record(index).unwrap();
+    storage.write(index, &record).unwrap();
+    assert_eq!(count_records(&storage), 2);
+
+    let restarted = restart(storage.clone(), counter.clone()).unwrap();
+    assert_eq!(restarted.state_version(), 2);
+    assert!(restarted.domains().get(DOMAIN_ID).is_some());
+    assert_eq!(restarted.counter().clone().get(), Ok(2));
+
+    // If the new state was only partially written, the previous state
+    // should be loaded instead
+    record[64] ^= 1;
+    storage.write(index, &record).unwrap();
+
+    let restarted = restart(storage, counter).unwrap();
+    assert!(restarted.is_provisioned());
+    assert_eq!(restarted.state_version(), 1);
+    assert!(restarted.domains().is_empty());
+}
+
+#[test]
+fn failed_write_leaves_state_unchanged() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+
+    // Provisioning and domain creation succeed, but later writes fail
+    let storage = FailingStorage {
+        storage: MemoryStorage::new(),
+        writes: 2,
+    };
+
+    let mut armistice =
+        ArmisticeWith::new(root_encryption_key(), rng(), storage, MemoryCounter::new()).unwrap();
+
+    let request = provision_request(1, &[&root_key]);
+    let signed_request = sign_provision_request(&armistice, request, &[&root_key]);
+    armistice.provision(&signed_request).unwrap();
+    create_domain(&mut armistice, &root_key, &admin_key);
+
+    let request = generate_request(&armistice, &admin_key);
+    assert_eq!(armistice.generate_key(&request), Err(Error::Storage));
+
+    let domain = armistice.domains().get(DOMAIN_ID).unwrap();
+    assert_eq!(domain.keys().len(), 0);
+    assert_eq!(domain.authorization_counter(), 0);
+
+    let request = round_trip(&domain::DeleteRequest {
+        id: DOMAIN_ID,
+        timestamp: timestamp(),
+        digest: None,
+    });
+
+    let binding = binding(&armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), &[&root_key]);
+    assert_eq!(
+        armistice.delete_domain(&domain::SignedDeleteRequest {
+            request,
+            signatures,
+            binding,
+        }),
+        Err(Error::Storage)
+    );
+
+    assert!(armistice.domains().get(DOMAIN_ID).is_some());
+    assert_eq!(armistice.authorization_counter(), 2);
+    assert_eq!(armistice.state_version(), 2);
+}
+
+#[test]
+fn oversize_state_rejected() {
+    let root_key = keypair(1);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+
+    let error = fill_domains(&mut armistice, &root_key);
+    assert_eq!(error, Error::Capacity);
+
+    let domain_summary = |armistice: &Armistice| {
+        armistice
+            .domains()
+            .iter()
+            .map(|domain| {
+                (
+                    domain.id(),
+                    domain.keys().len(),
+                    domain.authorization_counter(),
+                )
+            })
+            .collect::<std::vec::Vec<_>>()
+    };
+
+    // The refused change was neither applied nor persisted
+    let restarted = restart(armistice.storage().clone(), armistice.counter().clone()).unwrap();
+    assert_eq!(restarted.state_version(), armistice.state_version());
+    assert_eq!(domain_summary(&restarted), domain_summary(&armistice));
+}
+
+#[test]
+fn rolled_back_state_rejected() {
+    let root_key = keypair(1);
+    let admin_key = keypair(2);
+    let mut armistice = provisioned_armistice(1, &[&root_key]);
+    let old_storage = armistice.storage().clone();
+
+    create_domain(&mut armistice, &root_key, &admin_key);
+
+    assert_eq!(
+        restart(old_storage, armistice.counter().clone()).err(),
+        Some(Error::Version)
+    );
+}
+
+#[test]
+fn erased_state_rejected() {
+    let root_key = keypair(1);
+    let armistice = provisioned_armistice(1, &[&root_key]);
+
+    // Erasing sealed state must not return the device to being unprovisioned
+    assert_eq!(
+        restart(MemoryStorage::new(), armistice.counter().clone()).err(),
+        Some(Error::Version)
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn file_counter_round_trip() {
+    use armistice_core::counter::FileCounter;
+
+    let path = std::env::temp_dir().join(format!("armistice-counter-{}", std::process::id()));
+    let mut counter = FileCounter::open(&path);
+    assert_eq!(counter.get(), Ok(0));
+    assert_eq!(counter.increment(), Ok(1));
+    assert_eq!(counter.increment(), Ok(2));
+    assert_eq!(FileCounter::open(&path).get(), Ok(2));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn file_storage_round_trip() {
+    use armistice_core::storage::FileStorage;
+
+    let dir = std::env::temp_dir().join(format!("armistice-storage-{}", std::process::id()));
+    let mut storage = FileStorage::open(&dir).unwrap();
+
+    let mut record = [0u8; armistice_core::storage::RECORD_SIZE];
+    assert!(!storage.read(0, &mut record).unwrap());
+
+    record[0] = 42;
+    storage.write(0, &record).unwrap();
+
+    let mut read_back = [0u8; armistice_core::storage::RECORD_SIZE];
+    assert!(storage.read(0, &mut read_back).unwrap());
+    assert_eq!(&record[..], &read_back[..]);
+
+    storage.erase(0).unwrap();
+    assert!(!storage.read(0, &mut read_back).unwrap());
+    assert_eq!(storage.write(NUM_RECORDS, &record), Err(Error::Storage));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,173 @@
+//! Shared helpers for Armistice Core integration tests
+
+#![allow(dead_code)]
+
+use aes::{block_cipher::NewBlockCipher, Aes128};
+use armistice_core::{counter::MemoryCounter, crypto, storage::MemoryStorage, Error, Storage, Vec};
+use armistice_schema::{
+    authorization::Binding, domain, key, policy, provision, public_key::PublicKey,
+    signature::Signatures, veriform::Decoder, Message, Signature, Timestamp,
+};
+use core::convert::TryFrom;
+use ed25519_dalek::{Keypair, SecretKey, Signer};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+/// Armistice instantiated with a software AES-128 root key, a
+/// deterministic RNG, and in-memory storage and monotonic counter
+pub type Armistice = ArmisticeWith<MemoryStorage>;
+
+/// Armistice instantiated like [`Armistice`], but with the given storage
+pub type ArmisticeWith<S> = armistice_core::Armistice<Aes128, ChaCha20Rng, S, MemoryCounter>;
+
+/// Create a new Armistice instance with a test root encryption key
+pub fn armistice() -> Armistice {
+    Armistice::new(
+        root_encryption_key(),
+        rng(),
+        MemoryStorage::new(),
+        MemoryCounter::new(),
+    )
+    .unwrap()
+}
+
+/// Create the test root encryption key
+pub fn root_encryption_key() -> Aes128 {
+    Aes128::new(
+        &[
+            0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad,
+            0xbe, 0xef,
+        ]
+        .into(),
+    )
+}
+
+/// Create a deterministic RNG for tests
+pub fn rng() -> ChaCha20Rng {
+    ChaCha20Rng::from_seed([0u8; 32])
+}
+
+/// Create an Ed25519 keypair from the given secret scalar seed
+pub fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = (&secret).into();
+    Keypair { secret, public }
+}
+
+/// Get the schema public key for the given keypair
+pub fn public_key(keypair: &Keypair) -> PublicKey {
+    PublicKey::Ed25519(keypair.public.to_bytes())
+}
+
+/// Create the keypair of the caller allowed to use keys governed by
+/// [`caller_policy`]
+pub fn caller() -> Keypair {
+    keypair(20)
+}
+
+/// Create a key policy which only allows [`caller`] to use the key
+pub fn caller_policy() -> policy::Policy {
+    let mut policy = policy::Policy::default();
+    policy.callers.push(public_key(&caller())).unwrap();
+    policy
+}
+
+/// Sign as described by the given request on behalf of [`caller`]
+pub fn sign_as_caller<S: Storage>(
+    armistice: &mut ArmisticeWith<S>,
+    request: &key::SignRequest,
+) -> Result<key::SignResponse, Error> {
+    let caller = crypto::PublicKey::try_from(&public_key(&caller())).unwrap();
+    armistice.sign_as(request, Some(&caller))
+}
+
+/// Example timestamp: TAI64N for 2020-05-21
+pub fn timestamp() -> Timestamp {
+    Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap()
+}
+
+/// Round trip a message through the encoder and decoder, which causes
+/// `veriform` to compute its digest (as happens when received over the wire)
+pub fn round_trip<M: Message>(message: &M) -> M {
+    let mut buffer = [0u8; 1024];
+    let encoded = message.encode(&mut buffer).unwrap();
+    M::decode(&mut Decoder::new(), encoded).unwrap()
+}
+
+/// Sign the given digest with the given keypairs
+pub fn sign(digest: &[u8], signers: &[&Keypair]) -> Signatures {
+    let mut signatures = Signatures::new();
+
+    for signer in signers {
+        signatures
+            .push(Signature::Ed25519(signer.sign(digest).to_bytes()))
+            .unwrap();
+    }
+
+    signatures
+}
+
+/// Bind a request to the given device, with the authorization counter the
+/// next request its root keys authorize must have
+pub fn binding<S: Storage>(armistice: &ArmisticeWith<S>) -> Binding {
+    Binding {
+        device_id: *armistice.device_id(),
+        counter: armistice.authorization_counter() + 1,
+    }
+}
+
+/// Bind a request to the given device, with the authorization counter the
+/// next request authorized for the given domain must have
+pub fn domain_binding<S: Storage>(armistice: &ArmisticeWith<S>, domain: domain::Id) -> Binding {
+    Binding {
+        device_id: *armistice.device_id(),
+        counter: armistice
+            .domains()
+            .get(domain)
+            .map(|domain| domain.authorization_counter())
+            .unwrap_or_default()
+            + 1,
+    }
+}
+
+/// Create a provisioning request for the given root keys, round tripping it
+/// through the encoder so `veriform` computes its digest
+pub fn provision_request(threshold: u64, root_keypairs: &[&Keypair]) -> provision::Request {
+    let mut root_keys = Vec::new();
+
+    for keypair in root_keypairs {
+        root_keys.push(public_key(keypair)).unwrap();
+    }
+
+    round_trip(&provision::Request {
+        root_key_threshold: threshold,
+        root_keys,
+        timestamp: timestamp(),
+        digest: None,
+    })
+}
+
+/// Sign a provisioning request for the given device with the given keypairs
+pub fn sign_provision_request<S: Storage>(
+    armistice: &ArmisticeWith<S>,
+    request: provision::Request,
+    signers: &[&Keypair],
+) -> provision::SignedRequest {
+    let binding = binding(armistice);
+    let signatures = sign(&binding.digest(&request.digest.unwrap()), signers);
+
+    provision::SignedRequest {
+        request,
+        signatures,
+        binding,
+    }
+}
+
+/// Create an Armistice instance provisioned with the given root keys, with
+/// the request signed by all of them
+pub fn provisioned_armistice(threshold: u64, root_keypairs: &[&Keypair]) -> Armistice {
+    let mut armistice = armistice();
+    let request = provision_request(threshold, root_keypairs);
+    let signed_request = sign_provision_request(&armistice, request, root_keypairs);
+    armistice.handle_request(signed_request.into()).unwrap();
+    armistice
+}
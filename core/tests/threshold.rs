@@ -0,0 +1,162 @@
+//! Threshold key set tests
+
+use armistice_core::{crypto::PublicKey, threshold::ThresholdKeySet, Error};
+use armistice_schema::{self as schema, signature::Signatures, Signature};
+use core::convert::TryFrom;
+use ed25519_dalek::{Keypair, SecretKey, Signer};
+
+/// Example message digest
+const DIGEST: &[u8] = &[0x42; 32];
+
+/// Create an Ed25519 keypair from the given secret scalar seed
+fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = (&secret).into();
+    Keypair { secret, public }
+}
+
+/// Get the Armistice public key for the given keypair
+fn public_key(keypair: &Keypair) -> PublicKey {
+    PublicKey::Ed25519(keypair.public.to_bytes())
+}
+
+/// Sign the example digest with the given keypairs
+fn sign(signers: &[&Keypair]) -> Signatures {
+    let mut signatures = Signatures::new();
+
+    for signer in signers {
+        signatures
+            .push(Signature::Ed25519(signer.sign(DIGEST).to_bytes()))
+            .unwrap();
+    }
+
+    signatures
+}
+
+#[test]
+fn threshold_verification() {
+    let (key_1, key_2, key_3) = (keypair(1), keypair(2), keypair(3));
+    let key_set = ThresholdKeySet::new(
+        2,
+        vec![public_key(&key_1), public_key(&key_2), public_key(&key_3)],
+    )
+    .unwrap();
+
+    assert_eq!(key_set.verify(DIGEST, &sign(&[&key_1, &key_3])), Ok(()));
+    assert_eq!(key_set.count_signers(DIGEST, &sign(&[&key_1, &key_3])), 2);
+    assert_eq!(
+        key_set.verify(DIGEST, &sign(&[&key_2])),
+        Err(Error::Unauthorized)
+    );
+    assert_eq!(
+        key_set.verify(&[0x43; 32], &sign(&[&key_1, &key_2])),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn repeated_signatures_count_once() {
+    let (key_1, key_2) = (keypair(1), keypair(2));
+    let key_set = ThresholdKeySet::new(2, vec![public_key(&key_1), public_key(&key_2)]).unwrap();
+
+    assert_eq!(
+        key_set.verify(DIGEST, &sign(&[&key_1, &key_1, &key_1])),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn non_member_signatures_ignored() {
+    let (key_1, key_2, outsider) = (keypair(1), keypair(2), keypair(3));
+    let key_set = ThresholdKeySet::new(2, vec![public_key(&key_1), public_key(&key_2)]).unwrap();
+
+    assert!(!key_set.contains(&public_key(&outsider)));
+    assert_eq!(
+        key_set.verify(DIGEST, &sign(&[&key_1, &outsider])),
+        Err(Error::Unauthorized)
+    );
+}
+
+#[test]
+fn duplicate_keys_rejected() {
+    let (key, other) = (keypair(1), keypair(2));
+
+    assert_eq!(
+        ThresholdKeySet::new(1, vec![public_key(&key), public_key(&key)]),
+        Err(Error::Threshold)
+    );
+    assert_eq!(
+        ThresholdKeySet::new(
+            1,
+            vec![public_key(&key), public_key(&other), public_key(&key)]
+        ),
+        Err(Error::Threshold)
+    );
+    assert_eq!(
+        ThresholdKeySet::new(2, vec![public_key(&key), public_key(&key)]),
+        Err(Error::Threshold)
+    );
+}
+
+#[test]
+fn invalid_thresholds() {
+    let key = public_key(&keypair(1));
+
+    assert_eq!(ThresholdKeySet::new(0, vec![key]), Err(Error::Threshold));
+    assert_eq!(ThresholdKeySet::new(2, vec![key]), Err(Error::Threshold));
+    assert_eq!(ThresholdKeySet::new(1, vec![]), Err(Error::Threshold));
+
+    let too_many_keys = (1..=9).map(|seed| public_key(&keypair(seed)));
+    assert_eq!(
+        ThresholdKeySet::new(1, too_many_keys),
+        Err(Error::Threshold)
+    );
+}
+
+#[test]
+fn empty_key_set_authorizes_nothing() {
+    let key_set = ThresholdKeySet::default();
+    assert!(key_set.is_empty());
+    assert_eq!(key_set.verify(DIGEST, &[]), Err(Error::Unauthorized));
+}
+
+#[test]
+fn from_schema() {
+    let (key_1, key_2) = (keypair(1), keypair(2));
+
+    let mut public_keys = schema::threshold::PublicKeys::new();
+    for key in &[&key_1, &key_2] {
+        public_keys
+            .push(schema::PublicKey::Ed25519(key.public.to_bytes()))
+            .unwrap();
+    }
+
+    let key_set = ThresholdKeySet::try_from(&schema::ThresholdKeySet {
+        threshold: 2,
+        public_keys,
+    })
+    .unwrap();
+
+    assert_eq!(key_set.threshold(), 2);
+    assert_eq!(
+        key_set.public_keys(),
+        &[public_key(&key_1), public_key(&key_2)]
+    );
+}
+
+#[test]
+#[cfg(feature = "ecdsa")]
+fn from_schema_rejects_invalid_keys() {
+    let mut public_keys = schema::threshold::PublicKeys::new();
+    public_keys
+        .push(schema::PublicKey::EcdsaP256([0u8; 33]))
+        .unwrap();
+
+    assert_eq!(
+        ThresholdKeySet::try_from(&schema::ThresholdKeySet {
+            threshold: 1,
+            public_keys,
+        }),
+        Err(Error::Crypto)
+    );
+}
@@ -0,0 +1,299 @@
+//! Approval queue: operations which collect approvals from a domain's
+//! administrators over multiple requests
+//!
+//! Rather than gathering a threshold of signatures offline before sending a
+//! request, an operation may be submitted to the device's queue of pending
+//! operations with a [`SubmitRequest`]. The device assigns it an [`Id`] and a
+//! random nonce, and the domain's administrators then approve it one at a
+//! time by sending [`ApproveRequest`]s containing their signature over
+//! [`approval_digest`]. Approvers need not be online at the same time: they
+//! may use [`ListRequest`] to discover pending operations whenever they
+//! connect.
+//!
+//! The operation is performed as soon as a threshold of the domain's
+//! administrators have approved it, in which case the response to the final
+//! approval is the operation's own response (e.g. [`Response::Sign`]).
+//! Any single administrator may instead withdraw a pending operation with a
+//! [`CancelRequest`] signed over [`cancellation_digest`].
+//!
+//! [`Response::Sign`]: crate::Response::Sign
+
+use crate::{domain, key, Signature, Timestamp};
+use heapless::{consts::U4, Vec};
+use sha2::{Digest, Sha256};
+use veriform::{Message, Sha256Digest};
+
+/// Domain separation string used when computing approval digests
+const APPROVAL_DOMAIN: &[u8] = b"armistice.approval";
+
+/// Domain separation string used when computing cancellation digests
+const CANCELLATION_DOMAIN: &[u8] = b"armistice.cancellation";
+
+/// Identifiers assigned to pending operations
+pub type Id = u64;
+
+/// Random nonce assigned to a pending operation
+pub type Nonce = [u8; 32];
+
+/// Maximum number of operations which can be pending at once
+pub type MaxPending = U4;
+
+/// Summaries of pending operations
+pub type PendingOperations = Vec<Pending, MaxPending>;
+
+/// Operations which can be submitted to the approval queue
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Operation {
+    /// Sign a message
+    #[field(tag = 0, wire_type = "message")]
+    Sign(key::SignRequest),
+
+    /// Generate a new key within a domain
+    #[field(tag = 1, wire_type = "message")]
+    GenerateKey(key::GenerateRequest),
+
+    /// Export a key wrapped under the root key
+    #[field(tag = 2, wire_type = "message")]
+    ExportKey(key::ExportRequest),
+
+    /// Import a previously exported key
+    #[field(tag = 3, wire_type = "message")]
+    ImportKey(key::ImportRequest),
+}
+
+impl Operation {
+    /// Get the domain this operation is performed in
+    pub fn domain(&self) -> domain::Id {
+        match self {
+            Operation::Sign(request) => request.domain,
+            Operation::GenerateKey(request) => request.domain,
+            Operation::ExportKey(request) => request.domain,
+            Operation::ImportKey(request) => request.domain,
+        }
+    }
+}
+
+/// Request to add an operation to the approval queue
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SubmitRequest {
+    /// Operation to be performed once approved
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub operation: Operation,
+
+    /// Date/time when the operation is submitted
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (covered by approvals along with the nonce)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Response to an operation being added to the approval queue
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SubmitResponse {
+    /// Identifier assigned to the pending operation
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Random nonce which approvals must cover
+    #[field(tag = 1, wire_type = "bytes", critical = true, size = 32)]
+    pub nonce: Nonce,
+
+    /// Number of approvals required to perform the operation
+    #[field(tag = 2, wire_type = "uint64", critical = true)]
+    pub threshold: u64,
+}
+
+/// Request to approve a pending operation
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ApproveRequest {
+    /// Identifier of the pending operation
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Signature by one of the domain's administrators over the operation's
+    /// [`approval_digest`]
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub signature: Signature,
+}
+
+/// Response to an approval which leaves the operation pending
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ApproveResponse {
+    /// Identifier of the pending operation
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Number of approvals collected so far
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub approvals: u64,
+
+    /// Number of approvals required to perform the operation
+    #[field(tag = 2, wire_type = "uint64", critical = true)]
+    pub threshold: u64,
+}
+
+/// Request to list pending operations
+#[derive(Message, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ListRequest {}
+
+/// Response containing summaries of all pending operations
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ListResponse {
+    /// Pending operations (oldest first)
+    #[field(tag = 0, wire_type = "sequence", critical = true, max = 4)]
+    pub pending: PendingOperations,
+}
+
+/// Summary of a pending operation.
+///
+/// The operation itself is not included: approvers are expected to obtain
+/// the submission request from its submitter and check its digest matches
+/// before approving it.
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Pending {
+    /// Identifier of the pending operation
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Domain the operation is performed in
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Digest of the operation's submission request
+    #[field(tag = 2, wire_type = "bytes", critical = true, size = 32)]
+    pub digest: Sha256Digest,
+
+    /// Random nonce which approvals must cover
+    #[field(tag = 3, wire_type = "bytes", critical = true, size = 32)]
+    pub nonce: Nonce,
+
+    /// Number of approvals collected so far
+    #[field(tag = 4, wire_type = "uint64", critical = true)]
+    pub approvals: u64,
+
+    /// Number of approvals required to perform the operation
+    #[field(tag = 5, wire_type = "uint64", critical = true)]
+    pub threshold: u64,
+}
+
+impl Pending {
+    /// Compute the digest administrators sign to approve this operation
+    pub fn approval_digest(&self) -> Sha256Digest {
+        approval_digest(&self.nonce, &self.digest)
+    }
+
+    /// Compute the digest an administrator signs to withdraw this operation
+    pub fn cancellation_digest(&self) -> Sha256Digest {
+        cancellation_digest(&self.nonce, &self.digest)
+    }
+}
+
+/// Request to withdraw a pending operation
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct CancelRequest {
+    /// Identifier of the pending operation
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Signature by one of the domain's administrators over the operation's
+    /// [`cancellation_digest`]
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub signature: Signature,
+}
+
+/// Response to a pending operation being withdrawn
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CancelResponse {
+    /// Identifier of the withdrawn operation
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+}
+
+/// Compute the digest administrators sign to approve the pending operation
+/// with the given nonce and submission request digest
+pub fn approval_digest(nonce: &Nonce, request_digest: &Sha256Digest) -> Sha256Digest {
+    digest(APPROVAL_DOMAIN, nonce, request_digest)
+}
+
+/// Compute the digest an administrator signs to withdraw the pending
+/// operation with the given nonce and submission request digest
+pub fn cancellation_digest(nonce: &Nonce, request_digest: &Sha256Digest) -> Sha256Digest {
+    digest(CANCELLATION_DOMAIN, nonce, request_digest)
+}
+
+/// Compute a domain-separated digest of a pending operation
+fn digest(domain: &[u8], nonce: &Nonce, request_digest: &Sha256Digest) -> Sha256Digest {
+    let mut hasher = Sha256::new();
+    hasher.input(domain);
+    hasher.input(nonce);
+    hasher.input(request_digest);
+
+    let mut digest = Sha256Digest::default();
+    digest.copy_from_slice(&hasher.result());
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{approval_digest, cancellation_digest, ApproveRequest, Operation, SubmitRequest};
+    use crate::{key, Signature, Timestamp};
+    use heapless::{consts::U256, Vec};
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn submit_request_round_trip() {
+        let request = SubmitRequest {
+            operation: Operation::Sign(key::SignRequest {
+                domain: 1,
+                slot: 2,
+                payload: key::Payload::Sha256([3; 32]),
+            }),
+            timestamp: Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208])
+                .unwrap(),
+            digest: None,
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = SubmitRequest::decode(&mut decoder, &buffer).unwrap();
+        assert_eq!(request.operation, decoded.operation);
+        assert_eq!(decoded.operation.domain(), 1);
+        assert!(decoded.digest.is_some());
+    }
+
+    #[test]
+    fn approve_request_round_trip() {
+        let request = ApproveRequest {
+            id: 7,
+            signature: Signature::Ed25519([4; 64]),
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            request,
+            ApproveRequest::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn approval_and_cancellation_digests_differ() {
+        let approval = approval_digest(&[1; 32], &[2; 32]);
+        let cancellation = cancellation_digest(&[1; 32], &[2; 32]);
+
+        assert_ne!(approval, cancellation);
+        assert_ne!(approval, approval_digest(&[3; 32], &[2; 32]));
+        assert_ne!(approval, approval_digest(&[1; 32], &[3; 32]));
+    }
+}
@@ -0,0 +1,106 @@
+//! Authorization: binds signed requests to the device they're intended for
+//! and to a point in that device's sequence of authorized requests, so they
+//! can't be replayed (either to another device, or to the same device later)
+//!
+//! Requests which require signatures from a threshold of keys (e.g. root keys
+//! or domain administrators) are sent along with a [`Binding`]. Rather than
+//! the request digest alone, signers sign [`Binding::digest`], which commits
+//! to the request digest, the target device's ID, and an authorization
+//! counter. Devices only accept counters greater than that of every request
+//! they've previously authorized.
+
+use sha2::{Digest, Sha256};
+use veriform::{Message, Sha256Digest};
+
+/// Domain separation string used when computing authorization digests
+const AUTHORIZATION_DOMAIN: &[u8] = b"armistice.authorization";
+
+/// Device-unique identifier (see [`info::Response::device_id`])
+///
+/// [`info::Response::device_id`]: crate::info::Response::device_id
+pub type DeviceId = [u8; 16];
+
+/// Binding of a signed request to a device and authorization counter
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Binding {
+    /// ID of the device the request is intended for
+    #[field(tag = 0, wire_type = "bytes", critical = true, size = 16)]
+    pub device_id: DeviceId,
+
+    /// Authorization counter: must be greater than that of every request
+    /// the device has previously authorized
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub counter: u64,
+}
+
+impl Binding {
+    /// Compute the digest to be signed to authorize the request with the
+    /// given digest under this binding
+    pub fn digest(&self, request_digest: &Sha256Digest) -> Sha256Digest {
+        let mut hasher = Sha256::new();
+        hasher.input(AUTHORIZATION_DOMAIN);
+        hasher.input(self.device_id);
+        hasher.input(self.counter.to_be_bytes());
+        hasher.input(request_digest);
+
+        let mut digest = Sha256Digest::default();
+        digest.copy_from_slice(&hasher.result());
+        digest
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::Binding;
+    use heapless::{consts::U64, Vec};
+    use veriform::{Decoder, Message};
+
+    /// Create an example `authorization::Binding`
+    pub(crate) fn example_binding() -> Binding {
+        Binding {
+            device_id: [7; 16],
+            counter: 1,
+        }
+    }
+
+    #[test]
+    fn binding_round_trip() {
+        let binding = example_binding();
+
+        let mut buffer: Vec<u8, U64> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 64]).unwrap();
+        binding.encode(&mut buffer).unwrap();
+        buffer.truncate(binding.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(binding, Binding::decode(&mut decoder, &buffer).unwrap());
+    }
+
+    #[test]
+    fn digest_binds_device_and_counter() {
+        let binding = example_binding();
+        let digest = binding.digest(&[1; 32]);
+
+        assert_eq!(
+            digest,
+            [
+                0x16, 0x12, 0x42, 0xca, 0x04, 0x14, 0x4c, 0x0f, 0xaa, 0xc6, 0xac, 0x43, 0x7d, 0x98,
+                0x8d, 0xf2, 0x76, 0x90, 0xa1, 0xd6, 0x52, 0x46, 0x2c, 0x1b, 0xe2, 0xa2, 0x2d, 0xe6,
+                0x36, 0x29, 0xfd, 0x1e,
+            ]
+        );
+
+        let other_device = Binding {
+            device_id: [8; 16],
+            ..binding
+        };
+        assert_ne!(other_device.digest(&[1; 32]), digest);
+
+        let later = Binding {
+            counter: 2,
+            ..binding
+        };
+        assert_ne!(later.digest(&[1; 32]), digest);
+        assert_ne!(binding.digest(&[2; 32]), digest);
+    }
+}
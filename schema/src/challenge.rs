@@ -0,0 +1,121 @@
+//! Challenges: device-issued nonces which operators sign to approve a
+//! pending operation
+//!
+//! Operations in domains whose policy requires challenges (see
+//! [`domain::Policy::challenge_lifetime`]) must be approved by signing a
+//! fresh challenge rather than a free-standing message. The request to be
+//! performed is first sent to the device as a [`Request`] containing its
+//! digest, and the device returns a random nonce bound to it. Signers then
+//! sign [`digest`] over the nonce and the request's authorization digest
+//! (see [`Binding::digest`]).
+//!
+//! Challenges are single use, and expire once the device has received
+//! [`Response::expires_in`] further requests.
+//!
+//! [`domain::Policy::challenge_lifetime`]: crate::domain::Policy::challenge_lifetime
+//! [`Binding::digest`]: crate::authorization::Binding::digest
+
+use crate::domain;
+use sha2::{Digest, Sha256};
+use veriform::{Message, Sha256Digest};
+
+/// Domain separation string used when computing challenge digests
+const CHALLENGE_DOMAIN: &[u8] = b"armistice.challenge";
+
+/// Random nonce issued by the device
+pub type Nonce = [u8; 32];
+
+/// Request for a challenge bound to a pending operation
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Request {
+    /// Domain the operation is performed in
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Digest of the pending operation's request
+    #[field(tag = 1, wire_type = "bytes", critical = true, size = 32)]
+    pub operation: Sha256Digest,
+}
+
+/// Challenge issued by the device
+#[derive(Message, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    /// Random nonce to be signed along with the operation
+    #[field(tag = 0, wire_type = "bytes", critical = true, size = 32)]
+    pub nonce: Nonce,
+
+    /// Number of further requests the device can receive before the
+    /// challenge expires
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub expires_in: u64,
+}
+
+impl Response {
+    /// Compute the digest to be signed to approve the operation with the
+    /// given authorization digest in response to this challenge
+    pub fn digest(&self, authorization_digest: &Sha256Digest) -> Sha256Digest {
+        digest(&self.nonce, authorization_digest)
+    }
+}
+
+/// Compute the digest to be signed to approve the operation with the given
+/// authorization digest in response to the challenge with the given nonce
+pub fn digest(nonce: &Nonce, authorization_digest: &Sha256Digest) -> Sha256Digest {
+    let mut hasher = Sha256::new();
+    hasher.input(CHALLENGE_DOMAIN);
+    hasher.input(nonce);
+    hasher.input(authorization_digest);
+
+    let mut digest = Sha256Digest::default();
+    digest.copy_from_slice(&hasher.result());
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Request, Response};
+    use heapless::{consts::U128, Vec};
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn request_round_trip() {
+        let request = Request {
+            domain: 42,
+            operation: [1; 32],
+        };
+
+        let mut buffer: Vec<u8, U128> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 128]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(request, Request::decode(&mut decoder, &buffer).unwrap());
+    }
+
+    #[test]
+    fn digest_binds_nonce_and_operation() {
+        let challenge = Response {
+            nonce: [3; 32],
+            expires_in: 16,
+        };
+        let digest = challenge.digest(&[1; 32]);
+
+        assert_eq!(
+            digest,
+            [
+                0xd5, 0x50, 0x0f, 0xd9, 0x41, 0xde, 0xd9, 0x0e, 0xee, 0x1e, 0x1e, 0x5f, 0xc5, 0xe6,
+                0x2b, 0x15, 0xc6, 0x6b, 0x3f, 0x5c, 0xde, 0xe6, 0x5a, 0x9d, 0x06, 0x65, 0x05, 0x57,
+                0x6d, 0x49, 0xe0, 0xc5,
+            ]
+        );
+
+        let other_nonce = Response {
+            nonce: [4; 32],
+            ..challenge
+        };
+        assert_ne!(other_nonce.digest(&[1; 32]), digest);
+        assert_ne!(challenge.digest(&[2; 32]), digest);
+        assert_eq!(super::digest(&[3; 32], &[1; 32]), digest);
+    }
+}
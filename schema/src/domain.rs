@@ -0,0 +1,294 @@
+//! Domain messages: manage namespaced containers for keys
+//!
+//! Each domain has its own set of administrators (a threshold key set) and a
+//! policy which constrains the keys it contains. Creating and deleting
+//! domains requires the approval of a threshold of the root keys, whereas
+//! domains are updated by a threshold of their own administrators.
+
+use crate::{authorization::Binding, signature::Signatures, threshold::ThresholdKeySet, Timestamp};
+use heapless::{consts::U8, Vec};
+use veriform::{Message, Sha256Digest};
+
+/// Domain identifiers
+pub type Id = u64;
+
+/// Domain configurations
+pub type Configs = Vec<Config, U8>;
+
+/// Domain configuration
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// Domain identifier
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Threshold key set which administers this domain
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub admins: ThresholdKeySet,
+
+    /// Policy for keys within this domain
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub policy: Policy,
+}
+
+/// Domain policy: constraints on the keys within a domain
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Policy {
+    /// Maximum number of key slots available in this domain
+    #[field(tag = 0, wire_type = "uint64", critical = true, max = 8)]
+    pub max_keys: u64,
+
+    /// Number of requests the device can receive before a challenge issued
+    /// for an operation in this domain expires, or zero if operations don't
+    /// require challenges (see [`challenge`])
+    ///
+    /// [`challenge`]: crate::challenge
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub challenge_lifetime: u64,
+}
+
+/// Request to create a new domain (signed by the root keys)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct CreateRequest {
+    /// Configuration of the domain to create
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub config: Config,
+
+    /// Date/time when the domain is created (agreed upon by all signers)
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (to be signed by the root keys)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Domain creation request along with root key signatures over its digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedCreateRequest {
+    /// Domain creation request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: CreateRequest,
+
+    /// Signatures over the creation request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to a domain being created
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct CreateResponse {
+    /// Identifier of the newly created domain
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+}
+
+/// Request to update an existing domain's administrators and/or policy
+/// (signed by the domain's current administrators)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct UpdateRequest {
+    /// New configuration for the domain (identified by its `id`)
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub config: Config,
+
+    /// Date/time when the domain is updated (agreed upon by all signers)
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (to be signed by the domain administrators)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Domain update request along with administrator signatures over its digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedUpdateRequest {
+    /// Domain update request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: UpdateRequest,
+
+    /// Signatures over the update request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to a domain being updated
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct UpdateResponse {
+    /// Identifier of the updated domain
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+}
+
+/// Request to delete a domain along with any keys it contains
+/// (signed by the root keys)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct DeleteRequest {
+    /// Identifier of the domain to delete
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+
+    /// Date/time when the domain is deleted (agreed upon by all signers)
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (to be signed by the root keys)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Domain deletion request along with root key signatures over its digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedDeleteRequest {
+    /// Domain deletion request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: DeleteRequest,
+
+    /// Signatures over the deletion request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to a domain being deleted
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct DeleteResponse {
+    /// Identifier of the deleted domain
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub id: Id,
+}
+
+/// Request to list all domains
+#[derive(Message, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ListRequest {}
+
+/// Response containing the configurations of all domains
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ListResponse {
+    /// Configurations of all domains on the device
+    #[field(tag = 0, wire_type = "sequence", critical = true, max = 8)]
+    pub domains: Configs,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{
+        Config, CreateRequest, DeleteRequest, ListResponse, Policy, SignedCreateRequest,
+        SignedDeleteRequest,
+    };
+    use crate::{authorization::tests::example_binding, threshold, Signature, Timestamp};
+    use heapless::{consts::U512, Vec};
+    use veriform::{Decoder, Message};
+
+    /// Create an example domain `Config`
+    pub(crate) fn example_config() -> Config {
+        Config {
+            id: 42,
+            admins: threshold::tests::example_key_set(),
+            policy: Policy {
+                max_keys: 4,
+                challenge_lifetime: 16,
+            },
+        }
+    }
+
+    /// Create an example `domain::SignedCreateRequest`
+    pub(crate) fn example_signed_create_request() -> SignedCreateRequest {
+        let mut signatures = Vec::new();
+        signatures.push(Signature::Ed25519([1u8; 64])).unwrap();
+
+        // TAI64N for 2020-05-21
+        let timestamp =
+            Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap();
+
+        SignedCreateRequest {
+            request: CreateRequest {
+                config: example_config(),
+                timestamp,
+                digest: None,
+            },
+            signatures,
+            binding: example_binding(),
+        }
+    }
+
+    #[test]
+    fn signed_create_request_round_trip() {
+        let signed_request = example_signed_create_request();
+
+        let mut buffer: Vec<u8, U512> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 512]).unwrap();
+        signed_request.encode(&mut buffer).unwrap();
+        buffer.truncate(signed_request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = SignedCreateRequest::decode(&mut decoder, &buffer).unwrap();
+
+        assert_eq!(signed_request.request.config, decoded.request.config);
+        assert_eq!(signed_request.signatures, decoded.signatures);
+        assert!(decoded.request.digest.is_some());
+    }
+
+    #[test]
+    fn signed_delete_request_round_trip() {
+        let mut signatures = Vec::new();
+        signatures.push(Signature::Ed25519([2u8; 64])).unwrap();
+
+        let signed_request = SignedDeleteRequest {
+            request: DeleteRequest {
+                id: 42,
+                timestamp: example_signed_create_request().request.timestamp,
+                digest: None,
+            },
+            signatures,
+            binding: example_binding(),
+        };
+
+        let mut buffer: Vec<u8, U512> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 512]).unwrap();
+        signed_request.encode(&mut buffer).unwrap();
+        buffer.truncate(signed_request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = SignedDeleteRequest::decode(&mut decoder, &buffer).unwrap();
+
+        assert_eq!(signed_request.request.id, decoded.request.id);
+        assert_eq!(signed_request.signatures, decoded.signatures);
+        assert!(decoded.request.digest.is_some());
+    }
+
+    #[test]
+    fn list_response_round_trip() {
+        let mut domains = Vec::new();
+        domains.push(example_config()).unwrap();
+        let response = ListResponse { domains };
+
+        let mut buffer: Vec<u8, U512> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 512]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            response,
+            ListResponse::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Error messages: describe why a request failed
+
+use core::{fmt, str};
+use heapless::{consts::U128, Vec};
+use veriform::Message;
+
+/// Maximum size of the detail message in an error response
+pub type MaxDetailSize = U128;
+
+/// Detail message bytes (UTF-8)
+pub type DetailBytes = Vec<u8, MaxDetailSize>;
+
+/// Error codes
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Code {
+    /// Internal error (or an error code unknown to this version)
+    Internal,
+
+    /// Request could not be decoded
+    Decode,
+
+    /// Response could not be encoded
+    Encode,
+
+    /// Device has not yet been provisioned
+    Unprovisioned,
+
+    /// Device has already been provisioned
+    Provisioned,
+
+    /// Threshold is invalid
+    Threshold,
+
+    /// Cryptographic error (e.g. unsupported algorithm or invalid key)
+    Crypto,
+
+    /// Insufficient valid signatures
+    Unauthorized,
+
+    /// Capacity exceeded
+    Capacity,
+
+    /// Duplicate entry
+    Duplicate,
+
+    /// Entry not found
+    NotFound,
+
+    /// Storage error
+    Storage,
+
+    /// Version is invalid
+    Version,
+
+    /// Request must be sent within an encrypted session (or the session is
+    /// invalid)
+    Session,
+
+    /// Request is stale, replayed, or bound to a different device
+    Replay,
+
+    /// Operation requires a challenge which is missing or has expired
+    Challenge,
+
+    /// Operation denied by the key's policy (the detail message gives the
+    /// reason)
+    Policy,
+}
+
+impl Code {
+    /// Get the code with the given wire identifier, if it's a known one
+    pub fn from_u64(code: u64) -> Option<Self> {
+        match code {
+            0 => Some(Code::Internal),
+            1 => Some(Code::Decode),
+            2 => Some(Code::Encode),
+            3 => Some(Code::Unprovisioned),
+            4 => Some(Code::Provisioned),
+            5 => Some(Code::Threshold),
+            6 => Some(Code::Crypto),
+            7 => Some(Code::Unauthorized),
+            8 => Some(Code::Capacity),
+            9 => Some(Code::Duplicate),
+            10 => Some(Code::NotFound),
+            11 => Some(Code::Storage),
+            12 => Some(Code::Version),
+            13 => Some(Code::Session),
+            14 => Some(Code::Replay),
+            15 => Some(Code::Challenge),
+            16 => Some(Code::Policy),
+            _ => None,
+        }
+    }
+}
+
+impl From<Code> for u64 {
+    fn from(code: Code) -> u64 {
+        match code {
+            Code::Internal => 0,
+            Code::Decode => 1,
+            Code::Encode => 2,
+            Code::Unprovisioned => 3,
+            Code::Provisioned => 4,
+            Code::Threshold => 5,
+            Code::Crypto => 6,
+            Code::Unauthorized => 7,
+            Code::Capacity => 8,
+            Code::Duplicate => 9,
+            Code::NotFound => 10,
+            Code::Storage => 11,
+            Code::Version => 12,
+            Code::Session => 13,
+            Code::Replay => 14,
+            Code::Challenge => 15,
+            Code::Policy => 16,
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Code::Internal => "internal error",
+            Code::Decode => "request could not be decoded",
+            Code::Encode => "response could not be encoded",
+            Code::Unprovisioned => "device not provisioned",
+            Code::Provisioned => "device already provisioned",
+            Code::Threshold => "invalid threshold",
+            Code::Crypto => "cryptographic error",
+            Code::Unauthorized => "insufficient valid signatures",
+            Code::Capacity => "capacity exceeded",
+            Code::Duplicate => "duplicate entry",
+            Code::NotFound => "not found",
+            Code::Storage => "storage error",
+            Code::Version => "invalid version",
+            Code::Session => "encrypted session required",
+            Code::Replay => "stale or replayed request",
+            Code::Challenge => "missing or expired challenge",
+            Code::Policy => "denied by key policy",
+        })
+    }
+}
+
+/// Response indicating a request failed
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    /// Error code (see [`Code`])
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub code: u64,
+
+    /// Detail message describing the error (UTF-8; empty if none)
+    #[field(tag = 1, wire_type = "bytes", max = 128)]
+    pub detail: DetailBytes,
+}
+
+impl Response {
+    /// Create a new error response with the given detail message, which is
+    /// truncated (at a character boundary) if it exceeds the maximum size
+    pub fn new(code: Code, detail: &str) -> Self {
+        let mut len = detail.len().min(DetailBytes::new().capacity());
+
+        while !detail.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        let mut bytes = DetailBytes::new();
+        bytes.extend_from_slice(&detail.as_bytes()[..len]).unwrap();
+
+        Response {
+            code: code.into(),
+            detail: bytes,
+        }
+    }
+
+    /// Get the error [`Code`].
+    ///
+    /// Codes unknown to this version are reported as [`Code::Internal`].
+    pub fn code(&self) -> Code {
+        Code::from_u64(self.code).unwrap_or(Code::Internal)
+    }
+
+    /// Get the detail message, if there is one
+    pub fn detail(&self) -> Option<&str> {
+        if self.detail.is_empty() {
+            None
+        } else {
+            str::from_utf8(&self.detail).ok()
+        }
+    }
+}
+
+impl From<Code> for Response {
+    fn from(code: Code) -> Response {
+        Response {
+            code: code.into(),
+            detail: DetailBytes::new(),
+        }
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "{}: {}", self.code(), detail),
+            None => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Code, Response};
+    use heapless::{consts::U256, Vec};
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn code_round_trip() {
+        for code in 0..=16 {
+            assert_eq!(u64::from(Code::from_u64(code).unwrap()), code);
+        }
+
+        assert_eq!(Code::from_u64(17), None);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let response = Response::new(Code::Unauthorized, "root signatures");
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = Response::decode(&mut decoder, &buffer).unwrap();
+
+        assert_eq!(response, decoded);
+        assert_eq!(decoded.code(), Code::Unauthorized);
+        assert_eq!(decoded.detail(), Some("root signatures"));
+    }
+
+    #[test]
+    fn detail_truncated_at_char_boundary() {
+        // 100 two-byte characters
+        let mut bytes = [0u8; 200];
+
+        for chunk in bytes.chunks_mut(2) {
+            chunk.copy_from_slice("é".as_bytes());
+        }
+
+        let response = Response::new(Code::Internal, core::str::from_utf8(&bytes).unwrap());
+
+        assert_eq!(response.detail.len(), 128);
+        assert_eq!(response.detail().unwrap().chars().count(), 64);
+    }
+
+    #[test]
+    fn unknown_code() {
+        let response = Response {
+            code: 42,
+            detail: Vec::new(),
+        };
+
+        assert_eq!(response.code(), Code::Internal);
+        assert_eq!(response.detail(), None);
+    }
+}
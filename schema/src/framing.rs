@@ -0,0 +1,479 @@
+//! Framing: split messages into fragments which fit within a transport's
+//! maximum packet size (e.g. the 512-byte USB bulk endpoints) and reassemble
+//! them on the other side.
+//!
+//! Each fragment has the following layout:
+//!
+//! ```text
+//! [sequence: u16][length: u32][payload]
+//! ```
+//!
+//! The sequence number starts at zero for the first fragment of a message
+//! and increments by one for each subsequent fragment. The length is that of
+//! the entire message, and is repeated in every fragment so the receiver can
+//! detect fragments belonging to a different message. Integers are
+//! big endian.
+
+use core::{convert::TryInto, fmt};
+use heapless::{consts::U4096, ArrayLength, Vec};
+
+/// Size of the sequence number field
+const SEQUENCE_SIZE: usize = 2;
+
+/// Size of a fragment header
+pub const HEADER_SIZE: usize = SEQUENCE_SIZE + 4;
+
+/// Maximum size of a (reassembled) request or response message
+pub type MaxMessageSize = U4096;
+
+/// Framing errors
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Message exceeds the maximum size
+    Capacity,
+
+    /// Fragment length is inconsistent with the message length
+    Length,
+
+    /// Fragment received out of sequence
+    Sequence,
+
+    /// Fragment is too short to contain a header
+    Truncated,
+}
+
+impl Error {
+    /// Get a description of this error
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Error::Capacity => "message too large",
+            Error::Length => "inconsistent fragment length",
+            Error::Sequence => "fragment out of sequence",
+            Error::Truncated => "truncated fragment",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Fragment of a message
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Fragment<'a> {
+    /// Sequence number of this fragment within its message
+    pub sequence: u16,
+
+    /// Length of the entire message
+    pub length: u32,
+
+    /// Portion of the message contained in this fragment
+    pub payload: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    /// Parse a fragment from a packet received from a transport
+    pub fn parse(packet: &'a [u8]) -> Result<Self, Error> {
+        if packet.len() < HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+
+        let (header, payload) = packet.split_at(HEADER_SIZE);
+        let (sequence, length) = header.split_at(SEQUENCE_SIZE);
+
+        Ok(Fragment {
+            sequence: u16::from_be_bytes(sequence.try_into().unwrap()),
+            length: u32::from_be_bytes(length.try_into().unwrap()),
+            payload,
+        })
+    }
+
+    /// Get the size of this fragment once encoded
+    pub fn encoded_len(&self) -> usize {
+        HEADER_SIZE + self.payload.len()
+    }
+
+    /// Encode this fragment into the given packet buffer, returning the
+    /// portion of the buffer it occupies
+    pub fn encode<'b>(&self, packet: &'b mut [u8]) -> Result<&'b [u8], Error> {
+        let len = self.encoded_len();
+
+        if packet.len() < len {
+            return Err(Error::Capacity);
+        }
+
+        packet[..SEQUENCE_SIZE].copy_from_slice(&self.sequence.to_be_bytes());
+        packet[SEQUENCE_SIZE..HEADER_SIZE].copy_from_slice(&self.length.to_be_bytes());
+        packet[HEADER_SIZE..len].copy_from_slice(self.payload);
+        Ok(&packet[..len])
+    }
+}
+
+/// Split a message into fragments which fit within the given packet size.
+///
+/// Empty messages are sent as a single fragment with an empty payload.
+pub fn fragments(message: &[u8], packet_size: usize) -> Result<Fragments<'_>, Error> {
+    if packet_size <= HEADER_SIZE {
+        return Err(Error::Length);
+    }
+
+    let length = message.len().try_into().map_err(|_| Error::Capacity)?;
+    let chunk_size = packet_size - HEADER_SIZE;
+
+    // The final sequence number must be representable
+    if message.len().saturating_sub(1) / chunk_size > usize::from(u16::MAX) {
+        return Err(Error::Capacity);
+    }
+
+    Ok(Fragments {
+        message,
+        length,
+        chunk_size,
+        sequence: 0,
+        offset: 0,
+        done: false,
+    })
+}
+
+/// Compute the size of the payload of the fragment with the given sequence
+/// number when a message of the given length is split into packets of the
+/// given size.
+///
+/// Stream transports (e.g. TCP) don't preserve packet boundaries, so
+/// receivers use this to determine how much of the stream belongs to each
+/// fragment after reading its header.
+pub fn payload_len(sequence: u16, length: u32, packet_size: usize) -> Result<usize, Error> {
+    if packet_size <= HEADER_SIZE {
+        return Err(Error::Length);
+    }
+
+    let chunk_size = packet_size - HEADER_SIZE;
+    let length = length as usize;
+    let offset = usize::from(sequence)
+        .checked_mul(chunk_size)
+        .filter(|&offset| offset < length || offset == 0)
+        .ok_or(Error::Sequence)?;
+
+    Ok(chunk_size.min(length - offset))
+}
+
+/// Iterator over the [`Fragment`]s of a message
+#[derive(Clone, Debug)]
+pub struct Fragments<'a> {
+    /// Message being fragmented
+    message: &'a [u8],
+
+    /// Length of the message
+    length: u32,
+
+    /// Maximum size of a fragment's payload
+    chunk_size: usize,
+
+    /// Sequence number of the next fragment
+    sequence: u16,
+
+    /// Offset of the next fragment's payload within the message
+    offset: usize,
+
+    /// Has the final fragment been produced?
+    done: bool,
+}
+
+impl<'a> Iterator for Fragments<'a> {
+    type Item = Fragment<'a>;
+
+    fn next(&mut self) -> Option<Fragment<'a>> {
+        if self.done {
+            return None;
+        }
+
+        let end = self.message.len().min(self.offset + self.chunk_size);
+
+        let fragment = Fragment {
+            sequence: self.sequence,
+            length: self.length,
+            payload: &self.message[self.offset..end],
+        };
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.offset = end;
+        self.done = end == self.message.len();
+        Some(fragment)
+    }
+}
+
+/// Reassembles messages from [`Fragment`]s, buffering up to `N` bytes
+#[derive(Clone, Debug, Default)]
+pub struct Reassembler<N: ArrayLength<u8> = MaxMessageSize> {
+    /// Message reassembled so far
+    buffer: Vec<u8, N>,
+
+    /// Length of the message being reassembled (`None` if idle)
+    length: Option<usize>,
+
+    /// Expected sequence number of the next fragment
+    sequence: u16,
+}
+
+impl<N> Reassembler<N>
+where
+    N: ArrayLength<u8>,
+{
+    /// Create a new [`Reassembler`]
+    pub fn new() -> Self {
+        Reassembler {
+            buffer: Vec::new(),
+            length: None,
+            sequence: 0,
+        }
+    }
+
+    /// Add a packet containing a fragment, returning the reassembled message
+    /// if it's now complete.
+    ///
+    /// A fragment with a sequence number of zero starts a new message,
+    /// discarding any partially reassembled one. Any error also discards the
+    /// partially reassembled message.
+    pub fn push(&mut self, packet: &[u8]) -> Result<Option<&[u8]>, Error> {
+        match Fragment::parse(packet).and_then(|fragment| self.push_fragment(fragment)) {
+            Ok(true) => Ok(Some(&self.buffer)),
+            Ok(false) => Ok(None),
+            Err(e) => {
+                self.reset();
+                Err(e)
+            }
+        }
+    }
+
+    /// Discard any partially reassembled message
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.length = None;
+        self.sequence = 0;
+    }
+
+    /// Is a message partially reassembled?
+    pub fn is_pending(&self) -> bool {
+        self.length.is_some()
+    }
+
+    /// Add a (parsed) fragment, returning whether the message is complete
+    fn push_fragment(&mut self, fragment: Fragment<'_>) -> Result<bool, Error> {
+        let length = fragment.length as usize;
+
+        if fragment.sequence == 0 {
+            self.reset();
+
+            if length > self.buffer.capacity() {
+                return Err(Error::Capacity);
+            }
+
+            self.length = Some(length);
+        } else if fragment.sequence != self.sequence {
+            return Err(Error::Sequence);
+        } else if self.length != Some(length) {
+            return Err(Error::Length);
+        }
+
+        if self.buffer.len() + fragment.payload.len() > length {
+            return Err(Error::Length);
+        }
+
+        self.buffer
+            .extend_from_slice(fragment.payload)
+            .map_err(|_| Error::Capacity)?;
+
+        if self.buffer.len() == length {
+            self.length = None;
+            self.sequence = 0;
+            Ok(true)
+        } else {
+            self.sequence = self.sequence.checked_add(1).ok_or(Error::Sequence)?;
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fragments, payload_len, Error, Fragment, Reassembler, HEADER_SIZE};
+    use heapless::consts::{U16, U4096};
+
+    /// Packet size used by these tests (USB High-Speed bulk endpoints)
+    const PACKET_SIZE: usize = 512;
+
+    /// Create a message of the given size with a recognizable pattern
+    fn example_message(buffer: &mut [u8]) -> &[u8] {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = i as u8 ^ (i >> 8) as u8;
+        }
+
+        buffer
+    }
+
+    #[test]
+    fn multi_fragment_round_trip() {
+        let mut buffer = [0u8; 3000];
+        let message = example_message(&mut buffer);
+        let mut reassembler = Reassembler::<U4096>::new();
+        let mut count = 0;
+
+        for (i, fragment) in fragments(message, PACKET_SIZE).unwrap().enumerate() {
+            let mut packet = [0u8; PACKET_SIZE];
+            let packet = fragment.encode(&mut packet).unwrap();
+            assert_eq!(fragment.sequence as usize, i);
+            count += 1;
+
+            match reassembler.push(packet).unwrap() {
+                Some(reassembled) => assert_eq!(reassembled, message),
+                None => assert!(reassembler.is_pending()),
+            }
+        }
+
+        assert_eq!(count, 6);
+        assert!(!reassembler.is_pending());
+    }
+
+    #[test]
+    fn exact_multiple_of_packet_size() {
+        let mut buffer = [0u8; 2 * (PACKET_SIZE - HEADER_SIZE)];
+        let message = example_message(&mut buffer);
+        assert_eq!(fragments(message, PACKET_SIZE).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn empty_message() {
+        let mut fragments = fragments(&[], PACKET_SIZE).unwrap();
+        let fragment = fragments.next().unwrap();
+        assert_eq!(fragment.payload, &[]);
+        assert_eq!(fragments.next(), None);
+
+        let mut packet = [0u8; HEADER_SIZE];
+        let mut reassembler = Reassembler::<U16>::new();
+        let message = reassembler.push(fragment.encode(&mut packet).unwrap());
+        assert_eq!(message, Ok(Some(&[][..])));
+    }
+
+    #[test]
+    fn stream_payload_len() {
+        let mut buffer = [0u8; 1500];
+        let message = example_message(&mut buffer);
+
+        for fragment in fragments(message, PACKET_SIZE).unwrap() {
+            assert_eq!(
+                payload_len(fragment.sequence, fragment.length, PACKET_SIZE),
+                Ok(fragment.payload.len())
+            );
+        }
+
+        assert_eq!(payload_len(0, 0, PACKET_SIZE), Ok(0));
+        assert_eq!(payload_len(3, 1500, PACKET_SIZE), Err(Error::Sequence));
+    }
+
+    #[test]
+    fn packet_too_small() {
+        assert_eq!(
+            fragments(&[1, 2, 3], HEADER_SIZE).err(),
+            Some(Error::Length)
+        );
+    }
+
+    #[test]
+    fn message_too_large() {
+        let packet = Fragment {
+            sequence: 0,
+            length: 17,
+            payload: &[0u8; 10],
+        };
+
+        let mut buffer = [0u8; 32];
+        let mut reassembler = Reassembler::<U16>::new();
+
+        assert_eq!(
+            reassembler.push(packet.encode(&mut buffer).unwrap()),
+            Err(Error::Capacity)
+        );
+    }
+
+    #[test]
+    fn truncated_fragment() {
+        let mut reassembler = Reassembler::<U16>::new();
+        assert_eq!(reassembler.push(&[0, 0, 0]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn out_of_sequence() {
+        let message = [42u8; 16];
+        let mut fragments = fragments(&message, HEADER_SIZE + 4).unwrap();
+        let first = fragments.next().unwrap();
+        fragments.next().unwrap();
+        let third = fragments.next().unwrap();
+
+        let mut reassembler = Reassembler::<U16>::new();
+        let mut packet = [0u8; 16];
+        assert_eq!(
+            reassembler.push(first.encode(&mut packet).unwrap()),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.push(third.encode(&mut packet).unwrap()),
+            Err(Error::Sequence)
+        );
+        assert!(!reassembler.is_pending());
+    }
+
+    #[test]
+    fn new_message_discards_partial_one() {
+        let first_message = [1u8; 16];
+        let second_message = [2u8; 3];
+        let mut reassembler = Reassembler::<U16>::new();
+        let mut packet = [0u8; 16];
+
+        let fragment = fragments(&first_message, HEADER_SIZE + 4)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(
+            reassembler.push(fragment.encode(&mut packet).unwrap()),
+            Ok(None)
+        );
+
+        let fragment = fragments(&second_message, HEADER_SIZE + 4)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(
+            reassembler.push(fragment.encode(&mut packet).unwrap()),
+            Ok(Some(&second_message[..]))
+        );
+    }
+
+    #[test]
+    fn inconsistent_length() {
+        let mut reassembler = Reassembler::<U16>::new();
+        let mut packet = [0u8; 16];
+
+        let first = Fragment {
+            sequence: 0,
+            length: 8,
+            payload: &[0u8; 4],
+        };
+
+        let second = Fragment {
+            sequence: 1,
+            length: 9,
+            payload: &[0u8; 4],
+        };
+
+        assert_eq!(
+            reassembler.push(first.encode(&mut packet).unwrap()),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.push(second.encode(&mut packet).unwrap()),
+            Err(Error::Length)
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Device information: identify a device, the firmware it's running, and the
+//! capabilities it supports
+
+use crate::{
+    authorization::{Binding, DeviceId},
+    domain,
+    key::Algorithm,
+    Uuid,
+};
+use core::str;
+use heapless::{
+    consts::{U32, U8},
+    Vec,
+};
+use veriform::Message;
+
+/// Version of this message schema.
+///
+/// Incremented whenever a change is made to the schema which is not
+/// backwards compatible.
+pub const SCHEMA_VERSION: u64 = 8;
+
+/// Maximum size of a firmware version string
+pub type MaxFirmwareVersionSize = U32;
+
+/// Firmware version string bytes (UTF-8)
+pub type FirmwareVersion = Vec<u8, MaxFirmwareVersionSize>;
+
+/// Key slot usage for all domains
+pub type SlotUsages = Vec<SlotUsage, U8>;
+
+/// Authorization counters of all domains
+pub type DomainCounters = Vec<DomainCounter, U8>;
+
+/// Request for information about a device
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Request {}
+
+/// Response containing information about a device
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    /// Version of the firmware the device is running (UTF-8)
+    #[field(tag = 0, wire_type = "bytes", critical = true, max = 32)]
+    pub firmware_version: FirmwareVersion,
+
+    /// Version of the message schema the firmware was built with
+    /// (see [`SCHEMA_VERSION`])
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub schema_version: u64,
+
+    /// Key algorithms supported by the firmware: a bitmask where bit `n` is
+    /// set if the [`Algorithm`] with wire identifier `n` is supported
+    #[field(tag = 2, wire_type = "uint64", critical = true)]
+    pub algorithms: u64,
+
+    /// UUID of the root configuration (nil if unprovisioned)
+    #[field(tag = 3, wire_type = "message", critical = true)]
+    pub uuid: Uuid,
+
+    /// Version of the root configuration (zero if unprovisioned)
+    #[field(tag = 4, wire_type = "uint64", critical = true)]
+    pub root_version: u64,
+
+    /// Key slot usage for each domain on the device
+    #[field(tag = 5, wire_type = "sequence", critical = true, max = 8)]
+    pub slots: SlotUsages,
+
+    /// Maximum size of an encoded request or response message
+    #[field(tag = 6, wire_type = "uint64", critical = true)]
+    pub max_message_size: u64,
+
+    /// X25519 static public key used to establish encrypted sessions (see
+    /// [`session`](crate::session)). Clients should pin it rather than trust
+    /// the value reported by a device they've never seen before.
+    #[field(tag = 7, wire_type = "bytes", critical = true, size = 32)]
+    pub session_key: [u8; 32],
+
+    /// Device-unique identifier, which authorized requests are bound to
+    #[field(tag = 8, wire_type = "bytes", critical = true, size = 16)]
+    pub device_id: DeviceId,
+
+    /// Authorization counter of the most recent request authorized by the
+    /// root keys (zero if none have been)
+    #[field(tag = 9, wire_type = "uint64", critical = true)]
+    pub authorization_counter: u64,
+
+    /// Authorization counter of the most recent request authorized for each
+    /// domain on the device
+    #[field(tag = 10, wire_type = "sequence", critical = true, max = 8)]
+    pub domain_counters: DomainCounters,
+}
+
+impl Response {
+    /// Get the firmware version string, if it's valid UTF-8
+    pub fn firmware_version(&self) -> Option<&str> {
+        str::from_utf8(&self.firmware_version).ok()
+    }
+
+    /// Was the device built with the same message schema as this crate?
+    pub fn is_compatible(&self) -> bool {
+        self.schema_version == SCHEMA_VERSION
+    }
+
+    /// Has the device been provisioned?
+    pub fn is_provisioned(&self) -> bool {
+        self.root_version != 0
+    }
+
+    /// Get a [`Binding`] of a request to this device which is valid for the
+    /// next request the root keys authorize.
+    ///
+    /// Returns `None` if the root keys' counter is exhausted.
+    pub fn next_binding(&self) -> Option<Binding> {
+        Some(Binding {
+            device_id: self.device_id,
+            counter: self.authorization_counter.checked_add(1)?,
+        })
+    }
+
+    /// Get a [`Binding`] of a request to this device which is valid for the
+    /// next request authorized for the given domain.
+    ///
+    /// Returns `None` if the device has no such domain or its counter is
+    /// exhausted.
+    pub fn next_domain_binding(&self, domain: domain::Id) -> Option<Binding> {
+        let counter = self
+            .domain_counters
+            .iter()
+            .find(|counter| counter.domain == domain)?;
+
+        Some(Binding {
+            device_id: self.device_id,
+            counter: counter.counter.checked_add(1)?,
+        })
+    }
+
+    /// Does the device support keys for the given [`Algorithm`]?
+    pub fn supports(&self, algorithm: Algorithm) -> bool {
+        let id = u64::from(algorithm);
+        id < 64 && self.algorithms & (1 << id) != 0
+    }
+}
+
+/// Compute an [`Response::algorithms`] bitmask for the given algorithms
+pub fn algorithm_mask(algorithms: &[Algorithm]) -> u64 {
+    algorithms
+        .iter()
+        .fold(0, |mask, &algorithm| mask | 1 << u64::from(algorithm))
+}
+
+/// Key slot usage for a domain
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SlotUsage {
+    /// Domain identifier
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Number of key slots in use
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub used: u64,
+
+    /// Maximum number of key slots permitted by the domain's policy
+    #[field(tag = 2, wire_type = "uint64", critical = true)]
+    pub max: u64,
+}
+
+/// Authorization counter of a domain
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct DomainCounter {
+    /// Domain identifier
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Authorization counter of the most recent request authorized for the
+    /// domain
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub counter: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        algorithm_mask, DomainCounter, DomainCounters, Response, SlotUsage, SlotUsages,
+        SCHEMA_VERSION,
+    };
+    use crate::{key::Algorithm, Uuid};
+    use heapless::{consts::U256, Vec};
+    use veriform::{Decoder, Message};
+
+    /// Create an example `info::Response`
+    fn example_response() -> Response {
+        let mut firmware_version = Vec::new();
+        firmware_version.extend_from_slice(b"0.1.0").unwrap();
+
+        let mut slots = SlotUsages::new();
+        slots
+            .push(SlotUsage {
+                domain: 1,
+                used: 2,
+                max: 4,
+            })
+            .unwrap();
+
+        let mut domain_counters = DomainCounters::new();
+        domain_counters
+            .push(DomainCounter {
+                domain: 1,
+                counter: 3,
+            })
+            .unwrap();
+
+        Response {
+            firmware_version,
+            schema_version: SCHEMA_VERSION,
+            algorithms: algorithm_mask(&[Algorithm::Ed25519, Algorithm::Bls12381]),
+            uuid: Uuid::parse_str("88888888-4444-4444-4444-121212121212").unwrap(),
+            root_version: 1,
+            slots,
+            max_message_size: 512,
+            session_key: [42; 32],
+            device_id: [7; 16],
+            authorization_counter: 5,
+            domain_counters,
+        }
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let response = example_response();
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = Response::decode(&mut decoder, &buffer).unwrap();
+
+        assert_eq!(response, decoded);
+        assert_eq!(decoded.firmware_version(), Some("0.1.0"));
+        assert!(decoded.is_compatible());
+        assert!(decoded.is_provisioned());
+    }
+
+    #[test]
+    fn supported_algorithms() {
+        let response = example_response();
+
+        assert!(response.supports(Algorithm::Ed25519));
+        assert!(!response.supports(Algorithm::EcdsaP256));
+        assert!(!response.supports(Algorithm::EcdsaSecp256k1));
+        assert!(response.supports(Algorithm::Bls12381));
+    }
+
+    #[test]
+    fn next_binding() {
+        let binding = example_response().next_binding().unwrap();
+        assert_eq!(binding.device_id, [7; 16]);
+        assert_eq!(binding.counter, 6);
+    }
+
+    #[test]
+    fn next_binding_exhausted() {
+        let mut response = example_response();
+        response.authorization_counter = u64::MAX;
+        response.domain_counters[0].counter = u64::MAX;
+
+        assert!(response.next_binding().is_none());
+        assert!(response.next_domain_binding(1).is_none());
+    }
+
+    #[test]
+    fn next_domain_binding() {
+        let binding = example_response().next_domain_binding(1).unwrap();
+        assert_eq!(binding.device_id, [7; 16]);
+        assert_eq!(binding.counter, 4);
+        assert!(example_response().next_domain_binding(2).is_none());
+    }
+}
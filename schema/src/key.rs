@@ -0,0 +1,520 @@
+//! Key messages: generate keys within domains and sign messages with them
+
+use crate::{
+    authorization::Binding, domain, policy::Policy, public_key::PublicKey, signature::Signatures,
+    Signature, Timestamp,
+};
+use heapless::{
+    consts::{U1024, U128},
+    Vec,
+};
+use veriform::{Message, Sha256Digest};
+
+/// Key slot numbers (i.e. identifiers for keys within a domain)
+pub type Slot = u64;
+
+/// Maximum size of a message which can be signed
+pub type MaxMessageSize = U1024;
+
+/// Message bytes to be signed
+pub type MessageBytes = Vec<u8, MaxMessageSize>;
+
+/// Maximum size of a wrapped key
+pub type MaxWrappedKeySize = U128;
+
+/// Wrapped key: a secret key sealed under a device's root key, which can
+/// only be imported by the same device
+pub type WrappedKey = Vec<u8, MaxWrappedKeySize>;
+
+/// Key algorithms
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    /// Ed25519 signing keys
+    Ed25519,
+
+    /// ECDSA/P-256 signing keys
+    EcdsaP256,
+
+    /// ECDSA/secp256k1 signing keys
+    EcdsaSecp256k1,
+
+    /// BLS12-381 signing keys (Ethereum 2.0 ciphersuite)
+    Bls12381,
+}
+
+impl Algorithm {
+    /// Get the algorithm with the given wire identifier, if it's a known one
+    pub fn from_u64(algorithm: u64) -> Option<Self> {
+        match algorithm {
+            0 => Some(Algorithm::Ed25519),
+            1 => Some(Algorithm::EcdsaP256),
+            2 => Some(Algorithm::EcdsaSecp256k1),
+            3 => Some(Algorithm::Bls12381),
+            _ => None,
+        }
+    }
+}
+
+impl From<Algorithm> for u64 {
+    fn from(algorithm: Algorithm) -> u64 {
+        match algorithm {
+            Algorithm::Ed25519 => 0,
+            Algorithm::EcdsaP256 => 1,
+            Algorithm::EcdsaSecp256k1 => 2,
+            Algorithm::Bls12381 => 3,
+        }
+    }
+}
+
+/// Request to generate a new key within a domain (signed by the domain's
+/// administrators)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct GenerateRequest {
+    /// Domain in which to generate the key
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Algorithm of the key to generate (see [`Algorithm`])
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub algorithm: u64,
+
+    /// Date/time when the key is generated (agreed upon by all signers)
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Policy constraining how the key may be used
+    #[field(tag = 3, wire_type = "message", critical = true)]
+    pub policy: Policy,
+
+    /// Digest of this message (to be signed by the domain administrators)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+impl GenerateRequest {
+    /// Get the requested key [`Algorithm`], if it's a known one
+    pub fn algorithm(&self) -> Option<Algorithm> {
+        Algorithm::from_u64(self.algorithm)
+    }
+}
+
+/// Key generation request along with domain administrator signatures over
+/// its digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedGenerateRequest {
+    /// Key generation request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: GenerateRequest,
+
+    /// Signatures over the key generation request's digest under the binding
+    /// (see [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to a key being generated
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct GenerateResponse {
+    /// Slot the newly generated key occupies within its domain
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub slot: Slot,
+
+    /// Public key of the newly generated key
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub public_key: PublicKey,
+}
+
+/// Data to be signed
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Payload {
+    /// Raw message, hashed as specified by the key's signature algorithm
+    #[field(tag = 0, wire_type = "bytes", max = 1024)]
+    Message(MessageBytes),
+
+    /// SHA-256 digest of a message which has been hashed in advance
+    /// (ECDSA only)
+    #[field(tag = 1, wire_type = "bytes", size = 32)]
+    Sha256(Sha256Digest),
+}
+
+/// Request to sign a message
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignRequest {
+    /// Domain containing the signing key
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Slot of the signing key within the domain
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub slot: Slot,
+
+    /// Data to be signed
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub payload: Payload,
+}
+
+/// Response containing a signature
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignResponse {
+    /// Signature over the requested message
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub signature: Signature,
+}
+
+/// Request for a proof of possession of a key's private key (BLS only).
+///
+/// Proofs of possession prevent rogue key attacks when aggregating public keys.
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct PossessionRequest {
+    /// Domain containing the key
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Slot of the key within the domain
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub slot: Slot,
+}
+
+/// Response containing a proof of possession
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct PossessionResponse {
+    /// Proof of possession: a signature over the key's public key
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub proof: Signature,
+}
+
+/// Request for the public key of a key
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct PublicKeyRequest {
+    /// Domain containing the key
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Slot of the key within the domain
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub slot: Slot,
+}
+
+/// Response containing a public key
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct PublicKeyResponse {
+    /// Public key of the requested key
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub public_key: PublicKey,
+}
+
+/// Request to export a key wrapped under the device's root key, e.g. to back
+/// it up off-device (signed by the domain's administrators)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ExportRequest {
+    /// Domain containing the key
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Slot of the key within the domain
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub slot: Slot,
+
+    /// Date/time when the key is exported (agreed upon by all signers)
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (to be signed by the domain administrators)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Key export request along with domain administrator signatures over its
+/// digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedExportRequest {
+    /// Key export request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: ExportRequest,
+
+    /// Signatures over the key export request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response containing a wrapped key
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ExportResponse {
+    /// Key wrapped under the device's root key, bound to the domain it was
+    /// exported from and its policy
+    #[field(tag = 0, wire_type = "bytes", critical = true, max = 128)]
+    pub wrapped_key: WrappedKey,
+
+    /// Policy constraining how the key may be used, which it's restored
+    /// with when imported
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub policy: Policy,
+}
+
+/// Request to import a previously exported key into the next free slot of
+/// the domain it was exported from (signed by the domain's administrators)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ImportRequest {
+    /// Domain to import the key into
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Key wrapped under the device's root key (see [`ExportResponse`])
+    #[field(tag = 1, wire_type = "bytes", critical = true, max = 128)]
+    pub wrapped_key: WrappedKey,
+
+    /// Date/time when the key is imported (agreed upon by all signers)
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Policy the key was exported with (see [`ExportResponse`]): keys can
+    /// only be imported with the policy their wrapping is bound to
+    #[field(tag = 3, wire_type = "message", critical = true)]
+    pub policy: Policy,
+
+    /// Digest of this message (to be signed by the domain administrators)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Key import request along with domain administrator signatures over its
+/// digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedImportRequest {
+    /// Key import request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: ImportRequest,
+
+    /// Signatures over the key import request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to a key being imported
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ImportResponse {
+    /// Slot the imported key occupies within its domain
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub slot: Slot,
+
+    /// Public key of the imported key
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub public_key: PublicKey,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Algorithm, ExportResponse, GenerateResponse, ImportRequest, Payload, PossessionRequest,
+        PossessionResponse, PublicKeyRequest, SignRequest, SignResponse,
+    };
+    use crate::{policy::tests::example_policy, PublicKey, Signature, Timestamp};
+    use heapless::{
+        consts::{U256, U512},
+        Vec,
+    };
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn algorithm_round_trip() {
+        for &algorithm in &[
+            Algorithm::Ed25519,
+            Algorithm::EcdsaP256,
+            Algorithm::EcdsaSecp256k1,
+            Algorithm::Bls12381,
+        ] {
+            assert_eq!(Algorithm::from_u64(u64::from(algorithm)), Some(algorithm));
+        }
+
+        assert_eq!(Algorithm::from_u64(u64::MAX), None);
+    }
+
+    #[test]
+    fn generate_response_round_trip() {
+        let response = GenerateResponse {
+            slot: 3,
+            public_key: PublicKey::Ed25519([7u8; 32]),
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            response,
+            GenerateResponse::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn sign_request_round_trip() {
+        let mut message = Vec::new();
+        message.extend_from_slice(b"example message").unwrap();
+
+        let request = SignRequest {
+            domain: 42,
+            slot: 0,
+            payload: Payload::Message(message),
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(request, SignRequest::decode(&mut decoder, &buffer).unwrap());
+    }
+
+    #[test]
+    fn prehashed_sign_request_round_trip() {
+        let request = SignRequest {
+            domain: 42,
+            slot: 1,
+            payload: Payload::Sha256([5u8; 32]),
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(request, SignRequest::decode(&mut decoder, &buffer).unwrap());
+    }
+
+    #[test]
+    fn sign_response_round_trip() {
+        let response = SignResponse {
+            signature: Signature::EcdsaP256([9u8; 64]),
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            response,
+            SignResponse::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn possession_round_trip() {
+        let request = PossessionRequest {
+            domain: 42,
+            slot: 3,
+        };
+        let response = PossessionResponse {
+            proof: Signature::Bls12381([7u8; 96]),
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            request,
+            PossessionRequest::decode(&mut decoder, &buffer).unwrap()
+        );
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            response,
+            PossessionResponse::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn public_key_request_round_trip() {
+        let request = PublicKeyRequest {
+            domain: 42,
+            slot: 2,
+        };
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            request,
+            PublicKeyRequest::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn wrapped_key_round_trip() {
+        let mut wrapped_key = Vec::new();
+        wrapped_key.extend_from_slice(&[3u8; 96]).unwrap();
+
+        let response = ExportResponse {
+            wrapped_key: wrapped_key.clone(),
+            policy: example_policy(),
+        };
+
+        let mut buffer: Vec<u8, U512> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 512]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            response,
+            ExportResponse::decode(&mut decoder, &buffer).unwrap()
+        );
+
+        // TAI64N for 2020-05-21
+        let timestamp =
+            Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap();
+
+        let request = ImportRequest {
+            domain: 42,
+            wrapped_key,
+            timestamp,
+            policy: example_policy(),
+            digest: None,
+        };
+
+        let mut buffer: Vec<u8, U512> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 512]).unwrap();
+        request.encode(&mut buffer).unwrap();
+        buffer.truncate(request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = ImportRequest::decode(&mut decoder, &buffer).unwrap();
+        assert_eq!(request.wrapped_key, decoded.wrapped_key);
+        assert_eq!(request.policy, decoded.policy);
+        assert!(decoded.digest.is_some());
+    }
+}
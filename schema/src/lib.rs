@@ -5,12 +5,30 @@
 #![forbid(unsafe_code)]
 #![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
 
+pub mod approval;
+pub mod authorization;
+pub mod challenge;
+pub mod domain;
+pub mod error;
+pub mod framing;
+pub mod info;
+pub mod key;
+pub mod policy;
+pub mod program;
 pub mod provision;
 pub mod public_key;
 pub mod request;
 pub mod response;
+pub mod root;
+pub mod session;
+pub mod signature;
+pub mod state;
+pub mod threshold;
 
-pub use self::{public_key::PublicKey, request::Request, response::Response};
+pub use self::{
+    public_key::PublicKey, request::Request, response::Response, signature::Signature,
+    threshold::ThresholdKeySet,
+};
 pub use veriform::{
     self,
     builtins::{Timestamp, Uuid},
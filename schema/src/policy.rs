@@ -0,0 +1,203 @@
+//! Key policies: constraints on how an individual key may be used
+//!
+//! A policy is attached to each key when it's generated or imported, and is
+//! evaluated by the device before every operation which uses the key. Each
+//! field is a separate constraint, all of which must be satisfied. Fields
+//! left at their default (zero or empty) values don't constrain the key,
+//! except for [`Policy::callers`]: keys are denied to everyone by default, so
+//! a key whose policy names no callers may only be used with the approval of
+//! its domain's administrators (see [`approval`]).
+//!
+//! Constraints which concern the message being signed (prefixes and lengths)
+//! can only be checked for [`Payload::Message`] payloads: keys with such
+//! constraints don't sign prehashed payloads.
+//!
+//! [`approval`]: crate::approval
+//! [`Payload::Message`]: crate::key::Payload::Message
+
+use crate::{key::Payload, PublicKey};
+use heapless::{
+    consts::{U32, U4},
+    Vec,
+};
+use veriform::Message;
+
+/// Maximum number of allowed message prefixes
+pub type MaxPrefixes = U4;
+
+/// Maximum size of an allowed message prefix
+pub type MaxPrefixSize = U32;
+
+/// Allowed message prefix bytes
+pub type PrefixBytes = Vec<u8, MaxPrefixSize>;
+
+/// Allowed message prefixes
+pub type Prefixes = Vec<Prefix, MaxPrefixes>;
+
+/// Maximum number of allowed callers
+pub type MaxCallers = U4;
+
+/// Session identities of allowed callers
+pub type Callers = Vec<PublicKey, MaxCallers>;
+
+/// Hash algorithms used to compute the digests which keys sign
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    /// Message hashed by the device as specified by the key's signature
+    /// algorithm (i.e. [`Payload::Message`])
+    Intrinsic,
+
+    /// SHA-256 digest computed in advance by the caller (i.e.
+    /// [`Payload::Sha256`])
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Get the hash algorithm with the given wire identifier, if it's a
+    /// known one
+    pub fn from_u64(algorithm: u64) -> Option<Self> {
+        match algorithm {
+            0 => Some(HashAlgorithm::Intrinsic),
+            1 => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+}
+
+impl From<HashAlgorithm> for u64 {
+    fn from(algorithm: HashAlgorithm) -> u64 {
+        match algorithm {
+            HashAlgorithm::Intrinsic => 0,
+            HashAlgorithm::Sha256 => 1,
+        }
+    }
+}
+
+impl From<&Payload> for HashAlgorithm {
+    fn from(payload: &Payload) -> HashAlgorithm {
+        match payload {
+            Payload::Message(_) => HashAlgorithm::Intrinsic,
+            Payload::Sha256(_) => HashAlgorithm::Sha256,
+        }
+    }
+}
+
+/// Compute a [`Policy::hash_algorithms`] bitmask for the given algorithms
+pub fn hash_algorithm_mask(algorithms: &[HashAlgorithm]) -> u64 {
+    algorithms
+        .iter()
+        .fold(0, |mask, &algorithm| mask | 1 << u64::from(algorithm))
+}
+
+/// Policy for an individual key
+#[derive(Message, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Policy {
+    /// Prefixes one of which messages must begin with (any message if empty)
+    #[field(tag = 0, wire_type = "sequence", critical = true, max = 4)]
+    pub prefixes: Prefixes,
+
+    /// Minimum length of messages
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub min_length: u64,
+
+    /// Maximum length of messages (or zero if unlimited)
+    #[field(tag = 2, wire_type = "uint64", critical = true)]
+    pub max_length: u64,
+
+    /// Allowed hash algorithms: a bitmask where bit `n` is set if the
+    /// [`HashAlgorithm`] with wire identifier `n` is allowed (or zero if all
+    /// are allowed)
+    #[field(tag = 3, wire_type = "uint64", critical = true)]
+    pub hash_algorithms: u64,
+
+    /// Maximum number of signatures per window (or zero if unlimited)
+    #[field(tag = 4, wire_type = "uint64", critical = true)]
+    pub max_signatures: u64,
+
+    /// Length of the window [`Policy::max_signatures`] applies to, counted
+    /// in operations authorized by the key's domain administrators (which
+    /// advance its authorization counter), as the device has no trusted
+    /// clock
+    #[field(tag = 5, wire_type = "uint64", critical = true)]
+    pub window: u64,
+
+    /// Number of the domain administrators' approvals required to use the
+    /// key (see [`approval`]), or zero if it may be used without them
+    ///
+    /// [`approval`]: crate::approval
+    #[field(tag = 6, wire_type = "uint64", critical = true)]
+    pub required_approvals: u64,
+
+    /// Session identities of the callers allowed to use the key (if empty,
+    /// only uses approved by the domain's administrators are allowed; see
+    /// [`session::AuthenticateRequest`])
+    ///
+    /// [`session::AuthenticateRequest`]: crate::session::AuthenticateRequest
+    #[field(tag = 7, wire_type = "sequence", critical = true, max = 4)]
+    pub callers: Callers,
+}
+
+/// Message prefix allowed by a policy
+#[derive(Message, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Prefix {
+    /// Prefix bytes
+    #[field(tag = 0, wire_type = "bytes", critical = true, max = 32)]
+    pub bytes: PrefixBytes,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{hash_algorithm_mask, HashAlgorithm, Policy, Prefix};
+    use crate::PublicKey;
+    use heapless::{consts::U256, Vec};
+    use veriform::{Decoder, Message};
+
+    /// Create an example `Policy` which constrains every aspect of key usage
+    pub(crate) fn example_policy() -> Policy {
+        let mut prefix = Prefix::default();
+        prefix.bytes.extend_from_slice(b"armistice").unwrap();
+
+        let mut policy = Policy {
+            min_length: 16,
+            max_length: 256,
+            hash_algorithms: hash_algorithm_mask(&[HashAlgorithm::Intrinsic]),
+            max_signatures: 10,
+            window: 100,
+            required_approvals: 2,
+            ..Policy::default()
+        };
+
+        policy.prefixes.push(prefix).unwrap();
+        policy.callers.push(PublicKey::Ed25519([7; 32])).unwrap();
+        policy
+    }
+
+    #[test]
+    fn hash_algorithm_round_trip() {
+        for &algorithm in &[HashAlgorithm::Intrinsic, HashAlgorithm::Sha256] {
+            assert_eq!(
+                HashAlgorithm::from_u64(u64::from(algorithm)),
+                Some(algorithm)
+            );
+        }
+
+        assert_eq!(HashAlgorithm::from_u64(2), None);
+        assert_eq!(
+            hash_algorithm_mask(&[HashAlgorithm::Intrinsic, HashAlgorithm::Sha256]),
+            0b11
+        );
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        for policy in &[Policy::default(), example_policy()] {
+            let mut buffer: Vec<u8, U256> = Vec::new();
+            buffer.extend_from_slice(&[0u8; 256]).unwrap();
+            policy.encode(&mut buffer).unwrap();
+            buffer.truncate(policy.encoded_len());
+
+            let mut decoder = Decoder::new();
+            assert_eq!(policy, &Policy::decode(&mut decoder, &buffer).unwrap());
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! Authorization programs: user-defined logic which decides whether keys
+//! within a domain may be used
+//!
+//! Each domain may have a program installed, which the device runs before
+//! every operation which uses one of the domain's keys (after the key's own
+//! [`policy`] has been satisfied). Programs are installed with an
+//! [`InstallRequest`] signed by a threshold of either the domain's
+//! administrators or the root keys.
+//!
+//! Programs are bytecode for a small stack machine whose words are `u64`s.
+//! Each instruction is a one-byte [`Opcode`], some of which are followed by
+//! immediate operands (multi-byte integers are little endian). Execution
+//! begins at the first instruction and ends when the program executes
+//! [`Opcode::Allow`] or [`Opcode::Deny`]. Every instruction costs one unit of
+//! gas (plus one per byte compared by [`Opcode::PayloadPrefix`]), and a
+//! program which exhausts [`GAS_LIMIT`], overflows or underflows the stack,
+//! overflows an arithmetic operation, or runs past the end of its code
+//! faults, which denies the operation.
+//!
+//! Programs are validated when they're installed: every opcode must be
+//! known, every immediate must be complete, and every jump must land on an
+//! instruction.
+//!
+//! [`policy`]: crate::policy
+
+use crate::{authorization::Binding, domain, signature::Signatures, Timestamp};
+use heapless::{
+    consts::{U16, U256},
+    Vec,
+};
+use veriform::{Message, Sha256Digest};
+
+/// Maximum size of a program's code
+pub type MaxCodeSize = U256;
+
+/// Program code
+pub type Code = Vec<u8, MaxCodeSize>;
+
+/// Maximum number of words on a program's stack
+pub type MaxStackDepth = U16;
+
+/// Gas available to each execution of a program
+pub const GAS_LIMIT: u64 = 1024;
+
+/// Program instructions. Stack effects are given as `(before -- after)`,
+/// with the top of the stack on the right.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Opcode {
+    /// Halt, denying the operation
+    Deny,
+
+    /// Halt, allowing the operation
+    Allow,
+
+    /// `( -- n)`: push the 8-byte immediate
+    Push,
+
+    /// `( -- n)`: push the 1-byte immediate
+    PushByte,
+
+    /// `(a -- )`: discard the top word
+    Pop,
+
+    /// `(a -- a a)`: duplicate the top word
+    Dup,
+
+    /// `(a b -- b a)`: swap the top two words
+    Swap,
+
+    /// `(a b -- a+b)`: add (faults on overflow)
+    Add,
+
+    /// `(a b -- a-b)`: subtract (faults on underflow)
+    Sub,
+
+    /// `(a b -- a==b)`: push 1 if equal, otherwise 0
+    Eq,
+
+    /// `(a b -- a<b)`: push 1 if less than, otherwise 0
+    Lt,
+
+    /// `(a b -- a>b)`: push 1 if greater than, otherwise 0
+    Gt,
+
+    /// `(a b -- a&b)`: bitwise and
+    And,
+
+    /// `(a b -- a|b)`: bitwise or
+    Or,
+
+    /// `(a -- !a)`: push 1 if zero, otherwise 0
+    Not,
+
+    /// `( -- )`: continue at the 2-byte immediate code offset
+    Jump,
+
+    /// `(c -- )`: continue at the 2-byte immediate code offset if `c` is
+    /// non-zero
+    JumpIf,
+
+    /// `( -- n)`: push the [`Field`] identified by the 1-byte immediate
+    Load,
+
+    /// `(i -- b)`: push the payload byte at index `i` (faults if out of
+    /// bounds)
+    PayloadByte,
+
+    /// `( -- p)`: push 1 if the payload begins with the bytes which follow
+    /// the 1-byte immediate giving their length, otherwise 0
+    PayloadPrefix,
+}
+
+impl Opcode {
+    /// Get the opcode with the given encoding, if it's a known one
+    pub fn from_u8(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x00 => Some(Opcode::Deny),
+            0x01 => Some(Opcode::Allow),
+            0x02 => Some(Opcode::Push),
+            0x03 => Some(Opcode::PushByte),
+            0x04 => Some(Opcode::Pop),
+            0x05 => Some(Opcode::Dup),
+            0x06 => Some(Opcode::Swap),
+            0x10 => Some(Opcode::Add),
+            0x11 => Some(Opcode::Sub),
+            0x12 => Some(Opcode::Eq),
+            0x13 => Some(Opcode::Lt),
+            0x14 => Some(Opcode::Gt),
+            0x15 => Some(Opcode::And),
+            0x16 => Some(Opcode::Or),
+            0x17 => Some(Opcode::Not),
+            0x20 => Some(Opcode::Jump),
+            0x21 => Some(Opcode::JumpIf),
+            0x30 => Some(Opcode::Load),
+            0x31 => Some(Opcode::PayloadByte),
+            0x32 => Some(Opcode::PayloadPrefix),
+            _ => None,
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(opcode: Opcode) -> u8 {
+        match opcode {
+            Opcode::Deny => 0x00,
+            Opcode::Allow => 0x01,
+            Opcode::Push => 0x02,
+            Opcode::PushByte => 0x03,
+            Opcode::Pop => 0x04,
+            Opcode::Dup => 0x05,
+            Opcode::Swap => 0x06,
+            Opcode::Add => 0x10,
+            Opcode::Sub => 0x11,
+            Opcode::Eq => 0x12,
+            Opcode::Lt => 0x13,
+            Opcode::Gt => 0x14,
+            Opcode::And => 0x15,
+            Opcode::Or => 0x16,
+            Opcode::Not => 0x17,
+            Opcode::Jump => 0x20,
+            Opcode::JumpIf => 0x21,
+            Opcode::Load => 0x30,
+            Opcode::PayloadByte => 0x31,
+            Opcode::PayloadPrefix => 0x32,
+        }
+    }
+}
+
+/// Facts about the operation and key which programs can [`Opcode::Load`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Field {
+    /// Domain identifier
+    Domain,
+
+    /// Key slot
+    Slot,
+
+    /// Key algorithm (wire identifier of a [`key::Algorithm`])
+    ///
+    /// [`key::Algorithm`]: crate::key::Algorithm
+    Algorithm,
+
+    /// Hash algorithm of the payload (wire identifier of a
+    /// [`policy::HashAlgorithm`])
+    ///
+    /// [`policy::HashAlgorithm`]: crate::policy::HashAlgorithm
+    HashAlgorithm,
+
+    /// Length of the payload (the message, or the digest if prehashed)
+    PayloadLength,
+
+    /// Number of requests the device has received since it started
+    RequestCount,
+
+    /// Number of administrators who approved the operation
+    Approvals,
+
+    /// 1 if the caller authenticated within their session, otherwise 0
+    Authenticated,
+}
+
+impl Field {
+    /// Get the field with the given encoding, if it's a known one
+    pub fn from_u8(field: u8) -> Option<Self> {
+        match field {
+            0 => Some(Field::Domain),
+            1 => Some(Field::Slot),
+            2 => Some(Field::Algorithm),
+            3 => Some(Field::HashAlgorithm),
+            4 => Some(Field::PayloadLength),
+            5 => Some(Field::RequestCount),
+            6 => Some(Field::Approvals),
+            7 => Some(Field::Authenticated),
+            _ => None,
+        }
+    }
+}
+
+impl From<Field> for u8 {
+    fn from(field: Field) -> u8 {
+        match field {
+            Field::Domain => 0,
+            Field::Slot => 1,
+            Field::Algorithm => 2,
+            Field::HashAlgorithm => 3,
+            Field::PayloadLength => 4,
+            Field::RequestCount => 5,
+            Field::Approvals => 6,
+            Field::Authenticated => 7,
+        }
+    }
+}
+
+/// Authorization program
+#[derive(Message, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Program {
+    /// Program bytecode (empty if no program is installed)
+    #[field(tag = 0, wire_type = "bytes", critical = true, max = 256)]
+    pub code: Code,
+}
+
+/// Request to install an authorization program in a domain (signed by the
+/// domain's administrators or the root keys)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct InstallRequest {
+    /// Domain to install the program in
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+
+    /// Program to install, replacing any installed previously (or an empty
+    /// program to remove it)
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub program: Program,
+
+    /// Date/time when the program is installed (agreed upon by all signers)
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (to be signed by the domain administrators or
+    /// root keys)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Program installation request along with signatures over its digest
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedInstallRequest {
+    /// Program installation request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: InstallRequest,
+
+    /// Signatures over the installation request's digest under the binding
+    /// (see [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to a program being installed
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct InstallResponse {
+    /// Domain the program was installed in
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub domain: domain::Id,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, InstallRequest, Opcode, Program, SignedInstallRequest};
+    use crate::{authorization::tests::example_binding, Signature, Timestamp};
+    use heapless::{consts::U512, Vec};
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn opcode_round_trip() {
+        for byte in 0..=255 {
+            if let Some(opcode) = Opcode::from_u8(byte) {
+                assert_eq!(u8::from(opcode), byte);
+            }
+        }
+
+        assert_eq!(Opcode::from_u8(0xff), None);
+    }
+
+    #[test]
+    fn field_round_trip() {
+        for byte in 0..8 {
+            assert_eq!(u8::from(Field::from_u8(byte).unwrap()), byte);
+        }
+
+        assert_eq!(Field::from_u8(8), None);
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        let mut program = Program::default();
+        program
+            .code
+            .extend_from_slice(&[
+                Opcode::Load.into(),
+                Field::Slot.into(),
+                Opcode::JumpIf.into(),
+                6,
+                0,
+                Opcode::Allow.into(),
+                Opcode::Deny.into(),
+            ])
+            .unwrap();
+
+        let mut signatures = crate::signature::Signatures::new();
+        signatures.push(Signature::Ed25519([1; 64])).unwrap();
+
+        let signed_request = SignedInstallRequest {
+            request: InstallRequest {
+                domain: 1,
+                program,
+                timestamp: Timestamp::from_slice(&[
+                    64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208,
+                ])
+                .unwrap(),
+                digest: None,
+            },
+            signatures,
+            binding: example_binding(),
+        };
+
+        let mut buffer: Vec<u8, U512> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 512]).unwrap();
+        signed_request.encode(&mut buffer).unwrap();
+        buffer.truncate(signed_request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = SignedInstallRequest::decode(&mut decoder, &buffer).unwrap();
+        assert_eq!(decoded.request.program, signed_request.request.program);
+        assert_eq!(decoded.signatures, signed_request.signatures);
+        assert!(decoded.request.digest.is_some());
+    }
+}
@@ -1,9 +1,15 @@
 //! Armistice device provisioning messages: performs initial device setup
 
-use crate::{public_key::PublicKey, Timestamp, Uuid};
+use crate::{
+    authorization::Binding, public_key::PublicKey, signature::Signatures, Timestamp, Uuid,
+};
 use heapless::{consts::U8, Vec};
+use sha2::{Digest, Sha256};
 use veriform::{Message, Sha256Digest};
 
+/// Domain separation string used when deriving root configuration UUIDs
+const UUID_DERIVATION_DOMAIN: &[u8] = b"armistice.root.uuid";
+
 /// Root keys collection
 pub type RootKeys = Vec<PublicKey, U8>;
 
@@ -27,6 +33,55 @@ pub struct Request {
     pub digest: Option<Sha256Digest>,
 }
 
+impl Request {
+    /// Deterministically derive the UUID of the root configuration
+    /// established by this request.
+    ///
+    /// The UUID is computed from the request's digest (which commits to the
+    /// root keys, threshold, and timestamp) along with a device-unique salt,
+    /// so the same request produces a different UUID on every device.
+    ///
+    /// Returns `None` if the digest hasn't been computed, i.e. the request
+    /// was not produced by decoding a message.
+    pub fn uuid(&self, device_salt: &[u8]) -> Option<Uuid> {
+        let digest = self.digest.as_ref()?;
+
+        let mut hasher = Sha256::new();
+        hasher.input(UUID_DERIVATION_DOMAIN);
+        hasher.input((device_salt.len() as u64).to_be_bytes());
+        hasher.input(device_salt);
+        hasher.input(digest);
+
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hasher.result()[..16]);
+
+        // Set the RFC 4122 variant and version 8 (custom) bits
+        bytes[6] = (bytes[6] & 0x0f) | 0x80;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        Some(Uuid::from_bytes(bytes))
+    }
+}
+
+/// Provisioning request along with signatures over its digest from a
+/// threshold of the root keys it contains
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedRequest {
+    /// Provisioning request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: Request,
+
+    /// Signatures over the provisioning request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub signatures: Signatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
 /// Response to a device being provisioned
 #[derive(Message, Clone, Debug, Eq, PartialEq)]
 pub struct Response {
@@ -37,9 +92,12 @@ pub struct Response {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use super::{Request, Response};
-    use crate::{PublicKey, Timestamp, Uuid};
-    use heapless::{consts::U128, Vec};
+    use super::{Request, Response, SignedRequest};
+    use crate::{authorization::tests::example_binding, PublicKey, Signature, Timestamp, Uuid};
+    use heapless::{
+        consts::{U128, U256},
+        Vec,
+    };
     use veriform::{Decoder, Message};
 
     /// Get an example timestamp
@@ -77,6 +135,18 @@ pub(crate) mod tests {
         }
     }
 
+    /// Create an example `provision::SignedRequest`
+    pub(crate) fn example_signed_request() -> SignedRequest {
+        let mut signatures = Vec::new();
+        signatures.push(Signature::Ed25519([42u8; 64])).unwrap();
+
+        SignedRequest {
+            request: example_request(),
+            signatures,
+            binding: example_binding(),
+        }
+    }
+
     /// Create an example `provision::Response`
     pub(crate) fn example_response() -> Response {
         let uuid = Uuid::parse_str("88888888-4444-4444-4444-121212121212").unwrap();
@@ -96,6 +166,46 @@ pub(crate) mod tests {
         assert_eq!(request, Request::decode(&mut decoder, &buffer).unwrap());
     }
 
+    #[test]
+    fn signed_request_round_trip() {
+        let signed_request = example_signed_request();
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        signed_request.encode(&mut buffer).unwrap();
+        buffer.truncate(signed_request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            signed_request,
+            SignedRequest::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn uuid_derivation() {
+        let request = example_request();
+
+        assert_eq!(
+            request.uuid(&[0u8; 16]).unwrap(),
+            Uuid::parse_str("25f0677b-6371-8baa-92ad-2d751f14f057").unwrap()
+        );
+
+        assert_eq!(
+            request
+                .uuid(&[97, 21, 196, 77, 192, 41, 107, 198, 229, 231, 218, 45, 90, 240, 45, 73])
+                .unwrap(),
+            Uuid::parse_str("5c9705da-756c-85aa-8844-acec9e2100c8").unwrap()
+        );
+    }
+
+    #[test]
+    fn uuid_derivation_without_digest() {
+        let mut request = example_request();
+        request.digest = None;
+        assert!(request.uuid(&[0u8; 16]).is_none());
+    }
+
     #[test]
     fn response_round_trip() {
         let response = example_response();
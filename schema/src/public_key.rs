@@ -8,6 +8,18 @@ pub enum PublicKey {
     /// Ed25519 keys
     #[field(tag = 0, wire_type = "bytes", size = 32)]
     Ed25519([u8; 32]),
+
+    /// ECDSA/P-256 keys (compressed SEC1 encoding)
+    #[field(tag = 1, wire_type = "bytes", size = 33)]
+    EcdsaP256([u8; 33]),
+
+    /// ECDSA/secp256k1 keys (compressed SEC1 encoding)
+    #[field(tag = 2, wire_type = "bytes", size = 33)]
+    EcdsaSecp256k1([u8; 33]),
+
+    /// BLS12-381 keys (compressed G1 point)
+    #[field(tag = 3, wire_type = "bytes", size = 48)]
+    Bls12381([u8; 48]),
 }
 
 #[cfg(test)]
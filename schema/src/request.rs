@@ -1,51 +1,452 @@
 //! Armistice request messages
 
-use crate::provision;
+use crate::{approval, challenge, domain, info, key, program, provision, root, session};
 use veriform::Message;
 
 /// Armistice request messages
 #[derive(Message, Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Request {
     /// Perform initial device provisioning
     #[field(tag = 0, wire_type = "message")]
-    Provision(provision::Request),
+    Provision(provision::SignedRequest),
+
+    /// Rotate the root key set
+    #[field(tag = 1, wire_type = "message")]
+    RootRotate(root::SignedRotateRequest),
+
+    /// Create a new domain
+    #[field(tag = 2, wire_type = "message")]
+    DomainCreate(domain::SignedCreateRequest),
+
+    /// Update an existing domain
+    #[field(tag = 3, wire_type = "message")]
+    DomainUpdate(domain::SignedUpdateRequest),
+
+    /// Delete a domain
+    #[field(tag = 4, wire_type = "message")]
+    DomainDelete(domain::SignedDeleteRequest),
+
+    /// List all domains
+    #[field(tag = 5, wire_type = "message")]
+    DomainList(domain::ListRequest),
+
+    /// Generate a new key within a domain
+    #[field(tag = 6, wire_type = "message")]
+    GenerateKey(key::SignedGenerateRequest),
+
+    /// Sign a message
+    #[field(tag = 7, wire_type = "message")]
+    Sign(key::SignRequest),
+
+    /// Prove possession of a key (BLS)
+    #[field(tag = 8, wire_type = "message")]
+    ProvePossession(key::PossessionRequest),
+
+    /// Get information about the device
+    #[field(tag = 9, wire_type = "message")]
+    GetInfo(info::Request),
+
+    /// Get the public key of a key
+    #[field(tag = 10, wire_type = "message")]
+    GetPublicKey(key::PublicKeyRequest),
+
+    /// Export a key wrapped under the root key
+    #[field(tag = 11, wire_type = "message")]
+    ExportKey(key::SignedExportRequest),
+
+    /// Import a previously exported key
+    #[field(tag = 12, wire_type = "message")]
+    ImportKey(key::SignedImportRequest),
+
+    /// Establish an encrypted session
+    #[field(tag = 13, wire_type = "message")]
+    SessionInit(session::InitRequest),
+
+    /// Request encrypted within a session
+    #[field(tag = 14, wire_type = "message")]
+    Session(session::Encrypted),
+
+    /// Get a challenge to sign to approve a pending operation
+    #[field(tag = 15, wire_type = "message")]
+    GetChallenge(challenge::Request),
+
+    /// Add an operation to the approval queue
+    #[field(tag = 16, wire_type = "message")]
+    SubmitOperation(approval::SubmitRequest),
+
+    /// Approve a pending operation
+    #[field(tag = 17, wire_type = "message")]
+    Approve(approval::ApproveRequest),
+
+    /// List pending operations
+    #[field(tag = 18, wire_type = "message")]
+    ListPending(approval::ListRequest),
+
+    /// Withdraw a pending operation
+    #[field(tag = 19, wire_type = "message")]
+    Cancel(approval::CancelRequest),
+
+    /// Identify the client within an established session
+    #[field(tag = 20, wire_type = "message")]
+    SessionAuthenticate(session::AuthenticateRequest),
+
+    /// Install an authorization program in a domain
+    #[field(tag = 21, wire_type = "message")]
+    ProgramInstall(program::SignedInstallRequest),
 }
 
 // TODO(tarcieri): add to custom derive support for `veriform::Message`
 impl Request {
     /// Get a provisioning request, if this is one
-    pub fn provision(&self) -> Option<&provision::Request> {
+    pub fn provision(&self) -> Option<&provision::SignedRequest> {
         match self {
             Request::Provision(provision) => Some(provision),
+            _ => None,
+        }
+    }
+
+    /// Get a root rotation request, if this is one
+    pub fn root_rotate(&self) -> Option<&root::SignedRotateRequest> {
+        match self {
+            Request::RootRotate(rotate) => Some(rotate),
+            _ => None,
+        }
+    }
+
+    /// Get a domain creation request, if this is one
+    pub fn domain_create(&self) -> Option<&domain::SignedCreateRequest> {
+        match self {
+            Request::DomainCreate(create) => Some(create),
+            _ => None,
+        }
+    }
+
+    /// Get a domain update request, if this is one
+    pub fn domain_update(&self) -> Option<&domain::SignedUpdateRequest> {
+        match self {
+            Request::DomainUpdate(update) => Some(update),
+            _ => None,
+        }
+    }
+
+    /// Get a domain deletion request, if this is one
+    pub fn domain_delete(&self) -> Option<&domain::SignedDeleteRequest> {
+        match self {
+            Request::DomainDelete(delete) => Some(delete),
+            _ => None,
+        }
+    }
+
+    /// Get a domain list request, if this is one
+    pub fn domain_list(&self) -> Option<&domain::ListRequest> {
+        match self {
+            Request::DomainList(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Get a key generation request, if this is one
+    pub fn generate_key(&self) -> Option<&key::SignedGenerateRequest> {
+        match self {
+            Request::GenerateKey(generate) => Some(generate),
+            _ => None,
+        }
+    }
+
+    /// Get a signing request, if this is one
+    pub fn sign(&self) -> Option<&key::SignRequest> {
+        match self {
+            Request::Sign(sign) => Some(sign),
+            _ => None,
+        }
+    }
+
+    /// Get a proof of possession request, if this is one
+    pub fn prove_possession(&self) -> Option<&key::PossessionRequest> {
+        match self {
+            Request::ProvePossession(possession) => Some(possession),
+            _ => None,
+        }
+    }
+
+    /// Get a device information request, if this is one
+    pub fn get_info(&self) -> Option<&info::Request> {
+        match self {
+            Request::GetInfo(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Get the public key request, if this is one
+    pub fn get_public_key(&self) -> Option<&key::PublicKeyRequest> {
+        match self {
+            Request::GetPublicKey(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Get the key export request, if this is one
+    pub fn export_key(&self) -> Option<&key::SignedExportRequest> {
+        match self {
+            Request::ExportKey(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Get the key import request, if this is one
+    pub fn import_key(&self) -> Option<&key::SignedImportRequest> {
+        match self {
+            Request::ImportKey(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Get the session establishment request, if this is one
+    pub fn session_init(&self) -> Option<&session::InitRequest> {
+        match self {
+            Request::SessionInit(init) => Some(init),
+            _ => None,
+        }
+    }
+
+    /// Get the encrypted request, if this is one
+    pub fn session(&self) -> Option<&session::Encrypted> {
+        match self {
+            Request::Session(session) => Some(session),
+            _ => None,
+        }
+    }
+
+    /// Get a challenge request, if this is one
+    pub fn get_challenge(&self) -> Option<&challenge::Request> {
+        match self {
+            Request::GetChallenge(challenge) => Some(challenge),
+            _ => None,
+        }
+    }
+
+    /// Get an operation submission request, if this is one
+    pub fn submit_operation(&self) -> Option<&approval::SubmitRequest> {
+        match self {
+            Request::SubmitOperation(submit) => Some(submit),
+            _ => None,
+        }
+    }
+
+    /// Get an approval request, if this is one
+    pub fn approve(&self) -> Option<&approval::ApproveRequest> {
+        match self {
+            Request::Approve(approve) => Some(approve),
+            _ => None,
+        }
+    }
+
+    /// Get a pending operation list request, if this is one
+    pub fn list_pending(&self) -> Option<&approval::ListRequest> {
+        match self {
+            Request::ListPending(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Get a cancellation request, if this is one
+    pub fn cancel(&self) -> Option<&approval::CancelRequest> {
+        match self {
+            Request::Cancel(cancel) => Some(cancel),
+            _ => None,
+        }
+    }
+
+    /// Get a session authentication request, if this is one
+    pub fn session_authenticate(&self) -> Option<&session::AuthenticateRequest> {
+        match self {
+            Request::SessionAuthenticate(authenticate) => Some(authenticate),
+            _ => None,
+        }
+    }
+
+    /// Get a program installation request, if this is one
+    pub fn program_install(&self) -> Option<&program::SignedInstallRequest> {
+        match self {
+            Request::ProgramInstall(install) => Some(install),
+            _ => None,
         }
     }
 }
 
 // TODO(tarcieri): add to custom derive support for `veriform::Message`
-impl From<provision::Request> for Request {
-    fn from(request: provision::Request) -> Self {
+impl From<provision::SignedRequest> for Request {
+    fn from(request: provision::SignedRequest) -> Self {
         Request::Provision(request)
     }
 }
 
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<root::SignedRotateRequest> for Request {
+    fn from(request: root::SignedRotateRequest) -> Self {
+        Request::RootRotate(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::SignedCreateRequest> for Request {
+    fn from(request: domain::SignedCreateRequest) -> Self {
+        Request::DomainCreate(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::SignedUpdateRequest> for Request {
+    fn from(request: domain::SignedUpdateRequest) -> Self {
+        Request::DomainUpdate(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::SignedDeleteRequest> for Request {
+    fn from(request: domain::SignedDeleteRequest) -> Self {
+        Request::DomainDelete(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::ListRequest> for Request {
+    fn from(request: domain::ListRequest) -> Self {
+        Request::DomainList(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::SignedGenerateRequest> for Request {
+    fn from(request: key::SignedGenerateRequest) -> Self {
+        Request::GenerateKey(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::SignRequest> for Request {
+    fn from(request: key::SignRequest) -> Self {
+        Request::Sign(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::PossessionRequest> for Request {
+    fn from(request: key::PossessionRequest) -> Self {
+        Request::ProvePossession(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<info::Request> for Request {
+    fn from(request: info::Request) -> Self {
+        Request::GetInfo(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::PublicKeyRequest> for Request {
+    fn from(request: key::PublicKeyRequest) -> Self {
+        Request::GetPublicKey(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::SignedExportRequest> for Request {
+    fn from(request: key::SignedExportRequest) -> Self {
+        Request::ExportKey(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::SignedImportRequest> for Request {
+    fn from(request: key::SignedImportRequest) -> Self {
+        Request::ImportKey(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<session::InitRequest> for Request {
+    fn from(request: session::InitRequest) -> Self {
+        Request::SessionInit(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<session::Encrypted> for Request {
+    fn from(request: session::Encrypted) -> Self {
+        Request::Session(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<challenge::Request> for Request {
+    fn from(request: challenge::Request) -> Self {
+        Request::GetChallenge(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::SubmitRequest> for Request {
+    fn from(request: approval::SubmitRequest) -> Self {
+        Request::SubmitOperation(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::ApproveRequest> for Request {
+    fn from(request: approval::ApproveRequest) -> Self {
+        Request::Approve(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::ListRequest> for Request {
+    fn from(request: approval::ListRequest) -> Self {
+        Request::ListPending(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::CancelRequest> for Request {
+    fn from(request: approval::CancelRequest) -> Self {
+        Request::Cancel(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<session::AuthenticateRequest> for Request {
+    fn from(request: session::AuthenticateRequest) -> Self {
+        Request::SessionAuthenticate(request)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<program::SignedInstallRequest> for Request {
+    fn from(request: program::SignedInstallRequest) -> Self {
+        Request::ProgramInstall(request)
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::Request;
     use crate::provision;
-    use heapless::{consts::U128, Vec};
+    use heapless::{consts::U256, Vec};
     use veriform::{Decoder, Message};
 
     /// Create an example `Request`
     pub(crate) fn example_message() -> Request {
-        Request::Provision(provision::tests::example_request())
+        Request::Provision(provision::tests::example_signed_request())
     }
 
     #[test]
     fn encoding_round_trip() {
         let request = example_message();
 
-        let mut buffer: Vec<u8, U128> = Vec::new();
-        buffer.extend_from_slice(&[0u8; 128]).unwrap();
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
         request.encode(&mut buffer).unwrap();
         buffer.truncate(request.encoded_len());
 
@@ -1,14 +1,103 @@
 //! Armistice response messages
 
-use crate::provision;
+use crate::{approval, challenge, domain, error, info, key, program, provision, root, session};
 use veriform::Message;
 
 /// Armistice response messages
 #[derive(Message, Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Response {
     /// Perform initial device provisioning
     #[field(tag = 0, wire_type = "message")]
     Provision(provision::Response),
+
+    /// Rotate the root key set
+    #[field(tag = 1, wire_type = "message")]
+    RootRotate(root::RotateResponse),
+
+    /// Create a new domain
+    #[field(tag = 2, wire_type = "message")]
+    DomainCreate(domain::CreateResponse),
+
+    /// Update an existing domain
+    #[field(tag = 3, wire_type = "message")]
+    DomainUpdate(domain::UpdateResponse),
+
+    /// Delete a domain
+    #[field(tag = 4, wire_type = "message")]
+    DomainDelete(domain::DeleteResponse),
+
+    /// List all domains
+    #[field(tag = 5, wire_type = "message")]
+    DomainList(domain::ListResponse),
+
+    /// Generate a new key within a domain
+    #[field(tag = 6, wire_type = "message")]
+    GenerateKey(key::GenerateResponse),
+
+    /// Sign a message
+    #[field(tag = 7, wire_type = "message")]
+    Sign(key::SignResponse),
+
+    /// Prove possession of a key (BLS)
+    #[field(tag = 8, wire_type = "message")]
+    ProvePossession(key::PossessionResponse),
+
+    /// Request failed
+    #[field(tag = 9, wire_type = "message")]
+    Error(error::Response),
+
+    /// Get information about the device
+    #[field(tag = 10, wire_type = "message")]
+    GetInfo(info::Response),
+
+    /// Get the public key of a key
+    #[field(tag = 11, wire_type = "message")]
+    GetPublicKey(key::PublicKeyResponse),
+
+    /// Export a key wrapped under the root key
+    #[field(tag = 12, wire_type = "message")]
+    ExportKey(key::ExportResponse),
+
+    /// Import a previously exported key
+    #[field(tag = 13, wire_type = "message")]
+    ImportKey(key::ImportResponse),
+
+    /// Session established
+    #[field(tag = 14, wire_type = "message")]
+    SessionInit(session::InitResponse),
+
+    /// Response encrypted within a session
+    #[field(tag = 15, wire_type = "message")]
+    Session(session::Encrypted),
+
+    /// Challenge issued for a pending operation
+    #[field(tag = 16, wire_type = "message")]
+    GetChallenge(challenge::Response),
+
+    /// Operation added to the approval queue
+    #[field(tag = 17, wire_type = "message")]
+    SubmitOperation(approval::SubmitResponse),
+
+    /// Approval recorded (the operation remains pending)
+    #[field(tag = 18, wire_type = "message")]
+    Approve(approval::ApproveResponse),
+
+    /// List pending operations
+    #[field(tag = 19, wire_type = "message")]
+    ListPending(approval::ListResponse),
+
+    /// Pending operation withdrawn
+    #[field(tag = 20, wire_type = "message")]
+    Cancel(approval::CancelResponse),
+
+    /// Client identified within the session
+    #[field(tag = 21, wire_type = "message")]
+    SessionAuthenticate(session::AuthenticateResponse),
+
+    /// Authorization program installed
+    #[field(tag = 22, wire_type = "message")]
+    ProgramInstall(program::InstallResponse),
 }
 
 // TODO(tarcieri): add to custom derive support for `veriform::Message`
@@ -17,6 +106,183 @@ impl Response {
     pub fn provision(&self) -> Option<&provision::Response> {
         match self {
             Response::Provision(provision) => Some(provision),
+            _ => None,
+        }
+    }
+
+    /// Get a root rotation response, if this is one
+    pub fn root_rotate(&self) -> Option<&root::RotateResponse> {
+        match self {
+            Response::RootRotate(rotate) => Some(rotate),
+            _ => None,
+        }
+    }
+
+    /// Get a domain creation response, if this is one
+    pub fn domain_create(&self) -> Option<&domain::CreateResponse> {
+        match self {
+            Response::DomainCreate(create) => Some(create),
+            _ => None,
+        }
+    }
+
+    /// Get a domain update response, if this is one
+    pub fn domain_update(&self) -> Option<&domain::UpdateResponse> {
+        match self {
+            Response::DomainUpdate(update) => Some(update),
+            _ => None,
+        }
+    }
+
+    /// Get a domain deletion response, if this is one
+    pub fn domain_delete(&self) -> Option<&domain::DeleteResponse> {
+        match self {
+            Response::DomainDelete(delete) => Some(delete),
+            _ => None,
+        }
+    }
+
+    /// Get a domain list response, if this is one
+    pub fn domain_list(&self) -> Option<&domain::ListResponse> {
+        match self {
+            Response::DomainList(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Get a key generation response, if this is one
+    pub fn generate_key(&self) -> Option<&key::GenerateResponse> {
+        match self {
+            Response::GenerateKey(generate) => Some(generate),
+            _ => None,
+        }
+    }
+
+    /// Get a signing response, if this is one
+    pub fn sign(&self) -> Option<&key::SignResponse> {
+        match self {
+            Response::Sign(sign) => Some(sign),
+            _ => None,
+        }
+    }
+
+    /// Get a proof of possession response, if this is one
+    pub fn prove_possession(&self) -> Option<&key::PossessionResponse> {
+        match self {
+            Response::ProvePossession(possession) => Some(possession),
+            _ => None,
+        }
+    }
+
+    /// Get an error response, if this is one
+    pub fn error(&self) -> Option<&error::Response> {
+        match self {
+            Response::Error(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Get a device information response, if this is one
+    pub fn get_info(&self) -> Option<&info::Response> {
+        match self {
+            Response::GetInfo(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Get the public key response, if this is one
+    pub fn get_public_key(&self) -> Option<&key::PublicKeyResponse> {
+        match self {
+            Response::GetPublicKey(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Get the key export response, if this is one
+    pub fn export_key(&self) -> Option<&key::ExportResponse> {
+        match self {
+            Response::ExportKey(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Get the key import response, if this is one
+    pub fn import_key(&self) -> Option<&key::ImportResponse> {
+        match self {
+            Response::ImportKey(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Get the session establishment response, if this is one
+    pub fn session_init(&self) -> Option<&session::InitResponse> {
+        match self {
+            Response::SessionInit(init) => Some(init),
+            _ => None,
+        }
+    }
+
+    /// Get the encrypted response, if this is one
+    pub fn session(&self) -> Option<&session::Encrypted> {
+        match self {
+            Response::Session(session) => Some(session),
+            _ => None,
+        }
+    }
+
+    /// Get the challenge response, if this is one
+    pub fn get_challenge(&self) -> Option<&challenge::Response> {
+        match self {
+            Response::GetChallenge(challenge) => Some(challenge),
+            _ => None,
+        }
+    }
+
+    /// Get an operation submission response, if this is one
+    pub fn submit_operation(&self) -> Option<&approval::SubmitResponse> {
+        match self {
+            Response::SubmitOperation(submit) => Some(submit),
+            _ => None,
+        }
+    }
+
+    /// Get an approval response, if this is one
+    pub fn approve(&self) -> Option<&approval::ApproveResponse> {
+        match self {
+            Response::Approve(approve) => Some(approve),
+            _ => None,
+        }
+    }
+
+    /// Get a pending operation list response, if this is one
+    pub fn list_pending(&self) -> Option<&approval::ListResponse> {
+        match self {
+            Response::ListPending(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Get a cancellation response, if this is one
+    pub fn cancel(&self) -> Option<&approval::CancelResponse> {
+        match self {
+            Response::Cancel(cancel) => Some(cancel),
+            _ => None,
+        }
+    }
+
+    /// Get a session authentication response, if this is one
+    pub fn session_authenticate(&self) -> Option<&session::AuthenticateResponse> {
+        match self {
+            Response::SessionAuthenticate(authenticate) => Some(authenticate),
+            _ => None,
+        }
+    }
+
+    /// Get a program installation response, if this is one
+    pub fn program_install(&self) -> Option<&program::InstallResponse> {
+        match self {
+            Response::ProgramInstall(install) => Some(install),
+            _ => None,
         }
     }
 }
@@ -28,6 +294,160 @@ impl From<provision::Response> for Response {
     }
 }
 
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<root::RotateResponse> for Response {
+    fn from(response: root::RotateResponse) -> Response {
+        Response::RootRotate(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::CreateResponse> for Response {
+    fn from(response: domain::CreateResponse) -> Response {
+        Response::DomainCreate(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::UpdateResponse> for Response {
+    fn from(response: domain::UpdateResponse) -> Response {
+        Response::DomainUpdate(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::DeleteResponse> for Response {
+    fn from(response: domain::DeleteResponse) -> Response {
+        Response::DomainDelete(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<domain::ListResponse> for Response {
+    fn from(response: domain::ListResponse) -> Response {
+        Response::DomainList(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::GenerateResponse> for Response {
+    fn from(response: key::GenerateResponse) -> Response {
+        Response::GenerateKey(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::SignResponse> for Response {
+    fn from(response: key::SignResponse) -> Response {
+        Response::Sign(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::PossessionResponse> for Response {
+    fn from(response: key::PossessionResponse) -> Response {
+        Response::ProvePossession(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<error::Response> for Response {
+    fn from(response: error::Response) -> Response {
+        Response::Error(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<info::Response> for Response {
+    fn from(response: info::Response) -> Response {
+        Response::GetInfo(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::PublicKeyResponse> for Response {
+    fn from(response: key::PublicKeyResponse) -> Response {
+        Response::GetPublicKey(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::ExportResponse> for Response {
+    fn from(response: key::ExportResponse) -> Response {
+        Response::ExportKey(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<key::ImportResponse> for Response {
+    fn from(response: key::ImportResponse) -> Response {
+        Response::ImportKey(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<session::InitResponse> for Response {
+    fn from(response: session::InitResponse) -> Response {
+        Response::SessionInit(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<session::Encrypted> for Response {
+    fn from(response: session::Encrypted) -> Response {
+        Response::Session(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<challenge::Response> for Response {
+    fn from(response: challenge::Response) -> Response {
+        Response::GetChallenge(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::SubmitResponse> for Response {
+    fn from(response: approval::SubmitResponse) -> Response {
+        Response::SubmitOperation(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::ApproveResponse> for Response {
+    fn from(response: approval::ApproveResponse) -> Response {
+        Response::Approve(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::ListResponse> for Response {
+    fn from(response: approval::ListResponse) -> Response {
+        Response::ListPending(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<approval::CancelResponse> for Response {
+    fn from(response: approval::CancelResponse) -> Response {
+        Response::Cancel(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<session::AuthenticateResponse> for Response {
+    fn from(response: session::AuthenticateResponse) -> Response {
+        Response::SessionAuthenticate(response)
+    }
+}
+
+// TODO(tarcieri): add to custom derive support for `veriform::Message`
+impl From<program::InstallResponse> for Response {
+    fn from(response: program::InstallResponse) -> Response {
+        Response::ProgramInstall(response)
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::Response;
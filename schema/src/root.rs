@@ -0,0 +1,130 @@
+//! Root role messages: manage the keys which control administrative authority
+//!
+//! Root rotation is modeled on The Update Framework's root key rotation.
+//! See Section 4.3 and 5.2 of the TUF spec:
+//!
+//! <https://github.com/theupdateframework/specification/blob/master/tuf-spec.md>
+
+use crate::{authorization::Binding, threshold::ThresholdKeySet, Signature, Timestamp};
+use heapless::{consts::U16, Vec};
+use veriform::{Message, Sha256Digest};
+
+/// Signatures over a root rotation: room for a threshold of both the current
+/// and the new root keys, even when the two key sets are disjoint
+pub type RotationSignatures = Vec<Signature, U16>;
+
+/// Request to rotate the root key set
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct RotateRequest {
+    /// New root version number (must be exactly one greater than the current)
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub version: u64,
+
+    /// New threshold key set for the root role
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub key_set: ThresholdKeySet,
+
+    /// Date/time when rotation occurs (agreed upon by all signers)
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub timestamp: Timestamp,
+
+    /// Digest of this message (to be signed by both the old and new root keys)
+    #[digest(alg = "sha256")]
+    pub digest: Option<Sha256Digest>,
+}
+
+/// Root rotation request along with signatures over its digest from a
+/// threshold of both the current and the new root keys
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct SignedRotateRequest {
+    /// Root rotation request which has been signed
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub request: RotateRequest,
+
+    /// Signatures over the rotation request's digest under the binding (see
+    /// [`Binding::digest`])
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 16)]
+    pub signatures: RotationSignatures,
+
+    /// Binding of the request to the device and authorization counter,
+    /// which the signatures also cover
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub binding: Binding,
+}
+
+/// Response to the root key set being rotated
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct RotateResponse {
+    /// Root version number which is now in effect
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub version: u64,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{RotateRequest, RotateResponse, SignedRotateRequest};
+    use crate::{authorization::tests::example_binding, threshold, Signature, Timestamp};
+    use heapless::{consts::U256, Vec};
+    use veriform::{Decoder, Message};
+
+    /// Create an example `root::SignedRotateRequest`
+    pub(crate) fn example_signed_request() -> SignedRotateRequest {
+        let mut signatures = Vec::new();
+        signatures.push(Signature::Ed25519([1u8; 64])).unwrap();
+        signatures.push(Signature::Ed25519([2u8; 64])).unwrap();
+
+        // TAI64N for 2020-05-21
+        let timestamp =
+            Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208]).unwrap();
+
+        SignedRotateRequest {
+            request: RotateRequest {
+                version: 2,
+                key_set: threshold::tests::example_key_set(),
+                timestamp,
+                digest: None,
+            },
+            signatures,
+            binding: example_binding(),
+        }
+    }
+
+    /// Create an example `root::RotateResponse`
+    pub(crate) fn example_response() -> RotateResponse {
+        RotateResponse { version: 2 }
+    }
+
+    #[test]
+    fn signed_request_round_trip() {
+        let signed_request = example_signed_request();
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        signed_request.encode(&mut buffer).unwrap();
+        buffer.truncate(signed_request.encoded_len());
+
+        let mut decoder = Decoder::new();
+        let decoded = SignedRotateRequest::decode(&mut decoder, &buffer).unwrap();
+
+        assert_eq!(signed_request.request.version, decoded.request.version);
+        assert_eq!(signed_request.request.key_set, decoded.request.key_set);
+        assert_eq!(signed_request.signatures, decoded.signatures);
+        assert!(decoded.request.digest.is_some());
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let response = example_response();
+
+        let mut buffer: Vec<u8, U256> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 256]).unwrap();
+        response.encode(&mut buffer).unwrap();
+        buffer.truncate(response.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            response,
+            RotateResponse::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+}
@@ -0,0 +1,716 @@
+//! Sessions: an authenticated, end-to-end encrypted channel between clients
+//! and Armistice Core which hosts (e.g. a USB host or network proxy) relaying
+//! messages can neither read nor modify.
+//!
+//! Sessions are established using the [Noise Protocol Framework] handshake
+//! pattern `NK`, instantiated as `Noise_NK_25519_ChaChaPoly_SHA256`:
+//!
+//! ```text
+//! NK:
+//!   <- s
+//!   ...
+//!   -> e, es
+//!   <- e, ee
+//! ```
+//!
+//! The device's static key is derived from its root key, and clients must
+//! know (i.e. pin) its public key in advance. Clients are anonymous at the
+//! session layer: requests which need authorization carry signatures from a
+//! threshold of root keys or domain administrators. Clients may however
+//! identify themselves once a session is established by sending an
+//! [`AuthenticateRequest`] signed over the session's handshake hash, and
+//! key policies can restrict usage to particular identities (see
+//! [`policy`]).
+//!
+//! Once the handshake is complete, each request is encoded, encrypted, and
+//! sent as an [`Encrypted`] message, and so is its response. Messages are
+//! encrypted under a counter nonce, so they can't be replayed, reordered, or
+//! dropped without the session failing. Devices may hold sessions with
+//! several clients at once (e.g. behind a proxy), each identified by an
+//! [`Id`] the device assigns.
+//!
+//! [Noise Protocol Framework]: https://noiseprotocol.org/noise.html
+//! [`policy`]: crate::policy
+
+use crate::Signature;
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, AeadInPlace, NewAead},
+    ChaCha20Poly1305, Nonce, Tag,
+};
+use core::fmt;
+use heapless::{consts::U64, Vec};
+use hmac::{Hmac, Mac};
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use veriform::{Message, Sha256Digest};
+
+pub use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Noise protocol name
+pub const PROTOCOL_NAME: &[u8] = b"Noise_NK_25519_ChaChaPoly_SHA256";
+
+/// Prologue mixed into the handshake: both sides must agree on it
+pub const PROLOGUE: &[u8] = b"armistice";
+
+/// Size of an X25519 public key
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Size of a ChaCha20-Poly1305 tag
+pub const TAG_SIZE: usize = 16;
+
+/// Size of an `NK` handshake message with an empty payload: an ephemeral
+/// public key followed by the tag of the (encrypted) payload
+pub const HANDSHAKE_SIZE: usize = PUBLIC_KEY_SIZE + TAG_SIZE;
+
+/// Size of a SHA-256 hash (`HASHLEN`)
+const HASH_SIZE: usize = 32;
+
+/// Domain separation string used when computing authentication digests
+const AUTHENTICATION_DOMAIN: &[u8] = b"armistice.session.authenticate";
+
+/// Session identifiers: assigned by the device when a session is
+/// established, so it can hold sessions with several clients at once
+pub type Id = u64;
+
+/// Maximum size of a handshake message
+pub type MaxHandshakeSize = U64;
+
+/// Handshake message bytes
+pub type HandshakeBytes = Vec<u8, MaxHandshakeSize>;
+
+/// Maximum size of an encrypted message
+pub type MaxCiphertextSize = crate::framing::MaxMessageSize;
+
+/// Encrypted message bytes (including the tag)
+pub type Ciphertext = Vec<u8, MaxCiphertextSize>;
+
+/// Decrypted message bytes
+pub type Plaintext = Vec<u8, MaxCiphertextSize>;
+
+/// Session errors
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Message exceeds the maximum size
+    Capacity,
+
+    /// Message could not be authenticated (or a key exchange failed)
+    Crypto,
+
+    /// Handshake message is malformed
+    Handshake,
+
+    /// Too many messages have been sent in this session
+    Nonce,
+}
+
+impl Error {
+    /// Get a description of this error
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Error::Capacity => "message too large",
+            Error::Crypto => "message authentication failed",
+            Error::Handshake => "malformed handshake message",
+            Error::Nonce => "session nonces exhausted",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Request to establish a session: the initiator's handshake message
+/// (`-> e, es`)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct InitRequest {
+    /// Noise handshake message
+    #[field(tag = 0, wire_type = "bytes", critical = true, max = 64)]
+    pub handshake: HandshakeBytes,
+}
+
+/// Response establishing a session: the responder's handshake message
+/// (`<- e, ee`)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct InitResponse {
+    /// Noise handshake message
+    #[field(tag = 0, wire_type = "bytes", critical = true, max = 64)]
+    pub handshake: HandshakeBytes,
+
+    /// Identifier of the established session
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub session_id: Id,
+}
+
+/// Encrypted request or response sent within a session
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Encrypted {
+    /// Identifier of the session this message was sent within
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub session_id: Id,
+
+    /// Encoded request or response, encrypted under the session's keys
+    #[field(tag = 1, wire_type = "bytes", critical = true, max = 4096)]
+    pub ciphertext: Ciphertext,
+}
+
+/// Request to identify the client within an established session
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct AuthenticateRequest {
+    /// Public key identifying the client
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub public_key: crate::PublicKey,
+
+    /// Signature over the session's [`Session::authentication_digest`]
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub signature: Signature,
+}
+
+/// Response to the client being identified
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct AuthenticateResponse {
+    /// Public key now identifying the client within the session
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub public_key: crate::PublicKey,
+}
+
+/// Client side of a session handshake
+pub struct Initiator {
+    /// Handshake state
+    symmetric: SymmetricState,
+
+    /// Ephemeral secret key
+    ephemeral: StaticSecret,
+}
+
+impl Initiator {
+    /// Begin a handshake with the device which has the given static public
+    /// key, returning the request to send to it
+    pub fn new(
+        device_key: &PublicKey,
+        mut rng: impl CryptoRng + RngCore,
+    ) -> Result<(Self, InitRequest), Error> {
+        let mut symmetric = SymmetricState::new();
+        symmetric.mix_hash(device_key.as_bytes());
+
+        // -> e
+        let ephemeral = StaticSecret::new(&mut rng);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        symmetric.mix_hash(ephemeral_public.as_bytes());
+
+        // -> es
+        symmetric.mix_key(&diffie_hellman(&ephemeral, device_key)?);
+
+        let mut handshake = HandshakeBytes::new();
+        handshake
+            .extend_from_slice(ephemeral_public.as_bytes())
+            .map_err(|_| Error::Capacity)?;
+
+        symmetric.encrypt_and_hash(&[], &mut handshake)?;
+
+        let initiator = Initiator {
+            symmetric,
+            ephemeral,
+        };
+
+        Ok((initiator, InitRequest { handshake }))
+    }
+
+    /// Complete the handshake using the device's response, establishing a
+    /// [`Session`]
+    pub fn finish(mut self, response: &InitResponse) -> Result<Session, Error> {
+        let (remote_ephemeral, payload) = parse_handshake(&response.handshake)?;
+
+        // <- e
+        self.symmetric.mix_hash(remote_ephemeral.as_bytes());
+
+        // <- ee
+        self.symmetric
+            .mix_key(&diffie_hellman(&self.ephemeral, &remote_ephemeral)?);
+
+        self.symmetric.decrypt_and_hash(payload)?;
+
+        let (send, receive) = self.symmetric.split();
+
+        Ok(Session {
+            id: response.session_id,
+            handshake_hash: self.symmetric.hash,
+            send,
+            receive,
+        })
+    }
+}
+
+/// Device side of a session handshake: respond to the given request using
+/// the device's static secret key, establishing a [`Session`] with the given
+/// identifier
+pub fn respond(
+    static_key: &StaticSecret,
+    session_id: Id,
+    mut rng: impl CryptoRng + RngCore,
+    request: &InitRequest,
+) -> Result<(Session, InitResponse), Error> {
+    let mut symmetric = SymmetricState::new();
+    symmetric.mix_hash(PublicKey::from(static_key).as_bytes());
+
+    let (remote_ephemeral, payload) = parse_handshake(&request.handshake)?;
+
+    // -> e
+    symmetric.mix_hash(remote_ephemeral.as_bytes());
+
+    // -> es
+    symmetric.mix_key(&diffie_hellman(static_key, &remote_ephemeral)?);
+    symmetric.decrypt_and_hash(payload)?;
+
+    // <- e
+    let ephemeral = StaticSecret::new(&mut rng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    symmetric.mix_hash(ephemeral_public.as_bytes());
+
+    // <- ee
+    symmetric.mix_key(&diffie_hellman(&ephemeral, &remote_ephemeral)?);
+
+    let mut handshake = HandshakeBytes::new();
+    handshake
+        .extend_from_slice(ephemeral_public.as_bytes())
+        .map_err(|_| Error::Capacity)?;
+
+    symmetric.encrypt_and_hash(&[], &mut handshake)?;
+
+    // The responder receives with the initiator's sending key
+    let (receive, send) = symmetric.split();
+
+    let session = Session {
+        id: session_id,
+        handshake_hash: symmetric.hash,
+        send,
+        receive,
+    };
+
+    Ok((
+        session,
+        InitResponse {
+            handshake,
+            session_id,
+        },
+    ))
+}
+
+/// Established session: encrypts outgoing messages and decrypts incoming
+/// ones
+pub struct Session {
+    /// Session identifier
+    id: Id,
+
+    /// Hash of the handshake which established this session (identical on
+    /// both sides)
+    handshake_hash: [u8; HASH_SIZE],
+
+    /// Cipher for outgoing messages
+    send: CipherState,
+
+    /// Cipher for incoming messages
+    receive: CipherState,
+}
+
+impl Session {
+    /// Get this session's identifier
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Compute the digest clients sign to identify themselves within this
+    /// session (see [`AuthenticateRequest`]). It's bound to the handshake,
+    /// so signatures can't be replayed in other sessions.
+    pub fn authentication_digest(&self) -> Sha256Digest {
+        let mut hasher = Sha256::new();
+        hasher.input(AUTHENTICATION_DOMAIN);
+        hasher.input(self.handshake_hash);
+
+        let mut digest = Sha256Digest::default();
+        digest.copy_from_slice(&hasher.result());
+        digest
+    }
+
+    /// Encrypt an outgoing (encoded) message
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Encrypted, Error> {
+        let mut ciphertext = Ciphertext::new();
+        ciphertext
+            .extend_from_slice(plaintext)
+            .map_err(|_| Error::Capacity)?;
+
+        self.send.encrypt(&[], &mut ciphertext)?;
+
+        Ok(Encrypted {
+            session_id: self.id,
+            ciphertext,
+        })
+    }
+
+    /// Decrypt an incoming message, which must be the next one sent by the
+    /// other side of the session
+    pub fn decrypt(&mut self, message: &Encrypted) -> Result<Plaintext, Error> {
+        let mut plaintext = Plaintext::new();
+        plaintext
+            .extend_from_slice(&message.ciphertext)
+            .map_err(|_| Error::Capacity)?;
+
+        self.receive.decrypt(&[], &mut plaintext)?;
+        Ok(plaintext)
+    }
+}
+
+/// Noise `CipherState`: a key and a counter nonce
+struct CipherState {
+    /// Symmetric key (`None` until one has been derived)
+    key: Option<[u8; HASH_SIZE]>,
+
+    /// Counter nonce
+    nonce: u64,
+}
+
+impl CipherState {
+    /// Create a cipher state with the given key (if any)
+    fn new(key: Option<[u8; HASH_SIZE]>) -> Self {
+        CipherState { key, nonce: 0 }
+    }
+
+    /// Encrypt the given buffer in place, appending the tag (or leave it
+    /// unchanged if there's no key yet)
+    fn encrypt<N>(&mut self, associated_data: &[u8], buffer: &mut Vec<u8, N>) -> Result<(), Error>
+    where
+        N: heapless::ArrayLength<u8>,
+    {
+        let key = match self.key {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let nonce = self.next_nonce()?;
+
+        let tag = ChaCha20Poly1305::new(GenericArray::from_slice(&key))
+            .encrypt_in_place_detached(&nonce, associated_data, buffer)
+            .map_err(|_| Error::Crypto)?;
+
+        buffer.extend_from_slice(&tag).map_err(|_| Error::Capacity)
+    }
+
+    /// Decrypt the given buffer in place, removing the tag (or leave it
+    /// unchanged if there's no key yet)
+    fn decrypt<N>(&mut self, associated_data: &[u8], buffer: &mut Vec<u8, N>) -> Result<(), Error>
+    where
+        N: heapless::ArrayLength<u8>,
+    {
+        let key = match self.key {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let len = buffer.len().checked_sub(TAG_SIZE).ok_or(Error::Crypto)?;
+        let mut tag = [0u8; TAG_SIZE];
+        tag.copy_from_slice(&buffer[len..]);
+        buffer.truncate(len);
+
+        // The nonce is only consumed if the message is authentic
+        let nonce = nonce_bytes(self.nonce);
+
+        ChaCha20Poly1305::new(GenericArray::from_slice(&key))
+            .decrypt_in_place_detached(&nonce, associated_data, buffer, Tag::from_slice(&tag))
+            .map_err(|_| Error::Crypto)?;
+
+        self.next_nonce().map(|_| ())
+    }
+
+    /// Get the current nonce, incrementing the counter
+    fn next_nonce(&mut self) -> Result<Nonce, Error> {
+        // The maximum nonce value is reserved by the Noise specification
+        if self.nonce == u64::MAX {
+            return Err(Error::Nonce);
+        }
+
+        let nonce = nonce_bytes(self.nonce);
+        self.nonce += 1;
+        Ok(nonce)
+    }
+}
+
+/// Noise `SymmetricState`: the chaining key, handshake hash, and the cipher
+/// state used during the handshake
+struct SymmetricState {
+    /// Chaining key
+    chaining_key: [u8; HASH_SIZE],
+
+    /// Handshake hash
+    hash: [u8; HASH_SIZE],
+
+    /// Cipher state for handshake payloads
+    cipher: CipherState,
+}
+
+impl SymmetricState {
+    /// Initialize the handshake with the protocol name and prologue
+    fn new() -> Self {
+        // The protocol name is exactly `HASHLEN` bytes, so it's used as-is
+        let mut hash = [0u8; HASH_SIZE];
+        hash.copy_from_slice(PROTOCOL_NAME);
+
+        let mut symmetric = SymmetricState {
+            chaining_key: hash,
+            hash,
+            cipher: CipherState::new(None),
+        };
+
+        symmetric.mix_hash(PROLOGUE);
+        symmetric
+    }
+
+    /// Mix data into the handshake hash
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.input(self.hash);
+        hasher.input(data);
+        self.hash.copy_from_slice(&hasher.result());
+    }
+
+    /// Mix key material into the chaining key, deriving a new cipher key
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (chaining_key, key) = hkdf(&self.chaining_key, input_key_material);
+        self.chaining_key = chaining_key;
+        self.cipher = CipherState::new(Some(key));
+    }
+
+    /// Encrypt a handshake payload, mixing the ciphertext into the handshake
+    /// hash and appending it to the handshake message
+    fn encrypt_and_hash(
+        &mut self,
+        payload: &[u8],
+        handshake: &mut HandshakeBytes,
+    ) -> Result<(), Error> {
+        let mut ciphertext = HandshakeBytes::new();
+        ciphertext
+            .extend_from_slice(payload)
+            .map_err(|_| Error::Capacity)?;
+
+        self.cipher.encrypt(&self.hash, &mut ciphertext)?;
+        self.mix_hash(&ciphertext);
+
+        handshake
+            .extend_from_slice(&ciphertext)
+            .map_err(|_| Error::Capacity)
+    }
+
+    /// Decrypt and authenticate a handshake payload, mixing the ciphertext
+    /// into the handshake hash
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<(), Error> {
+        let mut payload = HandshakeBytes::new();
+        payload
+            .extend_from_slice(ciphertext)
+            .map_err(|_| Error::Handshake)?;
+
+        self.cipher.decrypt(&self.hash, &mut payload)?;
+        self.mix_hash(ciphertext);
+        Ok(())
+    }
+
+    /// Derive the cipher states for transport messages: the first is used
+    /// by the initiator to send, and the second by the responder
+    fn split(&self) -> (CipherState, CipherState) {
+        let (initiator_key, responder_key) = hkdf(&self.chaining_key, &[]);
+        (
+            CipherState::new(Some(initiator_key)),
+            CipherState::new(Some(responder_key)),
+        )
+    }
+}
+
+/// Parse a handshake message into the sender's ephemeral public key and the
+/// (encrypted) payload
+fn parse_handshake(handshake: &[u8]) -> Result<(PublicKey, &[u8]), Error> {
+    if handshake.len() != HANDSHAKE_SIZE {
+        return Err(Error::Handshake);
+    }
+
+    let mut public_key = [0u8; PUBLIC_KEY_SIZE];
+    public_key.copy_from_slice(&handshake[..PUBLIC_KEY_SIZE]);
+    Ok((PublicKey::from(public_key), &handshake[PUBLIC_KEY_SIZE..]))
+}
+
+/// Perform an X25519 key exchange, rejecting low-order public keys (which
+/// result in an all-zero shared secret)
+fn diffie_hellman(secret: &StaticSecret, public: &PublicKey) -> Result<[u8; 32], Error> {
+    let shared_secret = secret.diffie_hellman(public).to_bytes();
+
+    if shared_secret.iter().fold(0, |acc, byte| acc | byte) == 0 {
+        return Err(Error::Crypto);
+    }
+
+    Ok(shared_secret)
+}
+
+/// Noise `HKDF` with two outputs
+fn hkdf(chaining_key: &[u8], input_key_material: &[u8]) -> ([u8; HASH_SIZE], [u8; HASH_SIZE]) {
+    let temp_key = hmac(chaining_key, &[input_key_material]);
+    let output1 = hmac(&temp_key, &[&[1]]);
+    let output2 = hmac(&temp_key, &[&output1, &[2]]);
+    (output1, output2)
+}
+
+/// Compute HMAC-SHA-256 over the concatenation of the given inputs
+fn hmac(key: &[u8], inputs: &[&[u8]]) -> [u8; HASH_SIZE] {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any size");
+
+    for input in inputs {
+        mac.input(input);
+    }
+
+    let mut output = [0u8; HASH_SIZE];
+    output.copy_from_slice(&mac.result().code());
+    output
+}
+
+/// Encode a counter nonce as a ChaCha20-Poly1305 nonce: 32 bits of zeroes
+/// followed by the little endian counter
+fn nonce_bytes(counter: u64) -> Nonce {
+    let mut nonce = GenericArray::default();
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        respond, Encrypted, Error, HandshakeBytes, InitRequest, Initiator, PublicKey, Session,
+        StaticSecret,
+    };
+    use heapless::{consts::U128, Vec};
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+    use veriform::{Decoder, Message};
+
+    /// Establish a session with a device using the given static key,
+    /// returning the client and device sides
+    fn establish(device_key: &StaticSecret, rng: &mut ChaChaRng) -> (Session, Session) {
+        let (initiator, request) = Initiator::new(&PublicKey::from(device_key), &mut *rng).unwrap();
+        let (device, response) = respond(device_key, 1, &mut *rng, &request).unwrap();
+        (initiator.finish(&response).unwrap(), device)
+    }
+
+    #[test]
+    fn request_response_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(1);
+        let device_key = StaticSecret::new(&mut rng);
+        let (mut client, mut device) = establish(&device_key, &mut rng);
+        assert_eq!(client.id(), device.id());
+        assert_eq!(
+            client.authentication_digest(),
+            device.authentication_digest()
+        );
+
+        for _ in 0..3 {
+            let request = client.encrypt(b"request").unwrap();
+            assert_ne!(&request.ciphertext[..7], b"request");
+            assert_eq!(&device.decrypt(&request).unwrap()[..], b"request");
+
+            let response = device.encrypt(b"response").unwrap();
+            assert_eq!(&client.decrypt(&response).unwrap()[..], b"response");
+        }
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(2);
+        let device_key = StaticSecret::new(&mut rng);
+        let (mut client, _) = establish(&device_key, &mut rng);
+        let message = client.encrypt(b"example").unwrap();
+
+        let mut buffer: Vec<u8, U128> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 128]).unwrap();
+        message.encode(&mut buffer).unwrap();
+        buffer.truncate(message.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(message, Encrypted::decode(&mut decoder, &buffer).unwrap());
+    }
+
+    #[test]
+    fn wrong_device_key() {
+        let mut rng = ChaChaRng::seed_from_u64(3);
+        let device_key = StaticSecret::new(&mut rng);
+        let impostor_key = StaticSecret::new(&mut rng);
+
+        // An impostor can't complete a handshake meant for the real device
+        let (_, request) = Initiator::new(&PublicKey::from(&device_key), &mut rng).unwrap();
+        assert_eq!(
+            respond(&impostor_key, 1, &mut rng, &request).err(),
+            Some(Error::Crypto)
+        );
+
+        // ...nor convince a client to accept its own handshake
+        let (initiator, request) = Initiator::new(&PublicKey::from(&device_key), &mut rng).unwrap();
+        let (_, mut response) = respond(&device_key, 1, &mut rng, &request).unwrap();
+        let (_, impostor_response) = {
+            let (_, request) = Initiator::new(&PublicKey::from(&impostor_key), &mut rng).unwrap();
+            respond(&impostor_key, 1, &mut rng, &request).unwrap()
+        };
+        response.handshake = impostor_response.handshake;
+        assert_eq!(initiator.finish(&response).err(), Some(Error::Crypto));
+    }
+
+    #[test]
+    fn tampered_message_rejected() {
+        let mut rng = ChaChaRng::seed_from_u64(4);
+        let device_key = StaticSecret::new(&mut rng);
+        let (mut client, mut device) = establish(&device_key, &mut rng);
+
+        let mut request = client.encrypt(b"request").unwrap();
+        request.ciphertext[0] ^= 1;
+        assert_eq!(device.decrypt(&request).err(), Some(Error::Crypto));
+
+        // Failed messages don't consume a nonce, so the session survives
+        request.ciphertext[0] ^= 1;
+        assert_eq!(&device.decrypt(&request).unwrap()[..], b"request");
+    }
+
+    #[test]
+    fn replayed_message_rejected() {
+        let mut rng = ChaChaRng::seed_from_u64(5);
+        let device_key = StaticSecret::new(&mut rng);
+        let (mut client, mut device) = establish(&device_key, &mut rng);
+
+        let request = client.encrypt(b"request").unwrap();
+        device.decrypt(&request).unwrap();
+        assert_eq!(device.decrypt(&request).err(), Some(Error::Crypto));
+
+        // ...including into a different session with the same device
+        let (_, mut other_device) = establish(&device_key, &mut rng);
+        assert_eq!(other_device.decrypt(&request).err(), Some(Error::Crypto));
+    }
+
+    #[test]
+    fn authentication_digest_bound_to_session() {
+        let mut rng = ChaChaRng::seed_from_u64(7);
+        let device_key = StaticSecret::new(&mut rng);
+        let (client, _) = establish(&device_key, &mut rng);
+        let (other_client, _) = establish(&device_key, &mut rng);
+
+        assert_ne!(
+            client.authentication_digest(),
+            other_client.authentication_digest()
+        );
+    }
+
+    #[test]
+    fn malformed_handshake() {
+        let mut rng = ChaChaRng::seed_from_u64(6);
+        let device_key = StaticSecret::new(&mut rng);
+
+        let mut handshake = HandshakeBytes::new();
+        handshake.extend_from_slice(&[0u8; 16]).unwrap();
+        let request = InitRequest { handshake };
+
+        assert_eq!(
+            respond(&device_key, 1, &mut rng, &request).err(),
+            Some(Error::Handshake)
+        );
+    }
+}
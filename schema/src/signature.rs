@@ -0,0 +1,53 @@
+//! Armistice digital signatures
+
+use heapless::{consts::U8, Vec};
+use veriform::Message;
+
+/// Signature collection (e.g. signatures from members of a threshold key set)
+pub type Signatures = Vec<Signature, U8>;
+
+/// Digital signatures
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub enum Signature {
+    /// Ed25519 signatures
+    #[field(tag = 0, wire_type = "bytes", size = 64)]
+    Ed25519([u8; 64]),
+
+    /// ECDSA/P-256 signatures (fixed-width `r || s` encoding)
+    #[field(tag = 1, wire_type = "bytes", size = 64)]
+    EcdsaP256([u8; 64]),
+
+    /// ECDSA/secp256k1 signatures (fixed-width `r || s` encoding)
+    #[field(tag = 2, wire_type = "bytes", size = 64)]
+    EcdsaSecp256k1([u8; 64]),
+
+    /// BLS12-381 signatures (compressed G2 point)
+    #[field(tag = 3, wire_type = "bytes", size = 96)]
+    Bls12381([u8; 96]),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Signature;
+    use heapless::{consts::U128, Vec};
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn encoding_round_trip() {
+        let mut bytes = [0u8; 64];
+
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let signature = Signature::Ed25519(bytes);
+
+        let mut buffer: Vec<u8, U128> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 128]).unwrap();
+        signature.encode(&mut buffer).unwrap();
+        buffer.truncate(signature.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(signature, Message::decode(&mut decoder, &buffer).unwrap());
+    }
+}
@@ -0,0 +1,194 @@
+//! State messages: device state which is sealed under the root key and
+//! persisted to storage so it survives reboots
+//!
+//! These messages contain secret key material and are never sent over the
+//! wire: they are only ever encoded to be encrypted at rest.
+
+use crate::{domain, policy::Policy, program::Program, threshold::ThresholdKeySet, Uuid};
+use heapless::{consts::U8, Vec};
+use veriform::Message;
+
+/// Persisted domains
+pub type Domains = Vec<Domain, U8>;
+
+/// Keys within a persisted domain
+pub type Keys = Vec<Key, U8>;
+
+/// Persisted device state
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct State {
+    /// State version counter (incremented each time state is persisted)
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub version: u64,
+
+    /// Root configuration
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub root: RootConfig,
+
+    /// Domains and the keys they contain
+    #[field(tag = 2, wire_type = "sequence", critical = true, max = 8)]
+    pub domains: Domains,
+
+    /// Authorization counter of the most recent request authorized by the
+    /// root keys
+    #[field(tag = 3, wire_type = "uint64", critical = true)]
+    pub authorization_counter: u64,
+
+    /// Greatest authorization counter of any deleted domain, which domains
+    /// created afterwards continue from
+    #[field(tag = 4, wire_type = "uint64", critical = true)]
+    pub deleted_domain_counter: u64,
+}
+
+/// Persisted root configuration
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct RootConfig {
+    /// UUID assigned at provisioning time
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub uuid: Uuid,
+
+    /// Root version number
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub version: u64,
+
+    /// Threshold key set for the root role
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub key_set: ThresholdKeySet,
+}
+
+/// Persisted domain
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Domain {
+    /// Domain configuration
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub config: domain::Config,
+
+    /// Keys within this domain, indexed by slot
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub keys: Keys,
+
+    /// Authorization program installed in this domain (empty if none)
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub program: Program,
+
+    /// Authorization counter of the most recent request authorized for this
+    /// domain
+    #[field(tag = 3, wire_type = "uint64", critical = true)]
+    pub authorization_counter: u64,
+}
+
+/// Persisted key
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct Key {
+    /// Secret key material
+    #[field(tag = 0, wire_type = "message", critical = true)]
+    pub secret_key: SecretKey,
+
+    /// Policy constraining how the key may be used
+    #[field(tag = 1, wire_type = "message", critical = true)]
+    pub policy: Policy,
+
+    /// Record of the key's recent usage
+    #[field(tag = 2, wire_type = "message", critical = true)]
+    pub usage: Usage,
+}
+
+/// Record of a persisted key's recent usage, against which its policy's
+/// signature rate limit is checked
+#[derive(Message, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Usage {
+    /// Authorization counter of the key's domain at which the current rate
+    /// limit window began
+    #[field(tag = 0, wire_type = "uint64", critical = true)]
+    pub window_start: u64,
+
+    /// Number of signatures made in the current window
+    #[field(tag = 1, wire_type = "uint64", critical = true)]
+    pub signatures: u64,
+}
+
+/// Secret keys (serialized scalars/seeds)
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub enum SecretKey {
+    /// Ed25519 secret key (32-byte seed)
+    #[field(tag = 0, wire_type = "bytes", size = 32)]
+    Ed25519([u8; 32]),
+
+    /// ECDSA/P-256 secret scalar (big endian)
+    #[field(tag = 1, wire_type = "bytes", size = 32)]
+    EcdsaP256([u8; 32]),
+
+    /// ECDSA/secp256k1 secret scalar (big endian)
+    #[field(tag = 2, wire_type = "bytes", size = 32)]
+    EcdsaSecp256k1([u8; 32]),
+
+    /// BLS12-381 secret scalar (little endian)
+    #[field(tag = 3, wire_type = "bytes", size = 32)]
+    Bls12381([u8; 32]),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Domain, Key, RootConfig, SecretKey, State, Usage};
+    use crate::{
+        domain,
+        policy::{tests::example_policy, Policy},
+        program::{Opcode, Program},
+        threshold, Uuid,
+    };
+    use heapless::{consts::U1024, Vec};
+    use veriform::{Decoder, Message};
+
+    #[test]
+    fn state_round_trip() {
+        let mut keys = Vec::new();
+        keys.push(Key {
+            secret_key: SecretKey::Ed25519([1u8; 32]),
+            policy: Policy::default(),
+            usage: Usage::default(),
+        })
+        .unwrap();
+        keys.push(Key {
+            secret_key: SecretKey::Bls12381([2u8; 32]),
+            policy: example_policy(),
+            usage: Usage {
+                window_start: 5,
+                signatures: 1,
+            },
+        })
+        .unwrap();
+
+        let mut program = Program::default();
+        program.code.push(Opcode::Allow.into()).unwrap();
+
+        let mut domains = Vec::new();
+        domains
+            .push(Domain {
+                config: domain::tests::example_config(),
+                keys,
+                program,
+                authorization_counter: 4,
+            })
+            .unwrap();
+
+        let state = State {
+            version: 3,
+            root: RootConfig {
+                uuid: Uuid::parse_str("88888888-4444-4444-4444-121212121212").unwrap(),
+                version: 2,
+                key_set: threshold::tests::example_key_set(),
+            },
+            domains,
+            authorization_counter: 7,
+            deleted_domain_counter: 2,
+        };
+
+        let mut buffer: Vec<u8, U1024> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 1024]).unwrap();
+        state.encode(&mut buffer).unwrap();
+        buffer.truncate(state.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(state, State::decode(&mut decoder, &buffer).unwrap());
+    }
+}
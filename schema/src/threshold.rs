@@ -0,0 +1,57 @@
+//! Threshold key sets: M-of-N sets of public keys which authorize actions
+
+use crate::public_key::PublicKey;
+use heapless::{consts::U8, Vec};
+use veriform::Message;
+
+/// Public keys which are members of a threshold key set
+pub type PublicKeys = Vec<PublicKey, U8>;
+
+/// Threshold key set: actions are authorized by signatures from `threshold`
+/// of the listed `public_keys`
+#[derive(Message, Clone, Debug, Eq, PartialEq)]
+pub struct ThresholdKeySet {
+    /// Number of signatures required to authorize an action
+    #[field(tag = 0, wire_type = "uint64", critical = true, max = 8)]
+    pub threshold: u64,
+
+    /// Public keys which are members of this key set
+    #[field(tag = 1, wire_type = "sequence", critical = true, max = 8)]
+    pub public_keys: PublicKeys,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::ThresholdKeySet;
+    use crate::PublicKey;
+    use heapless::{consts::U128, Vec};
+    use veriform::{Decoder, Message};
+
+    /// Create an example `ThresholdKeySet`
+    pub(crate) fn example_key_set() -> ThresholdKeySet {
+        let mut public_keys = Vec::new();
+        public_keys.push(PublicKey::Ed25519([1u8; 32])).unwrap();
+        public_keys.push(PublicKey::Ed25519([2u8; 32])).unwrap();
+
+        ThresholdKeySet {
+            threshold: 2,
+            public_keys,
+        }
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        let key_set = example_key_set();
+
+        let mut buffer: Vec<u8, U128> = Vec::new();
+        buffer.extend_from_slice(&[0u8; 128]).unwrap();
+        key_set.encode(&mut buffer).unwrap();
+        buffer.truncate(key_set.encoded_len());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            key_set,
+            ThresholdKeySet::decode(&mut decoder, &buffer).unwrap()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Armistice simulator: hosts Armistice Core on a development machine,
+//! speaking the same wire protocol as a USB armory MkII over a byte stream
+//! (TCP or a Unix domain socket).
+//!
+//! State is persisted to files in a directory, sealed under a software root
+//! key which is stored in the same directory. This offers none of the
+//! protections of a real device and is intended for development and CI only.
+
+#![doc(html_root_url = "https://docs.rs/armistice_sim/0.0.0")]
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use aes::{block_cipher::NewBlockCipher, Aes128};
+use armistice::{
+    error::{Error, Kind},
+    schema::{
+        error,
+        framing::{MaxMessageSize, Reassembler},
+        Message, Response,
+    },
+    transport::{
+        stream::{read_packet, write_message},
+        InProcessTransport, Transport, PACKET_SIZE,
+    },
+};
+use armistice_core::{counter::FileCounter, storage::FileStorage, Armistice};
+use rand_core::{OsRng, RngCore};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// Name of the file containing the software root key
+pub const ROOT_KEY_FILE: &str = "root.key";
+
+/// Name of the file containing the monotonic counter
+pub const COUNTER_FILE: &str = "counter";
+
+/// Name of the directory containing sealed state records
+pub const STORAGE_DIR: &str = "storage";
+
+/// Size of the software root key
+const ROOT_KEY_SIZE: usize = 16;
+
+/// Armistice Core instantiated with simulator types
+pub type SimulatedArmistice = Armistice<Aes128, OsRng, FileStorage, FileCounter>;
+
+/// Simulated Armistice device
+pub struct Simulator {
+    /// Armistice Core handling requests
+    transport: InProcessTransport<Aes128, OsRng, FileStorage, FileCounter>,
+}
+
+impl Simulator {
+    /// Open a simulated device which persists its state to the given
+    /// directory, generating a software root key if one doesn't exist
+    pub fn open(state_dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let state_dir = state_dir.as_ref();
+        fs::create_dir_all(state_dir)?;
+
+        let root_key = load_or_generate_root_key(&state_dir.join(ROOT_KEY_FILE))?;
+        let storage = FileStorage::open(state_dir.join(STORAGE_DIR))?;
+        let counter = FileCounter::open(state_dir.join(COUNTER_FILE));
+
+        let armistice = Armistice::new(
+            Aes128::new(root_key.as_ref().into()),
+            OsRng,
+            storage,
+            counter,
+        )?;
+
+        Ok(Simulator {
+            transport: InProcessTransport::new(armistice),
+        })
+    }
+
+    /// Get the simulated Armistice Core
+    pub fn armistice(&self) -> &SimulatedArmistice {
+        self.transport.armistice()
+    }
+
+    /// Serve requests received over the given stream (i.e. a client
+    /// connection) until it's closed
+    pub fn serve(&mut self, mut stream: impl Read + Write) -> Result<(), Error> {
+        let mut reassembler = Reassembler::<MaxMessageSize>::new();
+        let mut packet = [0u8; PACKET_SIZE];
+
+        loop {
+            let len = match read_packet(&mut stream, &mut packet) {
+                Ok(len) => len,
+                Err(e) if e.is_disconnect() => return Ok(()),
+                Err(e) if e.kind() == &Kind::Encoding => {
+                    // Malformed header: we can no longer find fragment
+                    // boundaries in the stream, so respond and disconnect
+                    return write_message(&mut stream, &decode_error("malformed fragment"));
+                }
+                Err(e) => return Err(e),
+            };
+
+            let response = match reassembler.push(&packet[..len]) {
+                Ok(Some(request)) => self.transport.send_request(request)?,
+                // wait for the remaining fragments
+                Ok(None) => continue,
+                Err(e) => decode_error(e.as_str()),
+            };
+
+            write_message(&mut stream, &response)?;
+        }
+    }
+}
+
+/// Load the software root key from the given path, generating and saving
+/// a new one if it doesn't exist
+fn load_or_generate_root_key(path: &Path) -> Result<[u8; ROOT_KEY_SIZE], Error> {
+    let mut root_key = [0u8; ROOT_KEY_SIZE];
+
+    match fs::read(path) {
+        Ok(bytes) if bytes.len() == ROOT_KEY_SIZE => root_key.copy_from_slice(&bytes),
+        Ok(_) => return Err(Kind::Io.context("malformed root key file").into()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            OsRng.fill_bytes(&mut root_key);
+            create_private_file(path)?.write_all(&root_key)?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(root_key)
+}
+
+/// Create a file which is only readable by the current user
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)
+}
+
+/// Encode an error response indicating a request couldn't be decoded
+fn decode_error(detail: &str) -> Vec<u8> {
+    Response::from(error::Response::new(error::Code::Decode, detail))
+        .encode_vec()
+        .expect("couldn't encode error response")
+}
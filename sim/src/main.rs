@@ -0,0 +1,105 @@
+//! Armistice simulator command-line entrypoint
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice_sim::Simulator;
+use gumdrop::Options;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    process,
+};
+
+/// Command-line options
+#[derive(Debug, Options)]
+struct Opts {
+    /// Print help message
+    #[options(help = "print help message")]
+    help: bool,
+
+    /// TCP address to listen on
+    #[options(no_short, help = "TCP address to listen on (e.g. 127.0.0.1:6660)")]
+    tcp: Option<SocketAddr>,
+
+    /// Unix domain socket to listen on
+    #[options(no_short, help = "path of a Unix domain socket to listen on")]
+    unix: Option<PathBuf>,
+
+    /// Directory in which to persist state
+    #[options(
+        short = "s",
+        help = "directory in which to persist state",
+        default = "armistice-sim"
+    )]
+    state_dir: PathBuf,
+}
+
+fn main() {
+    let opts = Opts::parse_args_default_or_exit();
+
+    let mut simulator = Simulator::open(&opts.state_dir).unwrap_or_else(|e| {
+        eprintln!(
+            "error: couldn't open state in {}: {}",
+            opts.state_dir.display(),
+            e
+        );
+        process::exit(1);
+    });
+
+    eprintln!(
+        "armistice-sim: state in {} (provisioned: {})",
+        opts.state_dir.display(),
+        simulator.armistice().is_provisioned()
+    );
+
+    match (opts.tcp, opts.unix) {
+        (Some(addr), None) => {
+            let listener = TcpListener::bind(addr).unwrap_or_else(|e| exit(e));
+            eprintln!("armistice-sim: listening on tcp://{}", addr);
+
+            for stream in listener.incoming() {
+                serve(&mut simulator, stream);
+            }
+        }
+        #[cfg(unix)]
+        (None, Some(path)) => {
+            // Remove the socket left behind by a previous run, if any
+            if path.exists() {
+                std::fs::remove_file(&path).unwrap_or_else(|e| exit(e));
+            }
+
+            let listener =
+                std::os::unix::net::UnixListener::bind(&path).unwrap_or_else(|e| exit(e));
+            eprintln!("armistice-sim: listening on unix://{}", path.display());
+
+            for stream in listener.incoming() {
+                serve(&mut simulator, stream);
+            }
+        }
+        _ => {
+            eprintln!("error: specify exactly one of --tcp or --unix\n");
+            eprintln!("{}", Opts::usage());
+            process::exit(2);
+        }
+    }
+}
+
+/// Serve a client connection. Clients are served one at a time, just like
+/// a physical device.
+fn serve(simulator: &mut Simulator, stream: std::io::Result<impl Read + Write>) {
+    let result = stream
+        .map_err(Into::into)
+        .and_then(|stream| simulator.serve(stream));
+
+    if let Err(e) = result {
+        eprintln!("armistice-sim: connection error: {}", e);
+    }
+}
+
+/// Print an error and exit
+fn exit(err: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", err);
+    process::exit(1);
+}
@@ -0,0 +1,125 @@
+//! Armistice simulator tests
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs, rust_2018_idioms, unused_qualifications)]
+
+use armistice::{
+    schema::{
+        provision, signature::Signatures, veriform::Decoder, Message, PublicKey, Signature,
+        Timestamp,
+    },
+    transport::TcpTransport,
+    Armistice,
+};
+use armistice_sim::{Simulator, ROOT_KEY_FILE};
+use ed25519_dalek::{Keypair, SecretKey, Signer};
+use std::{
+    fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    thread,
+};
+
+/// Create a state directory unique to the given test
+fn state_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("armistice-sim-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Stack size of simulator threads: Armistice Core's state lives on the
+/// stack, and unoptimized builds need more room than a thread's default
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Serve a single client connection from a simulator opened from the given
+/// state directory, returning a client connected to it
+fn connect(state_dir: &Path) -> (Armistice, thread::JoinHandle<()>) {
+    let state_dir = state_dir.to_owned();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let mut simulator = Simulator::open(&state_dir).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            simulator.serve(stream).unwrap();
+        })
+        .unwrap();
+
+    let armistice = Armistice::new(TcpTransport::connect(addr).unwrap());
+    (armistice, server)
+}
+
+/// Create a signed provisioning request for the given root keypair, bound to
+/// the device's next authorization counter
+fn provision_request(
+    armistice: &mut Armistice,
+    root_keypair: &Keypair,
+) -> provision::SignedRequest {
+    let mut root_keys = provision::RootKeys::new();
+    root_keys
+        .push(PublicKey::Ed25519(root_keypair.public.to_bytes()))
+        .unwrap();
+
+    let request = provision::Request {
+        root_key_threshold: 1,
+        root_keys,
+        timestamp: Timestamp::from_slice(&[64, 0, 0, 0, 94, 198, 207, 194, 32, 254, 206, 208])
+            .unwrap(),
+        digest: None,
+    };
+
+    // Round trip through the encoder to compute the digest
+    let request =
+        provision::Request::decode(&mut Decoder::new(), &request.encode_vec().unwrap()).unwrap();
+
+    let binding = armistice.info().unwrap().next_binding().unwrap();
+
+    let mut signatures = Signatures::new();
+    signatures
+        .push(Signature::Ed25519(
+            root_keypair
+                .sign(&binding.digest(&request.digest.unwrap()))
+                .to_bytes(),
+        ))
+        .unwrap();
+
+    provision::SignedRequest {
+        request,
+        signatures,
+        binding,
+    }
+}
+
+#[test]
+fn state_persists_across_restarts() {
+    let dir = state_dir("restart");
+    let secret = SecretKey::from_bytes(&[1; 32]).unwrap();
+    let root_keypair = Keypair {
+        public: (&secret).into(),
+        secret,
+    };
+
+    let (mut armistice, server) = connect(&dir);
+    assert!(!armistice.info().unwrap().is_provisioned());
+
+    let request = provision_request(&mut armistice, &root_keypair);
+    let response = armistice.send_request(request).unwrap();
+
+    let uuid = response.provision().unwrap().uuid;
+    drop(armistice);
+    server.join().unwrap();
+
+    let root_key = fs::read(dir.join(ROOT_KEY_FILE)).unwrap();
+
+    let (mut armistice, server) = connect(&dir);
+    let info = armistice.info().unwrap();
+    assert!(info.is_provisioned());
+    assert_eq!(info.uuid, uuid);
+    drop(armistice);
+    server.join().unwrap();
+
+    assert_eq!(fs::read(dir.join(ROOT_KEY_FILE)).unwrap(), root_key);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -7,10 +7,25 @@
 #![deny(warnings, rust_2018_idioms, unused_qualifications)]
 #![forbid(unsafe_code)]
 
-use armistice_core::schema::{veriform::Decoder, Message, Request};
+use armistice_core::{
+    counter::MemoryCounter,
+    schema::{
+        error,
+        framing::{self, Reassembler},
+        veriform::Decoder,
+        Message, Request, Response,
+    },
+    storage::MemoryStorage,
+    MAX_MESSAGE_SIZE,
+};
 use core::time::Duration;
 use exception_reset as _; // default exception handler
-use heapless::pool::singleton::{Box, Pool};
+use heapless::{
+    consts::{U16, U4},
+    pool::singleton::{Box, Pool},
+    spsc::Queue,
+    Vec,
+};
 use panic_serial as _; // panic handler
 use usb_device::{
     bus::{InterfaceNumber, UsbBus, UsbBusAllocator},
@@ -18,17 +33,40 @@ use usb_device::{
     descriptor::DescriptorWriter,
     device::{UsbDevice, UsbDeviceBuilder, UsbVidPid},
     endpoint::{EndpointAddress, EndpointIn, EndpointOut},
+    UsbError,
+};
+use usbarmory::{
+    dcp::Aes128, led::Leds, memlog, rng::Rng, serial::Serial, time::Instant, usbd::Usbd,
 };
-use usbarmory::{dcp::Aes128, led::Leds, memlog, serial::Serial, time::Instant, usbd::Usbd};
 
 /// Max packet size for bulk transfers to/from High-Speed USB devices
 const MAX_PACKET_SIZE: u16 = 512;
 
+/// Number of bulk packets the memory pool can hold: enough for a full
+/// transmit queue (16) plus a full receive queue (4), plus the packet the
+/// request being processed arrived in (kept for error responses)
+const POOL_PACKETS: usize = 16 + 4 + 1;
+
 // Memory pool used for bulk packets
 heapless::pool!(P: [u8; MAX_PACKET_SIZE as usize]);
 
-/// Armistice instantiated with USB armory types
-type Armistice = armistice_core::Armistice<Aes128>;
+/// Queue of packets awaiting transmission: large enough to hold every
+/// fragment of a maximum size response
+type TxQueue = Queue<(Box<P>, usize), U16>;
+
+/// Queue of received packets awaiting processing. The bulk OUT endpoint
+/// isn't read while it's full, which leaves the host waiting to send more.
+type RxQueue = Queue<(Box<P>, usize), U4>;
+
+/// Armistice instantiated with USB armory types.
+///
+/// State is sealed to storage held in RAM: there's no driver for the eMMC or
+/// a hardware monotonic counter yet, so the device loses its provisioning,
+/// domains, keys, authorization counters, and rate limit windows when it
+/// restarts, and its replay protection doesn't survive restarts (see
+/// README.md).
+// TODO(tarcieri): persist state to eMMC and use a hardware monotonic counter
+type Armistice = armistice_core::Armistice<Aes128, Rng, MemoryStorage, MemoryCounter>;
 
 #[rtic::app()]
 const APP: () = {
@@ -37,22 +75,30 @@ const APP: () = {
         bulk_class: BulkClass<'static, Usbd>,
         dev: UsbDevice<'static, Usbd>,
         leds: Leds,
+        reassembler: Reassembler,
+        rx_queue: RxQueue,
         serial: Serial,
         status: StatusIndicator,
+        tx_queue: TxQueue,
     }
 
     #[init]
     fn init(_cx: init::Context) -> init::LateResources {
-        // enough memory for 2 bulk packets
-        static mut MEMORY: [u8; (2 * MAX_PACKET_SIZE + 64) as usize] =
-            [0; (2 * MAX_PACKET_SIZE + 64) as usize];
+        // enough memory for `POOL_PACKETS` bulk packets
+        static mut MEMORY: [u8; POOL_PACKETS * MAX_PACKET_SIZE as usize + 64] =
+            [0; POOL_PACKETS * MAX_PACKET_SIZE as usize + 64];
         static mut ALLOCATOR: Option<UsbBusAllocator<Usbd>> = None;
 
         // the pool will manage this memory
         P::grow(MEMORY);
 
-        let armistice =
-            Armistice::new(Aes128::new_unique().expect("couldn't get channel for UNIQUE key"));
+        let armistice = Armistice::new(
+            Aes128::new_unique().expect("couldn't get channel for UNIQUE key"),
+            Rng::take().expect("Rng"),
+            MemoryStorage::new(),
+            MemoryCounter::new(),
+        )
+        .expect("couldn't load sealed state");
         let status = StatusIndicator::new(!armistice.is_provisioned());
 
         let leds = Leds::take().expect("Leds");
@@ -76,10 +122,13 @@ const APP: () = {
         init::LateResources {
             armistice,
             leds,
+            reassembler: Reassembler::new(),
+            rx_queue: Queue::new(),
             serial,
             status,
             dev,
             bulk_class,
+            tx_queue: Queue::new(),
         }
     }
 
@@ -105,8 +154,8 @@ const APP: () = {
     #[task(
         binds = USB_OTG1,
         priority = 2,
-        resources = [dev, bulk_class],
-        spawn = [process_packet],
+        resources = [dev, bulk_class, rx_queue, tx_queue],
+        spawn = [process_packets],
     )]
     fn usb(cx: usb::Context) {
         let dev = cx.resources.dev;
@@ -114,63 +163,190 @@ const APP: () = {
 
         if dev.poll(&mut [bulk_class]) {
             // new `bulk_class` event
-            // owned pointer equivalent to `alloc::boxed::Box<[u8; N]>`
-            let mut buf: Box<P> = P::alloc().expect("OOM").freeze();
-
-            if let Ok(n) = bulk_class.ep_bulk_out.read(&mut *buf) {
-                cx.spawn.process_packet(buf, n).ok().expect("OOM");
-            } else {
-                // WouldBlock; try again later
-                // `drop` returns memory to the pool
-                // this will happen automatically but we are explicit for the
-                // sake of the example
-                drop(buf);
-            }
+            receive_packets(bulk_class, cx.resources.rx_queue);
         }
-    }
 
-    // transmit bulk packets
-    #[task(priority = 2, resources = [bulk_class])]
-    fn usb_tx(cx: usb_tx::Context, packet: Box<P>, len: usize) {
-        let bulk_class = cx.resources.bulk_class;
+        // continue transmitting any queued packets once the IN endpoint frees up
+        flush_tx_queue(bulk_class, cx.resources.tx_queue);
 
-        bulk_class
-            .ep_bulk_in
-            .write(&packet[..len])
-            .expect("I/O error?");
+        if ready_to_process(cx.resources.rx_queue, cx.resources.tx_queue) {
+            // fails if already pending, in which case it will process them
+            cx.spawn.process_packets().ok();
+        }
     }
 
-    // lower priority task that performs work based on the content of the packet
-    #[task(priority = 1, spawn =[usb_tx], resources = [armistice, leds, status])]
-    fn process_packet(cx: process_packet::Context, mut packet: Box<P>, len: usize) {
-        cx.resources.leds.blue.on();
+    // transmit queued bulk packets
+    #[task(
+        priority = 2,
+        resources = [bulk_class, rx_queue, tx_queue],
+        spawn = [process_packets],
+    )]
+    fn usb_tx(cx: usb_tx::Context) {
+        flush_tx_queue(cx.resources.bulk_class, cx.resources.tx_queue);
+
+        if ready_to_process(cx.resources.rx_queue, cx.resources.tx_queue) {
+            cx.spawn.process_packets().ok();
+        }
+    }
 
-        let mut decoder = Decoder::new();
+    // lower priority task that reassembles requests from received packets
+    // and performs work based on their content. Processing stops while a
+    // response is awaiting transmission, and resumes once it has been sent.
+    #[task(
+        priority = 1,
+        spawn = [usb_tx],
+        resources = [armistice, bulk_class, leds, reassembler, rx_queue, status, tx_queue],
+    )]
+    fn process_packets(mut cx: process_packets::Context) {
+        while cx.resources.tx_queue.lock(|tx_queue| tx_queue.is_empty()) {
+            let (packet, len) = match cx.resources.rx_queue.lock(|rx_queue| rx_queue.dequeue()) {
+                Some(received) => received,
+                None => return,
+            };
+
+            // read any packet left waiting in the OUT endpoint while the
+            // receive queue was full
+            let rx_queue = &mut cx.resources.rx_queue;
+            cx.resources
+                .bulk_class
+                .lock(|bulk_class| rx_queue.lock(|rx_queue| receive_packets(bulk_class, rx_queue)));
+
+            let request = match cx.resources.reassembler.push(&packet[..len]) {
+                Ok(Some(message)) => Request::decode(&mut Decoder::new(), message)
+                    .map_err(|_| error::Response::from(error::Code::Decode)),
+                // wait for the remaining fragments
+                Ok(None) => continue,
+                Err(e) => Err(error::Response::new(error::Code::Decode, e.as_str())),
+            };
+
+            cx.resources.leds.blue.on();
+
+            let response = match request {
+                Ok(request) => cx
+                    .resources
+                    .armistice
+                    .handle_request(request)
+                    .unwrap_or_else(Response::from),
+                Err(err) => Response::from(err),
+            };
+
+            let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+
+            let response_len = match response.encode(&mut buffer[..]).map(|bytes| bytes.len()) {
+                Ok(response_len) => response_len,
+                Err(_) => Response::from(error::Response::from(error::Code::Encode))
+                    .encode(&mut buffer[..])
+                    .expect("couldn't encode error response")
+                    .len(),
+            };
+
+            // the packet the request arrived in is kept in reserve, so the
+            // host is still sent an error if the pool runs out of packets
+            let packets = packetize(&buffer[..response_len]).or_else(|| {
+                memlog!("out of packets; replacing {}-byte response", response_len);
+                error_packet(packet, error::Code::Capacity)
+            });
+
+            match packets {
+                Some(packets) => {
+                    cx.resources.tx_queue.lock(|tx_queue| {
+                        for packet in packets {
+                            // the queue is empty and holds a maximum size response
+                            tx_queue.enqueue(packet).ok();
+                        }
+                    });
+
+                    // a transmission may already be pending, in which case it
+                    // will send the packets we just queued
+                    cx.spawn.usb_tx().ok();
+                }
+                None => memlog!("couldn't encode error response"),
+            }
 
-        // TODO(tarcieri): better error handling
-        if let Ok(request) = Request::decode(&mut decoder, &packet[..len]) {
-            if let Ok(response) = cx.resources.armistice.handle_request(request) {
-                if let Ok(response_len) = response.encode(&mut packet[..]).map(|bytes| bytes.len())
-                {
-                    cx.spawn.usb_tx(packet, response_len).ok().expect("OOM");
+            if cx.resources.armistice.is_provisioned() {
+                cx.resources.status.set_blink(false);
+                cx.resources.leds.white.on();
+            }
 
-                    if cx.resources.armistice.is_provisioned() {
-                        cx.resources.status.set_blink(false);
-                        cx.resources.leds.white.on();
-                    }
+            cx.resources.leds.blue.off();
+        }
+    }
+};
 
-                    cx.resources.leds.blue.off();
+/// Read packets from the bulk OUT endpoint until it's empty or the receive
+/// queue is full
+fn receive_packets(bulk_class: &mut BulkClass<'_, Usbd>, rx_queue: &mut RxQueue) {
+    while rx_queue.len() < rx_queue.capacity() {
+        // owned pointer equivalent to `alloc::boxed::Box<[u8; N]>`
+        let mut buf: Box<P> = match P::alloc() {
+            Some(buf) => buf.freeze(),
+            None => break,
+        };
 
-                    return;
-                }
+        match bulk_class.ep_bulk_out.read(&mut *buf) {
+            Ok(n) => {
+                // can't fail: the queue was checked to have room above
+                rx_queue.enqueue((buf, n)).ok();
             }
+            // WouldBlock; try again once the host sends another packet
+            // (dropping `buf` returns its memory to the pool)
+            Err(_) => break,
         }
+    }
+}
+
+/// Are there received packets to process, and no response awaiting
+/// transmission?
+fn ready_to_process(rx_queue: &RxQueue, tx_queue: &TxQueue) -> bool {
+    !rx_queue.is_empty() && tx_queue.is_empty()
+}
 
-        packet[..5].copy_from_slice(b"ERROR");
-        cx.spawn.usb_tx(packet, 5).ok().expect("OOM");
-        cx.resources.leds.blue.off();
+/// Split an encoded response into bulk packets, or return `None` if it
+/// can't be fragmented or the pool doesn't have enough free packets to hold
+/// them all
+fn packetize(response: &[u8]) -> Option<Vec<(Box<P>, usize), U16>> {
+    let fragments = framing::fragments(response, MAX_PACKET_SIZE.into()).ok()?;
+    let mut packets = Vec::new();
+
+    for fragment in fragments {
+        let mut packet: Box<P> = P::alloc()?.freeze();
+        let len = fragment.encode(&mut *packet).ok()?.len();
+        packets.push((packet, len)).ok()?;
     }
-};
+
+    Some(packets)
+}
+
+/// Encode an error response with the given code into the given packet,
+/// which holds it as a single fragment without allocating from the pool
+fn error_packet(mut packet: Box<P>, code: error::Code) -> Option<Vec<(Box<P>, usize), U16>> {
+    let mut buffer = [0u8; MAX_PACKET_SIZE as usize];
+    let response = Response::from(error::Response::from(code))
+        .encode(&mut buffer[..])
+        .ok()?;
+
+    let fragment = framing::fragments(response, MAX_PACKET_SIZE.into())
+        .ok()?
+        .next()?;
+    let len = fragment.encode(&mut *packet).ok()?.len();
+
+    let mut packets = Vec::new();
+    packets.push((packet, len)).ok()?;
+    Some(packets)
+}
+
+/// Write queued packets to the bulk IN endpoint until it's busy
+fn flush_tx_queue(bulk_class: &mut BulkClass<'_, Usbd>, tx_queue: &mut TxQueue) {
+    while let Some((packet, len)) = tx_queue.peek() {
+        match bulk_class.ep_bulk_in.write(&packet[..*len]) {
+            Ok(_) => {
+                tx_queue.dequeue();
+            }
+            Err(UsbError::WouldBlock) => break,
+            Err(_) => panic!("I/O error?"),
+        }
+    }
+}
 
 pub struct BulkClass<'a, B>
 where